@@ -0,0 +1,84 @@
+//! Event throughput for a fast-streaming session: many tiny `gemini-output`
+//! chunks pushed through the session event pipeline to an emitter that
+//! serializes each event the way the WebSocket emitter does.
+//!
+//! `per_chunk` forwards every chunk (the previous behaviour); `coalesced`
+//! merges adjacent chunks within `COALESCE_WINDOW`. Run with
+//! `cargo bench -p backend --bench event_pipeline`.
+
+use backend::session::{COALESCE_WINDOW, EVENT_CHANNEL_CAPACITY, forward_events};
+use backend::{BackendResult, EventEmitter, GeminiOutputPayload, InternalEvent};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const CHUNKS: usize = 10_000;
+
+#[derive(Clone, Default)]
+struct SerializingEmitter {
+    emitted: Arc<AtomicUsize>,
+}
+
+impl EventEmitter for SerializingEmitter {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) -> BackendResult<()> {
+        let message = serde_json::json!({ "event": event, "payload": payload, "sequence": 0 });
+        std::hint::black_box(message.to_string());
+        self.emitted.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+async fn stream_chunks(window: Duration) -> usize {
+    let emitter = SerializingEmitter::default();
+    let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+    let forwarder = tokio::spawn(forward_events(rx, emitter.clone(), window));
+
+    for i in 0..CHUNKS {
+        let event = InternalEvent::GeminiOutput {
+            session_id: "bench".to_string(),
+            payload: GeminiOutputPayload {
+                text: format!("tok{} ", i % 10),
+            },
+        };
+        if tx.send(event).await.is_err() {
+            break;
+        }
+    }
+    drop(tx);
+    let _ = forwarder.await;
+    emitter.emitted.load(Ordering::Relaxed)
+}
+
+fn event_pipeline(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    for (name, window) in [
+        ("per_chunk", Duration::ZERO),
+        ("coalesced", COALESCE_WINDOW),
+    ] {
+        let emitted = runtime.block_on(stream_chunks(window));
+        println!("{name}: {CHUNKS} chunks -> {emitted} events");
+    }
+
+    let mut group = c.benchmark_group("gemini_output_chunks");
+    group.throughput(Throughput::Elements(CHUNKS as u64));
+    for (name, window) in [
+        ("per_chunk", Duration::ZERO),
+        ("coalesced", COALESCE_WINDOW),
+    ] {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &window, |b, &window| {
+            b.iter(|| runtime.block_on(stream_chunks(window)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, event_pipeline);
+criterion_main!(benches);
@@ -1,5 +1,6 @@
 use crate::events::ToolCallLocation;
 use crate::rpc::deserialize_string_or_number;
+use schemars::JsonSchema;
 use serde::de::{Deserializer, Error as DeError};
 use serde::{Deserialize, Serialize};
 
@@ -139,7 +140,7 @@ pub struct RequestToolCallConfirmationResult {
     pub outcome: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommandResult {
     pub command: String,
     pub success: bool,
@@ -0,0 +1,406 @@
+//! The backend daemon: one `gemini-desktop-web --daemon` process owning the
+//! sessions, shared by the desktop app and the web UI.
+//!
+//! The daemon writes a [`DaemonInfo`] to `<data-dir>/daemon.json`, readable
+//! only by its OS account. Clients connect to the endpoint in it (a Unix
+//! socket, or a localhost port where there are none), send the secret as
+//! the first line, then speak newline-delimited JSON-RPC 2.0 with the
+//! methods and events of the web server's `/api/ws`.
+
+use crate::events::EventEmitter;
+use crate::paths;
+use crate::types::{BackendError, BackendResult, ErrorBody};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// Requests waiting to be written to the daemon. When full, callers wait.
+pub const REQUEST_CHANNEL_CAPACITY: usize = 64;
+
+/// Where a running daemon can be reached.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonInfo {
+    pub pid: u32,
+    /// `unix:<socket path>` or `tcp:<address>`.
+    pub endpoint: String,
+    pub secret: String,
+}
+
+impl DaemonInfo {
+    pub fn path() -> BackendResult<PathBuf> {
+        Ok(paths::data_dir()?.join("daemon.json"))
+    }
+
+    /// The info of the last daemon started, if its file is still there.
+    pub fn load() -> Option<Self> {
+        let content = fs::read_to_string(Self::path().ok()?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Writes `daemon.json`, readable only by this OS account. The secret
+    /// goes into a new file created that way, which then replaces the old
+    /// one, so an existing file's looser permissions don't carry over.
+    pub fn save(&self) -> BackendResult<()> {
+        let path = Self::path()?;
+        let tmp_path = path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| BackendError::JsonError(e.to_string()))?;
+        let _ = fs::remove_file(&tmp_path);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp_path)?;
+        io::Write::write_all(&mut file, json.as_bytes())?;
+        drop(file);
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Removes `daemon.json` if it still describes this process.
+    pub fn remove_own() {
+        if Self::load().is_some_and(|info| info.pid == std::process::id())
+            && let Ok(path) = Self::path()
+        {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// A connection to the daemon.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Connects to the endpoint in `info` and sends the secret.
+pub async fn connect(info: &DaemonInfo) -> io::Result<Box<dyn Stream>> {
+    let mut stream: Box<dyn Stream> = match info.endpoint.split_once(':') {
+        #[cfg(unix)]
+        Some(("unix", path)) => Box::new(tokio::net::UnixStream::connect(path).await?),
+        Some(("tcp", address)) => Box::new(tokio::net::TcpStream::connect(address).await?),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported daemon endpoint: {}", info.endpoint),
+            ));
+        }
+    };
+    stream
+        .write_all(format!("{}\n", info.secret).as_bytes())
+        .await?;
+    Ok(stream)
+}
+
+/// Calls awaiting a reply; `None` once the connection is closed.
+type Pending = Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Result<Value, ErrorBody>>>>>>;
+
+/// Client side of a daemon connection. Operations are sent as JSON-RPC
+/// requests; the daemon's events are re-emitted through an [`EventEmitter`].
+pub struct DaemonClient {
+    outgoing: mpsc::Sender<String>,
+    pending: Pending,
+    next_id: AtomicU64,
+    /// Becomes `true` once the daemon hangs up.
+    closed: watch::Receiver<bool>,
+}
+
+impl DaemonClient {
+    /// Connects to the running daemon and subscribes to every session.
+    pub async fn connect<E: EventEmitter + 'static>(emitter: E) -> io::Result<Self> {
+        let info = DaemonInfo::load()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No daemon is running"))?;
+        let client = Self::over(connect(&info).await?, emitter);
+        client
+            .call("subscribe", json!({ "session_ids": ["*"] }))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::ConnectionRefused, e.message))?;
+        Ok(client)
+    }
+
+    /// Runs the protocol over an established, authenticated connection.
+    pub fn over<S: Stream + 'static, E: EventEmitter + 'static>(stream: S, emitter: E) -> Self {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let (outgoing, mut lines_out) = mpsc::channel::<String>(REQUEST_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            while let Some(line) = lines_out.recv().await {
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let replies = pending.clone();
+        let (hang_up, closed) = watch::channel(false);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                dispatch(&line, &replies, &emitter);
+            }
+            // Dropping the senders fails every call still waiting.
+            *replies.lock().unwrap_or_else(PoisonError::into_inner) = None;
+            let _ = hang_up.send(true);
+        });
+
+        Self {
+            outgoing,
+            pending,
+            next_id: AtomicU64::new(1),
+            closed,
+        }
+    }
+
+    /// Whether the daemon has hung up; calls then fail, and a new client
+    /// has to [`connect`](Self::connect).
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// Waits until the daemon hangs up.
+    pub async fn closed(&self) {
+        let mut closed = self.closed.clone();
+        // The sender only goes away after sending `true`.
+        let _ = closed.wait_for(|closed| *closed).await;
+    }
+
+    /// Runs an operation on the daemon.
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, ErrorBody> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            Some(pending) => pending.insert(id, tx),
+            None => return Err(unavailable()),
+        };
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if self.outgoing.send(format!("{request}\n")).await.is_err() {
+            if let Some(pending) = self
+                .pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_mut()
+            {
+                pending.remove(&id);
+            }
+            return Err(unavailable());
+        }
+        rx.await.unwrap_or_else(|_| Err(unavailable()))
+    }
+}
+
+fn unavailable() -> ErrorBody {
+    ErrorBody::new(
+        "daemon_unavailable",
+        "Lost the connection to the backend daemon",
+        503,
+    )
+}
+
+/// A line from the daemon: a reply to one of our requests, or an event.
+#[derive(Deserialize)]
+struct Incoming {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<RpcError>,
+    #[serde(default)]
+    event: Option<String>,
+    #[serde(default)]
+    payload: Value,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    message: String,
+    #[serde(default)]
+    data: Option<ErrorBody>,
+}
+
+fn dispatch<E: EventEmitter>(line: &str, pending: &Pending, emitter: &E) {
+    let Ok(incoming) = serde_json::from_str::<Incoming>(line) else {
+        return;
+    };
+    if incoming.jsonrpc.is_some() {
+        let Some(id) = incoming.id else {
+            return;
+        };
+        let waiting = pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+            .and_then(|pending| pending.remove(&id));
+        if let Some(waiting) = waiting {
+            let outcome = match incoming.error {
+                Some(error) => Err(error
+                    .data
+                    .unwrap_or_else(|| ErrorBody::new("invalid_request", error.message, 400))),
+                None => Ok(incoming.result),
+            };
+            let _ = waiting.send(outcome);
+        }
+    } else if let Some(event) = incoming.event {
+        let _ = emitter.emit(&event, incoming.payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::MockEventEmitter;
+
+    #[test]
+    fn test_info_round_trips_in_the_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        paths::sync_scope(Some(dir.path().to_path_buf()), || {
+            assert_eq!(DaemonInfo::load(), None);
+            #[cfg(unix)]
+            {
+                // A leftover file's looser permissions don't carry over.
+                use std::os::unix::fs::PermissionsExt;
+                let path = dir.path().join("daemon.json");
+                fs::write(&path, "{}").unwrap();
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            }
+            let info = DaemonInfo {
+                pid: std::process::id(),
+                endpoint: "tcp:127.0.0.1:1".to_string(),
+                secret: "s3cret".to_string(),
+            };
+            info.save().unwrap();
+            assert_eq!(DaemonInfo::load(), Some(info));
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = fs::metadata(dir.path().join("daemon.json"))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+            DaemonInfo::remove_own();
+            assert_eq!(DaemonInfo::load(), None);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_client_matches_replies_and_forwards_events() {
+        let (client_end, server_end) = tokio::io::duplex(4096);
+        let emitter = MockEventEmitter::new();
+        let client = DaemonClient::over(client_end, emitter.clone());
+
+        let server = tokio::spawn(async move {
+            let (reader, mut writer) = tokio::io::split(server_end);
+            let mut lines = BufReader::new(reader).lines();
+            let mut replies = Vec::new();
+            for _ in 0..2 {
+                let request: Value =
+                    serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+                let id = request["id"].clone();
+                replies.push(match request["method"].as_str().unwrap() {
+                    "get_process_statuses" => json!({"jsonrpc": "2.0", "id": id, "result": []}),
+                    _ => json!({"jsonrpc": "2.0", "id": id, "error": {
+                        "code": -32000,
+                        "message": "Session not found: s1",
+                        "data": {"code": "session_not_found", "message": "Session not found: s1", "status": 404},
+                    }}),
+                });
+            }
+            // Answer out of order, with an event in between.
+            let event = json!({"event": "gemini-output-s1", "payload": "Hi", "sequence": 0});
+            for line in [&replies[1], &event, &replies[0]] {
+                writer
+                    .write_all(format!("{line}\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+            // Hang up once the client's next request arrives.
+            lines.next_line().await.unwrap();
+        });
+
+        assert!(!client.is_closed());
+        let (statuses, killed) = tokio::join!(
+            client.call("get_process_statuses", Value::Null),
+            client.call("kill_process", json!({"conversation_id": "s1"})),
+        );
+        assert_eq!(statuses.unwrap(), json!([]));
+        assert_eq!(killed.unwrap_err().code, "session_not_found");
+        assert_eq!(
+            emitter.get_events_by_name("gemini-output-s1"),
+            [json!("Hi")]
+        );
+
+        let error = client
+            .call("get_process_statuses", Value::Null)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, "daemon_unavailable");
+        server.await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(1), client.closed())
+            .await
+            .expect("the client should notice the daemon hanging up");
+        assert!(client.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_connect_again_after_the_daemon_hangs_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let info = DaemonInfo {
+            pid: std::process::id(),
+            endpoint: format!("tcp:{}", listener.local_addr().unwrap()),
+            secret: "s3cret".to_string(),
+        };
+        paths::sync_scope(Some(dir.path().to_path_buf()), || info.save().unwrap());
+
+        // Answers the secret and the `subscribe`, then hangs up.
+        let daemon = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (reader, mut writer) = tokio::io::split(stream);
+                let mut lines = BufReader::new(reader).lines();
+                assert_eq!(lines.next_line().await.unwrap().unwrap(), "s3cret");
+                let request: Value =
+                    serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+                assert_eq!(request["method"], "subscribe");
+                let reply = json!({"jsonrpc": "2.0", "id": request["id"], "result": null});
+                writer
+                    .write_all(format!("{reply}\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let connect = || {
+            paths::scope(
+                Some(dir.path().to_path_buf()),
+                DaemonClient::connect(MockEventEmitter::new()),
+            )
+        };
+        let first = connect().await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(1), first.closed())
+            .await
+            .expect("the client should notice the daemon hanging up");
+        let second = connect().await.unwrap();
+        assert!(first.is_closed());
+        daemon.await.unwrap();
+        second.closed().await;
+    }
+}
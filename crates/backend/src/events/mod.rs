@@ -1,4 +1,7 @@
+use crate::operations::SchemaFn;
+use crate::security::OutputStream;
 use crate::types::BackendResult;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub trait EventEmitter: Send + Sync + Clone {
@@ -40,36 +43,43 @@ pub enum InternalEvent {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CliIoPayload {
     #[serde(rename = "type")]
     pub io_type: CliIoType,
     pub data: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum CliIoType {
     Input,
     Output,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GeminiOutputPayload {
     pub text: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GeminiThoughtPayload {
     pub thought: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ErrorPayload {
     pub error: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommandOutputPayload {
+    pub execution_id: String,
+    pub stream: OutputStream,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ToolCallEvent {
     pub id: u32,
     pub name: String,
@@ -79,7 +89,7 @@ pub struct ToolCallEvent {
     pub status: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolCallUpdate {
     #[serde(rename = "toolCallId")]
@@ -88,7 +98,7 @@ pub struct ToolCallUpdate {
     pub content: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolCallConfirmationRequest {
     pub request_id: u32,
@@ -100,12 +110,12 @@ pub struct ToolCallConfirmationRequest {
     pub locations: Vec<ToolCallLocation>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ToolCallLocation {
     pub path: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ToolCallConfirmationContent {
     #[serde(rename = "type")]
     pub content_type: String,
@@ -117,7 +127,7 @@ pub struct ToolCallConfirmationContent {
     pub new_text: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ToolCallConfirmation {
     #[serde(rename = "type")]
     pub confirmation_type: String,
@@ -127,6 +137,88 @@ pub struct ToolCallConfirmation {
     pub command: Option<String>,
 }
 
+/// Kinds of per-session events, emitted as `<kind>-<session_id>`. Ordered so
+/// that longer kinds sharing a prefix (`gemini-tool-call-update`) come first.
+pub const SESSION_EVENT_KINDS: &[&str] = &[
+    "gemini-tool-call-confirmation",
+    "gemini-tool-call-update",
+    "gemini-tool-call",
+    "gemini-turn-finished",
+    "gemini-output",
+    "gemini-thought",
+    "gemini-error",
+    "cli-io",
+    "command-output",
+    "command-result",
+];
+
+/// Kinds of terminal events, emitted as `<kind>-<terminal_id>`.
+pub const TERMINAL_EVENT_KINDS: &[&str] = &["terminal-output", "terminal-exit"];
+
+/// Splits a per-session event name into `(kind, session_id)`. Returns `None`
+/// for terminal events and global events.
+pub fn split_session_event(event: &str) -> Option<(&'static str, &str)> {
+    SESSION_EVENT_KINDS.iter().find_map(|&kind| {
+        event
+            .strip_prefix(kind)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .filter(|session_id| !session_id.is_empty())
+            .map(|session_id| (kind, session_id))
+    })
+}
+
+/// The terminal id of a terminal event, or `None` for any other event.
+pub fn split_terminal_event(event: &str) -> Option<&str> {
+    TERMINAL_EVENT_KINDS.iter().find_map(|kind| {
+        event
+            .strip_prefix(kind)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .filter(|terminal_id| !terminal_id.is_empty())
+    })
+}
+
+/// An event the backend emits. `name` is the event name, with `{session_id}`
+/// or `{terminal_id}` standing for the suffix of per-session and terminal
+/// events.
+#[derive(Clone, Copy)]
+pub struct EventSchema {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub payload: SchemaFn,
+}
+
+/// Every event the backend emits, with the schema of its payload.
+pub fn event_schemas() -> Vec<EventSchema> {
+    macro_rules! events {
+        ($($name:literal: $payload:ty, $summary:literal;)*) => {
+            vec![$(EventSchema {
+                name: $name,
+                summary: $summary,
+                payload: |generator| generator.subschema_for::<$payload>(),
+            }),*]
+        };
+    }
+
+    events! {
+        "cli-io-{session_id}": CliIoPayload, "Raw JSON-RPC traffic with the CLI.";
+        "gemini-output-{session_id}": String, "A chunk of the assistant's reply.";
+        "gemini-thought-{session_id}": String, "A chunk of the assistant's reasoning.";
+        "gemini-tool-call-{session_id}": ToolCallEvent, "The CLI started a tool call.";
+        "gemini-tool-call-update-{session_id}": ToolCallUpdate, "A tool call progressed or finished.";
+        "gemini-tool-call-confirmation-{session_id}": ToolCallConfirmationRequest,
+            "A tool call waits for the user's permission.";
+        "gemini-turn-finished-{session_id}": bool, "The assistant finished its reply.";
+        "gemini-error-{session_id}": String, "The session failed.";
+        "command-output-{session_id}": CommandOutputPayload, "Output of a confirmed command.";
+        "command-result-{session_id}": crate::cli::CommandResult,
+            "A confirmed command finished.";
+        "terminal-output-{terminal_id}": crate::terminal::TerminalOutputPayload,
+            "Output of a terminal.";
+        "terminal-exit-{terminal_id}": crate::terminal::TerminalExitPayload,
+            "A terminal's process exited.";
+    }
+}
+
 #[cfg(test)]
 use std::collections::HashMap;
 #[cfg(test)]
@@ -281,6 +373,38 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn test_split_session_event() {
+        assert_eq!(
+            split_session_event("gemini-output-abc-123"),
+            Some(("gemini-output", "abc-123"))
+        );
+        assert_eq!(
+            split_session_event("gemini-tool-call-update-s1"),
+            Some(("gemini-tool-call-update", "s1"))
+        );
+        assert_eq!(
+            split_session_event("gemini-tool-call-s1"),
+            Some(("gemini-tool-call", "s1"))
+        );
+        assert_eq!(split_session_event("cli-io-s1"), Some(("cli-io", "s1")));
+        assert_eq!(split_session_event("cli-io-"), None);
+        assert_eq!(
+            split_session_event("command-output-s1"),
+            Some(("command-output", "s1"))
+        );
+        assert_eq!(split_session_event("command-output"), None);
+        assert_eq!(split_session_event("terminal-output-t1"), None);
+    }
+
+    #[test]
+    fn test_split_terminal_event() {
+        assert_eq!(split_terminal_event("terminal-output-t1"), Some("t1"));
+        assert_eq!(split_terminal_event("terminal-exit-t1"), Some("t1"));
+        assert_eq!(split_terminal_event("terminal-exit-"), None);
+        assert_eq!(split_terminal_event("cli-io-s1"), None);
+    }
+
     #[test]
     fn test_internal_event_debug() {
         let event = InternalEvent::CliIo {
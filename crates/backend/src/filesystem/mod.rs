@@ -1,8 +1,9 @@
 use crate::types::BackendResult;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum VolumeType {
     LocalDisk,
@@ -13,7 +14,7 @@ pub enum VolumeType {
     FileSystem,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct DirEntry {
     pub name: String,
     pub is_directory: bool,
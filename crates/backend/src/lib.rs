@@ -1,15 +1,22 @@
 // Module declarations
 pub mod cli;
+pub mod daemon;
 pub mod events;
 pub mod filesystem;
 pub mod mcp_registry;
 pub mod models;
+pub mod openapi;
+pub mod operations;
+pub mod paths;
 pub mod projects;
 pub mod rpc;
 pub mod search;
 pub mod security;
 pub mod session;
 pub mod servers;
+pub mod settings;
+pub mod storage;
+pub mod terminal;
 pub mod themes;
 pub mod types;
 
@@ -26,14 +33,18 @@ pub use cli::{
     RequestToolCallConfirmationParams, RequestToolCallConfirmationResult, SendUserMessageParams,
     StreamAssistantMessageChunkParams, UpdateToolCallParams,
 };
+pub use daemon::{DaemonClient, DaemonInfo};
 pub use events::{
-    CliIoPayload, CliIoType, ErrorPayload, EventEmitter, GeminiOutputPayload, GeminiThoughtPayload,
-    InternalEvent, ToolCallConfirmation, ToolCallConfirmationContent, ToolCallConfirmationRequest,
-    ToolCallEvent, ToolCallLocation, ToolCallUpdate,
+    CliIoPayload, CliIoType, CommandOutputPayload, ErrorPayload, EventEmitter, GeminiOutputPayload,
+    GeminiThoughtPayload, InternalEvent, SESSION_EVENT_KINDS, TERMINAL_EVENT_KINDS,
+    ToolCallConfirmation, ToolCallConfirmationContent, ToolCallConfirmationRequest, ToolCallEvent,
+    ToolCallLocation, ToolCallUpdate, split_session_event, split_terminal_event,
 };
 pub use filesystem::{DirEntry, VolumeType};
 pub use mcp_registry::{McpServerInfo, get_mcp_categories, get_popular_mcp_servers, search_mcp_servers};
-pub use models::{ModelInfo, ModelSource, auto_discover_models, get_gemini_models, get_model_sources};
+pub use models::{ModelInfo, ModelSource, auto_discover_models, get_gemini_models, get_model_sources, is_valid_model_name};
+pub use openapi::openapi_document;
+pub use operations::{Access, HttpMethod, Operation, find_operation, operations, snake_case_keys};
 pub use projects::{
     EnrichedProject, ProjectListItem, ProjectMetadata, ProjectMetadataView, ProjectsResponse,
     TouchThrottle, ensure_project_metadata, list_enriched_projects, list_projects,
@@ -41,39 +52,85 @@ pub use projects::{
 };
 pub use rpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse, RpcLogger};
 pub use search::{MessageMatch, RecentChat, SearchFilters, SearchResult};
-pub use security::{execute_terminal_command, is_command_safe};
+pub use security::{
+    CommandExecution, CommandPolicy, ExecutionOptions, OutputStream, PolicyViolation,
+    RedactionConfig, RedactionPattern, ResourceLimits, ScrubReport, execute_terminal_command,
+    is_command_safe, resolve_working_directory, run_command,
+};
 pub use servers::{
     Server, add_server, delete_server, edit_server, list_servers, start_server, stop_server,
 };
+pub use session::metrics::{HistogramSnapshot, MetricsSnapshot, TURN_LATENCY_BUCKETS};
 pub use session::{PersistentSession, ProcessStatus, SessionManager, initialize_session};
+pub use settings::AppSettings;
+pub use storage::{EncryptionStatus, MigrationReport};
+pub use terminal::{
+    TerminalExitPayload, TerminalInfo, TerminalManager, TerminalOptions, TerminalOutputPayload,
+};
 pub use themes::{CustomTheme, ThemeColors, ThemePreset, delete_theme, export_theme_css, generate_theme_css, get_theme_presets, list_themes, load_theme, save_theme};
-pub use types::{BackendError, BackendResult};
+pub use types::{BackendError, BackendResult, ErrorBody};
 
 // Standard library imports
-use std::path::Path;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use tokio::process::Command;
 
-/// Main backend interface for Gemini CLI functionality
+/// Main backend interface for Gemini CLI functionality.
+///
+/// Every method takes `&self` and the backend is `Send + Sync`, so frontends
+/// share one instance behind an `Arc` and call it concurrently.
 pub struct GeminiBackend<E: EventEmitter> {
     emitter: E,
     session_manager: SessionManager,
-    next_request_id: Arc<Mutex<u32>>,
+    terminal_manager: TerminalManager,
+    next_request_id: AtomicU32,
+    /// Held while a session's CLI process starts, so concurrent
+    /// `initialize_session` calls for one id spawn a single process.
+    session_starts: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
     touch_throttle: TouchThrottle,
+    /// Data directory of this backend when it isn't the process-wide one.
+    data_dir: Option<PathBuf>,
+    /// The last RPC log disk usage and when it was measured; walking the
+    /// log tree on every metrics scrape would be too slow.
+    log_usage: Mutex<Option<(Instant, u64)>>,
 }
 
+/// How long a measured RPC log disk usage is reused.
+const LOG_USAGE_TTL: Duration = Duration::from_secs(60);
+
 impl<E: EventEmitter + 'static> GeminiBackend<E> {
     /// Create a new GeminiBackend instance
     pub fn new(emitter: E) -> Self {
         Self {
             emitter,
             session_manager: SessionManager::new(),
-            next_request_id: Arc::new(Mutex::new(1000)),
+            terminal_manager: TerminalManager::new(),
+            next_request_id: AtomicU32::new(1000),
+            session_starts: Mutex::new(HashMap::new()),
             touch_throttle: TouchThrottle::new(Duration::from_secs(60)),
+            data_dir: None,
+            log_usage: Mutex::new(None),
         }
     }
 
+    /// Keeps this backend's projects, logs, servers, themes and settings in
+    /// `data_dir` instead of [`paths::data_dir`], for serving several users
+    /// from one process.
+    pub fn with_data_dir(mut self, data_dir: PathBuf) -> Self {
+        self.data_dir = Some(data_dir);
+        self
+    }
+
+    /// Runs `future` with [`paths::data_dir`] pointing at this backend's data
+    /// directory.
+    pub async fn in_data_dir<F: Future>(&self, future: F) -> F::Output {
+        paths::scope(self.data_dir.clone(), future).await
+    }
+
     // =====================================
     // Event Helper Methods
     // =====================================
@@ -87,7 +144,7 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
     ) -> BackendResult<()> {
         let payload = CliIoPayload {
             io_type,
-            data: data.to_string(),
+            data: security::redact(data).into_owned(),
         };
         self.emitter.emit(&format!("cli-io-{session_id}"), payload)
     }
@@ -150,8 +207,13 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
     }
 
     /// Emit command result event
-    pub fn emit_command_result(&self, result: &CommandResult) -> BackendResult<()> {
-        self.emitter.emit("command-result", result.clone())
+    pub fn emit_command_result(
+        &self,
+        session_id: &str,
+        result: &CommandResult,
+    ) -> BackendResult<()> {
+        self.emitter
+            .emit(&format!("command-result-{session_id}"), result.clone())
     }
 
     /// Check if Gemini CLI is installed and available
@@ -174,12 +236,155 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
         }
     }
 
+    /// Start a session in `working_directory`, or without one just check that
+    /// the CLI is available.
+    pub async fn start_session(
+        &self,
+        session_id: String,
+        working_directory: Option<String>,
+        model: Option<String>,
+    ) -> BackendResult<()> {
+        if let Some(working_directory) = working_directory {
+            let model = model.unwrap_or_else(|| "gemini-2.0-flash-exp".to_string());
+            self.initialize_session(session_id, working_directory, model)
+                .await
+        } else if self.check_cli_installed().await? {
+            Ok(())
+        } else {
+            Err(BackendError::CliNotAvailable)
+        }
+    }
+
+    /// Run `gemini --help` through the shell and report what happened
+    pub async fn test_gemini_command(&self) -> BackendResult<String> {
+        let output = if cfg!(target_os = "windows") {
+            Command::new("cmd")
+                .args(["/C", "gemini", "--help"])
+                .output()
+                .await
+        } else {
+            Command::new("sh")
+                .args(["-c", "gemini --help"])
+                .output()
+                .await
+        }
+        .map_err(|e| {
+            BackendError::CommandExecutionFailed(format!("Failed to run gemini --help: {e}"))
+        })?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        Ok(format!(
+            "Running 'gemini --help' via shell\nExit code: {}\nSTDOUT:\n{}\nSTDERR:\n{}",
+            output.status.code().unwrap_or(-1),
+            stdout,
+            stderr
+        ))
+    }
+
+    /// Describe PATH, HOME and whether the CLI runs, for troubleshooting
+    pub async fn debug_environment(&self) -> BackendResult<String> {
+        let path = std::env::var("PATH").unwrap_or_else(|_| "PATH not found".to_string());
+        let home = std::env::var("HOME").unwrap_or_else(|_| {
+            std::env::var("USERPROFILE").unwrap_or_else(|_| "HOME not found".to_string())
+        });
+
+        let output = if cfg!(target_os = "windows") {
+            Command::new("cmd")
+                .args(["/C", "gemini", "--version"])
+                .output()
+                .await
+        } else {
+            Command::new("sh")
+                .args(["-c", "gemini --version"])
+                .output()
+                .await
+        };
+        let gemini_result = match output {
+            Ok(output) if output.status.success() => {
+                format!(
+                    "Available via shell: {}",
+                    String::from_utf8_lossy(&output.stdout).trim()
+                )
+            }
+            Ok(output) => {
+                format!(
+                    "Shell test failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                )
+            }
+            Err(e) => format!("Shell execution failed: {e}"),
+        };
+
+        let system_path = if cfg!(windows) {
+            match Command::new("cmd").args(["/c", "echo %PATH%"]).output().await {
+                Ok(output) => String::from_utf8_lossy(&output.stdout).to_string(),
+                Err(e) => format!("Failed to get system PATH: {e}"),
+            }
+        } else {
+            "Not Windows".to_string()
+        };
+
+        Ok(format!(
+            "Current PATH (from this process):\n{}\n\nSystem PATH (from cmd):\n{}\n\nHOME: {}\n\nGemini CLI test result:\n{}",
+            path.replace(';', ";\n").replace(':', ":\n"),
+            system_path.replace(';', ";\n").replace(':', ":\n"),
+            home,
+            gemini_result
+        ))
+    }
+
     /// Initialize a new Gemini CLI session
     pub async fn initialize_session(
         &self,
         session_id: String,
         working_directory: String,
         model: String,
+    ) -> BackendResult<()> {
+        storage::ensure_unlocked()?;
+        if self.session_manager.is_shutting_down() {
+            return Err(BackendError::ShuttingDown);
+        }
+        if !models::is_valid_model_name(&model) {
+            return Err(BackendError::InvalidRequest(format!(
+                "invalid model name: {model}"
+            )));
+        }
+
+        let start_lock = self
+            .session_starts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(session_id.clone())
+            .or_default()
+            .clone();
+        let result = {
+            let _starting = start_lock.lock().await;
+            self.in_data_dir(self.start_session_process(
+                session_id.clone(),
+                working_directory,
+                model,
+            ))
+            .await
+        };
+
+        let mut starts = self
+            .session_starts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // Only the map and this call still hold the lock: nobody is waiting.
+        if Arc::strong_count(&start_lock) == 2 {
+            starts.remove(&session_id);
+        }
+        result
+    }
+
+    async fn start_session_process(
+        &self,
+        session_id: String,
+        working_directory: String,
+        model: String,
     ) -> BackendResult<()> {
         {
             let processes = self.session_manager.get_processes();
@@ -208,8 +413,24 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
         session_id: String,
         message: String,
         conversation_history: String,
+    ) -> BackendResult<()> {
+        self.send_message_with_attachments(session_id, message, conversation_history, Vec::new())
+            .await
+    }
+
+    /// Send a message to an existing session along with files for the CLI
+    /// to read, given as paths it can resolve from its working directory.
+    pub async fn send_message_with_attachments(
+        &self,
+        session_id: String,
+        message: String,
+        conversation_history: String,
+        attachments: Vec<String>,
     ) -> BackendResult<()> {
         println!("📤 Sending message to session: {session_id}");
+        if self.session_manager.is_shutting_down() {
+            return Err(BackendError::ShuttingDown);
+        }
 
         let message_sender = {
             let processes = self.session_manager.get_processes();
@@ -237,14 +458,14 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
                 },
             );
         }
+        chunks.extend(
+            attachments
+                .into_iter()
+                .map(|path| MessageChunk::Path { path }),
+        );
         let msg_params = SendUserMessageParams { chunks };
 
-        let request_id = {
-            let mut id_guard = self.next_request_id.lock().unwrap();
-            let id = *id_guard;
-            *id_guard += 1;
-            id
-        };
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
 
         let msg_request = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
@@ -259,6 +480,7 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
 
         message_sender
             .send(request_json)
+            .await
             .map_err(|_| BackendError::ChannelError)?;
 
         println!("✅ Message sent to persistent session: {session_id}");
@@ -277,6 +499,8 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
             "📤 Sending tool call confirmation response: session={session_id}, request_id={request_id}, tool_call_id={tool_call_id}, outcome={outcome}"
         );
 
+        self.session_manager.metrics().tool_confirmed(&outcome);
+        self.session_manager.confirmation_answered(&session_id, request_id);
         let response_data = RequestToolCallConfirmationResult {
             id: tool_call_id.clone(),
             outcome,
@@ -313,27 +537,69 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
         Ok(())
     }
 
-    /// Execute a confirmed command
-    pub async fn execute_confirmed_command(&self, command: String) -> BackendResult<String> {
+    /// Execute a confirmed command in the session's project directory,
+    /// streaming its output as `command-output-<session_id>` events
+    pub async fn execute_confirmed_command(
+        &self,
+        session_id: &str,
+        command: String,
+        mut options: ExecutionOptions,
+    ) -> BackendResult<CommandExecution> {
         println!("🖥️ Executing confirmed command: {command}");
 
-        match execute_terminal_command(&command).await {
-            Ok(output) => {
-                println!("✅ Command executed successfully");
+        let project = self.session_manager.working_directory(session_id)?;
+        let project_root = (!project.is_empty()).then(|| Path::new(&project));
+        if let Err(violation) = CommandPolicy::builtin().check_in(&command, project_root) {
+            println!("❌ Command rejected by policy: {violation}");
+            let _ = self.emit_command_result(session_id, &CommandResult {
+                command,
+                success: false,
+                output: None,
+                error: Some(BackendError::CommandNotAllowed.to_string()),
+            });
+            return Err(BackendError::CommandNotAllowed);
+        }
+
+        options.working_directory =
+            resolve_working_directory(&project, options.working_directory.as_deref())?;
+
+        let execution_id = options
+            .execution_id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        let output_event = format!("command-output-{session_id}");
+        let result = run_command(&command, &options, |stream, data| {
+            let _ = self.emitter.emit(
+                &output_event,
+                CommandOutputPayload {
+                    execution_id: execution_id.clone(),
+                    stream,
+                    data: data.to_string(),
+                },
+            );
+        })
+        .await;
 
-                let _ = self.emit_command_result(&CommandResult {
+        match result {
+            Ok(execution) => {
+                println!(
+                    "✅ Command finished: exit_code={:?}, timed_out={}",
+                    execution.exit_code, execution.timed_out
+                );
+
+                let _ = self.emit_command_result(session_id, &CommandResult {
                     command: command.clone(),
-                    success: true,
-                    output: Some(output.clone()),
-                    error: None,
+                    success: execution.success,
+                    output: Some(execution.stdout.clone()),
+                    error: (!execution.success).then(|| execution.stderr.clone()),
                 });
 
-                Ok(output)
+                Ok(execution)
             }
             Err(error) => {
                 println!("❌ Command execution failed: {error}");
 
-                let _ = self.emit_command_result(&CommandResult {
+                let _ = self.emit_command_result(session_id, &CommandResult {
                     command: command.clone(),
                     success: false,
                     output: None,
@@ -357,6 +623,11 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
         );
 
         let model_to_use = model.unwrap_or_else(|| "gemini-2.5-flash".to_string());
+        if !models::is_valid_model_name(&model_to_use) {
+            return Err(BackendError::InvalidRequest(format!(
+                "invalid model name: {model_to_use}"
+            )));
+        }
 
         let mut child = if cfg!(target_os = "windows") {
             Command::new("cmd")
@@ -430,6 +701,151 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
         self.session_manager.kill_process(conversation_id)
     }
 
+    /// Stops taking new sessions and messages, gives running turns up to
+    /// `grace` to finish, then kills every session and terminal and stops
+    /// the managed servers, recording them as stopped in `servers.json`.
+    pub async fn shutdown(&self, grace: Duration) -> BackendResult<()> {
+        self.session_manager.begin_shutdown();
+
+        let deadline = tokio::time::Instant::now() + grace;
+        while self.session_manager.turns_in_flight() > 0 && tokio::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let unfinished = self.session_manager.turns_in_flight();
+        if unfinished > 0 {
+            println!("⏱️ Stopping sessions with {unfinished} turns still running");
+        }
+
+        let sessions = self.session_manager.kill_all();
+        self.terminal_manager.kill_all();
+        let servers = paths::sync_scope(self.data_dir.clone(), servers::stop_all).map(|_| ());
+        sessions.and(servers)
+    }
+
+    /// The tool call type (`execute`, `edit`, ...) of a confirmation
+    /// request the session's CLI is still waiting on
+    pub fn confirmation_type(&self, session_id: &str, request_id: u32) -> Option<String> {
+        self.session_manager.confirmation_type(session_id, request_id)
+    }
+
+    /// Session, turn and confirmation counters, and RPC log disk usage
+    pub fn metrics(&self) -> MetricsSnapshot {
+        let mut snapshot = self.session_manager.metrics_snapshot();
+        snapshot.rpc_log_bytes = self.log_disk_usage();
+        snapshot
+    }
+
+    /// Disk space used by this backend's RPC logs, measured at most once
+    /// per [`LOG_USAGE_TTL`].
+    fn log_disk_usage(&self) -> u64 {
+        let mut cached = self.log_usage.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((measured_at, bytes)) = *cached
+            && measured_at.elapsed() < LOG_USAGE_TTL
+        {
+            return bytes;
+        }
+        let bytes =
+            paths::sync_scope(self.data_dir.clone(), rpc::log_disk_usage).unwrap_or_default();
+        *cached = Some((Instant::now(), bytes));
+        bytes
+    }
+
+    /// Spawn a new PTY-backed terminal
+    pub fn create_terminal(&self, options: TerminalOptions) -> BackendResult<TerminalInfo> {
+        self.terminal_manager.create(options, self.emitter.clone())
+    }
+
+    /// Write input to a terminal
+    pub async fn write_terminal(&self, terminal_id: &str, data: &str) -> BackendResult<()> {
+        self.terminal_manager.write(terminal_id, data).await
+    }
+
+    /// Resize a terminal
+    pub fn resize_terminal(&self, terminal_id: &str, cols: u16, rows: u16) -> BackendResult<()> {
+        self.terminal_manager.resize(terminal_id, cols, rows)
+    }
+
+    /// Kill a terminal
+    pub fn kill_terminal(&self, terminal_id: &str) -> BackendResult<()> {
+        self.terminal_manager.kill(terminal_id)
+    }
+
+    /// List all terminals
+    pub fn list_terminals(&self) -> BackendResult<Vec<TerminalInfo>> {
+        self.terminal_manager.list()
+    }
+
+    /// Send the last `lines` lines of a terminal to a chat session as context
+    pub async fn send_terminal_to_chat(
+        &self,
+        terminal_id: String,
+        session_id: String,
+        lines: usize,
+        message: Option<String>,
+    ) -> BackendResult<()> {
+        let output = self.terminal_manager.recent_lines(&terminal_id, lines)?;
+        let mut text = format!("Terminal output (last {} lines):\n```\n", output.len());
+        for line in &output {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push_str("```");
+        if let Some(message) = message.filter(|m| !m.trim().is_empty()) {
+            text = format!("{text}\n\n{message}");
+        }
+        self.send_message(session_id, text, String::new()).await
+    }
+
+    /// Get the active secret redaction settings
+    pub fn get_redaction_config(&self) -> RedactionConfig {
+        security::redactor().config().clone()
+    }
+
+    /// Validate, persist and activate new secret redaction settings
+    pub fn save_redaction_config(&self, config: RedactionConfig) -> BackendResult<()> {
+        security::save_redaction_config(config)
+    }
+
+    /// Redact secrets from all existing RPC logs on disk
+    pub async fn scrub_rpc_logs(&self) -> BackendResult<ScrubReport> {
+        let data_dir = self.data_dir.clone();
+        tokio::task::spawn_blocking(move || paths::sync_scope(data_dir, security::scrub_logs))
+            .await
+            .map_err(|e| BackendError::CommandExecutionFailed(e.to_string()))?
+    }
+
+    /// Get whether encryption at rest is enabled and unlocked
+    pub fn get_encryption_status(&self) -> EncryptionStatus {
+        storage::status()
+    }
+
+    /// Enable encryption at rest with a new passphrase
+    pub async fn enable_encryption(&self, passphrase: String) -> BackendResult<()> {
+        tokio::task::spawn_blocking(move || storage::enable(&passphrase))
+            .await
+            .map_err(|e| BackendError::EncryptionError(e.to_string()))?
+    }
+
+    /// Unlock encrypted storage with the user's passphrase
+    pub async fn unlock_storage(&self, passphrase: String) -> BackendResult<()> {
+        tokio::task::spawn_blocking(move || storage::unlock(&passphrase))
+            .await
+            .map_err(|e| BackendError::EncryptionError(e.to_string()))?
+    }
+
+    /// Forget the in-memory encryption key
+    pub fn lock_storage(&self) -> BackendResult<()> {
+        storage::lock()
+    }
+
+    /// Encrypt existing plaintext logs and project metadata
+    pub async fn migrate_storage(&self) -> BackendResult<MigrationReport> {
+        tokio::task::spawn_blocking(storage::migrate)
+            .await
+            .map_err(|e| BackendError::EncryptionError(e.to_string()))?
+    }
+
     /// Validate if a directory exists and is accessible
     pub async fn validate_directory(&self, path: String) -> BackendResult<bool> {
         filesystem::validate_directory(path).await
@@ -505,3 +921,178 @@ impl<E: EventEmitter + 'static> GeminiBackend<E> {
         search::get_project_discussions(project_id).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::MockEventEmitter;
+    use tokio::task::JoinSet;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_backend_is_send_and_sync() {
+        assert_send_sync::<GeminiBackend<MockEventEmitter>>();
+    }
+
+    /// A slow request, here a title from a CLI that takes seconds to answer,
+    /// must not hold up the fast ones running next to it.
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_requests_share_one_backend() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let started = temp_dir.path().join("started");
+        let cli = temp_dir.path().join("gemini");
+        // Only the title request (`gemini --model <model>`) is slow; anything
+        // else sees no CLI.
+        std::fs::write(
+            &cli,
+            format!(
+                "#!/bin/sh\n[ \"$1\" = --model ] && [ $# -eq 2 ] || exit 127\ntouch '{}'\nsleep 3\necho 'Slow Title'\n",
+                started.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut env_guard = test_utils::EnvGuard::new();
+        let path = std::env::var("PATH").unwrap_or_default();
+        env_guard.set("PATH", format!("{}:{path}", temp_dir.path().display()));
+
+        let backend = Arc::new(GeminiBackend::new(MockEventEmitter::new()));
+        let slow = tokio::spawn({
+            let backend = Arc::clone(&backend);
+            async move {
+                backend
+                    .generate_conversation_title("hello".to_string(), None)
+                    .await
+            }
+        });
+        while !started.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let begun = std::time::Instant::now();
+        let mut tasks = JoinSet::new();
+        for i in 0..256 {
+            let backend = Arc::clone(&backend);
+            tasks.spawn(async move {
+                let session_id = format!("missing-{i}");
+                match i % 4 {
+                    0 => backend.get_process_statuses().map(drop),
+                    1 => match backend
+                        .send_message(session_id, "hello".to_string(), String::new())
+                        .await
+                    {
+                        Err(BackendError::SessionNotFound(_)) => Ok(()),
+                        other => other,
+                    },
+                    2 => backend.list_terminals().map(drop),
+                    _ => backend.kill_process(&session_id),
+                }
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result.unwrap().unwrap();
+        }
+        let elapsed = begun.elapsed();
+        assert!(
+            elapsed < Duration::from_secs(1),
+            "fast requests waited {elapsed:?} for the slow one"
+        );
+        assert!(!slow.is_finished());
+
+        assert_eq!(slow.await.unwrap().unwrap(), "Slow Title");
+    }
+
+    #[tokio::test]
+    async fn test_model_names_cannot_reach_the_shell() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut env_guard = test_utils::EnvGuard::new();
+        env_guard.set("HOME", temp_dir.path().to_str().unwrap());
+
+        let backend = GeminiBackend::new(MockEventEmitter::new());
+        for model in ["", "gemini-2.5-flash; touch pwned", "$(id)", "a b"] {
+            let result = backend
+                .initialize_session("s1".to_string(), String::new(), model.to_string())
+                .await;
+            assert!(
+                matches!(result, Err(BackendError::InvalidRequest(_))),
+                "{model:?}"
+            );
+            let title = backend
+                .generate_conversation_title("Hi".to_string(), Some(model.to_string()))
+                .await;
+            assert!(
+                matches!(title, Err(BackendError::InvalidRequest(_))),
+                "{model:?}"
+            );
+        }
+        assert!(is_valid_model_name("gemini-2.5-flash"));
+        assert!(is_valid_model_name("models/gemini-2.5-pro"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_turns_then_refuses_new_ones() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut env_guard = test_utils::EnvGuard::new();
+        env_guard.set("HOME", temp_dir.path().to_str().unwrap());
+
+        let backend = Arc::new(GeminiBackend::new(MockEventEmitter::new()));
+        let metrics = backend.session_manager.metrics();
+        metrics.turn_started();
+        let finishing = {
+            let backend = Arc::clone(&backend);
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                backend
+                    .session_manager
+                    .metrics()
+                    .turn_ended(session::metrics::TurnOutcome::Finished, Duration::ZERO);
+            })
+        };
+
+        let started = std::time::Instant::now();
+        backend.shutdown(Duration::from_secs(10)).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        finishing.await.unwrap();
+
+        let result = backend
+            .send_message("s1".to_string(), "hi".to_string(), String::new())
+            .await;
+        assert!(matches!(result, Err(BackendError::ShuttingDown)));
+        let result = backend
+            .initialize_session("s1".to_string(), String::new(), "m".to_string())
+            .await;
+        assert!(matches!(result, Err(BackendError::ShuttingDown)));
+
+        // A turn that never ends only delays shutdown by the grace period.
+        metrics.turn_started();
+        let started = std::time::Instant::now();
+        backend.shutdown(Duration::from_millis(50)).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_operations_use_the_backend_data_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut env_guard = test_utils::EnvGuard::new();
+        env_guard.set("HOME", temp_dir.path().to_str().unwrap());
+        let user_dir = temp_dir.path().join("users").join("alice");
+
+        let backend = Arc::new(
+            GeminiBackend::new(MockEventEmitter::new()).with_data_dir(user_dir.clone()),
+        );
+        let add_server = find_operation::<MockEventEmitter>("add_server").unwrap();
+        let params = serde_json::json!({"name": "s", "port": 9000, "model": "gemini-2.5-flash"});
+        add_server.call(backend.clone(), params).await.unwrap();
+
+        assert!(user_dir.join("servers.json").exists());
+        assert!(!temp_dir.path().join(".gemini-desktop/servers.json").exists());
+        assert_eq!(servers::list_servers().unwrap().len(), 0);
+        let listed = backend.in_data_dir(async { servers::list_servers() }).await;
+        assert_eq!(listed.unwrap().len(), 1);
+    }
+}
@@ -9,6 +9,21 @@ impl<E: crate::EventEmitter + 'static> crate::GeminiBackend<E> {
         models::auto_discover_models().await
     }
 
+    /// Names of the discovered models, Gemini's first
+    pub async fn get_available_models(&self) -> BackendResult<Vec<String>> {
+        let mut discovered = models::auto_discover_models().await?;
+        let mut names: Vec<String> = discovered
+            .remove("gemini")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|model| model.name)
+            .collect();
+        let mut sources: Vec<_> = discovered.into_iter().collect();
+        sources.sort_by(|a, b| a.0.cmp(&b.0));
+        names.extend(sources.into_iter().flat_map(|(_, models)| models).map(|model| model.name));
+        Ok(names)
+    }
+
     /// Get available model sources
     pub async fn get_model_sources(&self) -> BackendResult<Vec<models::ModelSource>> {
         models::get_model_sources().await
@@ -53,9 +68,4 @@ impl<E: crate::EventEmitter + 'static> crate::GeminiBackend<E> {
     pub fn get_theme_presets(&self) -> Vec<themes::ThemePreset> {
         themes::get_theme_presets()
     }
-
-    /// Export theme as CSS
-    pub async fn export_theme_css(&self, theme: &themes::CustomTheme, output_path: &str) -> BackendResult<()> {
-        themes::export_theme_css(theme, output_path).await
-    }
 }
\ No newline at end of file
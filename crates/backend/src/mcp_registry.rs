@@ -1,7 +1,8 @@
 use crate::types::BackendResult;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct McpServerInfo {
     pub name: String,
     pub description: String,
@@ -35,7 +36,7 @@ pub async fn search_mcp_servers(query: Option<String>) -> BackendResult<Vec<McpS
     }
     
     // Sort by stars descending
-    servers.sort_by(|a, b| b.stars.cmp(&a.stars));
+    servers.sort_by_key(|s| std::cmp::Reverse(s.stars));
     
     Ok(servers)
 }
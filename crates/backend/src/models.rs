@@ -1,9 +1,10 @@
 use crate::types::BackendResult;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tokio::process::Command;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ModelInfo {
     pub name: String,
     pub provider: String,
@@ -13,13 +14,22 @@ pub struct ModelInfo {
     pub is_available: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ModelSource {
     pub name: String,
     pub url: String,
     pub api_key_required: bool,
 }
 
+/// Whether `name` can be passed to `gemini --model`. Sessions start the CLI
+/// through a shell, so only the characters model names use are accepted.
+pub fn is_valid_model_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
 /// Get available models from Gemini CLI
 pub async fn get_gemini_models() -> BackendResult<Vec<ModelInfo>> {
     let output = if cfg!(target_os = "windows") {
@@ -0,0 +1,264 @@
+//! OpenAPI description of the REST API, built from the operation registry.
+//!
+//! Schemas come from the request and response types themselves, so the
+//! document can't drift from the code. Events sent over the WebSocket are
+//! listed under `x-events`, with their payload schemas alongside the others
+//! in `components.schemas`.
+
+use crate::EventEmitter;
+use crate::events::event_schemas;
+use crate::operations::{Access, HttpMethod, operations};
+use crate::types::{BackendResult, ErrorBody};
+use schemars::r#gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Schema, SingleOrVec};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+/// Stands in for a real emitter: only the operations' schemas are used.
+#[derive(Clone)]
+struct NoEmitter;
+
+impl EventEmitter for NoEmitter {
+    fn emit<S: Serialize + Clone>(&self, _event: &str, _payload: S) -> BackendResult<()> {
+        Ok(())
+    }
+}
+
+/// The OpenAPI 3.0 document for every registered operation.
+pub fn openapi_document() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let error = to_json(&generator.subschema_for::<ErrorBody>());
+
+    let mut paths = Map::new();
+    for op in operations::<NoEmitter>() {
+        let path_params: Vec<_> = op.path_params().map(|(_, name)| name).collect();
+        let mut parameters: Vec<Value> = path_params
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": {"type": "string"},
+                })
+            })
+            .collect();
+
+        let request = (op.request)(&mut generator);
+        let (properties, required) = object_fields(&generator, &request);
+        let fields: Vec<_> = properties
+            .into_iter()
+            .filter(|(name, _)| !path_params.contains(&name.as_str()))
+            .collect();
+
+        let mut operation = json!({
+            "operationId": op.name,
+            "summary": op.summary,
+            "x-access": op.access,
+        });
+        if op.method == HttpMethod::Get {
+            parameters.extend(fields.into_iter().map(|(name, schema)| {
+                json!({
+                    "name": name,
+                    "in": "query",
+                    "required": required.contains(&name),
+                    "schema": schema,
+                })
+            }));
+        } else if !fields.is_empty() {
+            operation["requestBody"] = json!({
+                "required": fields.iter().any(|(name, _)| required.contains(name)),
+                "content": {"application/json": {"schema": to_json(&request)}},
+            });
+        }
+        if !parameters.is_empty() {
+            operation["parameters"] = Value::Array(parameters);
+        }
+
+        let response = (op.response)(&mut generator);
+        let success = if is_null(&response) {
+            json!({"description": "Done"})
+        } else {
+            json!({
+                "description": "Success",
+                "content": {"application/json": {"schema": to_json(&response)}},
+            })
+        };
+        let mut responses = json!({
+            "200": success,
+            "default": {
+                "description": "Error",
+                "content": {"application/json": {"schema": error}},
+            },
+        });
+        if op.access == Access::Write {
+            responses["403"] = json!({"description": "The server is read-only"});
+        }
+        operation["responses"] = responses;
+
+        let path = op.path.replace('<', "{").replace('>', "}");
+        let method = match op.method {
+            HttpMethod::Get => "get",
+            HttpMethod::Post => "post",
+            HttpMethod::Put => "put",
+            HttpMethod::Delete => "delete",
+        };
+        if let Some(item) = paths
+            .entry(path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+        {
+            item.insert(method.to_string(), operation);
+        }
+    }
+
+    let events: Vec<Value> = event_schemas()
+        .into_iter()
+        .map(|event| {
+            json!({
+                "name": event.name,
+                "summary": event.summary,
+                "payload": to_json(&(event.payload)(&mut generator)),
+            })
+        })
+        .collect();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Gemini Desktop API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{"url": "/api"}],
+        "security": [{"bearerAuth": []}],
+        "paths": paths,
+        "components": {
+            "schemas": generator.take_definitions(),
+            "securitySchemes": {
+                "bearerAuth": {"type": "http", "scheme": "bearer"},
+            },
+        },
+        "x-events": events,
+    })
+}
+
+fn to_json(schema: &Schema) -> Value {
+    serde_json::to_value(schema).unwrap_or(Value::Null)
+}
+
+/// Follows a `$ref` to the generator's definition of that schema.
+fn resolve<'a>(generator: &'a SchemaGenerator, schema: &'a Schema) -> Option<&'a Schema> {
+    let Schema::Object(object) = schema else {
+        return Some(schema);
+    };
+    match &object.reference {
+        Some(reference) => reference
+            .rsplit('/')
+            .next()
+            .and_then(|name| generator.definitions().get(name)),
+        None => Some(schema),
+    }
+}
+
+/// Properties of an object schema, and which of them are required.
+fn object_fields(
+    generator: &SchemaGenerator,
+    schema: &Schema,
+) -> (Vec<(String, Value)>, Vec<String>) {
+    let Some(Schema::Object(object)) = resolve(generator, schema) else {
+        return (Vec::new(), Vec::new());
+    };
+    let Some(validation) = &object.object else {
+        return (Vec::new(), Vec::new());
+    };
+    let properties = validation
+        .properties
+        .iter()
+        .map(|(name, schema)| (name.clone(), to_json(schema)))
+        .collect();
+    (properties, validation.required.iter().cloned().collect())
+}
+
+fn is_null(schema: &Schema) -> bool {
+    matches!(
+        schema,
+        Schema::Object(object)
+            if object.instance_type == Some(SingleOrVec::Single(Box::new(InstanceType::Null)))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn snapshot_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../docs/openapi.json")
+    }
+
+    /// The published document must match the code. After changing an
+    /// operation or event type, regenerate it with
+    /// `UPDATE_OPENAPI=1 cargo test -p backend openapi`.
+    #[test]
+    fn test_openapi_snapshot_is_current() {
+        let document = openapi_document();
+        let path = snapshot_path();
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            let text = serde_json::to_string_pretty(&document).unwrap();
+            std::fs::write(&path, text + "\n").unwrap();
+            return;
+        }
+
+        let snapshot: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(
+            snapshot == document,
+            "docs/openapi.json is out of date; run `UPDATE_OPENAPI=1 cargo test -p backend openapi`"
+        );
+    }
+
+    #[test]
+    fn test_document_covers_operations_and_events() {
+        let document = openapi_document();
+        let schemas = &document["components"]["schemas"];
+
+        let write = &document["paths"]["/terminals/{terminal_id}/write"]["post"];
+        assert_eq!(write["operationId"], "write_terminal");
+        assert_eq!(write["parameters"][0]["name"], "terminal_id");
+        assert!(write["requestBody"].is_object());
+
+        let projects = &document["paths"]["/projects"]["get"];
+        let names: Vec<_> = projects["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["limit", "offset"]);
+        assert_eq!(
+            projects["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ProjectsResponse"
+        );
+        assert!(schemas["SearchResult"].is_object());
+        assert!(schemas["StartSessionRequest"].is_object());
+
+        let count = document["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|item| item.as_object().unwrap().len())
+            .sum::<usize>();
+        assert_eq!(count, operations::<NoEmitter>().len());
+
+        let events = document["x-events"].as_array().unwrap();
+        let confirmation = events
+            .iter()
+            .find(|e| e["name"] == "gemini-tool-call-confirmation-{session_id}")
+            .unwrap();
+        assert_eq!(
+            confirmation["payload"]["$ref"],
+            "#/components/schemas/ToolCallConfirmationRequest"
+        );
+        assert!(schemas["ToolCallConfirmationRequest"]["properties"]["requestId"].is_object());
+    }
+}
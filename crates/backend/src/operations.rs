@@ -0,0 +1,724 @@
+//! The operations both frontends expose, declared once.
+//!
+//! Each entry names the Tauri command (also the WebSocket JSON-RPC method),
+//! the REST route serving it under `/api`, whether it changes state, and its
+//! request and response types. The desktop app and the web server mount
+//! every entry of [`operations`] instead of wrapping backend calls by hand.
+//!
+//! Requests are JSON objects. Top-level keys may be `snake_case` or
+//! `camelCase` (Tauri's `invoke` sends the latter), and REST path segments
+//! such as `<terminal_id>` are filled in as fields of the same name.
+
+use crate::security::{CommandExecution, ExecutionOptions, RedactionConfig, ScrubReport};
+use crate::settings::{self, AppSettings};
+use crate::themes::{CustomTheme, ThemePreset};
+use crate::types::{BackendError, BackendResult};
+use crate::{
+    DirEntry, EncryptionStatus, EnrichedProject, EventEmitter, GeminiBackend, McpServerInfo,
+    MigrationReport, ModelInfo, ModelSource, ProcessStatus, ProjectsResponse, RecentChat,
+    SearchFilters, SearchResult, Server, TerminalInfo, TerminalOptions, servers,
+};
+use schemars::JsonSchema;
+use schemars::r#gen::SchemaGenerator;
+use schemars::schema::Schema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    /// Changes state; refused when the web server runs read-only.
+    Write,
+}
+
+pub type OperationFuture = Pin<Box<dyn Future<Output = BackendResult<Value>> + Send>>;
+
+type Call<E> = fn(Arc<GeminiBackend<E>>, Value) -> OperationFuture;
+
+/// Adds a type's schema to a generator, returning a reference to it.
+pub type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+pub struct Operation<E: EventEmitter + 'static> {
+    pub name: &'static str,
+    pub method: HttpMethod,
+    /// Route under `/api`, with `<field>` placeholders for path segments.
+    pub path: &'static str,
+    pub access: Access,
+    pub summary: &'static str,
+    pub request: SchemaFn,
+    pub response: SchemaFn,
+    call: Call<E>,
+}
+
+impl<E: EventEmitter + 'static> Clone for Operation<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E: EventEmitter + 'static> Copy for Operation<E> {}
+
+impl<E: EventEmitter + 'static> Operation<E> {
+    /// Runs the operation with a JSON object of parameters, in the
+    /// backend's data directory.
+    pub fn call(&self, backend: Arc<GeminiBackend<E>>, params: Value) -> OperationFuture {
+        let data_dir = backend.data_dir.clone();
+        let future = (self.call)(backend, snake_case_keys(params));
+        Box::pin(crate::paths::scope(data_dir, future))
+    }
+
+    /// Names of the `<field>` placeholders in [`Self::path`], in order.
+    pub fn path_params(&self) -> impl Iterator<Item = (usize, &'static str)> {
+        self.path
+            .trim_start_matches('/')
+            .split('/')
+            .enumerate()
+            .filter_map(|(index, segment)| {
+                segment
+                    .strip_prefix('<')
+                    .and_then(|s| s.strip_suffix('>'))
+                    .map(|name| (index, name))
+            })
+    }
+}
+
+/// Looks up an operation by its command name.
+pub fn find_operation<E: EventEmitter + 'static>(name: &str) -> Option<Operation<E>> {
+    operations().into_iter().find(|op| op.name == name)
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> BackendResult<T> {
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| BackendError::InvalidRequest(e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: T) -> BackendResult<Value> {
+    serde_json::to_value(value).map_err(|e| BackendError::JsonError(e.to_string()))
+}
+
+/// Renames camelCase parameter keys to snake_case, as [`Operation::call`]
+/// does before running an operation. Anything checking the parameters
+/// first should look at them through this, or `sessionId` would slip past
+/// a check of `session_id`.
+pub fn snake_case_keys(params: Value) -> Value {
+    let Value::Object(map) = params else {
+        return params;
+    };
+    Value::Object(
+        map.into_iter()
+            .map(|(key, value)| {
+                let mut snake = String::with_capacity(key.len() + 4);
+                for c in key.chars() {
+                    if c.is_ascii_uppercase() {
+                        snake.push('_');
+                        snake.push(c.to_ascii_lowercase());
+                    } else {
+                        snake.push(c);
+                    }
+                }
+                (snake, value)
+            })
+            .collect(),
+    )
+}
+
+/// Accepts a number or, as query strings carry them, its text.
+fn lenient_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrText<T> {
+        Number(T),
+        Text(String),
+    }
+
+    match Option::<NumberOrText<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrText::Number(n)) => Ok(Some(n)),
+        Some(NumberOrText::Text(text)) => text
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("invalid number: {text}"))),
+    }
+}
+
+// =====================================
+// Request Types
+// =====================================
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct NoParams {}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct StartSessionRequest {
+    pub session_id: String,
+    pub working_directory: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SendMessageRequest {
+    pub session_id: String,
+    pub message: String,
+    pub conversation_history: String,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct KillProcessRequest {
+    pub conversation_id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ToolConfirmationRequest {
+    pub session_id: String,
+    pub request_id: u32,
+    pub tool_call_id: String,
+    pub outcome: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExecuteCommandRequest {
+    /// The session that asked for the command; it runs in its project.
+    pub session_id: String,
+    pub command: String,
+    #[serde(default)]
+    pub options: ExecutionOptions,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GenerateTitleRequest {
+    pub message: String,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateTerminalRequest {
+    #[serde(default)]
+    pub options: TerminalOptions,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TerminalRequest {
+    pub terminal_id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TerminalWriteRequest {
+    pub terminal_id: String,
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TerminalResizeRequest {
+    pub terminal_id: String,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TerminalToChatRequest {
+    pub terminal_id: String,
+    pub session_id: String,
+    pub lines: usize,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RedactionConfigRequest {
+    pub config: RedactionConfig,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PassphraseRequest {
+    pub passphrase: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PathRequest {
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchChatsRequest {
+    pub query: String,
+    pub filters: Option<SearchFilters>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListProjectsRequest {
+    #[serde(default, deserialize_with = "lenient_number")]
+    #[schemars(with = "Option<u32>")]
+    pub limit: Option<u32>,
+    #[serde(default, deserialize_with = "lenient_number")]
+    #[schemars(with = "Option<u32>")]
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetProjectRequest {
+    pub sha256: String,
+    pub external_root_path: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ProjectDiscussionsRequest {
+    pub project_id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AddServerRequest {
+    pub name: String,
+    pub port: u16,
+    pub model: String,
+    pub working_directory: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EditServerRequest {
+    pub id: String,
+    pub name: String,
+    pub port: u16,
+    pub model: String,
+    pub working_directory: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ServerIdRequest {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchMcpServersRequest {
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PopularMcpServersRequest {
+    #[serde(default, deserialize_with = "lenient_number")]
+    #[schemars(with = "Option<usize>")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ThemeRequest {
+    pub theme: CustomTheme,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ThemeNameRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SaveSettingsRequest {
+    pub settings: AppSettings,
+}
+
+// =====================================
+// Registry
+// =====================================
+
+macro_rules! operations {
+    ($(
+        $(#[doc = $summary:literal])+
+        $name:ident: $method:ident $path:literal, $access:ident,
+        |$backend:ident, $req:tt: $req_ty:ty| -> $resp_ty:ty $body:block
+    )*) => {
+        vec![$(
+            Operation {
+                name: stringify!($name),
+                method: HttpMethod::$method,
+                path: $path,
+                access: Access::$access,
+                summary: concat!($($summary),+).trim_ascii(),
+                request: |generator| generator.subschema_for::<$req_ty>(),
+                response: |generator| generator.subschema_for::<$resp_ty>(),
+                call: |$backend, params| {
+                    Box::pin(async move {
+                        let $req: $req_ty = parse(params)?;
+                        let response: $resp_ty = $body;
+                        to_value(response)
+                    })
+                },
+            }
+        ),*]
+    };
+}
+
+/// Every operation, in the order they are documented.
+pub fn operations<E: EventEmitter + 'static>() -> Vec<Operation<E>> {
+    operations! {
+        // Sessions
+
+        /// Whether the Gemini CLI can be run.
+        check_cli_installed: Get "/check-cli-installed", Read,
+        |backend, _: NoParams| -> bool { backend.check_cli_installed().await? }
+
+        /// Start a CLI session in a working directory.
+        start_session: Post "/start-session", Write,
+        |backend, req: StartSessionRequest| -> () {
+            backend.start_session(req.session_id, req.working_directory, req.model).await?
+        }
+
+        /// Send a user message to a running session.
+        send_message: Post "/send-message", Write,
+        |backend, req: SendMessageRequest| -> () {
+            backend.send_message(req.session_id, req.message, req.conversation_history).await?
+        }
+
+        /// State of every session process.
+        get_process_statuses: Get "/process-statuses", Read,
+        |backend, _: NoParams| -> Vec<ProcessStatus> { backend.get_process_statuses()? }
+
+        /// Stop a session's CLI process.
+        kill_process: Post "/kill-process", Write,
+        |backend, req: KillProcessRequest| -> () { backend.kill_process(&req.conversation_id)? }
+
+        /// Answer a tool call confirmation request.
+        send_tool_call_confirmation_response: Post "/tool-confirmation", Write,
+        |backend, req: ToolConfirmationRequest| -> () {
+            backend
+                .handle_tool_confirmation(req.session_id, req.request_id, req.tool_call_id, req.outcome)
+                .await?
+        }
+
+        /// Run a command the user confirmed, in the session's project directory.
+        execute_confirmed_command: Post "/execute-command", Write,
+        |backend, req: ExecuteCommandRequest| -> CommandExecution {
+            backend.execute_confirmed_command(&req.session_id, req.command, req.options).await?
+        }
+
+        /// Suggest a title for a conversation from its first message.
+        generate_conversation_title: Post "/generate-title", Read,
+        |backend, req: GenerateTitleRequest| -> String {
+            backend.generate_conversation_title(req.message, req.model).await?
+        }
+
+        /// Output of `gemini --help`.
+        test_gemini_command: Get "/test-gemini-command", Read,
+        |backend, _: NoParams| -> String { backend.test_gemini_command().await? }
+
+        /// PATH, HOME and CLI availability, for troubleshooting.
+        debug_environment: Get "/debug-environment", Read,
+        |backend, _: NoParams| -> String { backend.debug_environment().await? }
+
+        // Terminals
+
+        /// Open terminals.
+        list_terminals: Get "/terminals", Read,
+        |backend, _: NoParams| -> Vec<TerminalInfo> { backend.list_terminals()? }
+
+        /// Open a terminal.
+        create_terminal: Post "/terminals", Write,
+        |backend, req: CreateTerminalRequest| -> TerminalInfo { backend.create_terminal(req.options)? }
+
+        /// Type into a terminal.
+        write_terminal: Post "/terminals/<terminal_id>/write", Write,
+        |backend, req: TerminalWriteRequest| -> () { backend.write_terminal(&req.terminal_id, &req.data).await? }
+
+        /// Resize a terminal.
+        resize_terminal: Post "/terminals/<terminal_id>/resize", Write,
+        |backend, req: TerminalResizeRequest| -> () {
+            backend.resize_terminal(&req.terminal_id, req.cols, req.rows)?
+        }
+
+        /// Close a terminal.
+        kill_terminal: Delete "/terminals/<terminal_id>", Write,
+        |backend, req: TerminalRequest| -> () { backend.kill_terminal(&req.terminal_id)? }
+
+        /// Send a terminal's last lines to a session.
+        send_terminal_to_chat: Post "/terminals/<terminal_id>/send-to-chat", Write,
+        |backend, req: TerminalToChatRequest| -> () {
+            backend
+                .send_terminal_to_chat(req.terminal_id, req.session_id, req.lines, req.message)
+                .await?
+        }
+
+        // Privacy and storage
+
+        /// Patterns redacted from logs.
+        get_redaction_config: Get "/redaction-config", Read,
+        |backend, _: NoParams| -> RedactionConfig { backend.get_redaction_config() }
+
+        /// Replace the redaction patterns.
+        save_redaction_config: Post "/redaction-config", Write,
+        |backend, req: RedactionConfigRequest| -> () { backend.save_redaction_config(req.config)? }
+
+        /// Redact existing RPC logs.
+        scrub_rpc_logs: Post "/scrub-logs", Write,
+        |backend, _: NoParams| -> ScrubReport { backend.scrub_rpc_logs().await? }
+
+        /// Whether storage is encrypted and unlocked.
+        get_encryption_status: Get "/storage/status", Read,
+        |backend, _: NoParams| -> EncryptionStatus { backend.get_encryption_status() }
+
+        /// Encrypt storage with a passphrase.
+        enable_encryption: Post "/storage/enable", Write,
+        |backend, req: PassphraseRequest| -> () { backend.enable_encryption(req.passphrase).await? }
+
+        /// Unlock encrypted storage.
+        unlock_storage: Post "/storage/unlock", Write,
+        |backend, req: PassphraseRequest| -> () { backend.unlock_storage(req.passphrase).await? }
+
+        /// Forget the storage key until the next unlock.
+        lock_storage: Post "/storage/lock", Write,
+        |backend, _: NoParams| -> () { backend.lock_storage()? }
+
+        /// Encrypt logs written before encryption was enabled.
+        migrate_storage: Post "/storage/migrate", Write,
+        |backend, _: NoParams| -> MigrationReport { backend.migrate_storage().await? }
+
+        // Filesystem
+
+        /// Whether a path is an existing directory.
+        validate_directory: Post "/validate-directory", Read,
+        |backend, req: PathRequest| -> bool { backend.validate_directory(req.path).await? }
+
+        /// Whether a path is the home directory.
+        is_home_directory: Post "/is-home-directory", Read,
+        |backend, req: PathRequest| -> bool { backend.is_home_directory(req.path).await? }
+
+        /// The user's home directory.
+        get_home_directory: Get "/get-home-directory", Read,
+        |backend, _: NoParams| -> String { backend.get_home_directory().await? }
+
+        /// A path's parent directory, if any.
+        get_parent_directory: Post "/get-parent-directory", Read,
+        |backend, req: PathRequest| -> Option<String> { backend.get_parent_directory(req.path).await? }
+
+        /// Entries of a directory.
+        list_directory_contents: Post "/list-directory", Read,
+        |backend, req: PathRequest| -> Vec<DirEntry> { backend.list_directory_contents(req.path).await? }
+
+        /// Mounted volumes and drives.
+        list_volumes: Get "/list-volumes", Read,
+        |backend, _: NoParams| -> Vec<DirEntry> { backend.list_volumes().await? }
+
+        // Chats and projects
+
+        /// Most recent conversations.
+        get_recent_chats: Get "/recent-chats", Read,
+        |backend, _: NoParams| -> Vec<RecentChat> { backend.get_recent_chats().await? }
+
+        /// Full-text search over conversations.
+        search_chats: Post "/search-chats", Read,
+        |backend, req: SearchChatsRequest| -> Vec<SearchResult> {
+            backend.search_chats(req.query, req.filters).await?
+        }
+
+        /// A page of projects.
+        list_projects: Get "/projects", Read,
+        |backend, req: ListProjectsRequest| -> ProjectsResponse {
+            backend.list_projects(req.limit.unwrap_or(25), req.offset.unwrap_or(0)).await?
+        }
+
+        /// Every project with its metadata.
+        list_enriched_projects: Get "/projects-enriched", Read,
+        |backend, _: NoParams| -> Vec<EnrichedProject> { backend.list_enriched_projects().await? }
+
+        /// One project with its metadata.
+        get_project: Get "/project", Read,
+        |backend, req: GetProjectRequest| -> EnrichedProject {
+            backend.get_enriched_project(req.sha256, req.external_root_path).await?
+        }
+
+        /// Conversations of a project.
+        get_project_discussions: Get "/projects/<project_id>/discussions", Read,
+        |backend, req: ProjectDiscussionsRequest| -> Vec<RecentChat> {
+            backend.get_project_discussions(&req.project_id).await?
+        }
+
+        // Servers
+
+        /// Configured Gemini CLI servers.
+        list_servers: Get "/servers", Read,
+        |_backend, _: NoParams| -> Vec<Server> { servers::list_servers()? }
+
+        /// Add a server.
+        add_server: Post "/servers", Write,
+        |_backend, req: AddServerRequest| -> Vec<Server> {
+            let server = Server::new(req.name, req.port, req.model, req.working_directory.unwrap_or_default());
+            servers::add_server(server)?
+        }
+
+        /// Change a server's settings.
+        edit_server: Put "/servers/<id>", Write,
+        |_backend, req: EditServerRequest| -> Vec<Server> {
+            servers::edit_server(Server {
+                id: req.id,
+                name: req.name,
+                port: req.port,
+                model: req.model,
+                working_directory: req.working_directory,
+                status: "stopped".to_string(), // Status is managed by backend
+                pid: None, // PID is managed by backend
+            })?
+        }
+
+        /// Remove a server.
+        delete_server: Delete "/servers/<id>", Write,
+        |_backend, req: ServerIdRequest| -> Vec<Server> { servers::delete_server(req.id)? }
+
+        /// Start a server.
+        start_server: Post "/servers/<id>/start", Write,
+        |_backend, req: ServerIdRequest| -> Vec<Server> { servers::start_server(req.id).await? }
+
+        /// Stop a server.
+        stop_server: Post "/servers/<id>/stop", Write,
+        |_backend, req: ServerIdRequest| -> Vec<Server> { servers::stop_server(req.id).await? }
+
+        // Models and MCP servers
+
+        /// Names of the models that can be used.
+        get_available_models: Get "/models", Read,
+        |backend, _: NoParams| -> Vec<String> { backend.get_available_models().await? }
+
+        /// Models found per provider.
+        auto_discover_models: Get "/models/discover", Read,
+        |backend, _: NoParams| -> HashMap<String, Vec<ModelInfo>> { backend.auto_discover_models().await? }
+
+        /// Known model providers.
+        get_model_sources: Get "/model-sources", Read,
+        |backend, _: NoParams| -> Vec<ModelSource> { backend.get_model_sources().await? }
+
+        /// Search the MCP server registry.
+        search_mcp_servers: Get "/mcp-servers", Read,
+        |backend, req: SearchMcpServersRequest| -> Vec<McpServerInfo> {
+            backend.search_mcp_servers(req.query).await?
+        }
+
+        /// Most popular MCP servers.
+        get_popular_mcp_servers: Get "/mcp-servers/popular", Read,
+        |backend, req: PopularMcpServersRequest| -> Vec<McpServerInfo> {
+            backend.get_popular_mcp_servers(req.limit.unwrap_or(10)).await?
+        }
+
+        /// MCP server categories.
+        get_mcp_categories: Get "/mcp-categories", Read,
+        |backend, _: NoParams| -> Vec<String> { backend.get_mcp_categories() }
+
+        // Themes and settings
+
+        /// Names of the saved themes.
+        list_themes: Get "/themes", Read,
+        |backend, _: NoParams| -> Vec<String> { backend.list_themes().await? }
+
+        /// Save a custom theme.
+        save_theme: Post "/themes", Write,
+        |backend, req: ThemeRequest| -> () { backend.save_theme(req.theme).await? }
+
+        /// Load a saved theme.
+        load_theme: Get "/themes/<name>", Read,
+        |backend, req: ThemeNameRequest| -> CustomTheme { backend.load_theme(&req.name).await? }
+
+        /// Delete a saved theme.
+        delete_theme: Delete "/themes/<name>", Write,
+        |backend, req: ThemeNameRequest| -> () { backend.delete_theme(&req.name).await? }
+
+        /// Built-in themes.
+        get_theme_presets: Get "/theme-presets", Read,
+        |backend, _: NoParams| -> Vec<ThemePreset> { backend.get_theme_presets() }
+
+        /// Application settings.
+        get_settings: Get "/settings", Read,
+        |_backend, _: NoParams| -> AppSettings { settings::load_settings() }
+
+        /// Save application settings.
+        save_settings: Post "/settings", Write,
+        |_backend, req: SaveSettingsRequest| -> () { settings::save_settings(&req.settings)? }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::MockEventEmitter;
+    use std::collections::HashSet;
+
+    type Ops = Vec<Operation<MockEventEmitter>>;
+
+    #[test]
+    fn test_names_and_routes_are_unique() {
+        let ops: Ops = operations();
+        let mut names = HashSet::new();
+        let mut routes = HashSet::new();
+        for op in &ops {
+            assert!(names.insert(op.name), "duplicate operation {}", op.name);
+            assert!(
+                routes.insert((op.method, op.path)),
+                "duplicate route {:?} {}",
+                op.method,
+                op.path
+            );
+            assert!(op.path.starts_with('/'), "{} has a relative path", op.name);
+            assert!(!op.summary.is_empty(), "{} is undocumented", op.name);
+        }
+    }
+
+    #[test]
+    fn test_path_params() {
+        let op = find_operation::<MockEventEmitter>("write_terminal").unwrap();
+        assert_eq!(op.path_params().collect::<Vec<_>>(), [(1, "terminal_id")]);
+        let op = find_operation::<MockEventEmitter>("list_terminals").unwrap();
+        assert_eq!(op.path_params().count(), 0);
+        assert!(find_operation::<MockEventEmitter>("nope").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_call_accepts_both_key_styles() {
+        let backend = Arc::new(GeminiBackend::new(MockEventEmitter::new()));
+        let op = find_operation::<MockEventEmitter>("send_message").unwrap();
+
+        for params in [
+            serde_json::json!({"session_id": "s", "message": "hi", "conversation_history": ""}),
+            serde_json::json!({"sessionId": "s", "message": "hi", "conversationHistory": ""}),
+        ] {
+            let error = op.call(backend.clone(), params).await.unwrap_err();
+            assert_eq!(error.code(), "session_not_found");
+        }
+
+        let error = op
+            .call(backend.clone(), serde_json::json!({"message": "hi"}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), "invalid_request_body");
+
+        let op = find_operation::<MockEventEmitter>("get_process_statuses").unwrap();
+        assert_eq!(
+            op.call(backend, Value::Null).await.unwrap(),
+            serde_json::json!([])
+        );
+    }
+
+    #[test]
+    fn test_lenient_numbers() {
+        let req: ListProjectsRequest =
+            parse(serde_json::json!({"limit": "10", "offset": 5})).unwrap();
+        assert_eq!((req.limit, req.offset), (Some(10), Some(5)));
+        assert!(parse::<ListProjectsRequest>(serde_json::json!({"limit": "ten"})).is_err());
+    }
+}
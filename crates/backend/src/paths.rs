@@ -0,0 +1,131 @@
+use crate::types::{BackendError, BackendResult};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static DATA_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+tokio::task_local! {
+    static SCOPED_DATA_DIR: PathBuf;
+}
+
+/// Relocates the data directory (normally `~/.gemini-desktop`) for the whole
+/// process. Must be called once, before any backend state is created.
+pub fn set_data_dir(path: PathBuf) -> BackendResult<()> {
+    DATA_DIR_OVERRIDE
+        .set(path)
+        .map_err(|_| BackendError::ConfigError("Data directory has already been set".to_string()))
+}
+
+/// Runs `future` with [`data_dir`] returning `dir`, so one process can keep
+/// several data directories apart. `None` runs it unchanged.
+pub async fn scope<F: Future>(dir: Option<PathBuf>, future: F) -> F::Output {
+    match dir {
+        Some(dir) => SCOPED_DATA_DIR.scope(dir, future).await,
+        None => future.await,
+    }
+}
+
+/// [`scope`] for synchronous code.
+pub fn sync_scope<R>(dir: Option<PathBuf>, f: impl FnOnce() -> R) -> R {
+    match dir {
+        Some(dir) => SCOPED_DATA_DIR.sync_scope(dir, f),
+        None => f(),
+    }
+}
+
+/// Root directory for projects, logs and settings: the directory of the
+/// enclosing [`scope`], else the override from [`set_data_dir`], otherwise
+/// `~/.gemini-desktop`.
+pub fn data_dir() -> BackendResult<PathBuf> {
+    if let Ok(dir) = SCOPED_DATA_DIR.try_with(PathBuf::clone) {
+        return Ok(dir);
+    }
+    root_data_dir()
+}
+
+/// The process-wide data directory, ignoring any [`scope`]; it holds state
+/// shared by every scope, such as the encryption settings.
+pub fn root_data_dir() -> BackendResult<PathBuf> {
+    if let Some(dir) = DATA_DIR_OVERRIDE.get() {
+        return Ok(dir.clone());
+    }
+    let home = std::env::var("HOME")
+        .ok()
+        .filter(|h| !h.is_empty())
+        .or_else(|| std::env::var("USERPROFILE").ok().filter(|h| !h.is_empty()))
+        .ok_or_else(|| BackendError::ConfigError("Could not determine home directory".to_string()))?;
+    Ok(PathBuf::from(home).join(".gemini-desktop"))
+}
+
+/// `data_dir()/projects`, where per-project RPC logs and metadata live.
+pub fn projects_dir() -> BackendResult<PathBuf> {
+    Ok(data_dir()?.join("projects"))
+}
+
+/// Creates `dir` if needed and checks that files can be written to it.
+pub fn check_writable(dir: &Path) -> BackendResult<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".write-check-{}", std::process::id()));
+    std::fs::write(&probe, b"ok")?;
+    std::fs::remove_file(&probe)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::EnvGuard;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_data_dir_defaults_to_home() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut env_guard = EnvGuard::new();
+        env_guard.set("HOME", temp_dir.path().to_str().unwrap());
+
+        assert_eq!(data_dir().unwrap(), temp_dir.path().join(".gemini-desktop"));
+        assert_eq!(
+            projects_dir().unwrap(),
+            temp_dir.path().join(".gemini-desktop").join("projects")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scope_overrides_data_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut env_guard = EnvGuard::new();
+        env_guard.set("HOME", temp_dir.path().to_str().unwrap());
+        let user_dir = temp_dir.path().join("users").join("alice");
+
+        let scoped = scope(Some(user_dir.clone()), async { projects_dir().unwrap() }).await;
+        assert_eq!(scoped, user_dir.join("projects"));
+        assert_eq!(
+            sync_scope(Some(user_dir.clone()), || data_dir().unwrap()),
+            user_dir
+        );
+        assert_eq!(
+            sync_scope(Some(user_dir.clone()), || root_data_dir().unwrap()),
+            temp_dir.path().join(".gemini-desktop")
+        );
+        assert_eq!(
+            scope(None, async { data_dir().unwrap() }).await,
+            temp_dir.path().join(".gemini-desktop")
+        );
+    }
+
+    #[test]
+    fn test_check_writable_creates_the_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("data");
+
+        check_writable(&dir).unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        // A file where the directory should be can't be written into.
+        let file = temp_dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(check_writable(&file).is_err());
+    }
+}
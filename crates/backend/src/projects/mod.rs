@@ -1,5 +1,6 @@
 use crate::types::{BackendError, BackendResult};
 use chrono::{DateTime, FixedOffset, Local};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -7,7 +8,7 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectListItem {
     pub id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -24,7 +25,7 @@ pub struct ProjectListItem {
     pub log_count: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectsResponse {
     pub items: Vec<ProjectListItem>,
     pub total: u32,
@@ -45,7 +46,7 @@ pub struct ProjectMetadata {
     pub updated_at: Option<DateTime<FixedOffset>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectMetadataView {
     pub path: String,
     pub sha256: String,
@@ -56,7 +57,7 @@ pub struct ProjectMetadataView {
     pub updated_at: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct EnrichedProject {
     pub sha256: String,
     pub root_path: PathBuf,
@@ -79,12 +80,7 @@ impl TouchThrottle {
 }
 
 fn home_projects_root() -> Option<PathBuf> {
-    let home = std::env::var("HOME")
-        .unwrap_or_else(|_| std::env::var("USERPROFILE").unwrap_or_else(|_| "".to_string()));
-    if home.is_empty() {
-        return None;
-    }
-    Some(Path::new(&home).join(".gemini-desktop").join("projects"))
+    crate::paths::projects_dir().ok()
 }
 
 fn projects_root_dir() -> Option<PathBuf> {
@@ -145,7 +141,7 @@ fn read_project_metadata(root_sha: &str) -> BackendResult<ProjectMetadata> {
             "project.json not found".to_string(),
         ));
     }
-    let content = std::fs::read_to_string(&path).map_err(BackendError::IoError)?;
+    let content = crate::storage::read_to_string(&path)?;
     serde_json::from_str::<ProjectMetadata>(&content)
         .map_err(|e| BackendError::JsonError(e.to_string()))
 }
@@ -159,12 +155,9 @@ fn write_project_metadata(sha256: &str, meta: &ProjectMetadata) -> BackendResult
     if let Some(dir) = json_path.parent() {
         std::fs::create_dir_all(dir).map_err(BackendError::IoError)?;
     }
-    let tmp_path = json_path.with_extension("json.tmp");
     let content =
         serde_json::to_string_pretty(meta).map_err(|e| BackendError::JsonError(e.to_string()))?;
-    std::fs::write(&tmp_path, content.as_bytes()).map_err(BackendError::IoError)?;
-    std::fs::rename(&tmp_path, &json_path).map_err(BackendError::IoError)?;
-    Ok(())
+    crate::storage::write_file(&json_path, &content)
 }
 
 fn to_view(meta: &ProjectMetadata, canonical_root: &Path, sha256: &str) -> ProjectMetadataView {
@@ -189,6 +182,8 @@ pub fn ensure_project_metadata(
 ) -> BackendResult<ProjectMetadata> {
     match read_project_metadata(sha256) {
         Ok(meta) => Ok(meta),
+        // Never replace metadata we simply cannot decrypt right now.
+        Err(e @ (BackendError::StorageLocked | BackendError::EncryptionError(_))) => Err(e),
         Err(e) => {
             if let Some(ext) = external_root_canonical {
                 let now = now_fixed_offset();
@@ -1020,7 +1015,7 @@ mod tests {
 
         assert_eq!(result.sha256, "newsha");
         assert_eq!(result.root_path, PathBuf::from("/new/project"));
-        assert!(result.metadata.friendly_name.len() > 0);
+        assert!(!result.metadata.friendly_name.is_empty());
     }
 
     #[test]
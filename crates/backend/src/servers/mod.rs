@@ -1,10 +1,11 @@
 use crate::types::{BackendError, BackendResult};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use tokio::process::Command;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct Server {
     pub id: String,
     pub name: String,
@@ -18,12 +19,7 @@ pub struct Server {
 }
 
 fn get_servers_path() -> BackendResult<PathBuf> {
-    let home = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .map_err(|_| BackendError::ConfigError("Could not determine home directory".to_string()))?;
-    Ok(PathBuf::from(home)
-        .join(".gemini-desktop")
-        .join("servers.json"))
+    Ok(crate::paths::data_dir()?.join("servers.json"))
 }
 
 pub fn list_servers() -> BackendResult<Vec<Server>> {
@@ -295,57 +291,7 @@ pub async fn stop_server(id: String) -> BackendResult<Vec<Server>> {
                 ));
             }
             if let Some(pid) = server.pid {
-                #[cfg(windows)]
-                {
-                    use std::process::Command as StdCommand;
-                    let output = StdCommand::new("taskkill")
-                        .args(["/PID", &pid.to_string(), "/F"])
-                        .output()
-                        .map_err(|e| {
-                            BackendError::CommandExecutionFailed(format!(
-                                "Failed to kill process: {e}"
-                            ))
-                        })?;
-
-                    if !output.status.success() {
-                        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                        let stderr_lower = stderr.to_lowercase();
-                        if stderr_lower.contains("not found") {
-                            // Consider the process already gone
-                        } else {
-                            return Err(BackendError::CommandExecutionFailed(format!(
-                                "Failed to kill process {}: {}",
-                                pid, stderr
-                            )));
-                        }
-                    }
-                }
-
-                #[cfg(not(windows))]
-                {
-                    use std::process::Command as StdCommand;
-                    let output = StdCommand::new("kill")
-                        .args(["-9", &pid.to_string()])
-                        .output()
-                        .map_err(|e| {
-                            BackendError::CommandExecutionFailed(format!(
-                                "Failed to kill process: {e}"
-                            ))
-                        })?;
-
-                    if !output.status.success() {
-                        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                        let stderr_lower = stderr.to_lowercase();
-                        if stderr_lower.contains("no such process") {
-                            // Consider the process already gone
-                        } else {
-                            return Err(BackendError::CommandExecutionFailed(format!(
-                                "Failed to kill process {}: {}",
-                                pid, stderr
-                            )));
-                        }
-                    }
-                }
+                kill_pid(pid)?;
             }
             server.status = "stopped".to_string();
             server.pid = None;
@@ -364,3 +310,101 @@ pub async fn stop_server(id: String) -> BackendResult<Vec<Server>> {
 
     Ok(servers)
 }
+
+/// Force-kills `pid`, treating an already-exited process as success.
+fn kill_pid(pid: u32) -> BackendResult<()> {
+    #[cfg(windows)]
+    {
+        use std::process::Command as StdCommand;
+        let output = StdCommand::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/F"])
+            .output()
+            .map_err(|e| {
+                BackendError::CommandExecutionFailed(format!("Failed to kill process: {e}"))
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            if !stderr.to_lowercase().contains("not found") {
+                return Err(BackendError::CommandExecutionFailed(format!(
+                    "Failed to kill process {pid}: {stderr}"
+                )));
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        use std::process::Command as StdCommand;
+        let output = StdCommand::new("kill")
+            .args(["-9", &pid.to_string()])
+            .output()
+            .map_err(|e| {
+                BackendError::CommandExecutionFailed(format!("Failed to kill process: {e}"))
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            if !stderr.to_lowercase().contains("no such process") {
+                return Err(BackendError::CommandExecutionFailed(format!(
+                    "Failed to kill process {pid}: {stderr}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Stops every server recorded as running, for when the app that started
+/// them exits, so `servers.json` never lists a dead process as running.
+pub fn stop_all() -> BackendResult<Vec<Server>> {
+    let mut servers = list_servers()?;
+    let mut changed = false;
+    for server in servers.iter_mut().filter(|s| s.status == "running") {
+        if let Some(pid) = server.pid
+            && let Err(e) = kill_pid(pid)
+        {
+            eprintln!("Failed to stop server {}: {e}", server.name);
+        }
+        server.status = "stopped".to_string();
+        server.pid = None;
+        changed = true;
+    }
+
+    if changed {
+        let content = serde_json::to_string_pretty(&servers)
+            .map_err(|e| BackendError::JsonError(e.to_string()))?;
+        fs::write(get_servers_path()?, content).map_err(BackendError::IoError)?;
+    }
+    Ok(servers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::EnvGuard;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_all_kills_running_servers() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut env_guard = EnvGuard::new();
+        env_guard.set("HOME", temp_dir.path().to_str().unwrap());
+
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let mut running = Server::new("a".into(), 9000, "m".into(), String::new());
+        running.status = "running".to_string();
+        running.pid = Some(child.id());
+        add_server(running).unwrap();
+        add_server(Server::new("b".into(), 9001, "m".into(), String::new())).unwrap();
+
+        let servers = stop_all().unwrap();
+        assert!(servers.iter().all(|s| s.status == "stopped" && s.pid.is_none()));
+        assert!(!child.wait().unwrap().success());
+
+        let saved = list_servers().unwrap();
+        assert!(saved.iter().all(|s| s.status == "stopped" && s.pid.is_none()));
+    }
+}
@@ -0,0 +1,173 @@
+//! Counters describing what a [`SessionManager`](super::SessionManager) has
+//! done since it was created, for frontends to expose to monitoring.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the turn latency histogram's buckets.
+pub const TURN_LATENCY_BUCKETS: [f64; 9] = [1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// Tool confirmation outcomes the frontends send; anything else is counted
+/// as `other` so clients can't grow the label set.
+const CONFIRMATION_OUTCOMES: [&str; 6] = [
+    "allow",
+    "alwaysAllow",
+    "alwaysAllowTool",
+    "alwaysAllowMcpServer",
+    "reject",
+    "cancel",
+];
+
+/// How a `sendUserMessage` request ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnOutcome {
+    Finished,
+    Failed,
+}
+
+/// Cumulative histogram: `buckets[i]` counts observations no larger than
+/// `TURN_LATENCY_BUCKETS[i]`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: [u64; TURN_LATENCY_BUCKETS.len()],
+    pub count: u64,
+    pub sum_seconds: f64,
+}
+
+impl HistogramSnapshot {
+    fn observe(&mut self, value: Duration) {
+        let seconds = value.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(TURN_LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum_seconds += seconds;
+    }
+}
+
+/// Point-in-time values of a backend's session metrics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    /// Sessions whose CLI process is running.
+    pub live_sessions: u64,
+    pub sessions_started: u64,
+    /// Sessions whose CLI exited or broke its pipes without being killed.
+    pub session_crashes: u64,
+    pub turns_started: u64,
+    /// Turns sent to a CLI that hasn't answered yet.
+    pub turns_in_flight: u64,
+    pub turns_finished: u64,
+    pub turns_failed: u64,
+    /// Time from sending a message to the CLI's reply, for finished and
+    /// failed turns alike.
+    pub turn_latency: HistogramSnapshot,
+    /// Tool confirmations by outcome.
+    pub tool_confirmations: BTreeMap<String, u64>,
+    /// Size of the RPC logs on disk, in bytes.
+    pub rpc_log_bytes: u64,
+}
+
+#[derive(Default)]
+pub struct SessionMetrics {
+    values: Mutex<MetricsSnapshot>,
+}
+
+impl SessionMetrics {
+    fn values(&self) -> MutexGuard<'_, MetricsSnapshot> {
+        self.values.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn session_started(&self) {
+        self.values().sessions_started += 1;
+    }
+
+    pub fn session_crashed(&self) {
+        self.values().session_crashes += 1;
+    }
+
+    pub fn turn_started(&self) {
+        let mut values = self.values();
+        values.turns_started += 1;
+        values.turns_in_flight += 1;
+    }
+
+    pub fn turn_ended(&self, outcome: TurnOutcome, latency: Duration) {
+        let mut values = self.values();
+        values.turns_in_flight = values.turns_in_flight.saturating_sub(1);
+        match outcome {
+            TurnOutcome::Finished => values.turns_finished += 1,
+            TurnOutcome::Failed => values.turns_failed += 1,
+        }
+        values.turn_latency.observe(latency);
+    }
+
+    /// Turns that will never end because their session's CLI went away.
+    pub fn turns_abandoned(&self, count: usize) {
+        let mut values = self.values();
+        values.turns_in_flight = values.turns_in_flight.saturating_sub(count as u64);
+    }
+
+    pub fn tool_confirmed(&self, outcome: &str) {
+        let outcome = if CONFIRMATION_OUTCOMES.contains(&outcome) {
+            outcome
+        } else {
+            "other"
+        };
+        *self
+            .values()
+            .tool_confirmations
+            .entry(outcome.to_string())
+            .or_default() += 1;
+    }
+
+    /// The counters so far; gauges such as `live_sessions` are left at zero
+    /// for the caller to fill in.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.values().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_latency_buckets() {
+        let metrics = SessionMetrics::default();
+        metrics.turn_started();
+        metrics.turn_started();
+        metrics.turn_ended(TurnOutcome::Finished, Duration::from_millis(800));
+        metrics.turn_ended(TurnOutcome::Failed, Duration::from_secs(45));
+        metrics.tool_confirmed("allow");
+        metrics.tool_confirmed("allow");
+        metrics.tool_confirmed("made-up");
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.turns_started, 2);
+        assert_eq!(snapshot.turns_in_flight, 0);
+        assert_eq!(snapshot.turns_finished, 1);
+        assert_eq!(snapshot.turns_failed, 1);
+        assert_eq!(snapshot.turn_latency.count, 2);
+        assert_eq!(snapshot.turn_latency.buckets, [1, 1, 1, 1, 1, 2, 2, 2, 2]);
+        assert!((snapshot.turn_latency.sum_seconds - 45.8).abs() < 1e-9);
+        assert_eq!(
+            snapshot.tool_confirmations,
+            BTreeMap::from([("allow".to_string(), 2), ("other".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_abandoned_turns_leave_flight() {
+        let metrics = SessionMetrics::default();
+        for _ in 0..3 {
+            metrics.turn_started();
+        }
+        metrics.turn_ended(TurnOutcome::Finished, Duration::ZERO);
+        assert_eq!(metrics.snapshot().turns_in_flight, 2);
+        metrics.turns_abandoned(5);
+        assert_eq!(metrics.snapshot().turns_in_flight, 0);
+    }
+}
@@ -1,7 +1,10 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
 use tokio::process::{Child, ChildStdin, ChildStdout, Command};
 use tokio::sync::mpsc;
@@ -15,20 +18,38 @@ use crate::events::{
     InternalEvent, ToolCallConfirmationRequest, ToolCallEvent, ToolCallUpdate,
 };
 use crate::rpc::{FileRpcLogger, JsonRpcRequest, JsonRpcResponse, NoOpRpcLogger, RpcLogger};
+use crate::security::redact;
 use crate::types::{BackendError, BackendResult};
 
+pub mod metrics;
+
+use metrics::{MetricsSnapshot, SessionMetrics, TurnOutcome};
+
+/// Outgoing JSON-RPC messages queued for a session's CLI stdin.
+pub const MESSAGE_CHANNEL_CAPACITY: usize = 64;
+/// Parsed CLI events waiting to be emitted. When full, the session stops
+/// reading CLI stdout until the emitter catches up.
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+/// Adjacent output or thought chunks arriving within this window are emitted
+/// as one event.
+pub const COALESCE_WINDOW: Duration = Duration::from_millis(16);
+/// Merged chunks are flushed early once they reach this many bytes.
+const MAX_COALESCED_BYTES: usize = 16 * 1024;
+
 pub struct PersistentSession {
     pub conversation_id: String,
+    /// Where the CLI runs, the session's project; empty for none.
+    pub working_directory: String,
     pub pid: Option<u32>,
     pub created_at: u64,
     pub is_alive: bool,
     pub stdin: Option<ChildStdin>,
-    pub message_sender: Option<mpsc::UnboundedSender<String>>,
+    pub message_sender: Option<mpsc::Sender<String>>,
     pub rpc_logger: Arc<dyn RpcLogger>,
     pub child: Option<Child>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ProcessStatus {
     pub conversation_id: String,
     pub pid: Option<u32>,
@@ -49,14 +70,40 @@ impl From<&PersistentSession> for ProcessStatus {
 
 pub type ProcessMap = Arc<Mutex<HashMap<String, PersistentSession>>>;
 
+/// The tool call type (`execute`, `edit`, ...) of each confirmation request
+/// not answered yet, by session and request id.
+pub type PendingConfirmations = Arc<Mutex<HashMap<(String, u32), String>>>;
+
 pub struct SessionManager {
     processes: ProcessMap,
+    confirmations: PendingConfirmations,
+    metrics: Arc<SessionMetrics>,
+    shutting_down: AtomicBool,
 }
 
 impl SessionManager {
     pub fn new() -> Self {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
+            confirmations: Arc::new(Mutex::new(HashMap::new())),
+            metrics: Arc::new(SessionMetrics::default()),
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    /// The tool call type of a confirmation request the CLI is waiting on.
+    pub fn confirmation_type(&self, session_id: &str, request_id: u32) -> Option<String> {
+        self.confirmations
+            .lock()
+            .ok()?
+            .get(&(session_id.to_string(), request_id))
+            .cloned()
+    }
+
+    /// Forgets a confirmation request once it is answered.
+    pub(crate) fn confirmation_answered(&self, session_id: &str, request_id: u32) {
+        if let Ok(mut confirmations) = self.confirmations.lock() {
+            confirmations.remove(&(session_id.to_string(), request_id));
         }
     }
 
@@ -71,7 +118,23 @@ impl SessionManager {
         Ok(statuses)
     }
 
+    /// The project directory of a live session; empty if it has none.
+    pub fn working_directory(&self, session_id: &str) -> BackendResult<String> {
+        let processes = self
+            .processes
+            .lock()
+            .map_err(|_| BackendError::SessionInitFailed("Failed to lock processes".to_string()))?;
+        processes
+            .get(session_id)
+            .filter(|session| session.is_alive)
+            .map(|session| session.working_directory.clone())
+            .ok_or_else(|| BackendError::SessionNotFound(session_id.to_string()))
+    }
+
     pub fn kill_process(&self, conversation_id: &str) -> BackendResult<()> {
+        if let Ok(mut confirmations) = self.confirmations.lock() {
+            confirmations.retain(|(session_id, _), _| session_id != conversation_id);
+        }
         let mut processes = self
             .processes
             .lock()
@@ -79,7 +142,7 @@ impl SessionManager {
 
         if let Some(session) = processes.get_mut(conversation_id) {
             if let Some(mut child) = session.child.take() {
-                drop(child.kill());
+                let _ = child.start_kill();
             } else if let Some(pid) = session.pid {
                 #[cfg(windows)]
                 {
@@ -147,6 +210,60 @@ impl SessionManager {
     pub(crate) fn get_processes(&self) -> &ProcessMap {
         &self.processes
     }
+
+    pub(crate) fn metrics(&self) -> &SessionMetrics {
+        &self.metrics
+    }
+
+    /// From now on, refuse to start sessions or send messages.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Messages sent to a CLI that hasn't finished answering them.
+    pub fn turns_in_flight(&self) -> u64 {
+        self.metrics.snapshot().turns_in_flight
+    }
+
+    /// Kills every session's CLI process and flushes its RPC log.
+    pub fn kill_all(&self) -> BackendResult<()> {
+        let ids: Vec<String> = self
+            .processes
+            .lock()
+            .map_err(|_| BackendError::SessionInitFailed("Failed to lock processes".to_string()))?
+            .keys()
+            .cloned()
+            .collect();
+        let mut result = Ok(());
+        for id in &ids {
+            if let Err(e) = self.kill_process(id) {
+                eprintln!("Failed to stop session {id}: {e}");
+                result = Err(e);
+            }
+        }
+
+        if let Ok(processes) = self.processes.lock() {
+            for session in processes.values() {
+                let _ = session.rpc_logger.flush();
+            }
+        }
+        result
+    }
+
+    /// Session and turn counters, with the number of live sessions.
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        let mut snapshot = self.metrics.snapshot();
+        snapshot.live_sessions = self
+            .processes
+            .lock()
+            .map(|processes| processes.values().filter(|s| s.is_alive).count() as u64)
+            .unwrap_or_default();
+        snapshot
+    }
 }
 
 impl Default for SessionManager {
@@ -161,7 +278,7 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
     model: String,
     emitter: E,
     session_manager: &SessionManager,
-) -> BackendResult<(mpsc::UnboundedSender<String>, Arc<dyn RpcLogger>)> {
+) -> BackendResult<(mpsc::Sender<String>, Arc<dyn RpcLogger>)> {
     println!("🚀 Initializing persistent Gemini session for: {session_id}");
 
     let rpc_logger: Arc<dyn RpcLogger> = match FileRpcLogger::new(Some(&working_directory)) {
@@ -176,7 +293,7 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
         }
     };
 
-    let (message_tx, message_rx) = mpsc::unbounded_channel::<String>();
+    let (message_tx, message_rx) = mpsc::channel::<String>(MESSAGE_CHANNEL_CAPACITY);
 
     let mut cmd = {
         #[cfg(target_os = "windows")]
@@ -256,7 +373,7 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
         &format!("cli-io-{session_id}"),
         CliIoPayload {
             io_type: CliIoType::Input,
-            data: request_json.clone(),
+            data: redact(&request_json).into_owned(),
         },
     );
 
@@ -272,7 +389,7 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
         &format!("cli-io-{session_id}"),
         CliIoPayload {
             io_type: CliIoType::Output,
-            data: line.trim().to_string(),
+            data: redact(line.trim()).into_owned(),
         },
     );
 
@@ -301,6 +418,7 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
             session_id.clone(),
             PersistentSession {
                 conversation_id: session_id.clone(),
+                working_directory: working_directory.clone(),
                 pid,
                 created_at: std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
@@ -314,69 +432,20 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
             },
         );
     }
+    session_manager.metrics.session_started();
 
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<InternalEvent>();
+    let (event_tx, event_rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
 
     let session_id_for_events = session_id.clone();
     tokio::spawn(async move {
-        while let Some(internal_event) = event_rx.recv().await {
-            println!("internal_event: {internal_event:?}");
-            match internal_event {
-                InternalEvent::CliIo {
-                    session_id,
-                    payload,
-                } => {
-                    let _ = emitter.emit(&format!("cli-io-{session_id}"), payload);
-                }
-                InternalEvent::GeminiOutput {
-                    session_id,
-                    payload,
-                } => {
-                    let _ = emitter.emit(&format!("gemini-output-{session_id}"), payload.text);
-                }
-                InternalEvent::GeminiThought {
-                    session_id,
-                    payload,
-                } => {
-                    let _ = emitter.emit(&format!("gemini-thought-{session_id}"), payload.thought);
-                }
-                InternalEvent::ToolCall {
-                    session_id,
-                    payload,
-                } => {
-                    let _ = emitter.emit(&format!("gemini-tool-call-{session_id}"), payload);
-                }
-                InternalEvent::ToolCallUpdate {
-                    session_id,
-                    payload,
-                } => {
-                    let _ = emitter.emit(&format!("gemini-tool-call-update-{session_id}"), payload);
-                }
-                InternalEvent::ToolCallConfirmation {
-                    session_id,
-                    payload,
-                } => {
-                    let _ = emitter.emit(
-                        &format!("gemini-tool-call-confirmation-{session_id}"),
-                        payload,
-                    );
-                }
-                InternalEvent::GeminiTurnFinished { session_id } => {
-                    let _ = emitter.emit(&format!("gemini-turn-finished-{session_id}"), true);
-                }
-                InternalEvent::Error {
-                    session_id,
-                    payload,
-                } => {
-                    let _ = emitter.emit(&format!("gemini-error-{session_id}"), payload.error);
-                }
-            }
-        }
+        forward_events(event_rx, emitter, COALESCE_WINDOW).await;
         println!("🔄 Event forwarding task finished for session: {session_id_for_events}");
     });
 
     let session_id_clone = session_id.clone();
     let processes_clone = session_manager.get_processes().clone();
+    let confirmations = session_manager.confirmations.clone();
+    let metrics = session_manager.metrics.clone();
 
     tokio::spawn(async move {
         // Ensure the I/O loop does not block forever if the CLI becomes silent.
@@ -386,7 +455,9 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
             reader,
             message_rx,
             processes_clone,
+            confirmations,
             event_tx,
+            metrics,
         )
         .await;
     });
@@ -397,12 +468,15 @@ pub async fn initialize_session<E: EventEmitter + 'static>(
 async fn handle_session_io_internal(
     session_id: String,
     mut reader: AsyncBufReader<ChildStdout>,
-    mut message_rx: mpsc::UnboundedReceiver<String>,
+    mut message_rx: mpsc::Receiver<String>,
     processes: ProcessMap,
-    event_tx: mpsc::UnboundedSender<InternalEvent>,
+    confirmations: PendingConfirmations,
+    event_tx: mpsc::Sender<InternalEvent>,
+    metrics: Arc<SessionMetrics>,
 ) {
     let mut tool_call_id = 1001u32;
     let mut pending_send_message_requests = HashSet::<u32>::new();
+    let mut turn_started_at = HashMap::<u32, Instant>::new();
     let mut line_buffer = String::new();
 
     loop {
@@ -423,6 +497,8 @@ async fn handle_session_io_internal(
                             && json_request.method == "sendUserMessage"
                         {
                             pending_send_message_requests.insert(json_request.id);
+                            turn_started_at.insert(json_request.id, Instant::now());
+                            metrics.turn_started();
                         }
 
                         if let Ok(processes_guard) = processes.lock()
@@ -448,9 +524,9 @@ async fn handle_session_io_internal(
                             session_id: session_id.clone(),
                             payload: CliIoPayload {
                                 io_type: CliIoType::Input,
-                                data: message_json,
+                                data: redact(&message_json).into_owned(),
                             },
-                        });
+                        }).await;
 
                         {
                             let mut processes_guard = processes.lock().unwrap();
@@ -484,17 +560,22 @@ async fn handle_session_io_internal(
                             session_id: session_id.clone(),
                             payload: CliIoPayload {
                                 io_type: CliIoType::Output,
-                                data: line.clone(),
+                                data: redact(&line).into_owned(),
                             },
-                        });
+                        }).await;
 
-                        handle_cli_output_line(
+                        if let Some((request_id, outcome)) = handle_cli_output_line(
                             &session_id,
                             &line,
                             &event_tx,
+                            &confirmations,
                             &mut tool_call_id,
                             &mut pending_send_message_requests,
-                        ).await;
+                        ).await
+                            && let Some(started_at) = turn_started_at.remove(&request_id)
+                        {
+                            metrics.turn_ended(outcome, started_at.elapsed());
+                        }
 
                         line_buffer.clear();
                     }
@@ -510,11 +591,16 @@ async fn handle_session_io_internal(
     {
         let mut processes_guard = processes.lock().unwrap();
         if let Some(session) = processes_guard.get_mut(&session_id) {
+            // Killed sessions are already marked dead.
+            if session.is_alive {
+                metrics.session_crashed();
+            }
             session.is_alive = false;
             session.stdin = None;
             session.message_sender = None;
         }
     }
+    metrics.turns_abandoned(turn_started_at.len());
 
     println!("🛑 Session I/O handler finished for: {session_id}");
 }
@@ -547,17 +633,18 @@ pub async fn send_response_to_cli(
             .get_mut(session_id)
             .and_then(|s| s.message_sender.clone())
     } {
-        let _ = sender.send(response_json);
+        let _ = sender.send(response_json).await;
     }
 }
 
 async fn handle_cli_output_line(
     session_id: &str,
     line: &str,
-    event_tx: &mpsc::UnboundedSender<InternalEvent>,
+    event_tx: &mpsc::Sender<InternalEvent>,
+    confirmations: &PendingConfirmations,
     tool_call_id: &mut u32,
     pending_send_message_requests: &mut HashSet<u32>,
-) {
+) -> Option<(u32, TurnOutcome)> {
     if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
         if let Some(method) = json_value.get("method").and_then(|m| m.as_str()) {
             match method {
@@ -569,13 +656,13 @@ async fn handle_cli_output_line(
                             let _ = event_tx.send(InternalEvent::GeminiThought {
                                 session_id: session_id.to_string(),
                                 payload: GeminiThoughtPayload { thought },
-                            });
+                            }).await;
                         }
                         if let Some(text) = params.chunk.text {
                             let _ = event_tx.send(InternalEvent::GeminiOutput {
                                 session_id: session_id.to_string(),
                                 payload: GeminiOutputPayload { text },
-                            });
+                            }).await;
                         }
                     }
                 }
@@ -595,7 +682,7 @@ async fn handle_cli_output_line(
                         let _ = event_tx.send(InternalEvent::ToolCall {
                             session_id: session_id.to_string(),
                             payload: event,
-                        });
+                        }).await;
 
                         *tool_call_id += 1;
                     }
@@ -611,7 +698,7 @@ async fn handle_cli_output_line(
                                 status: params.status,
                                 content: params.content,
                             },
-                        });
+                        }).await;
                     }
                 }
                 "requestToolCallConfirmation" => {
@@ -619,6 +706,12 @@ async fn handle_cli_output_line(
                         json_value.get("params").cloned().unwrap_or_default(),
                     ) && let Some(id) = json_value.get("id").and_then(|i| i.as_u64())
                     {
+                        if let Ok(mut confirmations) = confirmations.lock() {
+                            confirmations.insert(
+                                (session_id.to_string(), id as u32),
+                                params.confirmation.confirmation_type.clone(),
+                            );
+                        }
                         let request = ToolCallConfirmationRequest {
                             request_id: id as u32,
                             session_id: session_id.to_string(),
@@ -632,7 +725,7 @@ async fn handle_cli_output_line(
                         let _ = event_tx.send(InternalEvent::ToolCallConfirmation {
                             session_id: session_id.to_string(),
                             payload: request,
-                        });
+                        }).await;
                     }
                 }
                 _ => {}
@@ -647,16 +740,177 @@ async fn handle_cli_output_line(
                     pending_send_message_requests.remove(&id_u32);
                     let _ = event_tx.send(InternalEvent::GeminiTurnFinished {
                         session_id: session_id.to_string(),
-                    });
+                    }).await;
+                    return Some((id_u32, TurnOutcome::Finished));
                 } else if let Some(error) = json_value.get("error") {
                     pending_send_message_requests.remove(&id_u32);
                     let error_msg = error.to_string();
                     let _ = event_tx.send(InternalEvent::Error {
                         session_id: session_id.to_string(),
                         payload: ErrorPayload { error: error_msg },
-                    });
+                    }).await;
+                    return Some((id_u32, TurnOutcome::Failed));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Emits a session's parsed CLI events in order, merging runs of output or
+/// thought chunks for the same session that arrive within `window`. A zero
+/// window emits every chunk as it comes.
+pub async fn forward_events<E: EventEmitter>(
+    mut event_rx: mpsc::Receiver<InternalEvent>,
+    emitter: E,
+    window: Duration,
+) {
+    let mut pending: Option<InternalEvent> = None;
+    let mut deadline = tokio::time::Instant::now();
+
+    loop {
+        let next = if pending.is_some() {
+            tokio::select! {
+                event = event_rx.recv() => event,
+                () = tokio::time::sleep_until(deadline) => {
+                    if let Some(buffered) = pending.take() {
+                        emit_internal_event(&emitter, buffered);
+                    }
+                    continue;
                 }
             }
+        } else {
+            event_rx.recv().await
+        };
+        let Some(event) = next else {
+            break;
+        };
+
+        let event = match pending.as_mut() {
+            Some(buffered) => match merge_chunk(buffered, event) {
+                None => {
+                    if chunk_len(buffered) >= MAX_COALESCED_BYTES
+                        && let Some(buffered) = pending.take()
+                    {
+                        emit_internal_event(&emitter, buffered);
+                    }
+                    continue;
+                }
+                Some(event) => {
+                    if let Some(buffered) = pending.take() {
+                        emit_internal_event(&emitter, buffered);
+                    }
+                    event
+                }
+            },
+            None => event,
+        };
+
+        if window.is_zero() || chunk_len(&event) == 0 {
+            emit_internal_event(&emitter, event);
+        } else {
+            pending = Some(event);
+            deadline = tokio::time::Instant::now() + window;
+        }
+    }
+
+    if let Some(buffered) = pending {
+        emit_internal_event(&emitter, buffered);
+    }
+}
+
+/// Length of a mergeable output or thought chunk; 0 for every other event.
+fn chunk_len(event: &InternalEvent) -> usize {
+    match event {
+        InternalEvent::GeminiOutput { payload, .. } => payload.text.len().max(1),
+        InternalEvent::GeminiThought { payload, .. } => payload.thought.len().max(1),
+        _ => 0,
+    }
+}
+
+/// Appends `event` to `buffered` if both are the same kind of chunk for the
+/// same session; otherwise hands `event` back.
+fn merge_chunk(buffered: &mut InternalEvent, event: InternalEvent) -> Option<InternalEvent> {
+    match (buffered, event) {
+        (
+            InternalEvent::GeminiOutput {
+                session_id,
+                payload,
+            },
+            InternalEvent::GeminiOutput {
+                session_id: next_session_id,
+                payload: next,
+            },
+        ) if *session_id == next_session_id => {
+            payload.text.push_str(&next.text);
+            None
+        }
+        (
+            InternalEvent::GeminiThought {
+                session_id,
+                payload,
+            },
+            InternalEvent::GeminiThought {
+                session_id: next_session_id,
+                payload: next,
+            },
+        ) if *session_id == next_session_id => {
+            payload.thought.push_str(&next.thought);
+            None
+        }
+        (_, event) => Some(event),
+    }
+}
+
+fn emit_internal_event<E: EventEmitter>(emitter: &E, event: InternalEvent) {
+    match event {
+        InternalEvent::CliIo {
+            session_id,
+            payload,
+        } => {
+            let _ = emitter.emit(&format!("cli-io-{session_id}"), payload);
+        }
+        InternalEvent::GeminiOutput {
+            session_id,
+            payload,
+        } => {
+            let _ = emitter.emit(&format!("gemini-output-{session_id}"), payload.text);
+        }
+        InternalEvent::GeminiThought {
+            session_id,
+            payload,
+        } => {
+            let _ = emitter.emit(&format!("gemini-thought-{session_id}"), payload.thought);
+        }
+        InternalEvent::ToolCall {
+            session_id,
+            payload,
+        } => {
+            let _ = emitter.emit(&format!("gemini-tool-call-{session_id}"), payload);
+        }
+        InternalEvent::ToolCallUpdate {
+            session_id,
+            payload,
+        } => {
+            let _ = emitter.emit(&format!("gemini-tool-call-update-{session_id}"), payload);
+        }
+        InternalEvent::ToolCallConfirmation {
+            session_id,
+            payload,
+        } => {
+            let _ = emitter.emit(
+                &format!("gemini-tool-call-confirmation-{session_id}"),
+                payload,
+            );
+        }
+        InternalEvent::GeminiTurnFinished { session_id } => {
+            let _ = emitter.emit(&format!("gemini-turn-finished-{session_id}"), true);
+        }
+        InternalEvent::Error {
+            session_id,
+            payload,
+        } => {
+            let _ = emitter.emit(&format!("gemini-error-{session_id}"), payload.error);
         }
     }
 }
@@ -671,10 +925,91 @@ mod tests {
     use std::time::Duration;
     use tokio::time::timeout;
 
+    fn output(session_id: &str, text: &str) -> InternalEvent {
+        InternalEvent::GeminiOutput {
+            session_id: session_id.to_string(),
+            payload: GeminiOutputPayload {
+                text: text.to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_events_coalesces_adjacent_chunks() {
+        use crate::events::MockEventEmitter;
+
+        let emitter = MockEventEmitter::new();
+        let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        for event in [
+            output("s1", "Hel"),
+            output("s1", "lo"),
+            output("s2", "other"),
+            InternalEvent::GeminiThought {
+                session_id: "s1".to_string(),
+                payload: GeminiThoughtPayload {
+                    thought: "hmm".to_string(),
+                },
+            },
+            output("s1", " world"),
+            InternalEvent::GeminiTurnFinished {
+                session_id: "s1".to_string(),
+            },
+        ] {
+            tx.send(event).await.unwrap();
+        }
+        drop(tx);
+
+        forward_events(rx, emitter.clone(), Duration::from_secs(60)).await;
+        let events: Vec<(String, serde_json::Value)> = emitter.get_events();
+        assert_eq!(
+            events,
+            vec![
+                ("gemini-output-s1".to_string(), json!("Hello")),
+                ("gemini-output-s2".to_string(), json!("other")),
+                ("gemini-thought-s1".to_string(), json!("hmm")),
+                ("gemini-output-s1".to_string(), json!(" world")),
+                ("gemini-turn-finished-s1".to_string(), json!(true)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_events_flushes_after_window() {
+        use crate::events::MockEventEmitter;
+
+        let emitter = MockEventEmitter::new();
+        let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let task = tokio::spawn(forward_events(
+            rx,
+            emitter.clone(),
+            Duration::from_millis(10),
+        ));
+
+        tx.send(output("s1", "a")).await.unwrap();
+        tx.send(output("s1", "b")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(emitter.get_events_by_name("gemini-output-s1"), [json!("ab")]);
+
+        tx.send(output("s1", "c")).await.unwrap();
+        drop(tx);
+        task.await.unwrap();
+        assert_eq!(emitter.get_event_count("gemini-output-s1"), 2);
+
+        // A zero window forwards every chunk unchanged.
+        let emitter = MockEventEmitter::new();
+        let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        tx.send(output("s1", "a")).await.unwrap();
+        tx.send(output("s1", "b")).await.unwrap();
+        drop(tx);
+        forward_events(rx, emitter.clone(), Duration::ZERO).await;
+        assert_eq!(emitter.get_event_count("gemini-output-s1"), 2);
+    }
+
     #[test]
     fn test_persistent_session_struct() {
         let session = PersistentSession {
             conversation_id: "test-id".to_string(),
+            working_directory: String::new(),
             pid: Some(12345),
             created_at: 1640995200,
             is_alive: true,
@@ -715,6 +1050,7 @@ mod tests {
     fn test_process_status_from_persistent_session() {
         let session = PersistentSession {
             conversation_id: "test-session".to_string(),
+            working_directory: String::new(),
             pid: Some(9876),
             created_at: 1640995300,
             is_alive: false,
@@ -756,6 +1092,7 @@ mod tests {
                 "test-session".to_string(),
                 PersistentSession {
                     conversation_id: "test-session".to_string(),
+                    working_directory: String::new(),
                     pid: Some(12345),
                     created_at: 1640995200,
                     is_alive: true,
@@ -794,6 +1131,7 @@ mod tests {
                 "test-session".to_string(),
                 PersistentSession {
                     conversation_id: "test-session".to_string(),
+                    working_directory: String::new(),
                     pid: None,
                     created_at: 1640995200,
                     is_alive: true,
@@ -840,7 +1178,7 @@ mod tests {
     #[tokio::test]
     async fn test_send_response_to_cli_with_session() {
         let processes: ProcessMap = Arc::new(Mutex::new(HashMap::new()));
-        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let (tx, mut rx) = mpsc::channel::<String>(MESSAGE_CHANNEL_CAPACITY);
 
         // Add session with message sender
         {
@@ -849,6 +1187,7 @@ mod tests {
                 "test-session".to_string(),
                 PersistentSession {
                     conversation_id: "test-session".to_string(),
+                    working_directory: String::new(),
                     pid: Some(12345),
                     created_at: 1640995200,
                     is_alive: true,
@@ -881,7 +1220,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_invalid_json() {
-        let (tx, _rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, _rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
 
@@ -890,6 +1229,7 @@ mod tests {
             "test-session",
             "invalid json",
             &tx,
+            &PendingConfirmations::default(),
             &mut tool_call_id,
             &mut pending_requests,
         )
@@ -901,7 +1241,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_stream_assistant_message_chunk() {
-        let (tx, mut rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, mut rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
 
@@ -920,6 +1260,7 @@ mod tests {
             "test-session",
             &input,
             &tx,
+            &PendingConfirmations::default(),
             &mut tool_call_id,
             &mut pending_requests,
         )
@@ -962,7 +1303,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_push_tool_call() {
-        let (tx, mut rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, mut rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
 
@@ -980,6 +1321,7 @@ mod tests {
             "test-session",
             &input,
             &tx,
+            &PendingConfirmations::default(),
             &mut tool_call_id,
             &mut pending_requests,
         )
@@ -1009,7 +1351,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_update_tool_call() {
-        let (tx, mut rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, mut rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
 
@@ -1027,6 +1369,7 @@ mod tests {
             "test-session",
             &input,
             &tx,
+            &PendingConfirmations::default(),
             &mut tool_call_id,
             &mut pending_requests,
         )
@@ -1057,9 +1400,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_request_tool_call_confirmation() {
-        let (tx, mut rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, mut rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
+        let confirmations = PendingConfirmations::default();
 
         let input = json!({
             "id": 42,
@@ -1078,6 +1422,7 @@ mod tests {
             "test-session",
             &input,
             &tx,
+            &confirmations,
             &mut tool_call_id,
             &mut pending_requests,
         )
@@ -1098,7 +1443,11 @@ mod tests {
                 assert_eq!(payload.label, "Delete File");
                 assert_eq!(payload.icon, "🗑️".to_string());
                 assert!(payload.content.is_some());
-                assert!(payload.confirmation.confirmation_type.len() > 0);
+                assert!(!payload.confirmation.confirmation_type.is_empty());
+                assert_eq!(
+                    confirmations.lock().unwrap()[&("test-session".to_string(), 42)],
+                    payload.confirmation.confirmation_type
+                );
                 assert_eq!(payload.locations.len(), 1);
                 assert_eq!(payload.locations[0].path, "file.txt");
             }
@@ -1108,7 +1457,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_pending_send_message_success() {
-        let (tx, mut rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, mut rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
         pending_requests.insert(123);
@@ -1119,14 +1468,16 @@ mod tests {
         })
         .to_string();
 
-        handle_cli_output_line(
+        let ended = handle_cli_output_line(
             "test-session",
             &input,
             &tx,
+            &PendingConfirmations::default(),
             &mut tool_call_id,
             &mut pending_requests,
         )
         .await;
+        assert_eq!(ended, Some((123, TurnOutcome::Finished)));
 
         let event = timeout(Duration::from_millis(100), rx.recv())
             .await
@@ -1144,7 +1495,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_pending_send_message_error() {
-        let (tx, mut rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, mut rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
         pending_requests.insert(123);
@@ -1155,14 +1506,16 @@ mod tests {
         })
         .to_string();
 
-        handle_cli_output_line(
+        let ended = handle_cli_output_line(
             "test-session",
             &input,
             &tx,
+            &PendingConfirmations::default(),
             &mut tool_call_id,
             &mut pending_requests,
         )
         .await;
+        assert_eq!(ended, Some((123, TurnOutcome::Failed)));
 
         let event = timeout(Duration::from_millis(100), rx.recv())
             .await
@@ -1184,7 +1537,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_cli_output_line_unknown_method() {
-        let (tx, _rx) = mpsc::unbounded_channel::<InternalEvent>();
+        let (tx, _rx) = mpsc::channel::<InternalEvent>(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
 
@@ -1199,6 +1552,7 @@ mod tests {
             "test-session",
             &input,
             &tx,
+            &PendingConfirmations::default(),
             &mut tool_call_id,
             &mut pending_requests,
         )
@@ -1275,36 +1629,28 @@ mod tests {
         )
         .await;
 
-        // The result may fail due to missing CLI, but we can test the error handling
+        // Without the CLI the shell exits at once, before or after the init
+        // request is written, so only a successful start guarantees events.
         match result {
             Ok((sender, _logger)) => {
-                // If successful, verify the session was created
                 let statuses = session_manager.get_process_statuses().unwrap();
                 assert_eq!(statuses.len(), 1);
                 assert_eq!(statuses[0].conversation_id, "test-session-123");
                 assert!(statuses[0].is_alive);
 
                 // Test that we can send a message (will be queued)
-                let test_message = "test message";
-                let send_result = sender.send(test_message.to_string());
+                let send_result = sender.send("test message".to_string()).await;
                 assert!(send_result.is_ok());
+                assert!(emitter.has_event("cli-io-test-session-123"));
             }
             Err(e) => {
-                // Expected if gemini CLI is not available
-                // Verify it's the expected error type
-                match e {
-                    crate::types::BackendError::SessionInitFailed(_) => {
-                        // This is expected when CLI is not available
-                        println!("Session init failed as expected (CLI not available): {}", e);
-                    }
-                    _ => panic!("Unexpected error type: {}", e),
-                }
+                assert!(
+                    matches!(e, crate::types::BackendError::SessionInitFailed(_)),
+                    "Unexpected error type: {e}"
+                );
+                assert!(session_manager.get_process_statuses().unwrap().is_empty());
             }
         }
-
-        // Verify events were emitted during initialization attempt
-        assert!(emitter.total_events() > 0);
-        assert!(emitter.has_event("cli-io-test-session-123"));
     }
 
     #[tokio::test]
@@ -1321,6 +1667,7 @@ mod tests {
                 "integration-test".to_string(),
                 PersistentSession {
                     conversation_id: "integration-test".to_string(),
+                    working_directory: String::new(),
                     pid: Some(12345),
                     created_at: 1640995200,
                     is_alive: true,
@@ -1355,7 +1702,7 @@ mod tests {
         use tokio::sync::mpsc;
 
         let _emitter = MockEventEmitter::new();
-        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
         let mut tool_call_id = 1001u32;
         let mut pending_requests = HashSet::new();
 
@@ -1376,6 +1723,7 @@ mod tests {
                 "integration-test",
                 message,
                 &tx,
+                &PendingConfirmations::default(),
                 &mut tool_call_id,
                 &mut pending_requests,
             )
@@ -1427,7 +1775,7 @@ mod tests {
         use tokio::sync::mpsc;
 
         let processes: ProcessMap = Arc::new(Mutex::new(HashMap::new()));
-        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let (tx, mut rx) = mpsc::channel::<String>(MESSAGE_CHANNEL_CAPACITY);
 
         // Set up a mock session with message sender
         {
@@ -1436,6 +1784,7 @@ mod tests {
                 "integration-test".to_string(),
                 PersistentSession {
                     conversation_id: "integration-test".to_string(),
+                    working_directory: String::new(),
                     pid: Some(12345),
                     created_at: 1640995200,
                     is_alive: true,
@@ -1493,6 +1842,7 @@ mod tests {
                         session_id.clone(),
                         PersistentSession {
                             conversation_id: session_id.clone(),
+                            working_directory: String::new(),
                             pid: Some(1000 + i as u32),
                             created_at: 1640995200 + i as u64,
                             is_alive: true,
@@ -1542,6 +1892,7 @@ mod tests {
                 "thread-test".to_string(),
                 PersistentSession {
                     conversation_id: "thread-test".to_string(),
+                    working_directory: String::new(),
                     pid: Some(999),
                     created_at: 1640995200,
                     is_alive: true,
@@ -1572,6 +1923,7 @@ mod tests {
                     format!("session-{}", i),
                     PersistentSession {
                         conversation_id: format!("session-{}", i),
+                        working_directory: String::new(),
                         pid: Some(1000 + i as u32),
                         created_at: 1640995200 + i as u64,
                         is_alive: true,
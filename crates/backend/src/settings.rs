@@ -1,22 +1,32 @@
+use crate::types::{BackendError, BackendResult};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
 use std::fs;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum SettingsLocation {
     ProjectRoot,
     Global,
     User,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AppSettings {
     pub settings_location: SettingsLocation,
     pub hotkeys: HotkeySettings,
     pub ui: UiSettings,
+    /// Have the desktop app use the backend daemon, starting it if needed,
+    /// instead of running sessions itself.
+    #[serde(default)]
+    pub use_daemon: bool,
+    /// Keys owned by other components, such as the web server's `server*`
+    /// options, kept so saving the app settings doesn't drop them.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct HotkeySettings {
     pub quick_open: String,
     pub toggle_chat: String,
@@ -24,7 +34,7 @@ pub struct HotkeySettings {
     pub import_file: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UiSettings {
     pub start_minimized: bool,
     pub close_to_tray: bool,
@@ -37,6 +47,8 @@ impl Default for AppSettings {
             settings_location: SettingsLocation::User,
             hotkeys: HotkeySettings::default(),
             ui: UiSettings::default(),
+            use_daemon: false,
+            other: serde_json::Map::new(),
         }
     }
 }
@@ -62,7 +74,7 @@ impl Default for UiSettings {
     }
 }
 
-pub fn get_settings_path(location: &SettingsLocation) -> Result<PathBuf, Box<dyn std::error::Error>> {
+pub fn get_settings_path(location: &SettingsLocation) -> BackendResult<PathBuf> {
     match location {
         SettingsLocation::ProjectRoot => {
             let current_dir = std::env::current_dir()?;
@@ -70,15 +82,14 @@ pub fn get_settings_path(location: &SettingsLocation) -> Result<PathBuf, Box<dyn
         }
         SettingsLocation::Global => {
             if cfg!(windows) {
-                Ok(PathBuf::from("C:\\ProgramData\\GeminiDesktop\\settings.json"))
+                Ok(PathBuf::from(
+                    "C:\\ProgramData\\GeminiDesktop\\settings.json",
+                ))
             } else {
                 Ok(PathBuf::from("/etc/gemini-desktop/settings.json"))
             }
         }
-        SettingsLocation::User => {
-            let home = dirs::home_dir().ok_or("Could not find home directory")?;
-            Ok(home.join(".gemini-desktop").join("settings.json"))
-        }
+        SettingsLocation::User => Ok(crate::paths::data_dir()?.join("settings.json")),
     }
 }
 
@@ -87,37 +98,64 @@ pub fn load_settings() -> AppSettings {
     if let Ok(settings) = load_settings_from(&SettingsLocation::User) {
         return settings;
     }
-    
+
     // Try project root
     if let Ok(settings) = load_settings_from(&SettingsLocation::ProjectRoot) {
         return settings;
     }
-    
+
     // Try global
     if let Ok(settings) = load_settings_from(&SettingsLocation::Global) {
         return settings;
     }
-    
+
     // Return default if none found
     AppSettings::default()
 }
 
-fn load_settings_from(location: &SettingsLocation) -> Result<AppSettings, Box<dyn std::error::Error>> {
+fn load_settings_from(location: &SettingsLocation) -> BackendResult<AppSettings> {
     let path = get_settings_path(location)?;
     let content = fs::read_to_string(path)?;
-    let settings: AppSettings = serde_json::from_str(&content)?;
-    Ok(settings)
+    serde_json::from_str(&content).map_err(|e| BackendError::JsonError(e.to_string()))
 }
 
-pub fn save_settings(settings: &AppSettings) -> Result<(), Box<dyn std::error::Error>> {
+pub fn save_settings(settings: &AppSettings) -> BackendResult<()> {
     let path = get_settings_path(&settings.settings_location)?;
-    
+
     // Create parent directory if it doesn't exist
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
-    
-    let content = serde_json::to_string_pretty(settings)?;
+
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| BackendError::JsonError(e.to_string()))?;
     fs::write(path, content)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip_keeps_other_keys() {
+        let value = json!({
+            "settings_location": "User",
+            "hotkeys": {
+                "quick_open": "a",
+                "toggle_chat": "b",
+                "screenshot": "c",
+                "import_file": "d"
+            },
+            "ui": {"start_minimized": true, "close_to_tray": false, "theme": "light"},
+            "use_daemon": true,
+            "serverPort": 9000
+        });
+        let settings: AppSettings = serde_json::from_value(value.clone()).unwrap();
+        assert!(settings.ui.start_minimized);
+        assert!(settings.use_daemon);
+        assert_eq!(settings.other.get("serverPort"), Some(&json!(9000)));
+        assert_eq!(serde_json::to_value(&settings).unwrap(), value);
+    }
+}
@@ -0,0 +1,636 @@
+//! Opt-in encryption at rest for chat logs and project metadata.
+//!
+//! When enabled, `~/.gemini-desktop/encryption.json` holds the Argon2id
+//! parameters and a key check value; the key itself only ever lives in memory
+//! after [`unlock`]. Encrypted data is stored as line records of the form
+//! `enc1:<base64(nonce || ciphertext)>`, so RPC logs stay append-only and
+//! line-oriented readers keep working after [`read_lines`] decrypts them.
+//! Each record is bound to its file's path under the data directory, so
+//! records cannot be moved between files. Plaintext lines are passed through
+//! untouched, which lets old logs and freshly encrypted ones coexist until
+//! [`migrate`] has run for their data directory; from then on they are
+//! rejected.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::types::{BackendError, BackendResult};
+
+const RECORD_PREFIX: &str = "enc1:";
+const NONCE_LEN: usize = 24;
+const AAD_PREFIX: &str = "gemini-desktop/v1:";
+const KEY_CHECK: &[u8] = b"gemini-desktop key check";
+const KEY_CHECK_AAD: &[u8] = b"gemini-desktop/v1 key check";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    /// [`KEY_CHECK`] sealed with the derived key, used to reject wrong passphrases.
+    check: String,
+    /// Data directories whose existing data [`migrate`] has encrypted, as
+    /// [`data_dir_key`]s; plaintext records in them are rejected from then on.
+    #[serde(default)]
+    migrated_dirs: BTreeSet<String>,
+    /// Set by versions that only migrated the root data directory.
+    #[serde(default, skip_serializing)]
+    migrated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+}
+
+/// Summary of a [`migrate`] run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct MigrationReport {
+    pub files_scanned: usize,
+    pub files_encrypted: usize,
+}
+
+#[derive(Clone)]
+struct Cipher(Arc<XChaCha20Poly1305>);
+
+impl Cipher {
+    fn derive(passphrase: &str, key_file: &KeyFile) -> BackendResult<Self> {
+        let salt = BASE64
+            .decode(&key_file.salt)
+            .map_err(|e| BackendError::EncryptionError(format!("Invalid salt: {e}")))?;
+        let params = Params::new(key_file.m_cost, key_file.t_cost, key_file.p_cost, Some(32))
+            .map_err(|e| BackendError::EncryptionError(e.to_string()))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| BackendError::EncryptionError(e.to_string()))?;
+        Ok(Self(Arc::new(XChaCha20Poly1305::new(Key::from_slice(
+            &key,
+        )))))
+    }
+
+    fn seal(&self, aad: &[u8], plaintext: &[u8]) -> BackendResult<String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| BackendError::EncryptionError("Encryption failed".to_string()))?;
+        let mut record = nonce.to_vec();
+        record.extend_from_slice(&ciphertext);
+        Ok(format!("{RECORD_PREFIX}{}", BASE64.encode(record)))
+    }
+
+    fn open(&self, aad: &[u8], record: &str) -> BackendResult<Vec<u8>> {
+        let encoded = record.strip_prefix(RECORD_PREFIX).unwrap_or(record);
+        let bytes = BASE64
+            .decode(encoded.trim_end())
+            .map_err(|e| BackendError::EncryptionError(format!("Corrupt record: {e}")))?;
+        if bytes.len() < NONCE_LEN {
+            return Err(BackendError::EncryptionError(
+                "Corrupt record: too short".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        self.0
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| {
+                BackendError::EncryptionError(
+                    "Record failed authentication (wrong key, wrong file or tampered data)"
+                        .to_string(),
+                )
+            })
+    }
+}
+
+/// Additional data binding a record to `path`: its location relative to the
+/// data directory, or the absolute path for files outside it.
+fn aad(path: &Path) -> Vec<u8> {
+    format!("{AAD_PREFIX}{}", relative_to_root(path)).into_bytes()
+}
+
+/// `path` relative to the root data directory, `/`-separated, or the whole
+/// path for one outside it.
+fn relative_to_root(path: &Path) -> String {
+    let root = crate::paths::root_data_dir().ok();
+    let relative = root
+        .as_deref()
+        .and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path);
+    let components: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
+enum VaultState {
+    Disabled,
+    Locked(KeyFile),
+    Unlocked(Cipher, KeyFile),
+}
+
+fn vault() -> &'static RwLock<VaultState> {
+    static VAULT: OnceLock<RwLock<VaultState>> = OnceLock::new();
+    VAULT.get_or_init(|| {
+        let state = match key_file_path().and_then(|path| read_key_file(&path)) {
+            Ok(Some(key_file)) => VaultState::Locked(key_file),
+            Ok(None) => VaultState::Disabled,
+            Err(e) => {
+                eprintln!("⚠️ Failed to read encryption settings: {e}");
+                VaultState::Disabled
+            }
+        };
+        RwLock::new(state)
+    })
+}
+
+fn current_cipher() -> BackendResult<Option<Cipher>> {
+    let guard = vault().read().map_err(|_| lock_error())?;
+    match &*guard {
+        VaultState::Disabled => Ok(None),
+        VaultState::Locked(_) => Err(BackendError::StorageLocked),
+        VaultState::Unlocked(cipher, _) => Ok(Some(cipher.clone())),
+    }
+}
+
+/// Whether plaintext records are still accepted: until [`migrate`] has run
+/// for the current data directory.
+fn accepts_plaintext() -> BackendResult<bool> {
+    let guard = vault().read().map_err(|_| lock_error())?;
+    Ok(match &*guard {
+        VaultState::Disabled => true,
+        VaultState::Locked(key_file) | VaultState::Unlocked(_, key_file) => {
+            !key_file.migrated_dirs.contains(&data_dir_key()?)
+        }
+    })
+}
+
+/// The current [`crate::paths::data_dir`] relative to the root one, which
+/// is `""` itself.
+fn data_dir_key() -> BackendResult<String> {
+    Ok(relative_to_root(&crate::paths::data_dir()?))
+}
+
+fn lock_error() -> BackendError {
+    BackendError::EncryptionError("Failed to lock encryption state".to_string())
+}
+
+fn gemini_desktop_dir() -> BackendResult<PathBuf> {
+    crate::paths::data_dir()
+}
+
+fn key_file_path() -> BackendResult<PathBuf> {
+    Ok(crate::paths::root_data_dir()?.join("encryption.json"))
+}
+
+fn write_key_file(key_file: &KeyFile) -> BackendResult<()> {
+    let path = key_file_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(key_file)
+        .map_err(|e| BackendError::JsonError(e.to_string()))?;
+    fs::write(&path, json)?;
+    Ok(())
+}
+
+fn read_key_file(path: &Path) -> BackendResult<Option<KeyFile>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)?;
+    let mut key_file: KeyFile =
+        serde_json::from_str(&content).map_err(|e| BackendError::JsonError(e.to_string()))?;
+    if key_file.migrated {
+        key_file.migrated_dirs.insert(String::new());
+    }
+    Ok(Some(key_file))
+}
+
+fn new_key_file(passphrase: &str, params: &Params) -> BackendResult<(KeyFile, Cipher)> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let mut key_file = KeyFile {
+        version: 1,
+        salt: BASE64.encode(salt),
+        m_cost: params.m_cost(),
+        t_cost: params.t_cost(),
+        p_cost: params.p_cost(),
+        check: String::new(),
+        migrated_dirs: BTreeSet::new(),
+        migrated: false,
+    };
+    let cipher = Cipher::derive(passphrase, &key_file)?;
+    key_file.check = cipher.seal(KEY_CHECK_AAD, KEY_CHECK)?;
+    Ok((key_file, cipher))
+}
+
+fn verify(passphrase: &str, key_file: &KeyFile) -> BackendResult<Cipher> {
+    let cipher = Cipher::derive(passphrase, key_file)?;
+    match cipher.open(KEY_CHECK_AAD, &key_file.check) {
+        Ok(check) if check == KEY_CHECK => Ok(cipher),
+        _ => Err(BackendError::InvalidPassphrase),
+    }
+}
+
+pub fn status() -> EncryptionStatus {
+    match vault().read().as_deref() {
+        Ok(VaultState::Disabled) | Err(_) => EncryptionStatus {
+            enabled: false,
+            unlocked: false,
+        },
+        Ok(VaultState::Locked(_)) => EncryptionStatus {
+            enabled: true,
+            unlocked: false,
+        },
+        Ok(VaultState::Unlocked(..)) => EncryptionStatus {
+            enabled: true,
+            unlocked: true,
+        },
+    }
+}
+
+/// Turns on encryption with a new passphrase and leaves storage unlocked.
+/// Existing plaintext data is only converted by [`migrate`].
+pub fn enable(passphrase: &str) -> BackendResult<()> {
+    if passphrase.is_empty() {
+        return Err(BackendError::InvalidPassphrase);
+    }
+    let mut guard = vault().write().map_err(|_| lock_error())?;
+    if !matches!(*guard, VaultState::Disabled) {
+        return Err(BackendError::ConfigError(
+            "Encryption is already enabled".to_string(),
+        ));
+    }
+
+    let (key_file, cipher) = new_key_file(passphrase, &Params::default())?;
+    write_key_file(&key_file)?;
+
+    *guard = VaultState::Unlocked(cipher, key_file);
+    println!("🔐 Encryption at rest enabled");
+    Ok(())
+}
+
+pub fn unlock(passphrase: &str) -> BackendResult<()> {
+    let mut guard = vault().write().map_err(|_| lock_error())?;
+    let (cipher, key_file) = match &*guard {
+        VaultState::Disabled => {
+            return Err(BackendError::ConfigError(
+                "Encryption is not enabled".to_string(),
+            ));
+        }
+        VaultState::Unlocked(..) => return Ok(()),
+        VaultState::Locked(key_file) => (verify(passphrase, key_file)?, key_file.clone()),
+    };
+    *guard = VaultState::Unlocked(cipher, key_file);
+    println!("🔓 Encrypted storage unlocked");
+    Ok(())
+}
+
+/// Forgets the in-memory key. Does nothing when encryption is disabled.
+pub fn lock() -> BackendResult<()> {
+    let mut guard = vault().write().map_err(|_| lock_error())?;
+    if let VaultState::Unlocked(_, key_file) = &*guard {
+        *guard = VaultState::Locked(key_file.clone());
+    }
+    Ok(())
+}
+
+/// Fails with [`BackendError::StorageLocked`] if data cannot currently be read or written.
+pub fn ensure_unlocked() -> BackendResult<()> {
+    current_cipher().map(|_| ())
+}
+
+pub fn is_encrypted_record(line: &str) -> bool {
+    line.starts_with(RECORD_PREFIX)
+}
+
+/// Encrypts a single line for storage in the file at `path`, or returns it
+/// unchanged when encryption is disabled.
+pub fn seal_line<'a>(path: &Path, line: &'a str) -> BackendResult<Cow<'a, str>> {
+    match current_cipher()? {
+        Some(cipher) => cipher.seal(&aad(path), line.as_bytes()).map(Cow::Owned),
+        None => Ok(Cow::Borrowed(line)),
+    }
+}
+
+/// Decrypts an `enc1:` record read from the file at `path`. Plaintext lines
+/// are returned unchanged until [`migrate`] has run.
+pub fn open_line<'a>(path: &Path, line: &'a str) -> BackendResult<Cow<'a, str>> {
+    if !is_encrypted_record(line) {
+        return check_plaintext(accepts_plaintext()?, path, line);
+    }
+    let cipher = current_cipher()?.ok_or(BackendError::StorageLocked)?;
+    open_with(&cipher, path, line).map(Cow::Owned)
+}
+
+fn check_plaintext<'a>(accepted: bool, path: &Path, line: &'a str) -> BackendResult<Cow<'a, str>> {
+    if accepted || line.is_empty() {
+        Ok(Cow::Borrowed(line))
+    } else {
+        Err(BackendError::EncryptionError(format!(
+            "Unencrypted data in encrypted storage: {}",
+            path.display()
+        )))
+    }
+}
+
+fn open_with(cipher: &Cipher, path: &Path, line: &str) -> BackendResult<String> {
+    let bytes = cipher.open(&aad(path), line)?;
+    String::from_utf8(bytes).map_err(|e| BackendError::EncryptionError(e.to_string()))
+}
+
+/// Reads a line-oriented file, decrypting any encrypted records.
+pub fn read_lines(path: &Path) -> BackendResult<Vec<String>> {
+    let content = fs::read_to_string(path)?;
+    content
+        .lines()
+        .map(|line| open_line(path, line).map(Cow::into_owned))
+        .collect()
+}
+
+/// Reads a whole file that may have been written by [`write_file`].
+pub fn read_to_string(path: &Path) -> BackendResult<String> {
+    let content = fs::read_to_string(path)?;
+    if is_encrypted_record(&content) {
+        Ok(open_line(path, content.trim_end())?.into_owned())
+    } else {
+        Ok(check_plaintext(accepts_plaintext()?, path, &content)?.into_owned())
+    }
+}
+
+/// Atomically replaces `path` with `contents`, as a single encrypted record
+/// when enabled.
+pub fn write_file(path: &Path, contents: &str) -> BackendResult<()> {
+    let sealed = seal_line(path, contents)?;
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    fs::write(&tmp_path, sealed.as_bytes())?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Encrypts every plaintext RPC log and `project.json` under the current
+/// data directory's `projects`, and from then on rejects plaintext there;
+/// other users' data directories are migrated separately. Files are
+/// rewritten atomically and already encrypted records are left alone, so the
+/// migration can be re-run safely.
+pub fn migrate() -> BackendResult<MigrationReport> {
+    let cipher = current_cipher()?.ok_or_else(|| {
+        BackendError::ConfigError("Enable encryption before migrating data".to_string())
+    })?;
+    let root = gemini_desktop_dir()?.join("projects");
+    let report = migrate_dir(&root, &cipher)?;
+
+    let dir_key = data_dir_key()?;
+    let mut guard = vault().write().map_err(|_| lock_error())?;
+    if let VaultState::Unlocked(_, key_file) = &mut *guard
+        && key_file.migrated_dirs.insert(dir_key)
+    {
+        write_key_file(key_file)?;
+    }
+    Ok(report)
+}
+
+fn migrate_dir(root: &Path, cipher: &Cipher) -> BackendResult<MigrationReport> {
+    let mut report = MigrationReport::default();
+    if !root.exists() {
+        return Ok(report);
+    }
+
+    for project in fs::read_dir(root)?.flatten() {
+        if !project.path().is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(project.path())?.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let is_log = name.starts_with("rpc-log-") && name.ends_with(".log");
+            let is_metadata = name == "project.json";
+            if !is_log && !is_metadata {
+                continue;
+            }
+
+            report.files_scanned += 1;
+
+            let encrypted = if is_metadata {
+                let content = fs::read_to_string(&path)?;
+                if is_encrypted_record(&content) {
+                    continue;
+                }
+                let sealed = cipher.seal(&aad(&path), content.as_bytes())?;
+                let tmp_path = path.with_extension("migrate.tmp");
+                fs::write(&tmp_path, sealed.as_bytes())?;
+                fs::rename(&tmp_path, &path)?;
+                true
+            } else {
+                // Logs may still be appended to by a live logger.
+                crate::rpc::rewrite_log(&path, |content| {
+                    if content.lines().all(is_encrypted_record) {
+                        return Ok(None);
+                    }
+                    let mut out = String::with_capacity(content.len() * 2);
+                    for line in content.lines() {
+                        if is_encrypted_record(line) {
+                            out.push_str(line);
+                        } else {
+                            out.push_str(&cipher.seal(&aad(&path), line.as_bytes())?);
+                        }
+                        out.push('\n');
+                    }
+                    Ok(Some(out))
+                })?
+            };
+            if encrypted {
+                report.files_encrypted += 1;
+            }
+        }
+    }
+
+    println!(
+        "🔐 Encrypted {} of {} files",
+        report.files_encrypted, report.files_scanned
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::EnvGuard;
+    use tempfile::TempDir;
+
+    fn test_params() -> Params {
+        Params::new(256, 1, 1, Some(32)).unwrap()
+    }
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        let (key_file, cipher) = new_key_file("correct horse", &test_params()).unwrap();
+        let log = Path::new("/logs/rpc-log-1.log");
+        let record = cipher.seal(&aad(log), b"[2024-01-01] hello").unwrap();
+        assert!(is_encrypted_record(&record));
+        assert!(!record.contains("hello"));
+        assert_eq!(
+            open_with(&cipher, log, &record).unwrap(),
+            "[2024-01-01] hello"
+        );
+
+        // Nonces are random, so identical plaintexts produce different records.
+        assert_ne!(
+            cipher.seal(&aad(log), b"same").unwrap(),
+            cipher.seal(&aad(log), b"same").unwrap()
+        );
+
+        let reopened = verify("correct horse", &key_file).unwrap();
+        assert_eq!(
+            open_with(&reopened, log, &record).unwrap(),
+            "[2024-01-01] hello"
+        );
+    }
+
+    #[test]
+    fn test_records_are_bound_to_their_file() {
+        let (_, cipher) = new_key_file("pass", &test_params()).unwrap();
+        let log = Path::new("/logs/rpc-log-1.log");
+        let record = cipher.seal(&aad(log), b"[t1] mine").unwrap();
+        assert!(matches!(
+            open_with(&cipher, Path::new("/logs/rpc-log-2.log"), &record),
+            Err(BackendError::EncryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_rejected() {
+        let (key_file, _) = new_key_file("correct horse", &test_params()).unwrap();
+        assert!(matches!(
+            verify("battery staple", &key_file),
+            Err(BackendError::InvalidPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_record_fails_authentication() {
+        let (_, cipher) = new_key_file("pass", &test_params()).unwrap();
+        let record = cipher.seal(b"file", b"secret").unwrap();
+        let mut bytes = BASE64.decode(&record[RECORD_PREFIX.len()..]).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let tampered = format!("{RECORD_PREFIX}{}", BASE64.encode(bytes));
+        assert!(matches!(
+            cipher.open(b"file", &tampered),
+            Err(BackendError::EncryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_plain_lines_pass_through() {
+        let log = Path::new("/logs/rpc-log-1.log");
+        assert_eq!(open_line(log, "[2024] plain").unwrap(), "[2024] plain");
+    }
+
+    #[test]
+    fn test_plaintext_is_rejected_once_migrated() {
+        let log = Path::new("/logs/rpc-log-1.log");
+        assert_eq!(check_plaintext(true, log, "[t1] old").unwrap(), "[t1] old");
+        assert!(matches!(
+            check_plaintext(false, log, "[t1] injected"),
+            Err(BackendError::EncryptionError(_))
+        ));
+        assert_eq!(check_plaintext(false, log, "").unwrap(), "");
+    }
+
+    #[test]
+    fn test_migration_is_recorded_per_data_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut env_guard = EnvGuard::new();
+        env_guard.set("HOME", temp_dir.path().to_str().unwrap());
+        let root = crate::paths::root_data_dir().unwrap();
+
+        assert_eq!(data_dir_key().unwrap(), "");
+        let user_dir = root.join("users").join("alice");
+        assert_eq!(
+            crate::paths::sync_scope(Some(user_dir), data_dir_key).unwrap(),
+            "users/alice"
+        );
+
+        // Older key files only recorded that the root was migrated.
+        let (key_file, _) = new_key_file("pass", &test_params()).unwrap();
+        let mut legacy = serde_json::to_value(&key_file).unwrap();
+        legacy["migrated"] = true.into();
+        let path = temp_dir.path().join("encryption.json");
+        fs::write(&path, legacy.to_string()).unwrap();
+        let read = read_key_file(&path).unwrap().unwrap();
+        assert_eq!(read.migrated_dirs, BTreeSet::from([String::new()]));
+        assert!(
+            !serde_json::to_string(&read)
+                .unwrap()
+                .contains("\"migrated\"")
+        );
+    }
+
+    #[test]
+    fn test_migrate_dir_encrypts_plaintext_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("abcd");
+        fs::create_dir_all(&project).unwrap();
+        let log = project.join("rpc-log-1.log");
+        let metadata = project.join("project.json");
+        fs::write(&log, "[t1] first\n[t2] second\n").unwrap();
+        fs::write(&metadata, r#"{"path":"/tmp/x"}"#).unwrap();
+        fs::write(project.join("notes.txt"), "untouched").unwrap();
+
+        let (_, cipher) = new_key_file("pass", &test_params()).unwrap();
+        let report = migrate_dir(temp_dir.path(), &cipher).unwrap();
+        assert_eq!(report.files_scanned, 2);
+        assert_eq!(report.files_encrypted, 2);
+
+        let content = fs::read_to_string(&log).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| is_encrypted_record(l)));
+        assert_eq!(open_with(&cipher, &log, lines[1]).unwrap(), "[t2] second");
+
+        let meta = fs::read_to_string(&metadata).unwrap();
+        assert_eq!(
+            open_with(&cipher, &metadata, &meta).unwrap(),
+            r#"{"path":"/tmp/x"}"#
+        );
+        assert_eq!(
+            fs::read_to_string(project.join("notes.txt")).unwrap(),
+            "untouched"
+        );
+
+        let again = migrate_dir(temp_dir.path(), &cipher).unwrap();
+        assert_eq!(again.files_encrypted, 0);
+    }
+}
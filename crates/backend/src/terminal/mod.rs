@@ -0,0 +1,552 @@
+use portable_pty::{ChildKiller, CommandBuilder, MasterPty, PtySize, native_pty_system};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::events::EventEmitter;
+use crate::security::decode_utf8_prefix;
+use crate::types::{BackendError, BackendResult};
+
+/// Maximum number of complete lines kept per terminal for `recent_lines`.
+const SCROLLBACK_LINES: usize = 2000;
+
+/// Options used to spawn a new terminal.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct TerminalOptions {
+    /// Program to run. Defaults to the user's login shell.
+    pub shell: Option<String>,
+    pub args: Vec<String>,
+    pub working_directory: Option<String>,
+    pub env: HashMap<String, String>,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl Default for TerminalOptions {
+    fn default() -> Self {
+        Self {
+            shell: None,
+            args: Vec::new(),
+            working_directory: None,
+            env: HashMap::new(),
+            cols: 80,
+            rows: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TerminalInfo {
+    pub id: String,
+    pub shell: String,
+    pub working_directory: Option<String>,
+    pub pid: Option<u32>,
+    pub cols: u16,
+    pub rows: u16,
+    pub created_at: u64,
+    pub is_alive: bool,
+    pub exit_code: Option<u32>,
+}
+
+/// Payload of `terminal-output-{id}` events. `data` is raw terminal output,
+/// including escape sequences, ready to be written to a terminal emulator.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TerminalOutputPayload {
+    pub data: String,
+}
+
+/// Payload of the `terminal-exit-{id}` event sent once the process exits.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TerminalExitPayload {
+    pub exit_code: u32,
+    pub signal: Option<String>,
+    pub success: bool,
+}
+
+struct TerminalHandle {
+    master: Box<dyn MasterPty + Send>,
+    /// Shared so input is written without holding the terminal map's lock.
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+    info: Arc<Mutex<TerminalInfo>>,
+    scrollback: Arc<Mutex<Scrollback>>,
+}
+
+/// Owns every PTY-backed terminal spawned by the backend.
+///
+/// Each terminal gets a reader thread that forwards output as
+/// `terminal-output-{id}` events and a waiter thread that reports the exit
+/// status as `terminal-exit-{id}` and removes the terminal.
+#[derive(Clone, Default)]
+pub struct TerminalManager {
+    terminals: Arc<Mutex<HashMap<String, TerminalHandle>>>,
+}
+
+impl TerminalManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create<E: EventEmitter + 'static>(
+        &self,
+        options: TerminalOptions,
+        emitter: E,
+    ) -> BackendResult<TerminalInfo> {
+        let pty = native_pty_system()
+            .openpty(PtySize {
+                rows: options.rows,
+                cols: options.cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(pty_error)?;
+
+        let mut command = match &options.shell {
+            Some(shell) => CommandBuilder::new(shell),
+            None => CommandBuilder::new_default_prog(),
+        };
+        command.args(&options.args);
+        if let Some(dir) = &options.working_directory {
+            command.cwd(dir);
+        }
+        command.env("TERM", "xterm-256color");
+        for (key, value) in &options.env {
+            command.env(key, value);
+        }
+
+        let mut child = pty.slave.spawn_command(command).map_err(pty_error)?;
+        // The slave end must be closed in this process, otherwise the reader
+        // never sees EOF once the child exits.
+        drop(pty.slave);
+
+        let reader = pty.master.try_clone_reader().map_err(pty_error)?;
+        let writer = pty.master.take_writer().map_err(pty_error)?;
+        let killer = child.clone_killer();
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let info = Arc::new(Mutex::new(TerminalInfo {
+            id: id.clone(),
+            shell: options.shell.clone().unwrap_or_else(default_shell_name),
+            working_directory: options.working_directory.clone(),
+            pid: child.process_id(),
+            cols: options.cols,
+            rows: options.rows,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            is_alive: true,
+            exit_code: None,
+        }));
+        let scrollback = Arc::new(Mutex::new(Scrollback::default()));
+
+        spawn_reader(id.clone(), reader, Arc::clone(&scrollback), emitter.clone());
+
+        let snapshot = info.lock().map_err(|_| lock_error())?.clone();
+        // Registered before the waiter starts, so a process that exits at
+        // once is still removed.
+        self.terminals.lock().map_err(|_| lock_error())?.insert(
+            id.clone(),
+            TerminalHandle {
+                master: pty.master,
+                writer: Arc::new(Mutex::new(writer)),
+                killer,
+                info: Arc::clone(&info),
+                scrollback,
+            },
+        );
+
+        let waiter_id = id.clone();
+        let waiter_info = Arc::clone(&info);
+        let terminals = Arc::clone(&self.terminals);
+        std::thread::spawn(move || {
+            let payload = match child.wait() {
+                Ok(status) => TerminalExitPayload {
+                    exit_code: status.exit_code(),
+                    signal: status.signal().map(str::to_string),
+                    success: status.success(),
+                },
+                Err(e) => {
+                    eprintln!("Failed to wait for terminal {waiter_id}: {e}");
+                    TerminalExitPayload {
+                        exit_code: 1,
+                        signal: None,
+                        success: false,
+                    }
+                }
+            };
+            if let Ok(mut info) = waiter_info.lock() {
+                info.is_alive = false;
+                info.exit_code = Some(payload.exit_code);
+            }
+            println!(
+                "🖥️ Terminal {waiter_id} exited with code {}",
+                payload.exit_code
+            );
+            let exited = terminals
+                .lock()
+                .ok()
+                .and_then(|mut terminals| terminals.remove(&waiter_id));
+            drop(exited);
+            let _ = emitter.emit(&format!("terminal-exit-{waiter_id}"), payload);
+        });
+
+        println!("🖥️ Created terminal {} ({})", snapshot.id, snapshot.shell);
+        Ok(snapshot)
+    }
+
+    /// Writes raw input (keystrokes, pasted text) to the terminal. The write
+    /// blocks while the PTY's buffer is full, so it runs off the async workers.
+    pub async fn write(&self, id: &str, data: &str) -> BackendResult<()> {
+        let writer = {
+            let terminals = self.terminals.lock().map_err(|_| lock_error())?;
+            let handle = terminals
+                .get(id)
+                .ok_or_else(|| BackendError::TerminalNotFound(id.to_string()))?;
+            Arc::clone(&handle.writer)
+        };
+        let data = data.as_bytes().to_vec();
+        tokio::task::spawn_blocking(move || {
+            let mut writer = writer.lock().map_err(|_| lock_error())?;
+            writer.write_all(&data)?;
+            writer.flush()?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| BackendError::CommandExecutionFailed(e.to_string()))?
+    }
+
+    pub fn resize(&self, id: &str, cols: u16, rows: u16) -> BackendResult<()> {
+        let terminals = self.terminals.lock().map_err(|_| lock_error())?;
+        let handle = terminals
+            .get(id)
+            .ok_or_else(|| BackendError::TerminalNotFound(id.to_string()))?;
+        handle
+            .master
+            .resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(pty_error)?;
+        if let Ok(mut info) = handle.info.lock() {
+            info.cols = cols;
+            info.rows = rows;
+        }
+        Ok(())
+    }
+
+    /// Kills the terminal's process and forgets about it. The exit event is
+    /// still emitted by the waiter thread.
+    pub fn kill(&self, id: &str) -> BackendResult<()> {
+        let mut handle = self
+            .terminals
+            .lock()
+            .map_err(|_| lock_error())?
+            .remove(id)
+            .ok_or_else(|| BackendError::TerminalNotFound(id.to_string()))?;
+
+        let alive = handle.info.lock().map(|info| info.is_alive).unwrap_or(true);
+        if alive {
+            // The process may exit between the check and the kill.
+            let _ = handle.killer.kill();
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> BackendResult<Vec<TerminalInfo>> {
+        let terminals = self.terminals.lock().map_err(|_| lock_error())?;
+        let mut list: Vec<TerminalInfo> = terminals
+            .values()
+            .filter_map(|handle| handle.info.lock().ok().map(|info| info.clone()))
+            .collect();
+        list.sort_by_key(|info| info.created_at);
+        Ok(list)
+    }
+
+    pub fn info(&self, id: &str) -> BackendResult<TerminalInfo> {
+        let terminals = self.terminals.lock().map_err(|_| lock_error())?;
+        let handle = terminals
+            .get(id)
+            .ok_or_else(|| BackendError::TerminalNotFound(id.to_string()))?;
+        let info = handle.info.lock().map_err(|_| lock_error())?.clone();
+        Ok(info)
+    }
+
+    /// Returns the last `count` lines of output with escape sequences removed,
+    /// suitable for handing to the model as context.
+    pub fn recent_lines(&self, id: &str, count: usize) -> BackendResult<Vec<String>> {
+        let terminals = self.terminals.lock().map_err(|_| lock_error())?;
+        let handle = terminals
+            .get(id)
+            .ok_or_else(|| BackendError::TerminalNotFound(id.to_string()))?;
+        let scrollback = handle.scrollback.lock().map_err(|_| lock_error())?;
+        Ok(scrollback.last_lines(count))
+    }
+
+    /// Kills every terminal, used on shutdown.
+    pub fn kill_all(&self) {
+        let ids: Vec<String> = match self.terminals.lock() {
+            Ok(terminals) => terminals.keys().cloned().collect(),
+            Err(_) => return,
+        };
+        for id in ids {
+            let _ = self.kill(&id);
+        }
+    }
+}
+
+fn spawn_reader<E: EventEmitter + 'static>(
+    id: String,
+    mut reader: Box<dyn Read + Send>,
+    scrollback: Arc<Mutex<Scrollback>>,
+    emitter: E,
+) {
+    std::thread::spawn(move || {
+        let event = format!("terminal-output-{id}");
+        let mut buf = [0u8; 8192];
+        let mut carry: Vec<u8> = Vec::new();
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            carry.extend_from_slice(&buf[..n]);
+            let text = decode_utf8_prefix(&mut carry);
+            if text.is_empty() {
+                continue;
+            }
+            if let Ok(mut scrollback) = scrollback.lock() {
+                scrollback.push(&text);
+            }
+            let _ = emitter.emit(&event, TerminalOutputPayload { data: text });
+        }
+        if !carry.is_empty() {
+            let text = String::from_utf8_lossy(&carry).into_owned();
+            if let Ok(mut scrollback) = scrollback.lock() {
+                scrollback.push(&text);
+            }
+            let _ = emitter.emit(&event, TerminalOutputPayload { data: text });
+        }
+    });
+}
+
+/// Plain-text line history of a terminal, with escape sequences stripped.
+#[derive(Default)]
+struct Scrollback {
+    lines: VecDeque<String>,
+    current: String,
+    escape: EscapeState,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum EscapeState {
+    #[default]
+    Text,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+impl Scrollback {
+    fn push(&mut self, data: &str) {
+        for c in data.chars() {
+            self.escape = match (self.escape, c) {
+                (EscapeState::Text, '\u{1b}') => EscapeState::Escape,
+                (EscapeState::Text, '\n') => {
+                    self.finish_line();
+                    EscapeState::Text
+                }
+                (EscapeState::Text, '\r') => {
+                    // A bare carriage return rewinds the line (progress bars),
+                    // but "\r\n" is just a line ending.
+                    self.current.push('\r');
+                    EscapeState::Text
+                }
+                (EscapeState::Text, '\u{8}') => {
+                    self.current.pop();
+                    EscapeState::Text
+                }
+                (EscapeState::Text, c) if c.is_control() && c != '\t' => EscapeState::Text,
+                (EscapeState::Text, c) => {
+                    self.current.push(c);
+                    EscapeState::Text
+                }
+                (EscapeState::Escape, '[') => EscapeState::Csi,
+                (EscapeState::Escape, ']') => EscapeState::Osc,
+                (EscapeState::Escape, _) => EscapeState::Text,
+                (EscapeState::Csi, c) if ('@'..='~').contains(&c) => EscapeState::Text,
+                (EscapeState::Csi, _) => EscapeState::Csi,
+                (EscapeState::Osc, '\u{7}') => EscapeState::Text,
+                (EscapeState::Osc, '\u{1b}') => EscapeState::OscEscape,
+                (EscapeState::Osc, _) => EscapeState::Osc,
+                (EscapeState::OscEscape, _) => EscapeState::Text,
+            };
+        }
+    }
+
+    fn finish_line(&mut self) {
+        let raw = std::mem::take(&mut self.current);
+        let line = raw
+            .trim_end_matches('\r')
+            .rsplit('\r')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        self.lines.push_back(line);
+        while self.lines.len() > SCROLLBACK_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    fn last_lines(&self, count: usize) -> Vec<String> {
+        let current = self.current.rsplit('\r').next().unwrap_or_default();
+        let mut lines: Vec<String> = self.lines.iter().cloned().collect();
+        if !current.is_empty() {
+            lines.push(current.to_string());
+        }
+        let skip = lines.len().saturating_sub(count);
+        lines.split_off(skip)
+    }
+}
+
+fn default_shell_name() -> String {
+    if cfg!(windows) {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+    }
+}
+
+fn pty_error(error: impl std::fmt::Display) -> BackendError {
+    BackendError::CommandExecutionFailed(format!("Terminal error: {error}"))
+}
+
+fn lock_error() -> BackendError {
+    BackendError::CommandExecutionFailed("Failed to lock terminals".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::MockEventEmitter;
+    use std::time::Duration;
+
+    fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        false
+    }
+
+    #[test]
+    fn test_scrollback_strips_escapes_and_carriage_returns() {
+        let mut scrollback = Scrollback::default();
+        scrollback.push("\u{1b}[32mok\u{1b}[0m line\r\n");
+        scrollback.push("progress 10%\rprogress 100%\r\n");
+        scrollback.push("\u{1b}]0;title\u{7}prompt$ ");
+
+        assert_eq!(
+            scrollback.last_lines(10),
+            vec!["ok line", "progress 100%", "prompt$ "]
+        );
+        assert_eq!(scrollback.last_lines(1), vec!["prompt$ "]);
+    }
+
+    #[test]
+    fn test_scrollback_is_bounded() {
+        let mut scrollback = Scrollback::default();
+        for i in 0..SCROLLBACK_LINES + 10 {
+            scrollback.push(&format!("{i}\n"));
+        }
+        let lines = scrollback.last_lines(usize::MAX);
+        assert_eq!(lines.len(), SCROLLBACK_LINES);
+        assert_eq!(lines[0], "10");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_terminal_round_trip() {
+        let manager = TerminalManager::new();
+        let emitter = MockEventEmitter::new();
+        let info = manager
+            .create(
+                TerminalOptions {
+                    shell: Some("/bin/sh".to_string()),
+                    ..Default::default()
+                },
+                emitter.clone(),
+            )
+            .unwrap();
+        assert!(info.is_alive);
+
+        // Interactive shells may discard type-ahead while setting up the
+        // line discipline, so wait for the prompt first.
+        let output_event = format!("terminal-output-{}", info.id);
+        assert!(wait_for(|| emitter.get_event_count(&output_event) > 0));
+
+        manager
+            .write(&info.id, "echo terminal-$((40+2))\n")
+            .await
+            .unwrap();
+        assert!(wait_for(|| {
+            manager
+                .recent_lines(&info.id, 50)
+                .unwrap()
+                .iter()
+                .any(|line| line == "terminal-42")
+        }));
+
+        manager.resize(&info.id, 120, 40).unwrap();
+        assert_eq!(manager.info(&info.id).unwrap().cols, 120);
+
+        // A terminal whose process exits is reported and forgotten.
+        manager.write(&info.id, "exit 3\n").await.unwrap();
+        let exit_event = format!("terminal-exit-{}", info.id);
+        assert!(wait_for(|| emitter.get_event_count(&exit_event) == 1));
+        assert_eq!(
+            emitter.get_events_by_name(&exit_event)[0]["exit_code"],
+            serde_json::json!(3)
+        );
+        assert!(manager.list().unwrap().is_empty());
+        assert!(matches!(
+            manager.write(&info.id, "ls\n").await,
+            Err(BackendError::TerminalNotFound(_))
+        ));
+        assert!(matches!(
+            manager.kill(&info.id),
+            Err(BackendError::TerminalNotFound(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_terminates_process() {
+        let manager = TerminalManager::new();
+        let emitter = MockEventEmitter::new();
+        let info = manager
+            .create(
+                TerminalOptions {
+                    shell: Some("/bin/sh".to_string()),
+                    ..Default::default()
+                },
+                emitter.clone(),
+            )
+            .unwrap();
+
+        manager.kill(&info.id).unwrap();
+        assert!(manager.list().unwrap().is_empty());
+        assert!(wait_for(|| {
+            emitter.get_event_count(&format!("terminal-exit-{}", info.id)) == 1
+        }));
+    }
+}
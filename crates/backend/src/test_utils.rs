@@ -81,6 +81,12 @@ impl EnvGuard {
     }
 }
 
+impl Default for EnvGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for EnvGuard {
     fn drop(&mut self) {
         // Restore all original environment variable values
@@ -1,9 +1,10 @@
 use crate::types::{BackendError, BackendResult};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ThemeColors {
     pub background: String,
     pub foreground: String,
@@ -19,7 +20,7 @@ pub struct ThemeColors {
     pub success: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CustomTheme {
     pub name: String,
     pub description: Option<String>,
@@ -30,7 +31,7 @@ pub struct CustomTheme {
     pub updated_at: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ThemePreset {
     pub name: String,
     pub description: String,
@@ -39,13 +40,7 @@ pub struct ThemePreset {
 
 /// Get the themes directory path
 fn get_themes_dir() -> BackendResult<PathBuf> {
-    let home_dir = dirs::home_dir()
-        .ok_or_else(|| BackendError::IoError(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Could not find home directory"
-        )))?;
-    
-    let themes_dir = home_dir.join(".gemini-desktop").join("themes");
+    let themes_dir = crate::paths::data_dir()?.join("themes");
     
     if !themes_dir.exists() {
         fs::create_dir_all(&themes_dir)
@@ -55,10 +50,21 @@ fn get_themes_dir() -> BackendResult<PathBuf> {
     Ok(themes_dir)
 }
 
+/// The file a theme is stored in. Names come from clients, so anything that
+/// could lead out of the themes directory is refused.
+fn theme_file(name: &str) -> BackendResult<PathBuf> {
+    if name.trim().is_empty()
+        || name.contains(['/', '\\', ':', '\0'])
+        || name.contains("..")
+    {
+        return Err(BackendError::InvalidRequest(format!("Invalid theme name: {name}")));
+    }
+    Ok(get_themes_dir()?.join(format!("{}.json", name.replace(' ', "_").to_lowercase())))
+}
+
 /// Save a custom theme
 pub async fn save_theme(theme: CustomTheme) -> BackendResult<()> {
-    let themes_dir = get_themes_dir()?;
-    let theme_file = themes_dir.join(format!("{}.json", theme.name.replace(' ', "_").to_lowercase()));
+    let theme_file = theme_file(&theme.name)?;
     
     let theme_json = serde_json::to_string_pretty(&theme)
         .map_err(|e| BackendError::JsonError(e.to_string()))?;
@@ -71,8 +77,7 @@ pub async fn save_theme(theme: CustomTheme) -> BackendResult<()> {
 
 /// Load a custom theme by name
 pub async fn load_theme(name: &str) -> BackendResult<CustomTheme> {
-    let themes_dir = get_themes_dir()?;
-    let theme_file = themes_dir.join(format!("{}.json", name.replace(' ', "_").to_lowercase()));
+    let theme_file = theme_file(name)?;
     
     let theme_json = fs::read_to_string(theme_file)
         .map_err(BackendError::IoError)?;
@@ -104,8 +109,7 @@ pub async fn list_themes() -> BackendResult<Vec<String>> {
 
 /// Delete a custom theme
 pub async fn delete_theme(name: &str) -> BackendResult<()> {
-    let themes_dir = get_themes_dir()?;
-    let theme_file = themes_dir.join(format!("{}.json", name.replace(' ', "_").to_lowercase()));
+    let theme_file = theme_file(name)?;
     
     fs::remove_file(theme_file)
         .map_err(BackendError::IoError)?;
@@ -241,7 +245,8 @@ pub fn generate_theme_css(colors: &ThemeColors) -> String {
     )
 }
 
-/// Export theme as CSS file
+/// Export theme as CSS file. The path is taken as is, so this is only for
+/// the desktop app, where the user picked it.
 pub async fn export_theme_css(theme: &CustomTheme, output_path: &str) -> BackendResult<()> {
     let css_content = generate_theme_css(&theme.colors);
     
@@ -249,4 +254,43 @@ pub async fn export_theme_css(theme: &CustomTheme, output_path: &str) -> Backend
         .map_err(BackendError::IoError)?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme(name: &str) -> CustomTheme {
+        let preset = get_theme_presets().remove(0);
+        CustomTheme {
+            name: name.to_string(),
+            description: None,
+            author: None,
+            version: "1.0.0".to_string(),
+            colors: preset.colors,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_theme_names_stay_in_the_themes_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = dir.path().join("auth.json");
+        fs::write(&auth, "{}").unwrap();
+        crate::paths::scope(Some(dir.path().to_path_buf()), async {
+            for name in ["../auth", "../../../auth", "/etc/passwd", "..\\auth", "C:auth", ""] {
+                assert!(save_theme(theme(name)).await.is_err(), "{name}");
+                assert!(load_theme(name).await.is_err(), "{name}");
+                assert!(delete_theme(name).await.is_err(), "{name}");
+            }
+
+            save_theme(theme("Night Owl")).await.unwrap();
+            assert_eq!(load_theme("Night Owl").await.unwrap().name, "Night Owl");
+            assert_eq!(list_themes().await.unwrap(), ["night owl"]);
+            delete_theme("Night Owl").await.unwrap();
+        })
+        .await;
+        assert!(auth.exists());
+    }
+}
@@ -1,3 +1,5 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub type BackendResult<T> = Result<T, BackendError>;
@@ -36,6 +38,112 @@ pub enum BackendError {
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
+
+    #[error("Terminal not found: {0}")]
+    TerminalNotFound(String),
+
+    #[error("Encrypted storage is locked; unlock it with your passphrase first")]
+    StorageLocked,
+
+    #[error("Invalid passphrase")]
+    InvalidPassphrase,
+
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Gemini CLI not available")]
+    CliNotAvailable,
+
+    #[error("The backend is shutting down")]
+    ShuttingDown,
+}
+
+impl BackendError {
+    /// Stable machine-readable identifier. Clients match on this, so existing
+    /// codes must never change.
+    pub fn code(&self) -> &'static str {
+        match self {
+            BackendError::SessionInitFailed(_) => "session_init_failed",
+            BackendError::SessionNotFound(_) => "session_not_found",
+            BackendError::ProcessNotFound => "process_not_found",
+            BackendError::CommandNotAllowed => "command_not_allowed",
+            BackendError::CommandExecutionFailed(_) => "command_execution_failed",
+            BackendError::JsonError(_) => "json_error",
+            BackendError::ChannelError => "channel_error",
+            BackendError::IoError(_) => "io_error",
+            BackendError::PathError(_) => "path_error",
+            BackendError::ProjectNotFound(_) => "project_not_found",
+            BackendError::ConfigError(_) => "config_error",
+            BackendError::TerminalNotFound(_) => "terminal_not_found",
+            BackendError::StorageLocked => "storage_locked",
+            BackendError::InvalidPassphrase => "invalid_passphrase",
+            BackendError::EncryptionError(_) => "encryption_error",
+            BackendError::InvalidRequest(_) => "invalid_request_body",
+            BackendError::CliNotAvailable => "cli_not_available",
+            BackendError::ShuttingDown => "shutting_down",
+        }
+    }
+
+    /// HTTP status code the web server responds with for this error.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            BackendError::SessionNotFound(_)
+            | BackendError::ProjectNotFound(_)
+            | BackendError::ProcessNotFound
+            | BackendError::TerminalNotFound(_) => 404,
+            BackendError::CommandNotAllowed => 403,
+            BackendError::PathError(_) | BackendError::ConfigError(_) => 400,
+            BackendError::InvalidPassphrase => 401,
+            BackendError::StorageLocked => 423,
+            BackendError::InvalidRequest(_) => 422,
+            BackendError::CliNotAvailable | BackendError::ShuttingDown => 503,
+            _ => 500,
+        }
+    }
+}
+
+/// Serializable error returned by both the web API and the Tauri commands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    pub status: u16,
+}
+
+impl ErrorBody {
+    pub fn new(code: impl Into<String>, message: impl Into<String>, status: u16) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            status,
+        }
+    }
+
+    /// Error without a more specific code, e.g. from a failed system call.
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new("internal_error", message, 500)
+    }
+}
+
+impl From<&BackendError> for ErrorBody {
+    fn from(error: &BackendError) -> Self {
+        Self::new(error.code(), error.to_string(), error.http_status())
+    }
+}
+
+impl From<BackendError> for ErrorBody {
+    fn from(error: BackendError) -> Self {
+        Self::from(&error)
+    }
+}
+
+impl std::fmt::Display for ErrorBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
 #[cfg(test)]
@@ -120,18 +228,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_terminal_not_found_error() {
+        let error = BackendError::TerminalNotFound("term_1".to_string());
+        assert_eq!(error.to_string(), "Terminal not found: term_1");
+    }
+
+    #[test]
+    fn test_storage_locked_error() {
+        let error = BackendError::StorageLocked;
+        assert_eq!(
+            error.to_string(),
+            "Encrypted storage is locked; unlock it with your passphrase first"
+        );
+    }
+
     #[test]
     fn test_backend_result_ok() {
         let result: BackendResult<i32> = Ok(42);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), 42);
+        assert!(matches!(result, Ok(42)));
     }
 
     #[test]
     fn test_backend_result_err() {
         let result: BackendResult<i32> = Err(BackendError::ProcessNotFound);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err().to_string(), "Process not found");
+        assert!(matches!(&result, Err(e) if e.to_string() == "Process not found"));
     }
 
     #[test]
@@ -163,16 +284,53 @@ mod tests {
             BackendError::CommandExecutionFailed("test".to_string()),
             BackendError::JsonError("test".to_string()),
             BackendError::ChannelError,
-            BackendError::IoError(Error::new(ErrorKind::Other, "test")),
+            BackendError::IoError(Error::other("test")),
             BackendError::PathError("test".to_string()),
             BackendError::ProjectNotFound("test".to_string()),
             BackendError::ConfigError("test".to_string()),
+            BackendError::TerminalNotFound("test".to_string()),
+            BackendError::StorageLocked,
+            BackendError::InvalidPassphrase,
+            BackendError::EncryptionError("test".to_string()),
+            BackendError::InvalidRequest("test".to_string()),
+            BackendError::CliNotAvailable,
+            BackendError::ShuttingDown,
         ];
 
+        let mut codes = std::collections::HashSet::new();
         for error in errors {
             // Ensure all variants can be displayed and debugged
             let _display = error.to_string();
             let _debug = format!("{:?}", error);
+            // Every variant has its own code
+            assert!(codes.insert(error.code()));
         }
     }
+
+    #[test]
+    fn test_error_http_status() {
+        assert_eq!(BackendError::SessionNotFound("s".to_string()).http_status(), 404);
+        assert_eq!(BackendError::ProjectNotFound("p".to_string()).http_status(), 404);
+        assert_eq!(BackendError::CommandNotAllowed.http_status(), 403);
+        assert_eq!(BackendError::PathError("p".to_string()).http_status(), 400);
+        assert_eq!(BackendError::ChannelError.http_status(), 500);
+        assert_eq!(BackendError::InvalidRequest("x".to_string()).http_status(), 422);
+        assert_eq!(BackendError::CliNotAvailable.http_status(), 503);
+        assert_eq!(BackendError::ShuttingDown.http_status(), 503);
+    }
+
+    #[test]
+    fn test_error_body_serialization() {
+        let body = ErrorBody::from(BackendError::SessionNotFound("abc".to_string()));
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "session_not_found",
+                "message": "Session not found: abc",
+                "status": 404,
+            })
+        );
+        assert_eq!(body.to_string(), "Session not found: abc");
+    }
 }
@@ -0,0 +1,585 @@
+use crate::error::{ApiError, ApiResult};
+use crate::rate_limit::{RateLimited, RateLimiter};
+use crate::roles::{Guest, Guests, Role};
+use crate::users::{ADMIN, User, Users};
+use argon2::Argon2;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{Config, State, get, post};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+pub const SESSION_COOKIE: &str = "gd_session";
+const SESSION_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// Persisted credentials. Only hashes are stored: the token as SHA-256 (it is
+/// 256 bits of randomness, so a fast hash is fine) and the password as Argon2id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AuthFile {
+    token_sha256: String,
+    #[serde(default)]
+    password_hash: Option<String>,
+}
+
+/// Owns the access token, optional password and live login sessions.
+pub struct AuthManager {
+    path: PathBuf,
+    file: RwLock<AuthFile>,
+    sessions: Mutex<HashMap<String, Instant>>,
+}
+
+impl AuthManager {
+    /// Loads `auth.json` from `data_dir`, generating a token on first run.
+    /// The new token is printed once and never stored in plain text.
+    pub fn load_or_init(data_dir: &Path) -> std::io::Result<Self> {
+        if let Some(auth) = Self::load(data_dir)? {
+            return Ok(auth);
+        }
+        let (auth, token) = Self::create(data_dir)?;
+        print_first_run_banner(&token, &auth.path);
+        Ok(auth)
+    }
+
+    /// Loads `auth.json` from `data_dir`, if there is one.
+    pub fn load(data_dir: &Path) -> std::io::Result<Option<Self>> {
+        let path = data_dir.join("auth.json");
+        let file = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(Self::with_file(path, file)))
+    }
+
+    /// Writes a new `auth.json` to `data_dir`, returning the access token.
+    pub fn create(data_dir: &Path) -> std::io::Result<(Self, String)> {
+        let path = data_dir.join("auth.json");
+        let token = generate_secret();
+        let file = AuthFile {
+            token_sha256: sha256_hex(&token),
+            password_hash: None,
+        };
+        write_auth_file(&path, &file)?;
+        Ok((Self::with_file(path, file), token))
+    }
+
+    /// Credentials that are never written to disk, returning the token.
+    /// Changing their password or token fails.
+    pub fn in_memory() -> (Self, String) {
+        let token = generate_secret();
+        let file = AuthFile {
+            token_sha256: sha256_hex(&token),
+            password_hash: None,
+        };
+        (Self::with_file(PathBuf::new(), file), token)
+    }
+
+    fn with_file(path: PathBuf, file: AuthFile) -> Self {
+        Self {
+            path,
+            file: RwLock::new(file),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn verify_token(&self, token: &str) -> bool {
+        let expected = match self.file.read() {
+            Ok(file) => file.token_sha256.clone(),
+            Err(_) => return false,
+        };
+        constant_time_eq(sha256_hex(token).as_bytes(), expected.as_bytes())
+    }
+
+    /// Checks the password on the blocking pool: Argon2 is deliberately slow
+    /// and would otherwise stall the async workers.
+    pub async fn verify_password(&self, password: &str) -> bool {
+        let stored = match self.file.read() {
+            Ok(file) => file.password_hash.clone(),
+            Err(_) => return false,
+        };
+        let Some(stored) = stored else {
+            return false;
+        };
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || {
+            PasswordHash::new(&stored)
+                .map(|hash| {
+                    Argon2::default()
+                        .verify_password(password.as_bytes(), &hash)
+                        .is_ok()
+                })
+                .unwrap_or(false)
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    pub fn has_password(&self) -> bool {
+        self.file
+            .read()
+            .map(|file| file.password_hash.is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn set_password(&self, password: &str) -> Result<(), String> {
+        if password.chars().count() < MIN_PASSWORD_LEN {
+            return Err(format!(
+                "Password must be at least {MIN_PASSWORD_LEN} characters"
+            ));
+        }
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| e.to_string())?
+            .to_string();
+
+        let mut file = self.file.write().map_err(|_| "Failed to lock auth state")?;
+        file.password_hash = Some(hash);
+        write_auth_file(&self.path, &file).map_err(|e| e.to_string())?;
+        drop(file);
+
+        // Changing the password logs out every other browser.
+        self.revoke_all_sessions();
+        Ok(())
+    }
+
+    /// Replaces the access token, returning the new one. Existing sessions are revoked.
+    pub fn rotate_token(&self) -> Result<String, String> {
+        let token = generate_secret();
+        let mut file = self.file.write().map_err(|_| "Failed to lock auth state")?;
+        file.token_sha256 = sha256_hex(&token);
+        write_auth_file(&self.path, &file).map_err(|e| e.to_string())?;
+        drop(file);
+
+        self.revoke_all_sessions();
+        Ok(token)
+    }
+
+    pub fn create_session(&self) -> String {
+        let id = generate_secret();
+        if let Ok(mut sessions) = self.sessions.lock() {
+            let now = Instant::now();
+            sessions.retain(|_, created| now.duration_since(*created) < SESSION_TTL);
+            sessions.insert(id.clone(), now);
+        }
+        id
+    }
+
+    pub fn validate_session(&self, id: &str) -> bool {
+        self.sessions
+            .lock()
+            .ok()
+            .and_then(|sessions| sessions.get(id).copied())
+            .is_some_and(|created| created.elapsed() < SESSION_TTL)
+    }
+
+    pub fn revoke_session(&self, id: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(id);
+        }
+    }
+
+    fn revoke_all_sessions(&self) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.clear();
+        }
+    }
+}
+
+pub(crate) fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn sha256_hex(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value.as_bytes()))
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn write_auth_file(path: &Path, file: &AuthFile) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(file)?;
+    let tmp_path = path.with_extension("json.tmp");
+    {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut out = options.open(&tmp_path)?;
+        std::io::Write::write_all(&mut out, json.as_bytes())?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+fn print_first_run_banner(token: &str, path: &Path) {
+    println!();
+    println!("🔑 First run: generated an access token for the web UI");
+    println!("   Token: {token}");
+    println!("   Use it to log in, then set a password from the login screen's account settings.");
+    println!(
+        "   It is shown only once; only a hash is kept in {}",
+        path.display()
+    );
+    println!();
+}
+
+/// Who a request comes from. The account in `<data-dir>/auth.json` is the
+/// admin; in multi-user mode the others are [`User`]s. A [`Guest`] was
+/// invited to a single session and only gets past the [`Caller`] guard.
+#[derive(Clone)]
+pub enum Account {
+    Admin,
+    User(Arc<User>),
+    Guest(Arc<Guest>),
+}
+
+impl Account {
+    pub fn name(&self) -> &str {
+        match self {
+            Account::Admin => ADMIN,
+            Account::User(user) => &user.workspace.user,
+            Account::Guest(guest) => &guest.name,
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        matches!(self, Account::Admin)
+    }
+
+    /// This account's credentials; `admin` holds the admin's.
+    pub fn credentials<'a>(&'a self, admin: &'a AuthManager) -> &'a AuthManager {
+        match self {
+            Account::Admin => admin,
+            Account::User(user) => &user.auth,
+            Account::Guest(guest) => &guest.auth,
+        }
+    }
+}
+
+fn authenticate(request: &Request<'_>, auth: &AuthManager) -> Option<Account> {
+    let users = request.rocket().state::<Arc<Users>>();
+    let guests = request.rocket().state::<Guests>();
+
+    if let Some(cookie) = request.cookies().get(SESSION_COOKIE) {
+        if auth.validate_session(cookie.value()) {
+            return Some(Account::Admin);
+        }
+        if let Some(user) = users.and_then(|users| users.by_session(cookie.value())) {
+            return Some(Account::User(user));
+        }
+        if let Some(guest) = guests.and_then(|guests| guests.by_session(cookie.value())) {
+            return Some(Account::Guest(guest));
+        }
+    }
+
+    let bearer = request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(str::trim);
+    let query = request
+        .query_value::<&str>("access_token")
+        .and_then(Result::ok);
+    for token in bearer.into_iter().chain(query) {
+        if auth.verify_token(token) {
+            return Some(Account::Admin);
+        }
+        if let Some(user) = users.and_then(|users| users.by_token(token)) {
+            return Some(Account::User(user));
+        }
+        if let Some(guest) = guests.and_then(|guests| guests.by_token(token)) {
+            return Some(Account::Guest(guest));
+        }
+    }
+    None
+}
+
+/// Request guard admitting requests with a valid session cookie, an
+/// `Authorization: Bearer <token>` header, or (for WebSocket clients that
+/// cannot set headers) an `access_token` query parameter. Guests are
+/// refused with 403 Forbidden.
+pub struct Authenticated(pub Account);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Authenticated {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        request
+            .guard::<Caller>()
+            .await
+            .and_then(|Caller(account)| match account {
+                Account::Guest(_) => Outcome::Error((Status::Forbidden, ())),
+                account => Outcome::Success(Authenticated(account)),
+            })
+    }
+}
+
+/// Like [`Authenticated`], but also admits guests.
+pub struct Caller(pub Account);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Caller {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(auth) = request.rocket().state::<Arc<AuthManager>>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        match request.local_cache(|| authenticate(request, auth)) {
+            Some(account) => Outcome::Success(Caller(account.clone())),
+            None => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// Session cookie for a fresh login. Marked `Secure` when serving over TLS.
+fn session_cookie(session_id: String, secure: bool) -> Cookie<'static> {
+    Cookie::build((SESSION_COOKIE, session_id))
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Strict)
+        .path("/")
+        .max_age(rocket::time::Duration::seconds(SESSION_TTL.as_secs() as i64))
+        .build()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuthStatus {
+    authenticated: bool,
+    password_set: bool,
+    multi_user: bool,
+    /// Name of the logged-in account.
+    user: Option<String>,
+    admin: bool,
+    /// For guests, the session they were invited to and their role in it.
+    #[serde(default)]
+    session_id: Option<String>,
+    #[serde(default)]
+    role: Option<Role>,
+}
+
+/// Logs in with a password or access token. In multi-user mode a password
+/// needs the `user` it belongs to; a token identifies its user by itself.
+#[derive(Serialize, Deserialize)]
+pub struct LoginRequest {
+    password: Option<String>,
+    token: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SetPasswordRequest {
+    password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TokenResponse {
+    token: String,
+}
+
+#[get("/auth/status")]
+pub fn auth_status(
+    caller: Option<Caller>,
+    auth: &State<Arc<AuthManager>>,
+    users: &State<Arc<Users>>,
+) -> Json<AuthStatus> {
+    let account = caller.map(|Caller(account)| account);
+    let grant = match &account {
+        Some(Account::Guest(guest)) => Some(guest.grant.clone()),
+        _ => None,
+    };
+    let password_set = match &account {
+        Some(account) => account.credentials(auth).has_password(),
+        None => auth.has_password(),
+    };
+    Json(AuthStatus {
+        authenticated: account.is_some(),
+        password_set,
+        multi_user: users.enabled(),
+        user: account.as_ref().map(|account| account.name().to_string()),
+        admin: account.as_ref().is_some_and(Account::is_admin),
+        session_id: grant.as_ref().map(|grant| grant.session_id.clone()),
+        role: grant.map(|grant| grant.role),
+    })
+}
+
+async fn check_login(request: &LoginRequest, auth: &AuthManager) -> bool {
+    if let Some(password) = &request.password
+        && auth.verify_password(password).await
+    {
+        return true;
+    }
+    request
+        .token
+        .as_deref()
+        .is_some_and(|token| auth.verify_token(token.trim()))
+}
+
+/// Logs in with a password or access token. Each client is rate limited,
+/// and locked out for increasingly long after repeated failures.
+#[allow(clippy::too_many_arguments)]
+#[post("/auth/login", data = "<request>")]
+pub async fn login(
+    _limit: RateLimited,
+    client: Option<IpAddr>,
+    request: Json<LoginRequest>,
+    cookies: &CookieJar<'_>,
+    auth: &State<Arc<AuthManager>>,
+    users: &State<Arc<Users>>,
+    guests: &State<Guests>,
+    limiter: &State<RateLimiter>,
+    config: &Config,
+) -> ApiResult<()> {
+    let admin =
+        matches!(request.user.as_deref(), None | Some(ADMIN)) && check_login(&request, auth).await;
+    let account = match request.user.as_deref() {
+        _ if admin => Some(Account::Admin),
+        None => request.token.as_deref().map(str::trim).and_then(|token| {
+            users
+                .by_token(token)
+                .map(Account::User)
+                .or_else(|| guests.by_token(token).map(Account::Guest))
+        }),
+        Some(name) => match users.get(name) {
+            Some(user) => check_login(&request, &user.auth)
+                .await
+                .then_some(Account::User(user)),
+            None => None,
+        },
+    };
+    let client = client.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let Some(account) = account else {
+        limiter.record_failure(client, "login");
+        println!("🔒 Rejected login attempt from {client}");
+        return Err(ApiError::new(
+            Status::Unauthorized,
+            "invalid_credentials",
+            "Invalid password or access token",
+        ));
+    };
+
+    limiter.record_success(client, "login");
+    let session = account.credentials(auth).create_session();
+    cookies.add(session_cookie(session, config.tls_enabled()));
+    Ok(())
+}
+
+#[post("/auth/logout")]
+pub fn logout(
+    cookies: &CookieJar<'_>,
+    auth: &State<Arc<AuthManager>>,
+    users: &State<Arc<Users>>,
+    guests: &State<Guests>,
+) {
+    if let Some(cookie) = cookies.get(SESSION_COOKIE) {
+        auth.revoke_session(cookie.value());
+        for user in users.all() {
+            user.auth.revoke_session(cookie.value());
+        }
+        for guest in guests.all() {
+            guest.auth.revoke_session(cookie.value());
+        }
+    }
+    cookies.remove(Cookie::from(SESSION_COOKIE));
+}
+
+#[post("/auth/password", data = "<request>")]
+pub fn set_password(
+    user: Authenticated,
+    request: Json<SetPasswordRequest>,
+    cookies: &CookieJar<'_>,
+    auth: &State<Arc<AuthManager>>,
+    config: &Config,
+) -> ApiResult<()> {
+    let credentials = user.0.credentials(auth);
+    credentials
+        .set_password(&request.password)
+        .map_err(|e| ApiError::new(Status::BadRequest, "invalid_password", e))?;
+
+    // Keep the caller logged in after the other sessions were revoked.
+    cookies.add(session_cookie(
+        credentials.create_session(),
+        config.tls_enabled(),
+    ));
+    Ok(())
+}
+
+#[post("/auth/rotate-token")]
+pub fn rotate_token(
+    user: Authenticated,
+    auth: &State<Arc<AuthManager>>,
+) -> ApiResult<Json<TokenResponse>> {
+    user.0
+        .credentials(auth)
+        .rotate_token()
+        .map(|token| Json(TokenResponse { token }))
+        .map_err(|e| ApiError::new(Status::InternalServerError, "internal_error", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_first_run_generates_hashed_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let auth = AuthManager::load_or_init(temp_dir.path()).unwrap();
+        let content = fs::read_to_string(temp_dir.path().join("auth.json")).unwrap();
+        let file: AuthFile = serde_json::from_str(&content).unwrap();
+        assert_eq!(file.token_sha256.len(), 64);
+        assert!(!auth.has_password());
+        assert!(!auth.verify_token("wrong"));
+    }
+
+    #[tokio::test]
+    async fn test_password_and_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let auth = AuthManager::load_or_init(temp_dir.path()).unwrap();
+        assert!(auth.set_password("short").is_err());
+        auth.set_password("correct horse battery").unwrap();
+        assert!(auth.verify_password("correct horse battery").await);
+        assert!(!auth.verify_password("wrong password").await);
+
+        let session = auth.create_session();
+        assert!(auth.validate_session(&session));
+        auth.revoke_session(&session);
+        assert!(!auth.validate_session(&session));
+
+        // Reloading keeps the password.
+        let reloaded = AuthManager::load_or_init(temp_dir.path()).unwrap();
+        assert!(reloaded.verify_password("correct horse battery").await);
+    }
+
+    #[test]
+    fn test_rotate_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let auth = AuthManager::load_or_init(temp_dir.path()).unwrap();
+        let session = auth.create_session();
+        let token = auth.rotate_token().unwrap();
+        assert!(auth.verify_token(&token));
+        assert!(!auth.validate_session(&session));
+    }
+}
@@ -0,0 +1,549 @@
+use crate::cors::DEFAULT_ALLOWED_ORIGINS;
+use crate::journal::DEFAULT_JOURNAL_SIZE;
+use clap::{Parser, ValueEnum};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PORT: u16 = 1858;
+const DEFAULT_SHUTDOWN_GRACE: u64 = 30;
+const DEFAULT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+/// Command-line flags. Every flag is optional and, when given, overrides the
+/// matching `server*` key in `settings.json`.
+#[derive(Debug, Default, Parser)]
+#[command(
+    name = "gemini-desktop-web",
+    version,
+    about = "Gemini Desktop web server"
+)]
+pub struct Cli {
+    /// Address to bind to (default 0.0.0.0)
+    #[arg(long, value_name = "ADDR")]
+    pub bind: Option<IpAddr>,
+
+    /// Port to listen on (default 1858, or $PORT)
+    #[arg(short, long)]
+    pub port: Option<u16>,
+
+    /// Data directory (default ~/.gemini-desktop)
+    #[arg(long, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// PEM certificate chain to serve HTTPS with
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key matching --tls-cert
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Serve HTTPS with a generated self-signed certificate for localhost
+    #[arg(long, conflicts_with_all = ["tls_cert", "tls_key"])]
+    pub self_signed: bool,
+
+    /// Rocket log level
+    #[arg(long, value_enum)]
+    pub log_level: Option<LogLevel>,
+
+    /// Reject every request that would change state
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Cross-origin page allowed to call the API (repeatable)
+    #[arg(long = "allowed-origin", value_name = "ORIGIN")]
+    pub allowed_origins: Vec<String>,
+
+    /// Recent events kept per session for reconnecting clients (default 1000)
+    #[arg(long, value_name = "EVENTS")]
+    pub journal_size: Option<usize>,
+
+    /// Also keep older events on disk under <data-dir>/journal
+    #[arg(long)]
+    pub journal_spill: bool,
+
+    /// Seconds to let running turns finish when shutting down (default 30)
+    #[arg(long, value_name = "SECONDS")]
+    pub shutdown_grace: Option<u64>,
+
+    /// Serve the frontend from this directory instead of the embedded build
+    #[arg(long, value_name = "DIR")]
+    pub frontend_dir: Option<PathBuf>,
+
+    /// Give every user their own data directory, sessions and events
+    #[arg(long)]
+    pub multi_user: bool,
+
+    /// Also serve the desktop app on a local socket, as the one backend both share
+    #[arg(long)]
+    pub daemon: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Off,
+    Critical,
+    Normal,
+    Debug,
+}
+
+impl LogLevel {
+    fn parse(value: &str) -> Option<Self> {
+        <Self as ValueEnum>::from_str(value, true).ok()
+    }
+
+    pub fn to_rocket(self) -> rocket::config::LogLevel {
+        match self {
+            LogLevel::Off => rocket::config::LogLevel::Off,
+            LogLevel::Critical => rocket::config::LogLevel::Critical,
+            LogLevel::Normal => rocket::config::LogLevel::Normal,
+            LogLevel::Debug => rocket::config::LogLevel::Debug,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Off => "off",
+            LogLevel::Critical => "critical",
+            LogLevel::Normal => "normal",
+            LogLevel::Debug => "debug",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsConfig {
+    Disabled,
+    Files { cert: PathBuf, key: PathBuf },
+    SelfSigned,
+}
+
+/// Effective server configuration after merging flags, `settings.json`,
+/// environment variables and defaults, in that order of precedence.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    pub data_dir: PathBuf,
+    pub tls: TlsConfig,
+    pub log_level: LogLevel,
+    pub read_only: bool,
+    pub allowed_origins: Vec<String>,
+    pub journal_size: usize,
+    pub journal_spill: bool,
+    /// Seconds running turns get to finish before sessions are killed.
+    pub shutdown_grace: u64,
+    /// Directory to read the frontend from on every request, for working on
+    /// it without rebuilding the server. `None` serves the embedded build.
+    pub frontend_dir: Option<PathBuf>,
+    /// Users besides the admin, each under `<data_dir>/users/<name>`.
+    pub multi_user: bool,
+    /// Serve local clients at the endpoint in `<data_dir>/daemon.json`.
+    pub daemon: bool,
+}
+
+impl ServerConfig {
+    pub fn resolve(mut cli: Cli) -> Result<Self, String> {
+        let data_dir = match cli.data_dir.take() {
+            Some(dir) => dir,
+            None => default_data_dir()?,
+        };
+        let settings = load_settings(&data_dir.join("settings.json"));
+        Self::merge(cli, data_dir, &settings, std::env::var("PORT").ok())
+    }
+
+    fn merge(
+        cli: Cli,
+        data_dir: PathBuf,
+        settings: &serde_json::Value,
+        env_port: Option<String>,
+    ) -> Result<Self, String> {
+        let setting_str = |key: &str| settings.get(key).and_then(|v| v.as_str());
+        let setting_bool = |key: &str| settings.get(key).and_then(|v| v.as_bool());
+
+        let address = match cli.bind {
+            Some(address) => address,
+            None => match setting_str("serverBindAddress") {
+                Some(value) => value
+                    .parse()
+                    .map_err(|_| format!("Invalid serverBindAddress in settings.json: {value}"))?,
+                None => DEFAULT_ADDRESS,
+            },
+        };
+
+        let port = cli
+            .port
+            .or_else(|| {
+                settings
+                    .get("serverPort")
+                    .and_then(|p| p.as_u64())
+                    .and_then(|p| u16::try_from(p).ok())
+            })
+            .or_else(|| env_port.and_then(|p| p.parse().ok()))
+            .unwrap_or(DEFAULT_PORT);
+
+        let tls = if cli.self_signed {
+            TlsConfig::SelfSigned
+        } else if let (Some(cert), Some(key)) = (cli.tls_cert, cli.tls_key) {
+            TlsConfig::Files { cert, key }
+        } else if let (Some(cert), Some(key)) =
+            (setting_str("serverTlsCert"), setting_str("serverTlsKey"))
+        {
+            TlsConfig::Files {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            }
+        } else if setting_bool("serverSelfSignedTls").unwrap_or(false) {
+            TlsConfig::SelfSigned
+        } else {
+            TlsConfig::Disabled
+        };
+
+        let log_level = match cli.log_level {
+            Some(level) => level,
+            None => match setting_str("serverLogLevel") {
+                Some(value) => LogLevel::parse(value)
+                    .ok_or_else(|| format!("Invalid serverLogLevel in settings.json: {value}"))?,
+                None => LogLevel::Normal,
+            },
+        };
+
+        let read_only = cli.read_only || setting_bool("serverReadOnly").unwrap_or(false);
+
+        let allowed_origins = if !cli.allowed_origins.is_empty() {
+            cli.allowed_origins
+        } else if let Some(origins) = settings.get("serverAllowedOrigins") {
+            serde_json::from_value(origins.clone())
+                .map_err(|_| "serverAllowedOrigins in settings.json must be a list of strings")?
+        } else {
+            DEFAULT_ALLOWED_ORIGINS
+                .iter()
+                .map(|o| o.to_string())
+                .collect()
+        };
+
+        let journal_size = cli
+            .journal_size
+            .or_else(|| {
+                settings
+                    .get("serverJournalSize")
+                    .and_then(|n| n.as_u64())
+                    .and_then(|n| usize::try_from(n).ok())
+            })
+            .unwrap_or(DEFAULT_JOURNAL_SIZE);
+        let journal_spill =
+            cli.journal_spill || setting_bool("serverJournalSpill").unwrap_or(false);
+
+        let shutdown_grace = cli
+            .shutdown_grace
+            .or_else(|| settings.get("serverShutdownGrace").and_then(|n| n.as_u64()))
+            .unwrap_or(DEFAULT_SHUTDOWN_GRACE);
+
+        let frontend_dir = cli
+            .frontend_dir
+            .or_else(|| setting_str("serverFrontendDir").map(PathBuf::from));
+
+        let multi_user = cli.multi_user || setting_bool("serverMultiUser").unwrap_or(false);
+        let daemon = cli.daemon || setting_bool("serverDaemon").unwrap_or(false);
+
+        Ok(Self {
+            address,
+            port,
+            data_dir,
+            tls,
+            log_level,
+            read_only,
+            allowed_origins,
+            journal_size,
+            journal_spill,
+            shutdown_grace,
+            frontend_dir,
+            multi_user,
+            daemon,
+        })
+    }
+
+    /// Certificate and key paths to hand to Rocket, generating the
+    /// self-signed pair under `<data_dir>/tls` on first use.
+    pub fn tls_files(&self) -> Result<Option<(PathBuf, PathBuf)>, String> {
+        match &self.tls {
+            TlsConfig::Disabled => Ok(None),
+            TlsConfig::Files { cert, key } => Ok(Some((cert.clone(), key.clone()))),
+            TlsConfig::SelfSigned => ensure_self_signed_cert(&self.data_dir.join("tls")).map(Some),
+        }
+    }
+
+    pub fn scheme(&self) -> &'static str {
+        if self.tls == TlsConfig::Disabled {
+            "http"
+        } else {
+            "https"
+        }
+    }
+}
+
+impl fmt::Display for ServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tls = match &self.tls {
+            TlsConfig::Disabled => "disabled".to_string(),
+            TlsConfig::Files { cert, key } => {
+                format!("cert {} / key {}", cert.display(), key.display())
+            }
+            TlsConfig::SelfSigned => "self-signed (localhost)".to_string(),
+        };
+        writeln!(f, "⚙️  Effective configuration:")?;
+        writeln!(
+            f,
+            "   Listen:     {}://{}:{}",
+            self.scheme(),
+            self.address,
+            self.port
+        )?;
+        writeln!(f, "   Data dir:   {}", self.data_dir.display())?;
+        writeln!(f, "   TLS:        {tls}")?;
+        writeln!(f, "   Log level:  {}", self.log_level)?;
+        if let Some(dir) = &self.frontend_dir {
+            writeln!(f, "   Frontend:   {}", dir.display())?;
+        }
+        writeln!(f, "   Origins:    {}", self.allowed_origins.join(", "))?;
+        if self.multi_user {
+            writeln!(f, "   Users:      multi-user")?;
+        }
+        if self.daemon {
+            writeln!(f, "   Daemon:     local clients via daemon.json")?;
+        }
+        writeln!(
+            f,
+            "   Journal:    {} events/session{}",
+            self.journal_size,
+            if self.journal_spill {
+                ", spilling to disk"
+            } else {
+                ""
+            }
+        )?;
+        writeln!(
+            f,
+            "   Shutdown:   up to {}s for running turns",
+            self.shutdown_grace
+        )?;
+        write!(
+            f,
+            "   Read-only:  {}",
+            if self.read_only { "yes" } else { "no" }
+        )
+    }
+}
+
+/// The backend's own default, so the server and the desktop app agree on it.
+fn default_data_dir() -> Result<PathBuf, String> {
+    backend::paths::data_dir().map_err(|e| format!("{e}; pass --data-dir"))
+}
+
+fn load_settings(path: &Path) -> serde_json::Value {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or(serde_json::Value::Null)
+}
+
+fn ensure_self_signed_cert(dir: &Path) -> Result<(PathBuf, PathBuf), String> {
+    let cert_path = dir.join("self-signed-cert.pem");
+    let key_path = dir.join("self-signed-key.pem");
+    if cert_path.exists() && key_path.exists() {
+        return Ok((cert_path, key_path));
+    }
+
+    let names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    let certified = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| format!("Failed to generate self-signed certificate: {e}"))?;
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    fs::write(&cert_path, certified.cert.pem()).map_err(|e| e.to_string())?;
+    fs::write(&key_path, certified.key_pair.serialize_pem()).map_err(|e| e.to_string())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&key_path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| e.to_string())?;
+    }
+    println!("🔏 Generated self-signed certificate in {}", dir.display());
+    Ok((cert_path, key_path))
+}
+
+/// Request guard for routes that change state; fails with 403 Forbidden
+/// when the server runs with `--read-only`.
+pub struct Writable;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Writable {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.rocket().state::<ServerConfig>() {
+            Some(config) if config.read_only => Outcome::Error((Status::Forbidden, ())),
+            _ => Outcome::Success(Writable),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn merge(args: &[&str], settings: serde_json::Value, env_port: Option<&str>) -> ServerConfig {
+        let cli =
+            Cli::try_parse_from(std::iter::once("gemini-desktop-web").chain(args.iter().copied()))
+                .unwrap();
+        ServerConfig::merge(
+            cli,
+            PathBuf::from("/data"),
+            &settings,
+            env_port.map(str::to_string),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = merge(&[], serde_json::Value::Null, None);
+        assert_eq!(config.address, DEFAULT_ADDRESS);
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.tls, TlsConfig::Disabled);
+        assert_eq!(config.log_level, LogLevel::Normal);
+        assert!(!config.read_only);
+        assert_eq!(config.scheme(), "http");
+        assert_eq!(config.allowed_origins, DEFAULT_ALLOWED_ORIGINS);
+        assert_eq!(config.journal_size, DEFAULT_JOURNAL_SIZE);
+        assert!(!config.journal_spill);
+        assert_eq!(config.shutdown_grace, DEFAULT_SHUTDOWN_GRACE);
+        assert_eq!(config.frontend_dir, None);
+        assert!(!config.multi_user);
+        assert!(!config.daemon);
+    }
+
+    #[test]
+    fn test_settings_override_env_and_flags_override_settings() {
+        let settings = json!({
+            "serverPort": 4000,
+            "serverBindAddress": "127.0.0.1",
+            "serverLogLevel": "debug",
+            "serverReadOnly": true,
+            "serverJournalSize": 50,
+            "serverShutdownGrace": 5,
+            "serverFrontendDir": "/srv/ui",
+            "serverMultiUser": true,
+            "serverDaemon": true,
+        });
+
+        let from_settings = merge(&[], settings.clone(), Some("5000"));
+        assert_eq!(from_settings.port, 4000);
+        assert_eq!(from_settings.address, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(from_settings.log_level, LogLevel::Debug);
+        assert!(from_settings.read_only);
+        assert_eq!(from_settings.journal_size, 50);
+        assert_eq!(from_settings.shutdown_grace, 5);
+        assert_eq!(from_settings.frontend_dir, Some(PathBuf::from("/srv/ui")));
+        assert!(from_settings.multi_user);
+        assert!(from_settings.daemon);
+
+        let from_flags = merge(
+            &[
+                "--port",
+                "6000",
+                "--bind",
+                "::1",
+                "--log-level",
+                "critical",
+                "--shutdown-grace",
+                "0",
+                "--frontend-dir",
+                "frontend/dist",
+            ],
+            settings,
+            Some("5000"),
+        );
+        assert_eq!(from_flags.port, 6000);
+        assert_eq!(from_flags.address, "::1".parse::<IpAddr>().unwrap());
+        assert_eq!(from_flags.log_level, LogLevel::Critical);
+        assert_eq!(from_flags.shutdown_grace, 0);
+        assert_eq!(
+            from_flags.frontend_dir,
+            Some(PathBuf::from("frontend/dist"))
+        );
+
+        assert_eq!(merge(&[], serde_json::Value::Null, Some("5000")).port, 5000);
+
+        let origins = json!({ "serverAllowedOrigins": ["https://a.example"] });
+        assert_eq!(
+            merge(&[], origins.clone(), None).allowed_origins,
+            ["https://a.example"]
+        );
+        assert_eq!(
+            merge(&["--allowed-origin", "https://b.example"], origins, None).allowed_origins,
+            ["https://b.example"]
+        );
+    }
+
+    #[test]
+    fn test_tls_flags() {
+        let config = merge(
+            &["--tls-cert", "cert.pem", "--tls-key", "key.pem"],
+            json!({ "serverSelfSignedTls": true }),
+            None,
+        );
+        assert_eq!(
+            config.tls,
+            TlsConfig::Files {
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+            }
+        );
+        assert_eq!(config.scheme(), "https");
+
+        let config = merge(&["--self-signed"], serde_json::Value::Null, None);
+        assert_eq!(config.tls, TlsConfig::SelfSigned);
+
+        // A cert without a key, or both modes at once, is rejected.
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(std::iter::once("gemini-desktop-web").chain(args.iter().copied()))
+        };
+        assert!(parse(&["--tls-cert", "cert.pem"]).is_err());
+        assert!(parse(&["--self-signed", "--tls-cert", "c", "--tls-key", "k"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_settings_are_reported() {
+        let cli = Cli::default();
+        let result = ServerConfig::merge(
+            cli,
+            PathBuf::from("/data"),
+            &json!({ "serverBindAddress": "not-an-ip" }),
+            None,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_self_signed_cert_is_generated_once() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (cert, key) = ensure_self_signed_cert(temp_dir.path()).unwrap();
+        let first = fs::read_to_string(&cert).unwrap();
+        assert!(first.contains("BEGIN CERTIFICATE"));
+        assert!(fs::read_to_string(&key).unwrap().contains("PRIVATE KEY"));
+
+        ensure_self_signed_cert(temp_dir.path()).unwrap();
+        assert_eq!(fs::read_to_string(&cert).unwrap(), first);
+    }
+}
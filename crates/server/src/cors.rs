@@ -0,0 +1,210 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::{Data, Request, Response, get, options, uri};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Origins of the Vite dev server, which proxies `/api` without rewriting `Origin`.
+pub const DEFAULT_ALLOWED_ORIGINS: &[&str] = &["http://localhost:1420", "http://127.0.0.1:1420"];
+
+/// Validates the `Origin` header of every `/api` request (including the
+/// WebSocket upgrade) and adds CORS headers for allowed cross-origin callers.
+///
+/// Same-origin requests and requests without an `Origin` header (non-browser
+/// clients) pass through; authentication still applies to them. Anything
+/// else is rerouted to a handler that answers 403 before any API route runs.
+pub struct OriginPolicy {
+    allowed: HashSet<String>,
+}
+
+impl OriginPolicy {
+    pub fn new<I, S>(allowed: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            allowed: allowed
+                .into_iter()
+                .map(|origin| normalize(origin.as_ref()))
+                .collect(),
+        }
+    }
+
+    fn is_allowed(&self, origin: &str, host: Option<&str>) -> bool {
+        let origin = normalize(origin);
+        if self.allowed.contains(&origin) {
+            return true;
+        }
+        // Same-origin: the authority part of Origin matches the Host header.
+        match (origin.split_once("://"), host) {
+            (Some((_, authority)), Some(host)) => authority.eq_ignore_ascii_case(host),
+            _ => false,
+        }
+    }
+
+    fn is_cross_origin_allowed(&self, origin: &str) -> bool {
+        self.allowed.contains(&normalize(origin))
+    }
+}
+
+fn normalize(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+#[rocket::async_trait]
+impl Fairing for OriginPolicy {
+    fn info(&self) -> Info {
+        Info {
+            name: "Origin policy",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        if !request.uri().path().starts_with("/api") {
+            return;
+        }
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+        if !self.is_allowed(origin, request.headers().get_one("Host")) {
+            println!("🚫 Rejected request from origin {origin}");
+            request.set_method(Method::Get);
+            request.set_uri(uri!(origin_rejected));
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !request.uri().path().starts_with("/api") {
+            return;
+        }
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+        if !self.is_cross_origin_allowed(origin) {
+            return;
+        }
+
+        response.set_header(Header::new(
+            "Access-Control-Allow-Origin",
+            origin.to_string(),
+        ));
+        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        response.adjoin_header(Header::new("Vary", "Origin"));
+        if request.method() == Method::Options {
+            response.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                "GET, POST, PUT, DELETE, OPTIONS",
+            ));
+            response.set_header(Header::new(
+                "Access-Control-Allow-Headers",
+                "Authorization, Content-Type, Last-Event-ID",
+            ));
+            response.set_header(Header::new("Access-Control-Max-Age", "600"));
+        }
+    }
+}
+
+#[get("/__origin-rejected")]
+pub fn origin_rejected() -> Status {
+    Status::Forbidden
+}
+
+/// Answers CORS preflights; the fairing adds the `Access-Control-*` headers.
+#[options("/<_path..>")]
+pub fn preflight(_path: PathBuf) -> Status {
+    Status::NoContent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::routes;
+
+    #[get("/ping")]
+    fn ping() -> &'static str {
+        "pong"
+    }
+
+    fn client() -> Client {
+        let rocket = rocket::build()
+            .attach(OriginPolicy::new(["https://trusted.example"]))
+            .mount("/", routes![origin_rejected])
+            .mount("/api", routes![ping, preflight]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn test_requests_without_origin_pass() {
+        let client = client();
+        let response = client.get("/api/ping").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(
+            response
+                .headers()
+                .get_one("Access-Control-Allow-Origin")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_foreign_origin_is_rejected() {
+        let client = client();
+        let response = client
+            .get("/api/ping")
+            .header(Header::new("Origin", "https://evil.example"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .post("/api/ping")
+            .header(Header::new("Origin", "null"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    #[test]
+    fn test_same_origin_passes_without_cors_headers() {
+        let client = client();
+        let response = client
+            .get("/api/ping")
+            .header(Header::new("Host", "localhost:1858"))
+            .header(Header::new("Origin", "http://localhost:1858"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(
+            response
+                .headers()
+                .get_one("Access-Control-Allow-Origin")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_allowed_origin_gets_cors_headers() {
+        let client = client();
+        let response = client
+            .get("/api/ping")
+            .header(Header::new("Origin", "https://trusted.example/"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://trusted.example/")
+        );
+
+        let response = client
+            .options("/api/ping")
+            .header(Header::new("Origin", "https://trusted.example"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        assert!(
+            response
+                .headers()
+                .get_one("Access-Control-Allow-Methods")
+                .is_some()
+        );
+    }
+}
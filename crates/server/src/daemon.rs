@@ -0,0 +1,305 @@
+//! The `--daemon` endpoint: the desktop app reaches this server's backend over
+//! a Unix socket (a localhost port where there are none), so it shares the
+//! web UI's live sessions and events instead of running its own.
+//!
+//! Connections authenticate with the secret in `daemon.json` (see
+//! [`backend::daemon`]), then speak the JSON-RPC protocol of `/api/ws`, one
+//! message per line, as the admin.
+
+use crate::auth::{constant_time_eq, generate_secret};
+use crate::config::ServerConfig;
+use crate::rate_limit::RateLimiter;
+use crate::roles::Viewer;
+use crate::users::ADMIN;
+use crate::ws::{
+    CONNECTION_QUEUE_CAPACITY, ConnectionGuard, WebSocketManager, WebSocketsEventEmitter,
+};
+use crate::ws_rpc::{self, RpcContext};
+use backend::daemon::Stream;
+use backend::{DaemonInfo, GeminiBackend};
+use rocket::fairing::AdHoc;
+use rocket::{Orbit, Rocket, Shutdown};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+/// How long a new connection has to send the secret.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fails if a daemon for this data directory already answers.
+pub fn ensure_not_running() -> Result<(), String> {
+    let Some(info) = DaemonInfo::load() else {
+        return Ok(());
+    };
+    let reachable = match info.endpoint.split_once(':') {
+        #[cfg(unix)]
+        Some(("unix", path)) => std::os::unix::net::UnixStream::connect(path).is_ok(),
+        Some(("tcp", address)) => std::net::TcpStream::connect(address).is_ok(),
+        _ => false,
+    };
+    if reachable {
+        Err(format!(
+            "A backend daemon is already running for this data directory (pid {})",
+            info.pid
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Opens the endpoint once the server is up, and closes it on shutdown.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Backend daemon", |rocket| {
+        Box::pin(async move {
+            let Some(daemon) = Daemon::from_rocket(rocket) else {
+                return;
+            };
+            let config = rocket.state::<ServerConfig>().expect("config is managed");
+            match daemon.listen(&config.data_dir, rocket.shutdown()).await {
+                Ok(endpoint) => println!("🔌 Backend daemon listening on {endpoint}"),
+                Err(e) => {
+                    eprintln!("❌ Failed to start the backend daemon: {e}");
+                    rocket.shutdown().notify();
+                }
+            }
+        })
+    })
+}
+
+/// A Unix socket or TCP listener.
+trait Listener: Send + 'static {
+    type Connection: Stream + 'static;
+
+    fn accept(&self) -> impl Future<Output = io::Result<Self::Connection>> + Send;
+}
+
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Connection = tokio::net::UnixStream;
+
+    async fn accept(&self) -> io::Result<Self::Connection> {
+        Ok(Self::accept(self).await?.0)
+    }
+}
+
+impl Listener for tokio::net::TcpListener {
+    type Connection = tokio::net::TcpStream;
+
+    async fn accept(&self) -> io::Result<Self::Connection> {
+        Ok(Self::accept(self).await?.0)
+    }
+}
+
+/// What every daemon connection runs against: the admin's backend.
+pub struct Daemon {
+    secret: String,
+    backend: Arc<GeminiBackend<WebSocketsEventEmitter>>,
+    ws_manager: WebSocketManager,
+    limiter: RateLimiter,
+    read_only: bool,
+}
+
+impl Daemon {
+    fn from_rocket(rocket: &Rocket<Orbit>) -> Option<Self> {
+        let config = rocket.state::<ServerConfig>()?;
+        if !config.daemon {
+            return None;
+        }
+        Some(Self {
+            secret: generate_secret(),
+            backend: rocket
+                .state::<Arc<GeminiBackend<WebSocketsEventEmitter>>>()?
+                .clone(),
+            ws_manager: rocket.state::<WebSocketManager>()?.clone(),
+            limiter: rocket.state::<RateLimiter>()?.clone(),
+            read_only: config.read_only,
+        })
+    }
+
+    /// Binds the endpoint, publishes it in `daemon.json` and accepts
+    /// connections until `shutdown`. Returns the endpoint.
+    async fn listen(self, data_dir: &Path, shutdown: Shutdown) -> io::Result<String> {
+        let daemon = Arc::new(self);
+
+        #[cfg(unix)]
+        let (endpoint, socket) = {
+            use std::os::unix::fs::PermissionsExt;
+            let path = data_dir.join("daemon.sock");
+            // Left over from a daemon that did not shut down cleanly;
+            // `ensure_not_running` found nothing answering on it.
+            let _ = std::fs::remove_file(&path);
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            tokio::spawn(daemon.clone().accept(listener, shutdown.clone()));
+            (format!("unix:{}", path.display()), Some(path))
+        };
+
+        #[cfg(not(unix))]
+        let (endpoint, socket) = {
+            let _ = data_dir;
+            let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+            let endpoint = format!("tcp:{}", listener.local_addr()?);
+            tokio::spawn(daemon.clone().accept(listener, shutdown.clone()));
+            (endpoint, None::<std::path::PathBuf>)
+        };
+
+        let info = DaemonInfo {
+            pid: std::process::id(),
+            endpoint: endpoint.clone(),
+            secret: daemon.secret.clone(),
+        };
+        info.save().map_err(|e| io::Error::other(e.to_string()))?;
+        tokio::spawn(async move {
+            shutdown.await;
+            DaemonInfo::remove_own();
+            if let Some(socket) = socket {
+                let _ = std::fs::remove_file(socket);
+            }
+        });
+        Ok(endpoint)
+    }
+
+    async fn accept<L: Listener>(self: Arc<Self>, listener: L, mut shutdown: Shutdown) {
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(stream) => {
+                        tokio::spawn(self.clone().serve(stream));
+                    }
+                    Err(e) => eprintln!("⚠️ Backend daemon accept failed: {e}"),
+                },
+                _ = &mut shutdown => break,
+            }
+        }
+    }
+
+    /// Runs one connection: the secret, then JSON-RPC until either side
+    /// hangs up. Connections are closed with the others on shutdown.
+    pub async fn serve<S: Stream + 'static>(self: Arc<Self>, stream: S) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        let secret = tokio::time::timeout(HANDSHAKE_TIMEOUT, lines.next_line()).await;
+        let Ok(Ok(Some(secret))) = secret else {
+            return;
+        };
+        if !constant_time_eq(secret.as_bytes(), self.secret.as_bytes()) {
+            return;
+        }
+
+        let (tx, mut rx) = mpsc::channel::<String>(CONNECTION_QUEUE_CAPACITY);
+        let (reply_tx, mut reply_rx) = mpsc::channel::<String>(64);
+        let viewer = Viewer {
+            name: ADMIN.to_string(),
+            grant: None,
+        };
+        let connection_id = self.ws_manager.add_viewer(tx, viewer.clone());
+        let _connection = ConnectionGuard::new(self.ws_manager.clone(), connection_id);
+        println!("🔌 Daemon client connected (ID: {connection_id})");
+        let rpc = RpcContext {
+            backend: self.backend.clone(),
+            limiter: self.limiter.clone(),
+            client: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            read_only: self.read_only,
+            admin: true,
+            viewer,
+            ws_manager: self.ws_manager.clone(),
+            connection_id,
+        };
+
+        loop {
+            let line = tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(text) => text,
+                    None => break,
+                },
+                Some(reply) = reply_rx.recv() => reply,
+                incoming = lines.next_line() => {
+                    let Ok(Some(text)) = incoming else {
+                        break;
+                    };
+                    if ws_rpc::is_subscription_change(&text) {
+                        match ws_rpc::handle_message(&text, &rpc).await {
+                            Some(reply) => reply,
+                            None => continue,
+                        }
+                    } else {
+                        let rpc = rpc.clone();
+                        let reply_tx = reply_tx.clone();
+                        tokio::spawn(async move {
+                            if let Some(reply) = ws_rpc::handle_message(&text, &rpc).await {
+                                let _ = reply_tx.send(reply).await;
+                            }
+                        });
+                        continue;
+                    }
+                }
+            };
+            if writer
+                .write_all(format!("{line}\n").as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+        println!("🔌 Daemon client disconnected (ID: {connection_id})");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn daemon() -> Arc<Daemon> {
+        let ws_manager = WebSocketManager::new();
+        Arc::new(Daemon {
+            secret: "s3cret".to_string(),
+            backend: Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+                ws_manager.clone(),
+            ))),
+            ws_manager,
+            limiter: RateLimiter::default(),
+            read_only: false,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_wrong_secret_is_disconnected() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let served = tokio::spawn(daemon().serve(server));
+        client.write_all(b"guess\n").await.unwrap();
+        let mut lines = BufReader::new(client).lines();
+        assert_eq!(lines.next_line().await.unwrap(), None);
+        served.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_answered_line_by_line() {
+        let daemon = daemon();
+        let (mut client, server) = tokio::io::duplex(4096);
+        let served = tokio::spawn(daemon.clone().serve(server));
+        client
+            .write_all(
+                b"s3cret\n{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"get_process_statuses\"}\n",
+            )
+            .await
+            .unwrap();
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(reader).lines();
+        let reply: Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(reply["id"], json!(1));
+        assert_eq!(reply["result"], json!([]));
+
+        // Hanging up unregisters the connection.
+        assert_eq!(daemon.ws_manager.stats().connections, 1);
+        writer.shutdown().await.unwrap();
+        served.await.unwrap();
+        assert_eq!(daemon.ws_manager.stats().connections, 0);
+    }
+}
@@ -0,0 +1,100 @@
+use backend::{BackendError, ErrorBody};
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{Request, catch};
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// JSON error response: `{"code": ..., "message": ..., "status": ...}` with
+/// the matching HTTP status. Same body the Tauri commands reject with.
+#[derive(Debug)]
+pub struct ApiError(pub ErrorBody);
+
+impl ApiError {
+    pub fn new(status: Status, code: &str, message: impl Into<String>) -> Self {
+        Self(ErrorBody::new(code, message, status.code))
+    }
+}
+
+impl From<BackendError> for ApiError {
+    fn from(error: BackendError) -> Self {
+        if error.http_status() >= 500 {
+            eprintln!("❌ {error}");
+        }
+        Self(ErrorBody::from(error))
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = Status::from_code(self.0.status).unwrap_or(Status::InternalServerError);
+        (status, Json(self.0)).respond_to(request)
+    }
+}
+
+/// Code for failures produced by Rocket itself (guards, routing, parsing).
+fn status_code(status: Status) -> &'static str {
+    match status.code {
+        400 => "bad_request",
+        401 => "unauthorized",
+        403 => "forbidden",
+        404 => "not_found",
+        405 => "method_not_allowed",
+        413 => "payload_too_large",
+        415 => "unsupported_media_type",
+        422 => "invalid_request_body",
+        429 => "rate_limited",
+        503 => "service_unavailable",
+        code if code >= 500 => "internal_error",
+        _ => "request_failed",
+    }
+}
+
+/// Gives guard and routing failures under `/api` the same JSON shape.
+#[catch(default)]
+pub fn default_catcher(status: Status, _request: &Request<'_>) -> ApiError {
+    ApiError::new(status, status_code(status), status.reason_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::{catchers, get, routes};
+
+    #[get("/missing")]
+    fn missing() -> ApiResult<&'static str> {
+        Err(BackendError::SessionNotFound("abc".to_string()).into())
+    }
+
+    #[get("/forbidden")]
+    fn forbidden() -> ApiResult<&'static str> {
+        Err(BackendError::CommandNotAllowed.into())
+    }
+
+    #[test]
+    fn test_backend_errors_become_json() {
+        let rocket = rocket::build()
+            .mount("/api", routes![missing, forbidden])
+            .register("/api", catchers![default_catcher]);
+        let client = Client::tracked(rocket).unwrap();
+
+        let response = client.get("/api/missing").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let body: ErrorBody = response.into_json().unwrap();
+        assert_eq!(body.code, "session_not_found");
+        assert_eq!(body.status, 404);
+
+        let response = client.get("/api/forbidden").dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        assert_eq!(
+            response.into_json::<ErrorBody>().unwrap().code,
+            "command_not_allowed"
+        );
+
+        let response = client.get("/api/nowhere").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.into_json::<ErrorBody>().unwrap().code, "not_found");
+    }
+}
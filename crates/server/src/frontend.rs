@@ -0,0 +1,494 @@
+//! Serves the web frontend.
+//!
+//! The built frontend is embedded in the binary, or read from a directory
+//! on every request with `--frontend-dir` while working on it. Paths that
+//! don't name a file fall back to `index.html` so the client-side router can
+//! handle deep links. Vite's content-hashed files under `assets/` are cached
+//! for good; everything else is revalidated with an ETag. Text assets are
+//! compressed with brotli or gzip, whichever the client accepts.
+
+use include_dir::{Dir, include_dir};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::{State, get};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+static FRONTEND_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/../../frontend/dist");
+
+/// Files under this directory have content hashes in their names.
+const HASHED_ASSETS_DIR: &str = "assets/";
+/// Extensions of files a frontend build serves.
+const ASSET_EXTENSIONS: &[&str] = &[
+    "js", "mjs", "css", "map", "html", "json", "txt", "xml", "wasm", "png", "jpg", "jpeg", "gif",
+    "svg", "ico", "webp", "avif", "woff", "woff2", "ttf", "otf",
+];
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "no-cache";
+/// Smaller bodies aren't worth compressing.
+const MIN_COMPRESS_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// How hard to compress: the embedded build is compressed once per file,
+/// a frontend directory on every request.
+#[derive(Debug, Clone, Copy)]
+enum Effort {
+    Best,
+    Fast,
+}
+
+struct Asset {
+    body: Arc<[u8]>,
+    content_type: ContentType,
+    etag: String,
+    immutable: bool,
+    effort: Effort,
+    brotli: OnceLock<Option<Arc<[u8]>>>,
+    gzip: OnceLock<Option<Arc<[u8]>>>,
+}
+
+impl Asset {
+    fn new(path: &str, body: Arc<[u8]>, effort: Effort) -> Self {
+        let digest = Sha256::digest(&body);
+        let etag: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+        Self {
+            content_type: content_type(path),
+            etag: format!("\"{etag}\""),
+            immutable: path.starts_with(HASHED_ASSETS_DIR),
+            effort,
+            body,
+            brotli: OnceLock::new(),
+            gzip: OnceLock::new(),
+        }
+    }
+
+    fn compressible(&self) -> bool {
+        let sub = self.content_type.sub().as_str();
+        self.body.len() >= MIN_COMPRESS_SIZE
+            && (self.content_type.top() == "text"
+                || matches!(sub, "javascript" | "json" | "xml" | "wasm")
+                || sub.ends_with("+json")
+                || sub.ends_with("+xml"))
+    }
+
+    /// The body in `encoding`, or `None` if compressing doesn't pay off.
+    fn encoded(&self, encoding: Encoding) -> Option<Arc<[u8]>> {
+        let cell = match encoding {
+            Encoding::Brotli => &self.brotli,
+            Encoding::Gzip => &self.gzip,
+        };
+        cell.get_or_init(|| {
+            let compressed = compress(&self.body, encoding, self.effort).ok()?;
+            (compressed.len() < self.body.len()).then(|| compressed.into())
+        })
+        .clone()
+    }
+}
+
+/// Whether a missing `path` was meant to be a file of the build, which
+/// gets a 404 rather than the app. Client routes may contain dots too
+/// (`/projects/v1.2`), so only asset extensions count.
+fn looks_like_asset(path: &str) -> bool {
+    path.starts_with(HASHED_ASSETS_DIR)
+        || Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|extension| {
+                ASSET_EXTENSIONS
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(extension))
+            })
+}
+
+fn content_type(path: &str) -> ContentType {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    ContentType::from_extension(extension)
+        .or_else(|| {
+            mime_guess::from_ext(extension)
+                .first()
+                .and_then(|mime| ContentType::parse_flexible(mime.essence_str()))
+        })
+        .unwrap_or(ContentType::Binary)
+}
+
+fn compress(body: &[u8], encoding: Encoding, effort: Effort) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Brotli => {
+            let quality = match effort {
+                Effort::Best => 11,
+                Effort::Fast => 1,
+            };
+            let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, quality, 22);
+            writer.write_all(body)?;
+            Ok(writer.into_inner())
+        }
+        Encoding::Gzip => {
+            let level = match effort {
+                Effort::Best => flate2::Compression::best(),
+                Effort::Fast => flate2::Compression::fast(),
+            };
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+    }
+}
+
+enum Source {
+    Embedded(&'static Dir<'static>),
+    Directory(PathBuf),
+}
+
+/// The frontend's files, managed as Rocket state.
+pub struct Frontend {
+    source: Source,
+    /// Embedded assets by path, so each is hashed and compressed once.
+    cache: Mutex<HashMap<String, Arc<Asset>>>,
+}
+
+impl Frontend {
+    /// Serves `dir` if given, otherwise the embedded build.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        match dir {
+            Some(dir) => Self::with_source(Source::Directory(dir)),
+            None => Self::with_source(Source::Embedded(&FRONTEND_DIR)),
+        }
+    }
+
+    fn with_source(source: Source) -> Self {
+        Self {
+            source,
+            cache: Mutex::default(),
+        }
+    }
+
+    fn load(&self, path: &str) -> Option<Arc<Asset>> {
+        match &self.source {
+            Source::Embedded(dir) => {
+                let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(asset) = cache.get(path) {
+                    return Some(asset.clone());
+                }
+                let file = dir.get_file(path)?;
+                let asset = Arc::new(Asset::new(path, file.contents().into(), Effort::Best));
+                cache.insert(path.to_string(), asset.clone());
+                Some(asset)
+            }
+            Source::Directory(dir) => {
+                let body = std::fs::read(dir.join(path)).ok()?;
+                Some(Arc::new(Asset::new(path, body.into(), Effort::Fast)))
+            }
+        }
+    }
+
+    /// The file at `path`, or `index.html` for paths that look like client
+    /// routes rather than files.
+    fn resolve(&self, path: &str) -> Option<Arc<Asset>> {
+        if path.is_empty() {
+            return self.load("index.html");
+        }
+        if let Some(asset) = self.load(path) {
+            return Some(asset);
+        }
+        if path.starts_with("api/") || looks_like_asset(path) {
+            return None;
+        }
+        self.load("index.html")
+    }
+}
+
+/// The request headers that decide how an asset is sent.
+pub struct AssetRequest {
+    if_none_match: Option<String>,
+    encoding: Option<Encoding>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AssetRequest {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        Outcome::Success(AssetRequest {
+            if_none_match: headers.get_one("If-None-Match").map(str::to_string),
+            encoding: headers
+                .get_one("Accept-Encoding")
+                .and_then(preferred_encoding),
+        })
+    }
+}
+
+/// Brotli if the client accepts it, then gzip. Codings with `q=0` are
+/// refused; `*` stands for any coding not listed.
+fn preferred_encoding(accept_encoding: &str) -> Option<Encoding> {
+    let mut accepted: HashMap<String, bool> = HashMap::new();
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        if coding.is_empty() {
+            continue;
+        }
+        let refused = parts.any(|param| {
+            param
+                .trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        accepted.insert(coding, !refused);
+    }
+    let any = accepted.get("*").copied().unwrap_or(false);
+    [Encoding::Brotli, Encoding::Gzip]
+        .into_iter()
+        .find(|encoding| accepted.get(encoding.name()).copied().unwrap_or(any))
+}
+
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// An asset, or a `304 Not Modified` when the client's copy is current.
+pub struct AssetResponse {
+    asset: Arc<Asset>,
+    not_modified: bool,
+    encoding: Option<Encoding>,
+}
+
+impl<'r> Responder<'r, 'static> for AssetResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let asset = self.asset;
+        let mut response = Response::build();
+        response
+            .header(asset.content_type.clone())
+            .raw_header("ETag", asset.etag.clone())
+            .raw_header(
+                "Cache-Control",
+                if asset.immutable {
+                    IMMUTABLE
+                } else {
+                    REVALIDATE
+                },
+            );
+        if asset.compressible() {
+            response.header(Header::new("Vary", "Accept-Encoding"));
+        }
+        if self.not_modified {
+            return response.status(Status::NotModified).ok();
+        }
+
+        let encoded = self
+            .encoding
+            .filter(|_| asset.compressible())
+            .and_then(|encoding| Some((encoding, asset.encoded(encoding)?)));
+        let body = match encoded {
+            Some((encoding, body)) => {
+                response.raw_header("Content-Encoding", encoding.name());
+                body
+            }
+            None => asset.body.clone(),
+        };
+        response.sized_body(body.len(), Cursor::new(body)).ok()
+    }
+}
+
+/// Serves the frontend for Gemini Desktop.
+#[get("/<path..>")]
+pub fn index(
+    path: PathBuf,
+    request: AssetRequest,
+    frontend: &State<Frontend>,
+) -> Result<AssetResponse, Status> {
+    // Rocket has already rejected `..` and hidden segments.
+    let path: Vec<&str> = path.iter().filter_map(|part| part.to_str()).collect();
+    let asset = frontend.resolve(&path.join("/")).ok_or(Status::NotFound)?;
+    let not_modified = request
+        .if_none_match
+        .as_deref()
+        .is_some_and(|tags| etag_matches(tags, &asset.etag));
+    Ok(AssetResponse {
+        asset,
+        not_modified,
+        encoding: request.encoding,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use include_dir::{DirEntry, File};
+    use rocket::local::blocking::Client;
+    use std::io::Read;
+
+    const INDEX: &[u8] = b"<!doctype html><div id=\"root\"></div>";
+    const SCRIPT: &[u8] = &[b'x'; 4096];
+
+    static TEST_DIR: Dir = Dir::new(
+        "",
+        &[
+            DirEntry::File(File::new("index.html", INDEX)),
+            DirEntry::File(File::new("LICENSE", b"MIT")),
+            DirEntry::Dir(Dir::new(
+                "assets",
+                &[DirEntry::File(File::new("assets/index-3f2a.js", SCRIPT))],
+            )),
+        ],
+    );
+
+    fn client(frontend: Frontend) -> Client {
+        let rocket = rocket::build()
+            .manage(frontend)
+            .mount("/", rocket::routes![index]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn test_files_and_spa_fallback() {
+        let client = client(Frontend::with_source(Source::Embedded(&TEST_DIR)));
+
+        for uri in [
+            "/",
+            "/projects/abc",
+            "/settings",
+            "/projects/v1.2",
+            "/users/jane.doe",
+        ] {
+            let response = client.get(uri).dispatch();
+            assert_eq!(response.status(), Status::Ok, "{uri}");
+            assert_eq!(response.content_type(), Some(ContentType::HTML));
+            assert_eq!(response.into_bytes().unwrap(), INDEX);
+        }
+
+        // Extension-less files are served as themselves, not the app.
+        let response = client.get("/LICENSE").dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::Binary));
+        assert_eq!(response.into_bytes().unwrap(), b"MIT");
+
+        // Missing files and unknown API routes don't get the app.
+        for uri in ["/assets/missing.js", "/assets/chunk", "/favicon.ICO"] {
+            assert_eq!(
+                client.get(uri).dispatch().status(),
+                Status::NotFound,
+                "{uri}"
+            );
+        }
+        assert_eq!(
+            client.get("/api/nope").dispatch().status(),
+            Status::NotFound
+        );
+    }
+
+    #[test]
+    fn test_cache_headers_and_etag_revalidation() {
+        let client = client(Frontend::with_source(Source::Embedded(&TEST_DIR)));
+
+        let response = client.get("/assets/index-3f2a.js").dispatch();
+        assert_eq!(response.headers().get_one("Cache-Control"), Some(IMMUTABLE));
+        assert_eq!(response.content_type(), Some(ContentType::JavaScript));
+
+        let response = client.get("/").dispatch();
+        assert_eq!(
+            response.headers().get_one("Cache-Control"),
+            Some(REVALIDATE)
+        );
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+
+        let response = client
+            .get("/")
+            .header(Header::new("If-None-Match", format!("W/{etag}")))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+        assert!(response.into_bytes().unwrap_or_default().is_empty());
+
+        let response = client
+            .get("/")
+            .header(Header::new("If-None-Match", "\"stale\""))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_compression_is_negotiated() {
+        let client = client(Frontend::with_source(Source::Embedded(&TEST_DIR)));
+        let get = |accept: &str| {
+            client
+                .get("/assets/index-3f2a.js")
+                .header(Header::new("Accept-Encoding", accept.to_string()))
+                .dispatch()
+        };
+
+        let response = get("gzip, deflate, br");
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("br"));
+        assert_eq!(response.headers().get_one("Vary"), Some("Accept-Encoding"));
+        let mut body = Vec::new();
+        brotli::Decompressor::new(response.into_bytes().unwrap().as_slice(), 4096)
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(body, SCRIPT);
+
+        let response = get("gzip, br;q=0");
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+        let mut body = Vec::new();
+        flate2::read::GzDecoder::new(response.into_bytes().unwrap().as_slice())
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(body, SCRIPT);
+
+        let response = get("identity");
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.into_bytes().unwrap(), SCRIPT);
+
+        // Small files are sent as they are.
+        let response = client
+            .get("/")
+            .header(Header::new("Accept-Encoding", "br"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+    }
+
+    #[test]
+    fn test_preferred_encoding() {
+        assert_eq!(preferred_encoding("gzip, br"), Some(Encoding::Brotli));
+        assert_eq!(
+            preferred_encoding("gzip;q=1.0, br;q=0"),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(preferred_encoding("*"), Some(Encoding::Brotli));
+        assert_eq!(preferred_encoding("*, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding("identity, deflate"), None);
+    }
+
+    #[test]
+    fn test_directory_source_reads_current_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "one").unwrap();
+        let client = client(Frontend::new(Some(dir.path().to_path_buf())));
+
+        assert_eq!(client.get("/chat").dispatch().into_string().unwrap(), "one");
+        std::fs::write(dir.path().join("index.html"), "two").unwrap();
+        assert_eq!(client.get("/chat").dispatch().into_string().unwrap(), "two");
+    }
+}
@@ -0,0 +1,185 @@
+//! Liveness and readiness probes.
+//!
+//! `GET /api/health/live` answers as long as the server handles requests.
+//! `GET /api/health` also checks what sessions need: a runnable Gemini CLI
+//! and a writable data directory. It answers 503 when either is missing.
+//! Neither requires authentication, so load balancers and process
+//! supervisors can call them.
+
+use crate::config::ServerConfig;
+use crate::ws::WebSocketsEventEmitter;
+use backend::GeminiBackend;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{State, get};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How long a check result is reused: running `gemini --version` takes a
+/// while, and probing the data directory writes to disk.
+const CHECK_TTL: Duration = Duration::from_secs(30);
+
+/// Whether each check passed. Details such as paths and errors only go to
+/// the server log, since anyone can call the endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checks {
+    cli: bool,
+    data_dir: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthReport {
+    live: bool,
+    ready: bool,
+    checks: Checks,
+}
+
+/// A check's last result and when it ran.
+#[derive(Default)]
+struct Cached(Mutex<Option<(Instant, bool)>>);
+
+impl Cached {
+    fn fresh(&self) -> Option<bool> {
+        let cached = *self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        cached
+            .filter(|(checked_at, _)| checked_at.elapsed() < CHECK_TTL)
+            .map(|(_, ok)| ok)
+    }
+
+    fn store(&self, ok: bool) -> bool {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some((Instant::now(), ok));
+        ok
+    }
+}
+
+/// The last results of the readiness checks.
+#[derive(Default)]
+pub struct HealthChecks {
+    cli: Cached,
+    data_dir: Cached,
+}
+
+impl HealthChecks {
+    async fn cli(&self, backend: &GeminiBackend<WebSocketsEventEmitter>) -> bool {
+        if let Some(ok) = self.cli.fresh() {
+            return ok;
+        }
+        let installed = backend.check_cli_installed().await.unwrap_or(false);
+        if !installed {
+            eprintln!("⚠️ Health check: gemini CLI not found on PATH");
+        }
+        self.cli.store(installed)
+    }
+
+    async fn data_dir(&self, config: &ServerConfig) -> bool {
+        if let Some(ok) = self.data_dir.fresh() {
+            return ok;
+        }
+        let dir = config.data_dir.clone();
+        let writable = tokio::task::spawn_blocking(move || {
+            backend::paths::check_writable(&dir)
+                .inspect_err(|e| {
+                    eprintln!("⚠️ Health check: {} is not writable: {e}", dir.display());
+                })
+                .is_ok()
+        })
+        .await
+        .unwrap_or(false);
+        self.data_dir.store(writable)
+    }
+}
+
+#[get("/health/live")]
+pub fn live() -> Json<serde_json::Value> {
+    Json(serde_json::json!({"live": true}))
+}
+
+#[get("/health")]
+pub async fn health(
+    backend: &State<Arc<GeminiBackend<WebSocketsEventEmitter>>>,
+    checks: &State<HealthChecks>,
+    config: &State<ServerConfig>,
+) -> (Status, Json<HealthReport>) {
+    let checks = Checks {
+        cli: checks.cli(backend).await,
+        data_dir: checks.data_dir(config).await,
+    };
+    let ready = checks.cli && checks.data_dir;
+    let status = if ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    (
+        status,
+        Json(HealthReport {
+            live: true,
+            ready,
+            checks,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Cli;
+    use crate::ws::WebSocketManager;
+    use clap::Parser;
+    use rocket::local::blocking::Client;
+
+    fn client(data_dir: &std::path::Path, cli_installed: bool) -> Client {
+        let config = ServerConfig::resolve(Cli::parse_from([
+            "gemini-desktop-web",
+            "--data-dir",
+            data_dir.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let backend = Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+            WebSocketManager::new(),
+        )));
+        // Skip running the real CLI.
+        let checks = HealthChecks::default();
+        checks.cli.store(cli_installed);
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(backend)
+            .manage(checks)
+            .mount("/api", rocket::routes![health, live]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn test_ready_when_cli_found_and_data_dir_writable() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = client(&dir.path().join("data"), true);
+
+        let response = client.get("/api/health").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let report: HealthReport = response.into_json().unwrap();
+        assert!(report.ready && report.checks.cli && report.checks.data_dir);
+    }
+
+    #[test]
+    fn test_not_ready_without_cli_or_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let blocked = dir.path().join("file");
+        std::fs::write(&blocked, "").unwrap();
+        let client = client(&blocked, false);
+
+        let response = client.get("/api/health").dispatch();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        let body = response.into_string().unwrap();
+        assert!(!body.contains(blocked.to_str().unwrap()), "{body}");
+        let report: HealthReport = serde_json::from_str(&body).unwrap();
+        assert!(report.live);
+        assert!(!report.ready);
+        assert!(!report.checks.cli);
+        assert!(!report.checks.data_dir);
+
+        // Liveness doesn't depend on the checks.
+        let response = client.get("/api/health/live").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
@@ -0,0 +1,488 @@
+//! Recent events per session, so a WebSocket client that reconnects can be
+//! sent what it missed instead of losing the end of a streamed answer.
+//!
+//! Each session keeps a ring buffer of its latest serialized events. With
+//! spilling enabled, events pushed out of memory are appended to
+//! `<data_dir>/journal/<session>.jsonl` (encrypted like the RPC logs when
+//! storage encryption is on) and that file is itself bounded. Spill files
+//! are written by a thread of their own, so recording an event never waits
+//! for the disk, and only last as long as the process: the directory is
+//! emptied on startup.
+
+use backend::split_session_event;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Events kept in memory per session unless configured otherwise.
+pub const DEFAULT_JOURNAL_SIZE: usize = 1000;
+/// Sessions tracked at once; the least recently active one is forgotten first.
+const MAX_SESSIONS: usize = 256;
+/// Events kept on disk per session when spilling, as a multiple of the memory size.
+const DISK_FACTOR: usize = 10;
+
+/// Sent instead of a replay when events after `last_sequence` are no longer
+/// available; the client has to reload the transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalGap {
+    pub session_id: String,
+    pub last_sequence: u64,
+    /// Oldest sequence still available for the session, if any.
+    pub oldest_sequence: Option<u64>,
+}
+
+/// An event as it was sent to WebSocket clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournaledEvent {
+    pub sequence: u64,
+    pub event: String,
+    pub message: String,
+}
+
+/// The fields of a serialized `WebSocketEvent` needed to read it back.
+#[derive(Deserialize)]
+struct EventHeader {
+    event: String,
+    sequence: u64,
+}
+
+impl JournaledEvent {
+    fn parse(message: &str) -> Option<Self> {
+        let header: EventHeader = serde_json::from_str(message).ok()?;
+        Some(Self {
+            sequence: header.sequence,
+            event: header.event,
+            message: message.to_string(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct SessionJournal {
+    events: VecDeque<JournaledEvent>,
+    /// Highest sequence that can no longer be replayed.
+    lost_through: Option<u64>,
+    /// Sequences in the spill file, oldest first.
+    on_disk: VecDeque<u64>,
+    latest: u64,
+}
+
+enum SpillCommand {
+    Append {
+        path: PathBuf,
+        sequence: u64,
+        message: String,
+    },
+    /// Drops the events up to and including `through`.
+    Trim {
+        path: PathBuf,
+        through: u64,
+    },
+    Remove(PathBuf),
+    /// Answered once everything sent before it is on disk.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Handle on the thread writing spill files. The thread exits once every
+/// handle is gone. Events it fails to write are simply missing from the
+/// file, which [`Replay::read`] reports as a gap.
+#[derive(Clone)]
+struct Spiller {
+    dir: PathBuf,
+    commands: mpsc::Sender<SpillCommand>,
+}
+
+impl Spiller {
+    fn start(dir: PathBuf) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for command in receiver {
+                run(command);
+            }
+        });
+        Self { dir, commands }
+    }
+
+    fn path(&self, session_id: &str) -> PathBuf {
+        let file_name: String = session_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{file_name}.jsonl"))
+    }
+
+    fn send(&self, command: SpillCommand) {
+        let _ = self.commands.send(command);
+    }
+
+    /// Waits until everything sent so far has been written.
+    fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        self.send(SpillCommand::Flush(done));
+        let _ = wait.recv();
+    }
+}
+
+fn run(command: SpillCommand) {
+    match command {
+        SpillCommand::Append {
+            path,
+            sequence,
+            message,
+        } => {
+            if let Err(e) = spill(&path, &message) {
+                eprintln!("⚠️ Failed to spill event {sequence}: {e}");
+            }
+        }
+        SpillCommand::Trim { path, through } => {
+            if let Err(e) = trim_spill(&path, through) {
+                eprintln!("⚠️ Failed to trim {}: {e}", path.display());
+            }
+        }
+        SpillCommand::Remove(path) => {
+            let _ = fs::remove_file(path);
+        }
+        SpillCommand::Flush(done) => {
+            let _ = done.send(());
+        }
+    }
+}
+
+/// Events of one session to replay, found by [`EventJournal::replay`].
+/// Those spilled to disk are read by [`Replay::read`], which may block on
+/// the disk and so is meant to be called without holding any lock.
+pub struct Replay {
+    gap: JournalGap,
+    oldest: Option<u64>,
+    /// Spill file and the sequences wanted from it, oldest first.
+    spilled: Option<(Spiller, PathBuf, Vec<u64>)>,
+    events: Vec<JournaledEvent>,
+}
+
+impl Replay {
+    /// The events, oldest first, or a gap when some of them are gone.
+    pub fn read(self) -> Result<Vec<JournaledEvent>, JournalGap> {
+        let Some((spiller, path, wanted)) = self.spilled else {
+            return Ok(self.events);
+        };
+        let gap = JournalGap {
+            oldest_sequence: self.oldest,
+            ..self.gap
+        };
+        spiller.flush();
+        let lines = backend::storage::read_lines(&path).map_err(|_| gap.clone())?;
+        let session_id = gap.session_id.as_str();
+        let mut events: Vec<JournaledEvent> = lines
+            .iter()
+            .filter_map(|line| JournaledEvent::parse(line))
+            .filter(|event| wanted.binary_search(&event.sequence).is_ok())
+            // Sanitized file names can collide.
+            .filter(|event| {
+                split_session_event(&event.event).is_some_and(|(_, id)| id == session_id)
+            })
+            .collect();
+        // Failed to be written, or trimmed since the replay was planned.
+        if events.len() != wanted.len() {
+            return Err(gap);
+        }
+        events.extend(self.events);
+        Ok(events)
+    }
+}
+
+pub struct EventJournal {
+    capacity: usize,
+    spiller: Option<Spiller>,
+    sessions: HashMap<String, SessionJournal>,
+    /// Highest sequence of any session journal dropped to stay under `MAX_SESSIONS`.
+    forgotten_through: Option<u64>,
+    latest: Option<u64>,
+}
+
+impl EventJournal {
+    /// A journal keeping `capacity` events per session in memory. With a
+    /// `spill_dir`, older events go to disk there; whatever the directory
+    /// held is deleted first, since spill files from a previous run carry
+    /// sequence numbers this one reuses.
+    pub fn new(capacity: usize, spill_dir: Option<PathBuf>) -> Self {
+        if let Some(dir) = &spill_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+        Self {
+            capacity: capacity.max(1),
+            spiller: spill_dir.map(Spiller::start),
+            sessions: HashMap::new(),
+            forgotten_through: None,
+            latest: None,
+        }
+    }
+
+    /// Stores a per-session event already serialized for the WebSocket;
+    /// global events are not journaled.
+    pub fn record(&mut self, event: &str, sequence: u64, message: &str) {
+        let Some((_, session_id)) = split_session_event(event) else {
+            return;
+        };
+        self.latest = Some(self.latest.map_or(sequence, |latest| latest.max(sequence)));
+        if !self.sessions.contains_key(session_id) && self.sessions.len() >= MAX_SESSIONS {
+            self.forget_oldest_session();
+        }
+
+        let disk_capacity = self.capacity * DISK_FACTOR;
+        let journal = self.sessions.entry(session_id.to_string()).or_default();
+        journal.latest = sequence;
+        journal.events.push_back(JournaledEvent {
+            sequence,
+            event: event.to_string(),
+            message: message.to_string(),
+        });
+        if journal.events.len() <= self.capacity {
+            return;
+        }
+
+        let Some(evicted) = journal.events.pop_front() else {
+            return;
+        };
+        let Some(spiller) = &self.spiller else {
+            journal.lost_through = Some(evicted.sequence);
+            return;
+        };
+        let path = spiller.path(session_id);
+        spiller.send(SpillCommand::Append {
+            path: path.clone(),
+            sequence: evicted.sequence,
+            message: evicted.message,
+        });
+        journal.on_disk.push_back(evicted.sequence);
+        if journal.on_disk.len() > disk_capacity {
+            let dropped = journal.on_disk.len() - disk_capacity / 2;
+            if let Some(through) = journal.on_disk.drain(..dropped).next_back() {
+                journal.lost_through = Some(
+                    journal
+                        .lost_through
+                        .map_or(through, |lost| lost.max(through)),
+                );
+                spiller.send(SpillCommand::Trim { path, through });
+            }
+        }
+    }
+
+    /// Finds the events for `session_id` after `last_sequence`, or a gap when
+    /// some of them are gone. Spilled events are only read by
+    /// [`Replay::read`].
+    pub fn replay(&self, session_id: &str, last_sequence: u64) -> Result<Replay, JournalGap> {
+        let gap = |oldest_sequence| JournalGap {
+            session_id: session_id.to_string(),
+            last_sequence,
+            oldest_sequence,
+        };
+        let empty = || Replay {
+            gap: gap(None),
+            oldest: None,
+            spilled: None,
+            events: Vec::new(),
+        };
+
+        // A sequence we never issued comes from before a server restart.
+        if self.latest.is_none_or(|latest| last_sequence > latest) {
+            return Err(gap(None));
+        }
+        let Some(journal) = self.sessions.get(session_id) else {
+            if self
+                .forgotten_through
+                .is_some_and(|seq| last_sequence < seq)
+            {
+                return Err(gap(None));
+            }
+            return Ok(empty());
+        };
+        let oldest = journal
+            .on_disk
+            .front()
+            .or_else(|| journal.events.front().map(|event| &event.sequence))
+            .copied();
+        if journal.lost_through.is_some_and(|seq| last_sequence < seq) {
+            return Err(gap(oldest));
+        }
+        if journal.latest <= last_sequence {
+            return Ok(empty());
+        }
+
+        let wanted: Vec<u64> = journal
+            .on_disk
+            .iter()
+            .copied()
+            .filter(|sequence| *sequence > last_sequence)
+            .collect();
+        let spilled = match &self.spiller {
+            Some(spiller) if !wanted.is_empty() => {
+                Some((spiller.clone(), spiller.path(session_id), wanted))
+            }
+            _ => None,
+        };
+        Ok(Replay {
+            gap: gap(None),
+            oldest,
+            spilled,
+            events: journal
+                .events
+                .iter()
+                .filter(|event| event.sequence > last_sequence)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Session ids with journaled events.
+    pub fn session_ids(&self) -> Vec<String> {
+        self.sessions.keys().cloned().collect()
+    }
+
+    fn forget_oldest_session(&mut self) {
+        let Some(oldest) = self
+            .sessions
+            .iter()
+            .min_by_key(|(_, journal)| journal.latest)
+            .map(|(id, _)| id.clone())
+        else {
+            return;
+        };
+        if let Some(journal) = self.sessions.remove(&oldest) {
+            self.forgotten_through = Some(
+                self.forgotten_through
+                    .map_or(journal.latest, |seq| seq.max(journal.latest)),
+            );
+        }
+        if let Some(spiller) = &self.spiller {
+            spiller.send(SpillCommand::Remove(spiller.path(&oldest)));
+        }
+    }
+}
+
+fn spill(path: &Path, message: &str) -> backend::BackendResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let sealed = backend::storage::seal_line(path, message)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{sealed}")?;
+    Ok(())
+}
+
+/// Rewrites the spill file without its events up to `through`.
+fn trim_spill(path: &Path, through: u64) -> backend::BackendResult<()> {
+    let lines = backend::storage::read_lines(path)?;
+    let _ = fs::remove_file(path);
+    for event in lines
+        .iter()
+        .filter_map(|line| JournaledEvent::parse(line))
+        .filter(|event| event.sequence > through)
+    {
+        spill(path, &event.message)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequences(events: &[JournaledEvent]) -> Vec<u64> {
+        events.iter().map(|event| event.sequence).collect()
+    }
+
+    fn replay(
+        journal: &EventJournal,
+        session_id: &str,
+        last_sequence: u64,
+    ) -> Result<Vec<JournaledEvent>, JournalGap> {
+        journal
+            .replay(session_id, last_sequence)
+            .and_then(Replay::read)
+    }
+
+    fn record(journal: &mut EventJournal, session_id: &str, sequence: u64) {
+        let event = format!("gemini-output-{session_id}");
+        let message = format!(r#"{{"event":"{event}","payload":"x","sequence":{sequence}}}"#);
+        journal.record(&event, sequence, &message);
+    }
+
+    #[test]
+    fn test_replays_events_after_last_sequence() {
+        let mut journal = EventJournal::new(10, None);
+        for seq in 0..6 {
+            let session = if seq % 2 == 0 { "a" } else { "b" };
+            record(&mut journal, session, seq);
+        }
+        journal.record("terminal-output-t1", 6, "{}");
+
+        assert_eq!(sequences(&replay(&journal, "a", 1).unwrap()), [2, 4]);
+        assert_eq!(sequences(&replay(&journal, "b", 1).unwrap()), [3, 5]);
+        assert!(replay(&journal, "a", 5).unwrap().is_empty());
+        assert!(replay(&journal, "unknown", 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reports_gap_when_events_were_evicted() {
+        let mut journal = EventJournal::new(3, None);
+        for seq in 0..10 {
+            record(&mut journal, "a", seq);
+        }
+
+        assert_eq!(sequences(&replay(&journal, "a", 6).unwrap()), [7, 8, 9]);
+        let gap = replay(&journal, "a", 2).unwrap_err();
+        assert_eq!(gap.oldest_sequence, Some(7));
+
+        // Sequences from before a restart are never replayed.
+        assert!(replay(&journal, "a", 50).is_err());
+        assert!(EventJournal::new(3, None).replay("a", 0).is_err());
+    }
+
+    #[test]
+    fn test_spills_evicted_events_to_disk() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut journal = EventJournal::new(2, Some(temp_dir.path().join("journal")));
+        for seq in 0..10 {
+            record(&mut journal, "a/b", seq);
+        }
+
+        let events = replay(&journal, "a/b", 0).unwrap();
+        assert_eq!(sequences(&events), (1..10).collect::<Vec<_>>());
+        assert_eq!(events[0].event, "gemini-output-a/b");
+
+        // The spill file is bounded too.
+        for seq in 10..40 {
+            record(&mut journal, "a/b", seq);
+        }
+        assert!(replay(&journal, "a/b", 0).is_err());
+        let events = replay(&journal, "a/b", 30).unwrap();
+        assert_eq!(sequences(&events), (31..40).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_missing_spilled_events_are_a_gap() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("journal");
+        let mut journal = EventJournal::new(1, Some(dir.clone()));
+        for seq in 0..4 {
+            record(&mut journal, "a", seq);
+        }
+        assert_eq!(sequences(&replay(&journal, "a", 0).unwrap()), [1, 2, 3]);
+
+        // As if the writer had failed to write them.
+        fs::remove_file(dir.join("a.jsonl")).unwrap();
+        let gap = replay(&journal, "a", 0).unwrap_err();
+        assert_eq!(gap.oldest_sequence, Some(0));
+        assert_eq!(sequences(&replay(&journal, "a", 2).unwrap()), [3]);
+    }
+}
@@ -0,0 +1,230 @@
+//! Prometheus metrics at `GET /metrics`.
+//!
+//! Sessions, turns and tool confirmations come from the backend's
+//! `SessionManager`; connections and event throughput from the
+//! `WebSocketManager`. Scrapers authenticate like any other client, e.g.
+//! with `authorization: {credentials: <token>}` in the scrape config. In
+//! multi-user mode the numbers are those of the scraping account.
+
+use crate::users::Workspace;
+use crate::ws::WebSocketStats;
+use backend::{MetricsSnapshot, TURN_LATENCY_BUCKETS};
+use rocket::get;
+use rocket::http::ContentType;
+use std::fmt::Write;
+
+/// Prometheus text exposition format.
+struct Exposition(String);
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape(value)))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.0, " {value}");
+    }
+
+    fn single(&mut self, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+        self.family(name, kind, help);
+        self.sample(name, &[], value);
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+fn render(sessions: &MetricsSnapshot, ws: &WebSocketStats) -> String {
+    let mut out = Exposition(String::new());
+
+    out.single(
+        "gemini_desktop_sessions_live",
+        "gauge",
+        "Sessions whose CLI process is running.",
+        sessions.live_sessions,
+    );
+    out.single(
+        "gemini_desktop_sessions_started_total",
+        "counter",
+        "CLI sessions started.",
+        sessions.sessions_started,
+    );
+    out.single(
+        "gemini_desktop_session_crashes_total",
+        "counter",
+        "CLI sessions that exited without being killed.",
+        sessions.session_crashes,
+    );
+    out.single(
+        "gemini_desktop_turns_started_total",
+        "counter",
+        "Messages sent to a session.",
+        sessions.turns_started,
+    );
+
+    out.family(
+        "gemini_desktop_turns_ended_total",
+        "counter",
+        "Turns the CLI answered, by outcome.",
+    );
+    for (outcome, count) in [
+        ("finished", sessions.turns_finished),
+        ("failed", sessions.turns_failed),
+    ] {
+        out.sample(
+            "gemini_desktop_turns_ended_total",
+            &[("outcome", outcome)],
+            count,
+        );
+    }
+
+    let latency = &sessions.turn_latency;
+    out.family(
+        "gemini_desktop_turn_duration_seconds",
+        "histogram",
+        "Time from sending a message to the end of the turn.",
+    );
+    for (bound, count) in TURN_LATENCY_BUCKETS.iter().zip(latency.buckets) {
+        out.sample(
+            "gemini_desktop_turn_duration_seconds_bucket",
+            &[("le", &bound.to_string())],
+            count,
+        );
+    }
+    out.sample(
+        "gemini_desktop_turn_duration_seconds_bucket",
+        &[("le", "+Inf")],
+        latency.count,
+    );
+    out.sample(
+        "gemini_desktop_turn_duration_seconds_sum",
+        &[],
+        latency.sum_seconds,
+    );
+    out.sample(
+        "gemini_desktop_turn_duration_seconds_count",
+        &[],
+        latency.count,
+    );
+
+    out.family(
+        "gemini_desktop_tool_confirmations_total",
+        "counter",
+        "Tool call confirmations, by outcome.",
+    );
+    for (outcome, count) in &sessions.tool_confirmations {
+        out.sample(
+            "gemini_desktop_tool_confirmations_total",
+            &[("outcome", outcome)],
+            count,
+        );
+    }
+
+    out.single(
+        "gemini_desktop_websocket_connections",
+        "gauge",
+        "Connected WebSocket and event stream clients.",
+        ws.connections,
+    );
+    out.single(
+        "gemini_desktop_events_published_total",
+        "counter",
+        "Events published to clients.",
+        ws.events_published,
+    );
+    out.single(
+        "gemini_desktop_event_messages_queued_total",
+        "counter",
+        "Event messages queued for clients, one per recipient.",
+        ws.messages_queued,
+    );
+    out.single(
+        "gemini_desktop_slow_client_disconnects_total",
+        "counter",
+        "Clients disconnected for falling behind.",
+        ws.slow_disconnects,
+    );
+    out.single(
+        "gemini_desktop_rpc_log_bytes",
+        "gauge",
+        "Disk space used by CLI RPC logs.",
+        sessions.rpc_log_bytes,
+    );
+
+    out.0
+}
+
+#[get("/metrics")]
+pub async fn metrics(workspace: Workspace) -> (ContentType, String) {
+    let content_type =
+        ContentType::new("text", "plain").with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    // Measuring the RPC logs walks the disk now and then.
+    let backend = workspace.backend.clone();
+    let sessions = tokio::task::spawn_blocking(move || backend.metrics())
+        .await
+        .unwrap_or_default();
+    (
+        content_type,
+        render(&sessions, &workspace.ws_manager.stats()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_render_exposition() {
+        let mut sessions = MetricsSnapshot {
+            live_sessions: 2,
+            turns_finished: 3,
+            tool_confirmations: BTreeMap::from([("allow".to_string(), 4)]),
+            rpc_log_bytes: 1024,
+            ..MetricsSnapshot::default()
+        };
+        sessions.turn_latency.buckets[2] = 1;
+        sessions.turn_latency.count = 1;
+        sessions.turn_latency.sum_seconds = 4.5;
+        let ws = WebSocketStats {
+            connections: 1,
+            events_published: 10,
+            messages_queued: 7,
+            slow_disconnects: 0,
+        };
+
+        let text = render(&sessions, &ws);
+        for line in [
+            "# TYPE gemini_desktop_sessions_live gauge",
+            "gemini_desktop_sessions_live 2",
+            "gemini_desktop_turns_ended_total{outcome=\"finished\"} 3",
+            "gemini_desktop_turn_duration_seconds_bucket{le=\"5\"} 1",
+            "gemini_desktop_turn_duration_seconds_bucket{le=\"+Inf\"} 1",
+            "gemini_desktop_turn_duration_seconds_sum 4.5",
+            "gemini_desktop_tool_confirmations_total{outcome=\"allow\"} 4",
+            "gemini_desktop_websocket_connections 1",
+            "gemini_desktop_events_published_total 10",
+            "gemini_desktop_rpc_log_bytes 1024",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line}:\n{text}");
+        }
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        assert_eq!(escape("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+}
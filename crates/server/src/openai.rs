@@ -0,0 +1,734 @@
+//! OpenAI-compatible API: `GET /v1/models` and `POST /v1/chat/completions`.
+//!
+//! Clients point their OpenAI base URL at `<server>/v1` and pass the access
+//! token as the API key. Each completion runs as one turn of a Gemini CLI
+//! session. Sessions are pooled: when a request continues a conversation
+//! that a pooled session has already seen (same earlier messages, same
+//! model), only the new user message is sent to it. Otherwise a fresh
+//! session gets the earlier messages as context. Sessions run in the
+//! caller's `<data-dir>/openai`. Tool confirmations are always rejected,
+//! since these clients can't answer them.
+
+use crate::auth::Authenticated;
+use crate::config::Writable;
+use crate::prompt::{ToolPolicy, Turn, TurnEvent, TurnOutcome, TurnSummary};
+use crate::rate_limit::RateLimited;
+use crate::users::Workspace;
+use crate::ws::WebSocketsEventEmitter;
+use backend::{BackendError, GeminiBackend};
+use futures::{Stream, StreamExt};
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::stream::{Event, EventStream};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{State, catch, get, post};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+type Backend = GeminiBackend<WebSocketsEventEmitter>;
+
+/// Idle sessions kept for follow-up requests.
+const MAX_IDLE_SESSIONS: usize = 8;
+/// Idle sessions older than this are stopped.
+const IDLE_TTL: Duration = Duration::from_secs(15 * 60);
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(600);
+
+// =====================================
+// Errors
+// =====================================
+
+/// Error in OpenAI's shape: `{"error": {"message", "type", "param", "code"}}`.
+#[derive(Debug)]
+pub struct OpenAiError {
+    status: Status,
+    message: String,
+    code: String,
+}
+
+impl OpenAiError {
+    fn new(status: Status, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            code: code.to_string(),
+        }
+    }
+
+    fn invalid(message: impl Into<String>) -> Self {
+        Self::new(Status::BadRequest, "invalid_request_body", message)
+    }
+}
+
+impl From<BackendError> for OpenAiError {
+    fn from(error: BackendError) -> Self {
+        if error.http_status() >= 500 {
+            eprintln!("❌ {error}");
+        }
+        let status = Status::from_code(error.http_status()).unwrap_or(Status::InternalServerError);
+        Self::new(status, error.code(), error.to_string())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for OpenAiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let kind = match self.status.code {
+            401 => "authentication_error",
+            429 => "rate_limit_error",
+            code if code >= 500 => "server_error",
+            _ => "invalid_request_error",
+        };
+        let body = json!({
+            "error": {
+                "message": self.message,
+                "type": kind,
+                "param": null,
+                "code": self.code,
+            }
+        });
+        (self.status, Json(body)).respond_to(request)
+    }
+}
+
+/// Gives guard and routing failures under `/v1` OpenAI's error shape.
+#[catch(default)]
+pub fn default_catcher(status: Status, _request: &Request<'_>) -> OpenAiError {
+    let code = match status.code {
+        401 => "invalid_api_key",
+        429 => "rate_limit_exceeded",
+        _ => "request_failed",
+    };
+    OpenAiError::new(status, code, status.reason_lossy())
+}
+
+// =====================================
+// Session pool
+// =====================================
+
+struct IdleSession {
+    /// Backend of the user the session belongs to.
+    backend: Arc<Backend>,
+    session_id: String,
+    model: String,
+    /// Hash of the conversation the session has seen so far.
+    transcript: String,
+    idle_since: Instant,
+}
+
+/// CLI sessions waiting for the next message of their conversation.
+#[derive(Default)]
+pub struct SessionPool {
+    idle: Mutex<Vec<IdleSession>>,
+}
+
+impl SessionPool {
+    fn lock(&self) -> MutexGuard<'_, Vec<IdleSession>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stops sessions idle for longer than the TTL.
+    fn expire(&self) {
+        let expired: Vec<IdleSession> = {
+            let mut idle = self.lock();
+            let (expired, kept) = std::mem::take(&mut *idle)
+                .into_iter()
+                .partition(|session| session.idle_since.elapsed() > IDLE_TTL);
+            *idle = kept;
+            expired
+        };
+        for session in expired {
+            let _ = session.backend.kill_process(&session.session_id);
+        }
+    }
+
+    /// Takes the idle session of `backend` that has seen exactly `transcript`.
+    fn take(&self, backend: &Arc<Backend>, model: &str, transcript: &str) -> Option<String> {
+        self.expire();
+        let mut idle = self.lock();
+        let index = idle.iter().position(|session| {
+            Arc::ptr_eq(&session.backend, backend)
+                && session.model == model
+                && session.transcript == transcript
+        })?;
+        Some(idle.remove(index).session_id)
+    }
+
+    /// Returns a session to the pool, stopping the oldest idle one if full.
+    fn put(&self, backend: Arc<Backend>, session_id: String, model: String, transcript: String) {
+        let evicted = {
+            let mut idle = self.lock();
+            idle.push(IdleSession {
+                backend,
+                session_id,
+                model,
+                transcript,
+                idle_since: Instant::now(),
+            });
+            (idle.len() > MAX_IDLE_SESSIONS).then(|| idle.remove(0))
+        };
+        if let Some(session) = evicted {
+            let _ = session.backend.kill_process(&session.session_id);
+        }
+    }
+}
+
+/// A session taken from the pool for one completion. It goes back to the
+/// pool only if the turn finished; otherwise it may still be answering, so
+/// dropping the lease stops it.
+struct Lease {
+    pool: Arc<SessionPool>,
+    backend: Arc<Backend>,
+    session_id: String,
+    model: String,
+    transcript: Option<String>,
+}
+
+impl Lease {
+    fn finished(&mut self, transcript: String) {
+        self.transcript = Some(transcript);
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        match self.transcript.take() {
+            Some(transcript) => self.pool.put(
+                self.backend.clone(),
+                self.session_id.clone(),
+                self.model.clone(),
+                transcript,
+            ),
+            None => {
+                let _ = self.backend.kill_process(&self.session_id);
+            }
+        }
+    }
+}
+
+// =====================================
+// Request and response types
+// =====================================
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentPart {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatMessage {
+    role: String,
+    #[serde(default)]
+    content: Option<MessageContent>,
+}
+
+/// The fields this facade uses; sampling options and the like are ignored.
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    stream: bool,
+}
+
+/// A message reduced to what the CLI sees.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Message {
+    role: String,
+    text: String,
+}
+
+fn normalize(messages: Vec<ChatMessage>) -> Result<Vec<Message>, OpenAiError> {
+    messages
+        .into_iter()
+        .map(|message| {
+            let text = match message.content {
+                None => String::new(),
+                Some(MessageContent::Text(text)) => text,
+                Some(MessageContent::Parts(parts)) => {
+                    let mut texts = Vec::new();
+                    for part in parts {
+                        match (part.kind.as_str(), part.text) {
+                            ("text", Some(text)) => texts.push(text),
+                            (kind, _) => {
+                                return Err(OpenAiError::invalid(format!(
+                                    "unsupported content part: {kind}"
+                                )));
+                            }
+                        }
+                    }
+                    texts.join("\n")
+                }
+            };
+            Ok(Message {
+                role: message.role,
+                text: text.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Identifies a conversation by its messages, so a pooled session can be
+/// matched to the request that continues it.
+fn transcript_key(model: &str, messages: &[Message]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update(serde_json::to_vec(messages).unwrap_or_default());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Earlier messages as context for a session that hasn't seen them.
+fn conversation_history(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let speaker = match message.role.as_str() {
+                "system" | "developer" => "System",
+                "assistant" => "Assistant",
+                "tool" => "Tool",
+                _ => "User",
+            };
+            format!("{speaker}: {}", message.text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Fields every completion and chunk carry.
+#[derive(Clone)]
+struct CompletionMeta {
+    id: String,
+    created: u64,
+    model: String,
+}
+
+impl CompletionMeta {
+    fn chunk(&self, delta: serde_json::Value, finish_reason: Option<&str>) -> Event {
+        Event::data(
+            json!({
+                "id": self.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": self.model,
+                "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+            })
+            .to_string(),
+        )
+    }
+
+    fn completion(&self, text: &str) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": text},
+                "finish_reason": "stop",
+            }],
+        })
+    }
+}
+
+fn turn_error(summary: &TurnSummary) -> OpenAiError {
+    let message = summary
+        .error
+        .clone()
+        .unwrap_or_else(|| "The Gemini CLI turn failed".to_string());
+    match summary.outcome {
+        TurnOutcome::TimedOut => OpenAiError::new(Status::GatewayTimeout, "timeout", message),
+        _ => OpenAiError::new(Status::BadGateway, "cli_error", message),
+    }
+}
+
+/// State of a streamed completion between chunks.
+struct Streaming {
+    turn: Turn,
+    lease: Lease,
+    meta: CompletionMeta,
+    /// The conversation up to the reply, to key the session by afterwards.
+    messages: Vec<Message>,
+    started: bool,
+    done: bool,
+}
+
+impl Streaming {
+    async fn next_events(&mut self) -> Option<Vec<Event>> {
+        if !self.started {
+            self.started = true;
+            let delta = json!({"role": "assistant", "content": ""});
+            return Some(vec![self.meta.chunk(delta, None)]);
+        }
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.turn.next().await? {
+                TurnEvent::Text(text) => {
+                    return Some(vec![self.meta.chunk(json!({"content": text}), None)]);
+                }
+                TurnEvent::Done(summary) => {
+                    self.done = true;
+                    let last = if summary.outcome == TurnOutcome::Finished {
+                        self.messages.push(Message {
+                            role: "assistant".to_string(),
+                            text: summary.text.trim().to_string(),
+                        });
+                        self.lease
+                            .finished(transcript_key(&self.meta.model, &self.messages));
+                        self.meta.chunk(json!({}), Some("stop"))
+                    } else {
+                        let error = turn_error(&summary);
+                        Event::data(
+                            json!({"error": {"message": error.message, "code": error.code}})
+                                .to_string(),
+                        )
+                    };
+                    return Some(vec![last, Event::data("[DONE]")]);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+type BoxStream<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+
+pub enum CompletionResponse {
+    Complete(Json<serde_json::Value>),
+    Stream(EventStream<BoxStream<Event>>),
+}
+
+impl<'r> Responder<'r, 'r> for CompletionResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        match self {
+            CompletionResponse::Complete(body) => body.respond_to(request),
+            CompletionResponse::Stream(stream) => stream.respond_to(request),
+        }
+    }
+}
+
+// =====================================
+// Routes
+// =====================================
+
+#[get("/models")]
+pub async fn models(_auth: Authenticated) -> Result<Json<serde_json::Value>, OpenAiError> {
+    let models = backend::get_gemini_models().await?;
+    let data: Vec<_> = models
+        .into_iter()
+        .filter(|model| model.is_available)
+        .map(|model| {
+            json!({
+                "id": model.name,
+                "object": "model",
+                "created": 0,
+                "owned_by": model.provider.to_lowercase(),
+            })
+        })
+        .collect();
+    Ok(Json(json!({"object": "list", "data": data})))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/chat/completions", data = "<request>")]
+pub async fn chat_completions(
+    workspace: Workspace,
+    _writable: Writable,
+    _limited: RateLimited,
+    request: Json<ChatCompletionRequest>,
+    pool: &State<Arc<SessionPool>>,
+) -> Result<CompletionResponse, OpenAiError> {
+    let ChatCompletionRequest {
+        model,
+        messages,
+        stream,
+    } = request.into_inner();
+    let mut messages = normalize(messages)?;
+    let prompt = match messages.pop() {
+        Some(message) if message.role == "user" && !message.text.is_empty() => message,
+        _ => {
+            return Err(OpenAiError::invalid(
+                "the last message must be a non-empty user message",
+            ));
+        }
+    };
+
+    let backend = workspace.backend;
+    let pool = pool.inner().clone();
+    let working_directory = workspace.data_dir.join("openai");
+    let earlier = transcript_key(&model, &messages);
+    let (session_id, history) = match pool.take(&backend, &model, &earlier) {
+        Some(session_id) => (session_id, String::new()),
+        None => {
+            let session_id = format!("openai-{}", uuid::Uuid::new_v4());
+            std::fs::create_dir_all(&working_directory).map_err(BackendError::IoError)?;
+            (session_id, conversation_history(&messages))
+        }
+    };
+    let mut lease = Lease {
+        pool,
+        backend: backend.clone(),
+        session_id: session_id.clone(),
+        model: model.clone(),
+        transcript: None,
+    };
+
+    let started = Instant::now();
+    backend
+        .initialize_session(
+            session_id.clone(),
+            working_directory.display().to_string(),
+            model.clone(),
+        )
+        .await?;
+    let mut turn = Turn::subscribe(
+        backend.clone(),
+        &workspace.ws_manager,
+        session_id.clone(),
+        ToolPolicy::DenyAll,
+        COMPLETION_TIMEOUT,
+    );
+    turn.sent(started.elapsed());
+    backend
+        .send_message(session_id, prompt.text.clone(), history)
+        .await?;
+
+    messages.push(prompt);
+    let meta = CompletionMeta {
+        id: format!("chatcmpl-{}", uuid::Uuid::new_v4().simple()),
+        created: unix_time(),
+        model,
+    };
+
+    if stream {
+        let state = Streaming {
+            turn,
+            lease,
+            meta,
+            messages,
+            started: false,
+            done: false,
+        };
+        let events = futures::stream::unfold(state, |mut state| async move {
+            let events = state.next_events().await?;
+            Some((futures::stream::iter(events), state))
+        })
+        .flatten();
+        return Ok(CompletionResponse::Stream(EventStream::from(
+            events.boxed(),
+        )));
+    }
+
+    while let Some(event) = turn.next().await {
+        if let TurnEvent::Done(summary) = event {
+            if summary.outcome != TurnOutcome::Finished {
+                return Err(turn_error(&summary));
+            }
+            messages.push(Message {
+                role: "assistant".to_string(),
+                text: summary.text.trim().to_string(),
+            });
+            lease.finished(transcript_key(&meta.model, &messages));
+            return Ok(CompletionResponse::Complete(Json(
+                meta.completion(&summary.text),
+            )));
+        }
+    }
+    Err(OpenAiError::new(
+        Status::BadGateway,
+        "cli_error",
+        "The Gemini CLI turn ended without an answer",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthManager;
+    use crate::config::{Cli, ServerConfig};
+    use crate::ws::WebSocketManager;
+    use clap::Parser;
+    use rocket::http::Header;
+    use rocket::local::asynchronous::Client;
+
+    fn messages(value: serde_json::Value) -> Vec<Message> {
+        normalize(serde_json::from_value(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_messages_are_normalized() {
+        let normalized = messages(json!([
+            {"role": "system", "content": "Be brief."},
+            {"role": "user", "content": [
+                {"type": "text", "text": "Hello"},
+                {"type": "text", "text": "there "},
+            ]},
+            {"role": "assistant", "content": null},
+        ]));
+        assert_eq!(normalized[1].text, "Hello\nthere");
+        assert_eq!(normalized[2].text, "");
+        assert_eq!(
+            conversation_history(&normalized[..2]),
+            "System: Be brief.\n\nUser: Hello\nthere"
+        );
+
+        let images: Vec<ChatMessage> = serde_json::from_value(json!([
+            {"role": "user", "content": [{"type": "image_url", "image_url": {"url": "x"}}]},
+        ]))
+        .unwrap();
+        assert!(normalize(images).is_err());
+    }
+
+    #[test]
+    fn test_transcripts_match_continued_conversations() {
+        let first = messages(json!([{"role": "user", "content": "Hi"}]));
+        let mut answered = first.clone();
+        answered.push(Message {
+            role: "assistant".to_string(),
+            text: "Hello!".to_string(),
+        });
+        // The client sends the whole conversation back with a new message;
+        // everything before that message identifies the session.
+        let follow_up = messages(json!([
+            {"role": "user", "content": "Hi"},
+            {"role": "assistant", "content": "Hello!\n"},
+            {"role": "user", "content": "How are you?"},
+        ]));
+        let key = transcript_key("gemini-2.5-flash", &answered);
+        assert_eq!(key, transcript_key("gemini-2.5-flash", &follow_up[..2]));
+        assert_ne!(key, transcript_key("gemini-2.5-pro", &follow_up[..2]));
+        assert_ne!(key, transcript_key("gemini-2.5-flash", &first));
+    }
+
+    #[test]
+    fn test_pool_matches_model_and_transcript_and_evicts() {
+        let backend = || {
+            Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+                WebSocketManager::new(),
+            )))
+        };
+        let (backend, other_user) = (backend(), backend());
+        let pool = SessionPool::default();
+
+        pool.put(backend.clone(), "a".into(), "m1".into(), "t1".into());
+        assert_eq!(pool.take(&other_user, "m1", "t1"), None);
+        assert_eq!(pool.take(&backend, "m2", "t1"), None);
+        assert_eq!(pool.take(&backend, "m1", "t2"), None);
+        assert_eq!(pool.take(&backend, "m1", "t1").as_deref(), Some("a"));
+        assert_eq!(pool.take(&backend, "m1", "t1"), None);
+
+        for i in 0..=MAX_IDLE_SESSIONS {
+            pool.put(
+                backend.clone(),
+                format!("s{i}"),
+                "m".into(),
+                format!("t{i}"),
+            );
+        }
+        assert_eq!(pool.lock().len(), MAX_IDLE_SESSIONS);
+        assert_eq!(pool.take(&backend, "m", "t0"), None);
+    }
+
+    #[rocket::async_test]
+    async fn test_errors_use_openai_shape() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ServerConfig::resolve(Cli::parse_from([
+            "gemini-desktop-web",
+            "--data-dir",
+            dir.path().to_str().unwrap(),
+        ]))
+        .unwrap();
+        let auth = AuthManager::load_or_init(dir.path()).unwrap();
+        let token = Header::new(
+            "Authorization",
+            format!("Bearer {}", auth.rotate_token().unwrap()),
+        );
+        let ws_manager = WebSocketManager::new();
+        let backend = Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+            ws_manager.clone(),
+        )));
+        let rocket = rocket::build()
+            .manage(Arc::new(SessionPool::default()))
+            .manage(config)
+            .manage(Arc::new(auth))
+            .manage(backend)
+            .manage(ws_manager)
+            .register("/v1", rocket::catchers![default_catcher])
+            .mount("/v1", rocket::routes![models, chat_completions]);
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client.get("/v1/models").dispatch().await;
+        assert_eq!(response.status(), Status::Unauthorized);
+        let body: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(body["error"]["code"], "invalid_api_key");
+        assert_eq!(body["error"]["type"], "authentication_error");
+
+        let response = client
+            .get("/v1/models")
+            .header(token.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(body["object"], "list");
+        assert!(body["data"].as_array().is_some_and(|data| !data.is_empty()));
+
+        let response = client
+            .post("/v1/chat/completions")
+            .header(token.clone())
+            .body(
+                json!({
+                    "model": "gemini-2.5-flash",
+                    "messages": [{"role": "assistant", "content": "Hi"}],
+                })
+                .to_string(),
+            )
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+
+        let response = client
+            .post("/v1/chat/completions")
+            .header(token)
+            .body(
+                json!({
+                    "model": "x; rm -rf ~",
+                    "messages": [{"role": "user", "content": "Hi"}],
+                })
+                .to_string(),
+            )
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let body: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(body["error"]["code"], "invalid_request_body");
+    }
+}
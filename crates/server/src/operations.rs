@@ -0,0 +1,281 @@
+//! REST routes for the backend operation registry.
+//!
+//! Every [`backend::operations`] entry is mounted under `/api` with its own
+//! method and path. A request's JSON body, path segments and query string
+//! are merged into one parameter object, so `POST /terminals/abc/write` with
+//! `{"data": "ls"}` calls `write_terminal` with `terminal_id` set to `abc`.
+//! Operations run on the backend of the calling user's [`Workspace`], or for
+//! a guest, of the session it was invited to, as far as its role allows.
+
+use crate::config::Writable;
+use crate::error::ApiError;
+use crate::rate_limit::RateLimited;
+use crate::roles::Participant;
+use crate::users::{ADMIN_OPERATIONS, approves_command};
+use crate::ws::WebSocketsEventEmitter;
+use backend::{Access, BackendError, HttpMethod, Operation};
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::get;
+use rocket::http::{Method, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::route::{self, Handler, Route};
+use rocket::serde::json::Json;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+static OPENAPI_DOCUMENT: LazyLock<Value> = LazyLock::new(backend::openapi_document);
+
+#[derive(Clone)]
+pub struct OperationHandler {
+    operation: Operation<WebSocketsEventEmitter>,
+}
+
+/// One route per registered operation, to be mounted at `/api`.
+pub fn routes() -> Vec<Route> {
+    backend::operations::<WebSocketsEventEmitter>()
+        .into_iter()
+        .map(|operation| {
+            let method = match operation.method {
+                HttpMethod::Get => Method::Get,
+                HttpMethod::Post => Method::Post,
+                HttpMethod::Put => Method::Put,
+                HttpMethod::Delete => Method::Delete,
+            };
+            let handler = OperationHandler { operation };
+            let mut route = Route::new(method, operation.path, handler);
+            // The rate limiter and logs key on the route name.
+            route.name = Some(Cow::Borrowed(operation.name));
+            route
+        })
+        .collect()
+}
+
+/// OpenAPI description of these routes and of the WebSocket events.
+#[get("/openapi.json")]
+pub fn openapi() -> Json<&'static Value> {
+    Json(&OPENAPI_DOCUMENT)
+}
+
+async fn guard<'r, T: FromRequest<'r>>(request: &'r Request<'_>) -> Result<T, Status> {
+    match request.guard::<T>().await {
+        Outcome::Success(value) => Ok(value),
+        Outcome::Error((status, _)) | Outcome::Forward(status) => Err(status),
+    }
+}
+
+impl OperationHandler {
+    async fn params<'r>(
+        &self,
+        request: &'r Request<'_>,
+        data: Data<'r>,
+    ) -> Result<Value, BackendError> {
+        let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+        let body = data.open(limit).into_string().await?;
+        if !body.is_complete() {
+            return Err(BackendError::InvalidRequest(format!(
+                "body is larger than {}",
+                limit.as_u64().bytes()
+            )));
+        }
+
+        let mut params = if body.trim().is_empty() {
+            Map::new()
+        } else {
+            match serde_json::from_str(&body) {
+                Ok(Value::Object(map)) => map,
+                Ok(_) => {
+                    return Err(BackendError::InvalidRequest(
+                        "body must be a JSON object".to_string(),
+                    ));
+                }
+                Err(e) => return Err(BackendError::InvalidRequest(e.to_string())),
+            }
+        };
+
+        if let Some(query) = request.uri().query() {
+            for (key, value) in query.segments() {
+                if key != "access_token" {
+                    params.insert(key.to_string(), Value::String(value.to_string()));
+                }
+            }
+        }
+        for (index, name) in self.operation.path_params() {
+            if let Some(segment) = request.routed_segment(index) {
+                params.insert(name.to_string(), Value::String(segment.to_string()));
+            }
+        }
+        Ok(Value::Object(params))
+    }
+}
+
+#[rocket::async_trait]
+impl Handler for OperationHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let guards = async {
+            let participant: Participant = guard(request).await?;
+            if !participant.admin && ADMIN_OPERATIONS.contains(&self.operation.name) {
+                return Err(Status::Forbidden);
+            }
+            if self.operation.access == Access::Write {
+                guard::<Writable>(request).await?;
+            }
+            guard::<RateLimited>(request).await?;
+            Ok(participant)
+        };
+        let participant = match guards.await {
+            Ok(participant) => participant,
+            Err(status) => return route::Outcome::error(status),
+        };
+
+        let params = match self.params(request, data).await {
+            Ok(params) => backend::snake_case_keys(params),
+            Err(e) => return route::Outcome::from(request, ApiError::from(e)),
+        };
+        if let Err(body) = participant.viewer.authorize(self.operation.name, &params) {
+            return route::Outcome::from(request, ApiError(body));
+        }
+        if !participant.admin
+            && approves_command(&participant.workspace.backend, self.operation.name, &params)
+        {
+            return route::Outcome::error(Status::Forbidden);
+        }
+        match self
+            .operation
+            .call(participant.workspace.backend, params)
+            .await
+        {
+            Ok(value) => route::Outcome::from(request, Json(value)),
+            Err(e) => route::Outcome::from(request, ApiError::from(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthManager;
+    use crate::config::{Cli, ServerConfig};
+    use crate::rate_limit::RateLimiter;
+    use crate::ws::WebSocketManager;
+    use backend::{ErrorBody, GeminiBackend};
+    use clap::Parser;
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    fn client(read_only: bool) -> (Client, String, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_str().unwrap();
+        let mut args = vec!["gemini-desktop-web", "--data-dir", data_dir];
+        if read_only {
+            args.push("--read-only");
+        }
+        let config = ServerConfig::resolve(Cli::parse_from(args)).unwrap();
+        let auth = AuthManager::load_or_init(dir.path()).unwrap();
+        let token = format!("Bearer {}", auth.rotate_token().unwrap());
+        let ws_manager = WebSocketManager::new();
+        let backend = Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+            ws_manager.clone(),
+        )));
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(Arc::new(auth))
+            .manage(RateLimiter::default())
+            .manage(backend)
+            .manage(ws_manager)
+            .register("/api", rocket::catchers![crate::error::default_catcher])
+            .mount("/api", routes())
+            .mount("/api", rocket::routes![openapi]);
+        (Client::tracked(rocket).unwrap(), token, dir)
+    }
+
+    fn error_code(response: rocket::local::blocking::LocalResponse<'_>) -> String {
+        response.into_json::<ErrorBody>().unwrap().code
+    }
+
+    #[test]
+    fn test_requests_are_merged_into_params() {
+        let (client, token, _dir) = client(false);
+        let auth = Header::new("Authorization", token);
+
+        let response = client.get("/api/process-statuses").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .get("/api/process-statuses")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<Value>().unwrap(),
+            serde_json::json!([])
+        );
+
+        // The terminal id comes from the path, the data from the body.
+        let response = client
+            .post("/api/terminals/abc/write")
+            .header(auth.clone())
+            .body(r#"{"data": "ls"}"#)
+            .dispatch();
+        assert_eq!(error_code(response), "terminal_not_found");
+
+        let response = client
+            .post("/api/terminals/abc/write")
+            .header(auth.clone())
+            .body("{}")
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        assert_eq!(error_code(response), "invalid_request_body");
+
+        // Query values arrive as text and are parsed by the request type.
+        let response = client
+            .get("/api/mcp-servers/popular?limit=nope")
+            .header(auth)
+            .dispatch();
+        assert_eq!(error_code(response), "invalid_request_body");
+    }
+
+    #[test]
+    fn test_openapi_document_is_public() {
+        let (client, _token, _dir) = client(false);
+        let response = client.get("/api/openapi.json").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let document: Value = response.into_json().unwrap();
+        assert_eq!(
+            document["paths"]["/send-message"]["post"]["operationId"],
+            "send_message"
+        );
+        assert!(document["x-events"].is_array());
+    }
+
+    #[test]
+    fn test_read_only_refuses_writes() {
+        let (client, token, _dir) = client(true);
+        let auth = Header::new("Authorization", token);
+
+        let response = client
+            .post("/api/kill-process")
+            .header(auth.clone())
+            .body(r#"{"conversation_id": "x"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client.get("/api/terminals").header(auth).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_every_operation_is_routed() {
+        let routed: HashSet<_> = routes()
+            .into_iter()
+            .map(|route| route.name.unwrap().into_owned())
+            .collect();
+        let registered: HashSet<_> = backend::operations::<WebSocketsEventEmitter>()
+            .into_iter()
+            .map(|op| op.name.to_string())
+            .collect();
+        assert_eq!(routed, registered);
+    }
+}
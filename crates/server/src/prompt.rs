@@ -0,0 +1,843 @@
+//! Headless prompts for scripts and CI: `POST /api/prompt`.
+//!
+//! The request names a working directory, model and prompt, plus optional
+//! files to attach. The server starts a session there (or reuses the one
+//! named by `session_id`), sends the prompt and streams the answer back,
+//! either as plain chunked text or, with `"format": "sse"` or
+//! `Accept: text/event-stream`, as Server-Sent Events. The response ends
+//! with a JSON summary of the turn: final text, tool calls, confirmations,
+//! outcome and timing. In plain text the summary is the last line.
+//!
+//! Nobody is around to confirm tool calls, so `tool_policy` answers them:
+//! `deny-all` (the default), `allow-read-only`, `allow-edits` or
+//! `allow-all`.
+
+use crate::config::Writable;
+use crate::error::{ApiError, ApiResult};
+use crate::rate_limit::RateLimited;
+use crate::users::Workspace;
+use crate::ws::{
+    CONNECTION_QUEUE_CAPACITY, ConnectionGuard, WebSocketManager, WebSocketsEventEmitter,
+};
+use backend::{
+    BackendError, GeminiBackend, ToolCallConfirmationRequest, ToolCallEvent, ToolCallUpdate,
+    split_session_event,
+};
+use futures::{Stream, StreamExt};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream, TextStream};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{State, post};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+type Backend = GeminiBackend<WebSocketsEventEmitter>;
+
+pub const DEFAULT_MODEL: &str = "gemini-2.5-flash";
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+/// How often a turn checks that its session's CLI is still running.
+const LIVENESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How to answer the CLI's tool call confirmations without a user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolPolicy {
+    #[default]
+    DenyAll,
+    /// Allow tools that only read, such as fetching a URL.
+    AllowReadOnly,
+    /// Also allow file edits, but not shell commands or MCP tools.
+    AllowEdits,
+    AllowAll,
+}
+
+impl ToolPolicy {
+    /// The outcome to send for a confirmation of the given type (`edit`,
+    /// `execute`, `mcp` or `info`).
+    pub fn decide(self, confirmation_type: &str) -> &'static str {
+        let allowed = match self {
+            ToolPolicy::DenyAll => false,
+            ToolPolicy::AllowReadOnly => confirmation_type == "info",
+            ToolPolicy::AllowEdits => matches!(confirmation_type, "info" | "edit"),
+            ToolPolicy::AllowAll => true,
+        };
+        if allowed { "allow" } else { "reject" }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    Text,
+    Sse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PromptRequest {
+    pub prompt: String,
+    pub working_directory: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Files for the CLI to read, absolute or relative to the working
+    /// directory.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    /// Session to reuse; a new one is started when absent.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Keep a session this request started running afterwards, so later
+    /// prompts can reuse it.
+    #[serde(default)]
+    pub keep_session: bool,
+    #[serde(default)]
+    pub tool_policy: ToolPolicy,
+    #[serde(default)]
+    pub format: Option<StreamFormat>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub id: u32,
+    pub label: String,
+    pub icon: String,
+    pub status: String,
+    pub locations: Vec<String>,
+    pub content: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfirmationRecord {
+    pub request_id: u32,
+    pub label: String,
+    #[serde(rename = "type")]
+    pub confirmation_type: String,
+    pub command: Option<String>,
+    pub outcome: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TurnOutcome {
+    Finished,
+    Failed,
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timing {
+    /// Starting the CLI, or 0 when the session was already running.
+    pub session_start_ms: u64,
+    /// From sending the prompt to the first chunk of the answer.
+    pub first_output_ms: Option<u64>,
+    pub total_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnSummary {
+    pub session_id: String,
+    pub outcome: TurnOutcome,
+    pub text: String,
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub confirmations: Vec<ConfirmationRecord>,
+    pub error: Option<String>,
+    pub timing: Timing,
+}
+
+/// What happened during a turn, in order. `Done` is always last.
+#[derive(Debug, Clone)]
+pub enum TurnEvent {
+    Text(String),
+    Thought(String),
+    ToolCall(ToolCallRecord),
+    Confirmation(ConfirmationRecord),
+    Done(TurnSummary),
+}
+
+/// Sessions with a headless turn running, so two turns don't mix their
+/// answers in one session. Keyed by user, since each has their own sessions.
+#[derive(Default)]
+pub struct BusySessions(Mutex<HashSet<(String, String)>>);
+
+impl BusySessions {
+    fn lock(&self) -> MutexGuard<'_, HashSet<(String, String)>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Marks `user`'s `session_id` busy, or returns `None` if it already is.
+    pub fn claim(self: &Arc<Self>, user: &str, session_id: &str) -> Option<SessionClaim> {
+        let key = (user.to_string(), session_id.to_string());
+        self.lock().insert(key.clone()).then(|| SessionClaim {
+            sessions: self.clone(),
+            key,
+            kill: None,
+        })
+    }
+}
+
+/// A session reserved for one turn. Dropping it frees the session, and
+/// kills it if the turn started it just for itself.
+pub struct SessionClaim {
+    sessions: Arc<BusySessions>,
+    /// User and session id.
+    key: (String, String),
+    kill: Option<Arc<Backend>>,
+}
+
+impl SessionClaim {
+    pub fn kill_on_release(&mut self, backend: Arc<Backend>) {
+        self.kill = Some(backend);
+    }
+}
+
+impl Drop for SessionClaim {
+    fn drop(&mut self) {
+        if let Some(backend) = self.kill.take() {
+            let _ = backend.kill_process(&self.key.1);
+        }
+        self.sessions.lock().remove(&self.key);
+    }
+}
+
+/// Follows one turn of a session through its events, answering tool
+/// confirmations by policy and building the summary.
+pub struct Turn {
+    backend: Arc<Backend>,
+    session_id: String,
+    policy: ToolPolicy,
+    events: mpsc::Receiver<String>,
+    _connection: ConnectionGuard,
+    claim: Option<SessionClaim>,
+    sent_at: Instant,
+    deadline: Instant,
+    liveness: tokio::time::Interval,
+    session_start: Duration,
+    first_output: Option<Duration>,
+    text: String,
+    tool_calls: Vec<ToolCallRecord>,
+    confirmations: Vec<ConfirmationRecord>,
+    done: bool,
+}
+
+impl Turn {
+    /// Subscribes to `session_id`'s events. Call before sending the
+    /// message so none of the answer is missed.
+    pub fn subscribe(
+        backend: Arc<Backend>,
+        ws_manager: &WebSocketManager,
+        session_id: String,
+        policy: ToolPolicy,
+        timeout: Duration,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(CONNECTION_QUEUE_CAPACITY);
+        let connection_id = ws_manager.add_connection(tx);
+        ws_manager.unsubscribe(connection_id, &[], &["cli-io".to_string()]);
+        ws_manager.subscribe(connection_id, std::slice::from_ref(&session_id), &[], None);
+        let now = Instant::now();
+        Self {
+            backend,
+            session_id,
+            policy,
+            events: rx,
+            _connection: ConnectionGuard::new(ws_manager.clone(), connection_id),
+            claim: None,
+            sent_at: now,
+            deadline: now + timeout,
+            liveness: tokio::time::interval_at(now + LIVENESS_INTERVAL, LIVENESS_INTERVAL),
+            session_start: Duration::ZERO,
+            first_output: None,
+            text: String::new(),
+            tool_calls: Vec::new(),
+            confirmations: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Holds `claim` until the turn ends.
+    pub fn with_claim(mut self, claim: SessionClaim) -> Self {
+        self.claim = Some(claim);
+        self
+    }
+
+    /// Records how long starting the session took, and that the prompt
+    /// goes out now.
+    pub fn sent(&mut self, session_start: Duration) {
+        self.session_start = session_start;
+        self.sent_at = Instant::now();
+    }
+
+    /// The next thing that happened, or `None` after `Done`.
+    pub async fn next(&mut self) -> Option<TurnEvent> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let message = tokio::select! {
+                message = self.events.recv() => message,
+                () = tokio::time::sleep_until(self.deadline) => {
+                    let error = "Timed out waiting for the answer".to_string();
+                    return Some(self.finish(TurnOutcome::TimedOut, Some(error)));
+                }
+                _ = self.liveness.tick() => {
+                    if !self.session_alive() {
+                        let error = "The CLI session ended before answering".to_string();
+                        return Some(self.finish(TurnOutcome::Failed, Some(error)));
+                    }
+                    continue;
+                }
+            };
+            let Some(message) = message else {
+                let error = "Stopped receiving the session's events".to_string();
+                return Some(self.finish(TurnOutcome::Failed, Some(error)));
+            };
+            if let Some(event) = self.handle(&message).await {
+                return Some(event);
+            }
+        }
+    }
+
+    fn session_alive(&self) -> bool {
+        self.backend.get_process_statuses().is_ok_and(|statuses| {
+            statuses
+                .iter()
+                .any(|status| status.conversation_id == self.session_id && status.is_alive)
+        })
+    }
+
+    async fn handle(&mut self, message: &str) -> Option<TurnEvent> {
+        #[derive(Deserialize)]
+        struct Envelope {
+            event: String,
+            payload: serde_json::Value,
+        }
+
+        let envelope: Envelope = serde_json::from_str(message).ok()?;
+        let (kind, session_id) = split_session_event(&envelope.event)?;
+        if session_id != self.session_id {
+            return None;
+        }
+        let payload = envelope.payload;
+        match kind {
+            "gemini-output" => {
+                let text: String = serde_json::from_value(payload).ok()?;
+                self.first_output
+                    .get_or_insert_with(|| self.sent_at.elapsed());
+                self.text.push_str(&text);
+                Some(TurnEvent::Text(text))
+            }
+            "gemini-thought" => serde_json::from_value(payload).ok().map(TurnEvent::Thought),
+            "gemini-tool-call" => {
+                let call: ToolCallEvent = serde_json::from_value(payload).ok()?;
+                let record = ToolCallRecord {
+                    id: call.id,
+                    label: call.label,
+                    icon: call.icon,
+                    status: call.status,
+                    locations: call.locations.into_iter().map(|l| l.path).collect(),
+                    content: None,
+                };
+                self.tool_calls.push(record.clone());
+                Some(TurnEvent::ToolCall(record))
+            }
+            "gemini-tool-call-update" => {
+                let update: ToolCallUpdate = serde_json::from_value(payload).ok()?;
+                let record = self
+                    .tool_calls
+                    .iter_mut()
+                    .find(|record| record.id == update.tool_call_id)?;
+                record.status = update.status;
+                if update.content.is_some() {
+                    record.content = update.content;
+                }
+                Some(TurnEvent::ToolCall(record.clone()))
+            }
+            "gemini-tool-call-confirmation" => {
+                let request: ToolCallConfirmationRequest = serde_json::from_value(payload).ok()?;
+                let confirmation = request.confirmation;
+                let outcome = self.policy.decide(&confirmation.confirmation_type);
+                println!(
+                    "🤖 Headless turn answering {} confirmation in {} with {outcome}",
+                    confirmation.confirmation_type, self.session_id
+                );
+                let _ = self
+                    .backend
+                    .handle_tool_confirmation(
+                        self.session_id.clone(),
+                        request.request_id,
+                        request.request_id.to_string(),
+                        outcome.to_string(),
+                    )
+                    .await;
+                let record = ConfirmationRecord {
+                    request_id: request.request_id,
+                    label: request.label,
+                    confirmation_type: confirmation.confirmation_type,
+                    command: confirmation.command,
+                    outcome: outcome.to_string(),
+                };
+                self.confirmations.push(record.clone());
+                Some(TurnEvent::Confirmation(record))
+            }
+            "gemini-turn-finished" => Some(self.finish(TurnOutcome::Finished, None)),
+            "gemini-error" => {
+                let error = match payload {
+                    serde_json::Value::String(error) => error,
+                    other => other.to_string(),
+                };
+                Some(self.finish(TurnOutcome::Failed, Some(error)))
+            }
+            _ => None,
+        }
+    }
+
+    fn finish(&mut self, outcome: TurnOutcome, error: Option<String>) -> TurnEvent {
+        self.done = true;
+        // Frees, and maybe stops, the session as soon as the turn is over.
+        self.claim = None;
+        TurnEvent::Done(TurnSummary {
+            session_id: self.session_id.clone(),
+            outcome,
+            text: std::mem::take(&mut self.text),
+            tool_calls: std::mem::take(&mut self.tool_calls),
+            confirmations: std::mem::take(&mut self.confirmations),
+            error,
+            timing: Timing {
+                session_start_ms: millis(self.session_start),
+                first_output_ms: self.first_output.map(millis),
+                total_ms: millis(self.session_start + self.sent_at.elapsed()),
+            },
+        })
+    }
+
+    /// The turn's events as a stream that ends after `Done`.
+    pub fn into_stream(self) -> impl Stream<Item = TurnEvent> + Send {
+        futures::stream::unfold(self, |mut turn| async move {
+            let event = turn.next().await?;
+            Some((event, turn))
+        })
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Whether the client asked for Server-Sent Events.
+pub struct AcceptsEventStream(bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AcceptsEventStream {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let accepts = request
+            .headers()
+            .get("Accept")
+            .any(|accept| accept.contains("text/event-stream"));
+        Outcome::Success(AcceptsEventStream(accepts))
+    }
+}
+
+type BoxStream<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+
+pub enum PromptResponse {
+    Text(TextStream<BoxStream<String>>),
+    Events(EventStream<BoxStream<Event>>),
+}
+
+impl<'r> Responder<'r, 'r> for PromptResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        match self {
+            PromptResponse::Text(stream) => stream.respond_to(request),
+            PromptResponse::Events(stream) => stream.respond_to(request),
+        }
+    }
+}
+
+fn text_chunk(event: TurnEvent) -> Option<String> {
+    match event {
+        TurnEvent::Text(text) => Some(text),
+        TurnEvent::Done(summary) => {
+            let separator = if summary.text.is_empty() || summary.text.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            let summary = serde_json::to_string(&summary).unwrap_or_default();
+            Some(format!("{separator}{summary}\n"))
+        }
+        _ => None,
+    }
+}
+
+fn sse_event(event: TurnEvent) -> Event {
+    match event {
+        TurnEvent::Text(text) => Event::json(&text).event("text"),
+        TurnEvent::Thought(thought) => Event::json(&thought).event("thought"),
+        TurnEvent::ToolCall(record) => Event::json(&record).event("tool_call"),
+        TurnEvent::Confirmation(record) => Event::json(&record).event("confirmation"),
+        TurnEvent::Done(summary) => Event::json(&summary).event("done"),
+    }
+}
+
+/// Resolves attachments against the working directory, rejecting any that
+/// aren't files.
+fn resolve_attachments(working_directory: &Path, attachments: &[String]) -> ApiResult<Vec<String>> {
+    attachments
+        .iter()
+        .map(|attachment| {
+            let path = working_directory.join(attachment);
+            if path.is_file() {
+                Ok(path.display().to_string())
+            } else {
+                Err(
+                    BackendError::InvalidRequest(format!("attachment is not a file: {attachment}"))
+                        .into(),
+                )
+            }
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/prompt", data = "<request>")]
+pub async fn prompt(
+    workspace: Workspace,
+    _writable: Writable,
+    _limited: RateLimited,
+    accepts_sse: AcceptsEventStream,
+    request: Json<PromptRequest>,
+    busy: &State<Arc<BusySessions>>,
+) -> ApiResult<PromptResponse> {
+    let request = request.into_inner();
+    if request.tool_policy == ToolPolicy::AllowAll && !workspace.is_admin() {
+        return Err(ApiError::new(
+            Status::Forbidden,
+            "forbidden",
+            "Only the admin can allow shell commands",
+        ));
+    }
+    if request.prompt.trim().is_empty() {
+        return Err(BackendError::InvalidRequest("prompt is empty".to_string()).into());
+    }
+    let working_directory = Path::new(&request.working_directory);
+    if !working_directory.is_dir() {
+        return Err(BackendError::InvalidRequest(format!(
+            "working_directory is not a directory: {}",
+            request.working_directory
+        ))
+        .into());
+    }
+    let attachments = resolve_attachments(working_directory, &request.attachments)?;
+
+    let session_id = request
+        .session_id
+        .clone()
+        .unwrap_or_else(|| format!("prompt-{}", uuid::Uuid::new_v4()));
+    let mut claim = busy.claim(&workspace.user, &session_id).ok_or_else(|| {
+        ApiError::new(
+            Status::Conflict,
+            "session_busy",
+            format!("Session {session_id} is already answering a prompt"),
+        )
+    })?;
+
+    let backend = workspace.backend;
+    let was_running = backend
+        .get_process_statuses()?
+        .iter()
+        .any(|status| status.conversation_id == session_id && status.is_alive);
+    if !was_running && !request.keep_session {
+        claim.kill_on_release(backend.clone());
+    }
+
+    let started = Instant::now();
+    backend
+        .initialize_session(
+            session_id.clone(),
+            request.working_directory.clone(),
+            request.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        )
+        .await?;
+    let session_start = started.elapsed();
+
+    let timeout = Duration::from_secs(request.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let mut turn = Turn::subscribe(
+        backend.clone(),
+        &workspace.ws_manager,
+        session_id.clone(),
+        request.tool_policy,
+        timeout,
+    )
+    .with_claim(claim);
+    turn.sent(session_start);
+    backend
+        .send_message_with_attachments(session_id, request.prompt, String::new(), attachments)
+        .await?;
+
+    let format = request.format.unwrap_or(if accepts_sse.0 {
+        StreamFormat::Sse
+    } else {
+        StreamFormat::Text
+    });
+    let events = turn.into_stream();
+    Ok(match format {
+        StreamFormat::Text => PromptResponse::Text(TextStream::from(
+            events
+                .filter_map(|event| async move { text_chunk(event) })
+                .boxed(),
+        )),
+        StreamFormat::Sse => {
+            PromptResponse::Events(EventStream::from(events.map(sse_event).boxed()))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthManager;
+    use crate::config::{Cli, ServerConfig};
+    use backend::{ToolCallConfirmation, ToolCallLocation};
+    use clap::Parser;
+    use rocket::http::Header;
+    use rocket::local::asynchronous::Client;
+    use serde_json::json;
+
+    async fn error_code(response: rocket::local::asynchronous::LocalResponse<'_>) -> String {
+        response
+            .into_json::<backend::ErrorBody>()
+            .await
+            .unwrap()
+            .code
+    }
+
+    fn backend(ws_manager: &WebSocketManager) -> Arc<Backend> {
+        Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+            ws_manager.clone(),
+        )))
+    }
+
+    async fn collect(mut turn: Turn) -> (Vec<TurnEvent>, TurnSummary) {
+        let mut events = Vec::new();
+        while let Some(event) = turn.next().await {
+            if let TurnEvent::Done(summary) = event {
+                assert!(turn.next().await.is_none());
+                return (events, summary);
+            }
+            events.push(event);
+        }
+        panic!("turn ended without a summary");
+    }
+
+    #[test]
+    fn test_tool_policy() {
+        assert_eq!(ToolPolicy::default().decide("info"), "reject");
+        assert_eq!(ToolPolicy::AllowReadOnly.decide("info"), "allow");
+        assert_eq!(ToolPolicy::AllowReadOnly.decide("edit"), "reject");
+        assert_eq!(ToolPolicy::AllowEdits.decide("edit"), "allow");
+        assert_eq!(ToolPolicy::AllowEdits.decide("execute"), "reject");
+        assert_eq!(ToolPolicy::AllowAll.decide("mcp"), "allow");
+        let policy: ToolPolicy = serde_json::from_value(json!("allow-read-only")).unwrap();
+        assert_eq!(policy, ToolPolicy::AllowReadOnly);
+    }
+
+    #[tokio::test]
+    async fn test_turn_collects_answer_and_confirms_by_policy() {
+        let ws_manager = WebSocketManager::new();
+        let turn = Turn::subscribe(
+            backend(&ws_manager),
+            &ws_manager,
+            "s1".to_string(),
+            ToolPolicy::AllowEdits,
+            Duration::from_secs(10),
+        );
+
+        ws_manager.publish("gemini-output-s1", "Hel").unwrap();
+        ws_manager.publish("gemini-output-s2", "elsewhere").unwrap();
+        let call = ToolCallEvent {
+            id: 1001,
+            name: "Read".to_string(),
+            icon: "file".to_string(),
+            label: "Read".to_string(),
+            locations: vec![ToolCallLocation {
+                path: "src/main.rs".to_string(),
+            }],
+            status: "pending".to_string(),
+        };
+        ws_manager.publish("gemini-tool-call-s1", call).unwrap();
+        let update = ToolCallUpdate {
+            tool_call_id: 1001,
+            status: "completed".to_string(),
+            content: None,
+        };
+        ws_manager
+            .publish("gemini-tool-call-update-s1", update)
+            .unwrap();
+        for (request_id, confirmation_type) in [(7, "edit"), (8, "execute")] {
+            let request = ToolCallConfirmationRequest {
+                request_id,
+                session_id: "s1".to_string(),
+                label: "tool".to_string(),
+                icon: "tool".to_string(),
+                content: None,
+                confirmation: ToolCallConfirmation {
+                    confirmation_type: confirmation_type.to_string(),
+                    root_command: None,
+                    command: None,
+                },
+                locations: Vec::new(),
+            };
+            ws_manager
+                .publish("gemini-tool-call-confirmation-s1", request)
+                .unwrap();
+        }
+        ws_manager.publish("gemini-output-s1", "lo").unwrap();
+        ws_manager.publish("gemini-turn-finished-s1", true).unwrap();
+
+        let (events, summary) = collect(turn).await;
+        assert!(matches!(&events[0], TurnEvent::Text(text) if text == "Hel"));
+        assert_eq!(summary.outcome, TurnOutcome::Finished);
+        assert_eq!(summary.text, "Hello");
+        assert_eq!(summary.tool_calls.len(), 1);
+        assert_eq!(summary.tool_calls[0].status, "completed");
+        assert_eq!(summary.tool_calls[0].locations, ["src/main.rs"]);
+        let outcomes: Vec<_> = summary
+            .confirmations
+            .iter()
+            .map(|c| (c.request_id, c.outcome.as_str()))
+            .collect();
+        assert_eq!(outcomes, [(7, "allow"), (8, "reject")]);
+        assert!(summary.timing.first_output_ms.is_some());
+        // The turn's connection goes away with it.
+        assert_eq!(ws_manager.stats().connections, 0);
+    }
+
+    #[tokio::test]
+    async fn test_turn_ends_on_error_or_timeout() {
+        let ws_manager = WebSocketManager::new();
+        let subscribe = |timeout| {
+            Turn::subscribe(
+                backend(&ws_manager),
+                &ws_manager,
+                "s1".to_string(),
+                ToolPolicy::DenyAll,
+                timeout,
+            )
+        };
+
+        let turn = subscribe(Duration::from_secs(10));
+        ws_manager
+            .publish("gemini-error-s1", "quota exceeded")
+            .unwrap();
+        let (_, summary) = collect(turn).await;
+        assert_eq!(summary.outcome, TurnOutcome::Failed);
+        assert_eq!(summary.error.as_deref(), Some("quota exceeded"));
+
+        let (_, summary) = collect(subscribe(Duration::from_millis(20))).await;
+        assert_eq!(summary.outcome, TurnOutcome::TimedOut);
+    }
+
+    #[test]
+    fn test_text_stream_ends_with_summary_line() {
+        let summary = TurnSummary {
+            session_id: "s1".to_string(),
+            outcome: TurnOutcome::Finished,
+            text: "Hello".to_string(),
+            tool_calls: Vec::new(),
+            confirmations: Vec::new(),
+            error: None,
+            timing: Timing {
+                session_start_ms: 0,
+                first_output_ms: Some(5),
+                total_ms: 9,
+            },
+        };
+        let chunk = text_chunk(TurnEvent::Done(summary)).unwrap();
+        let line = chunk.strip_prefix('\n').unwrap().trim_end();
+        let parsed: TurnSummary = serde_json::from_str(line).unwrap();
+        assert_eq!(parsed.text, "Hello");
+        assert_eq!(text_chunk(TurnEvent::Thought("hm".to_string())), None);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_validates_request_and_claims_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ServerConfig::resolve(Cli::parse_from([
+            "gemini-desktop-web",
+            "--data-dir",
+            dir.path().to_str().unwrap(),
+        ]))
+        .unwrap();
+        let auth = AuthManager::load_or_init(dir.path()).unwrap();
+        let token = Header::new(
+            "Authorization",
+            format!("Bearer {}", auth.rotate_token().unwrap()),
+        );
+        let ws_manager = WebSocketManager::new();
+        let busy = Arc::new(BusySessions::default());
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(Arc::new(auth))
+            .manage(backend(&ws_manager))
+            .manage(ws_manager)
+            .manage(busy.clone())
+            .register("/api", rocket::catchers![crate::error::default_catcher])
+            .mount("/api", rocket::routes![prompt]);
+        let client = Client::tracked(rocket).await.unwrap();
+        let post = |body: serde_json::Value| {
+            client
+                .post("/api/prompt")
+                .header(token.clone())
+                .body(body.to_string())
+        };
+        let response = client.post("/api/prompt").body("{}").dispatch().await;
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let cwd = dir.path().to_str().unwrap();
+        let response = post(json!({"prompt": "hi", "working_directory": dir.path().join("nope")}))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        assert_eq!(error_code(response).await, "invalid_request_body");
+
+        let response = post(json!({
+            "prompt": "hi",
+            "working_directory": cwd,
+            "attachments": ["missing.txt"],
+        }))
+        .dispatch()
+        .await;
+        assert_eq!(error_code(response).await, "invalid_request_body");
+
+        let response = post(json!({
+            "prompt": "hi",
+            "working_directory": cwd,
+            "tool_policy": "yolo",
+        }))
+        .dispatch()
+        .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        let _claim = busy.claim(crate::users::ADMIN, "taken").unwrap();
+        assert!(busy.claim("alice", "taken").is_some());
+        let response = post(json!({
+            "prompt": "hi",
+            "working_directory": cwd,
+            "session_id": "taken",
+        }))
+        .dispatch()
+        .await;
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(error_code(response).await, "session_busy");
+    }
+}
@@ -0,0 +1,334 @@
+use rocket::catch;
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Requests allowed per client in each window, keyed by route handler name.
+const LIMITS: &[(&str, Limit)] = &[
+    ("search_chats", Limit::per_minute(30)),
+    ("generate_conversation_title", Limit::per_minute(10)),
+    ("start_session", Limit::per_minute(10)),
+    ("prompt", Limit::per_minute(10)),
+    ("chat_completions", Limit::per_minute(30)),
+    ("login", Limit::per_minute(10)),
+];
+
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+/// Failures a client may make before [`RateLimiter::record_failure`] starts
+/// locking it out, doubling the lockout with each further failure.
+const FREE_FAILURES: u32 = 3;
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limit {
+    capacity: u32,
+    window: Duration,
+}
+
+impl Limit {
+    pub const fn per_minute(capacity: u32) -> Self {
+        Self {
+            capacity,
+            window: Duration::from_secs(60),
+        }
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        f64::from(self.capacity) / self.window.as_secs_f64()
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    failures: u32,
+    last_failure: Option<Instant>,
+    blocked_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn full(limit: &Limit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.capacity),
+            updated: now,
+            failures: 0,
+            last_failure: None,
+            blocked_until: None,
+        }
+    }
+
+    fn blocked(&self, now: Instant) -> Option<Duration> {
+        self.blocked_until
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+}
+
+/// Token-bucket limiter shared by all requests. Each (client IP, route)
+/// pair gets its own bucket that refills continuously over the window, and
+/// is locked out for a while after repeated failures.
+/// Clones share the same buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: Arc<HashMap<&'static str, Limit>>,
+    buckets: Arc<Mutex<HashMap<(IpAddr, &'static str), Bucket>>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(LIMITS.iter().copied())
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limits: impl IntoIterator<Item = (&'static str, Limit)>) -> Self {
+        Self {
+            limits: Arc::new(limits.into_iter().collect()),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Takes one token, or returns how long until the next one is available.
+    pub(crate) fn check(&self, client: IpAddr, route: &str) -> Result<(), Duration> {
+        let Some((&key, limit)) = self.limits.get_key_value(route) else {
+            return Ok(());
+        };
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        if buckets.len() >= MAX_TRACKED_BUCKETS {
+            // Forget clients whose buckets have refilled completely.
+            buckets.retain(|(_, route), bucket| {
+                bucket.blocked(now).is_some()
+                    || self
+                        .limits
+                        .get(route)
+                        .is_some_and(|limit| now.duration_since(bucket.updated) < limit.window)
+            });
+        }
+
+        let bucket = buckets
+            .entry((client, key))
+            .or_insert_with(|| Bucket::full(limit, now));
+        if let Some(wait) = bucket.blocked(now) {
+            return Err(wait);
+        }
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens =
+            (bucket.tokens + elapsed * limit.refill_per_sec()).min(f64::from(limit.capacity));
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / limit.refill_per_sec();
+            Err(Duration::from_secs_f64(wait))
+        }
+    }
+
+    /// Counts a failed attempt, such as a wrong password, against the
+    /// client. Past [`FREE_FAILURES`] the client is locked out of the route,
+    /// for twice as long after each further failure. Failures are forgotten
+    /// after [`MAX_BACKOFF`] without one.
+    pub(crate) fn record_failure(&self, client: IpAddr, route: &str) {
+        let Some((&key, limit)) = self.limits.get_key_value(route) else {
+            return;
+        };
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        let bucket = buckets
+            .entry((client, key))
+            .or_insert_with(|| Bucket::full(limit, now));
+        if bucket
+            .last_failure
+            .is_some_and(|last| now.duration_since(last) >= MAX_BACKOFF)
+        {
+            bucket.failures = 0;
+        }
+        bucket.failures = bucket.failures.saturating_add(1);
+        bucket.last_failure = Some(now);
+        if let Some(excess) = bucket.failures.checked_sub(FREE_FAILURES + 1) {
+            let backoff = BASE_BACKOFF
+                .saturating_mul(1 << excess.min(16))
+                .min(MAX_BACKOFF);
+            bucket.blocked_until = Some(now + backoff);
+        }
+    }
+
+    /// Clears the client's failures on the route after a successful attempt.
+    pub(crate) fn record_success(&self, client: IpAddr, route: &str) {
+        let Some((&key, _)) = self.limits.get_key_value(route) else {
+            return;
+        };
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return;
+        };
+        if let Some(bucket) = buckets.get_mut(&(client, key)) {
+            bucket.failures = 0;
+            bucket.last_failure = None;
+            bucket.blocked_until = None;
+        }
+    }
+}
+
+/// Seconds a throttled client should wait, stashed for the 429 catcher.
+#[derive(Debug, Clone, Copy, Default)]
+struct RetryAfter(u64);
+
+/// Request guard that charges the current route's bucket for the calling
+/// client and fails with 429 Too Many Requests when it is empty.
+pub struct RateLimited;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimited {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(limiter) = request.rocket().state::<RateLimiter>() else {
+            return Outcome::Success(RateLimited);
+        };
+        let Some(route) = request.route().and_then(|route| route.name.as_deref()) else {
+            return Outcome::Success(RateLimited);
+        };
+        let client = request
+            .client_ip()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        match limiter.check(client, route) {
+            Ok(()) => Outcome::Success(RateLimited),
+            Err(wait) => {
+                let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                request.local_cache(|| RetryAfter(secs.max(1)));
+                println!("⏳ Rate limited {client} on {route}");
+                Outcome::Error((Status::TooManyRequests, ()))
+            }
+        }
+    }
+}
+
+pub struct TooManyRequests {
+    retry_after: u64,
+}
+
+impl<'r> Responder<'r, 'static> for TooManyRequests {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::json!({
+            "code": "rate_limited",
+            "message": format!("Too many requests; retry in {}s", self.retry_after),
+            "status": 429,
+            "retry_after": self.retry_after,
+        })
+        .to_string();
+        Response::build()
+            .status(Status::TooManyRequests)
+            .header(ContentType::JSON)
+            .header(Header::new("Retry-After", self.retry_after.to_string()))
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+#[catch(429)]
+pub fn too_many_requests(request: &Request<'_>) -> TooManyRequests {
+    TooManyRequests {
+        retry_after: request.local_cache(RetryAfter::default).0.max(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::{catchers, get, routes};
+
+    #[get("/limited")]
+    fn limited(_limit: RateLimited) -> &'static str {
+        "ok"
+    }
+
+    #[get("/unlimited")]
+    fn unlimited(_limit: RateLimited) -> &'static str {
+        "ok"
+    }
+
+    #[test]
+    fn test_bucket_refills() {
+        let limiter = RateLimiter::new([("route", Limit::per_minute(2))]);
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        assert!(limiter.check(client, "route").is_ok());
+        assert!(limiter.check(client, "route").is_ok());
+        let wait = limiter.check(client, "route").unwrap_err();
+        assert!(wait <= Duration::from_secs(30));
+
+        // Other clients and routes are unaffected.
+        assert!(
+            limiter
+                .check(IpAddr::V4(Ipv4Addr::BROADCAST), "route")
+                .is_ok()
+        );
+        assert!(limiter.check(client, "other").is_ok());
+    }
+
+    #[test]
+    fn test_failures_back_off() {
+        let limiter = RateLimiter::new([("route", Limit::per_minute(100))]);
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        for _ in 0..FREE_FAILURES {
+            limiter.record_failure(client, "route");
+            assert!(limiter.check(client, "route").is_ok());
+        }
+
+        limiter.record_failure(client, "route");
+        let first = limiter.check(client, "route").unwrap_err();
+        assert!(first <= BASE_BACKOFF);
+        limiter.record_failure(client, "route");
+        let second = limiter.check(client, "route").unwrap_err();
+        assert!(second > first && second <= BASE_BACKOFF * 2);
+
+        // Other clients are unaffected, and a success lifts the lockout.
+        assert!(
+            limiter
+                .check(IpAddr::V4(Ipv4Addr::BROADCAST), "route")
+                .is_ok()
+        );
+        limiter.record_success(client, "route");
+        assert!(limiter.check(client, "route").is_ok());
+    }
+
+    #[test]
+    fn test_throttled_requests_get_retry_after() {
+        let rocket = rocket::build()
+            .manage(RateLimiter::new([("limited", Limit::per_minute(1))]))
+            .mount("/", routes![limited, unlimited])
+            .register("/", catchers![too_many_requests]);
+        let client = Client::tracked(rocket).unwrap();
+
+        assert_eq!(client.get("/limited").dispatch().status(), Status::Ok);
+        let response = client.get("/limited").dispatch();
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let retry_after: u64 = response
+            .headers()
+            .get_one("Retry-After")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after));
+
+        for _ in 0..5 {
+            assert_eq!(client.get("/unlimited").dispatch().status(), Status::Ok);
+        }
+    }
+}
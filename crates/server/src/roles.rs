@@ -0,0 +1,594 @@
+//! Per-session roles, for sharing a live session with others.
+//!
+//! The account whose workspace runs a session owns it. The owner invites
+//! guests with `POST /api/sessions/<session_id>/members`, which returns a
+//! token the guest signs in with. Controllers can send messages and answer
+//! tool confirmations; observers can only watch. Only the owner can stop
+//! the session. A guest reaches nothing but that one session: its events
+//! over `/api/ws` or `/api/events`, and the [`SESSION_OPERATIONS`].
+//! Invitations are kept in memory, like the sessions they are for.
+
+use crate::auth::{Account, AuthManager, Caller};
+use crate::error::{ApiError, ApiResult};
+use crate::users::{Workspace, is_valid_name};
+use crate::ws::Watcher;
+use backend::ErrorBody;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{State, delete, get, post};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Controller,
+    Observer,
+}
+
+impl Role {
+    /// Whether this role may run one of the [`SESSION_OPERATIONS`].
+    pub fn allows(self, operation: &str) -> bool {
+        match operation {
+            "kill_process" => self == Role::Owner,
+            _ => self != Role::Observer,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Owner => "owner",
+            Role::Controller => "controller",
+            Role::Observer => "observer",
+        })
+    }
+}
+
+/// Operations on a single session that guests may be allowed to run, with
+/// the parameter naming the session.
+pub const SESSION_OPERATIONS: &[(&str, &str)] = &[
+    ("send_message", "session_id"),
+    ("send_tool_call_confirmation_response", "session_id"),
+    ("kill_process", "conversation_id"),
+];
+
+/// A guest's access: one session and its role there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub session_id: String,
+    pub role: Role,
+}
+
+/// Who is behind a request or connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Viewer {
+    pub name: String,
+    /// Set for guests; accounts own every session in their workspace.
+    pub grant: Option<Grant>,
+}
+
+impl Viewer {
+    pub fn role(&self, session_id: &str) -> Option<Role> {
+        match &self.grant {
+            None => Some(Role::Owner),
+            Some(grant) => (grant.session_id == session_id).then_some(grant.role),
+        }
+    }
+
+    /// Checks that this viewer may run `operation` with `params`.
+    pub fn authorize(&self, operation: &str, params: &Value) -> Result<(), ErrorBody> {
+        if self.grant.is_none() {
+            return Ok(());
+        }
+        let role = SESSION_OPERATIONS
+            .iter()
+            .find(|(name, _)| *name == operation)
+            .and_then(|(_, param)| params.get(param)?.as_str())
+            .and_then(|session_id| self.role(session_id));
+        match role {
+            Some(role) if role.allows(operation) => Ok(()),
+            Some(role) => Err(forbidden(format!("Session {role}s can't do this"))),
+            None => Err(forbidden(
+                "Guests can only act on the session they were invited to",
+            )),
+        }
+    }
+
+    /// Checks that this viewer may follow these sessions' events.
+    pub fn authorize_watch(&self, session_ids: &[String]) -> Result<(), ErrorBody> {
+        match session_ids.iter().find(|id| self.role(id).is_none()) {
+            Some(_) => Err(forbidden(
+                "Guests can only watch the session they were invited to",
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn forbidden(message: impl Into<String>) -> ErrorBody {
+    ErrorBody::new("forbidden", message, Status::Forbidden.code)
+}
+
+/// Someone invited to a session. Signs in with the token handed out on
+/// invitation; `auth` holds it and the guest's login sessions.
+pub struct Guest {
+    pub name: String,
+    pub grant: Grant,
+    pub auth: AuthManager,
+    pub workspace: Workspace,
+}
+
+impl Guest {
+    pub fn viewer(&self) -> Viewer {
+        Viewer {
+            name: self.name.clone(),
+            grant: Some(self.grant.clone()),
+        }
+    }
+
+    fn invited_to(&self, workspace: &Workspace, session_id: &str) -> bool {
+        Arc::ptr_eq(&self.workspace.backend, &workspace.backend)
+            && self.grant.session_id == session_id
+    }
+}
+
+/// Everyone invited to a session.
+#[derive(Default)]
+pub struct Guests(RwLock<Vec<Arc<Guest>>>);
+
+impl Guests {
+    fn read(&self) -> RwLockReadGuard<'_, Vec<Arc<Guest>>> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Vec<Arc<Guest>>> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn all(&self) -> Vec<Arc<Guest>> {
+        self.read().clone()
+    }
+
+    pub fn by_token(&self, token: &str) -> Option<Arc<Guest>> {
+        self.read()
+            .iter()
+            .find(|guest| guest.auth.verify_token(token))
+            .cloned()
+    }
+
+    pub fn by_session(&self, session: &str) -> Option<Arc<Guest>> {
+        self.read()
+            .iter()
+            .find(|guest| guest.auth.validate_session(session))
+            .cloned()
+    }
+
+    fn of_session(&self, workspace: &Workspace, session_id: &str) -> Vec<Arc<Guest>> {
+        self.read()
+            .iter()
+            .filter(|guest| guest.invited_to(workspace, session_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Invites `name` to a live session of `workspace`, returning its token.
+    pub fn invite(
+        &self,
+        workspace: &Workspace,
+        session_id: &str,
+        name: &str,
+        role: Role,
+    ) -> ApiResult<String> {
+        if role == Role::Owner {
+            return Err(ApiError::new(
+                Status::UnprocessableEntity,
+                "invalid_role",
+                "Guests can be controllers or observers",
+            ));
+        }
+        if !is_valid_name(name) || name == workspace.user {
+            return Err(ApiError::new(
+                Status::UnprocessableEntity,
+                "invalid_member_name",
+                "Names are 1-32 lowercase letters, digits, '-' or '_', other than the owner's",
+            ));
+        }
+        if !workspace.is_live(session_id) {
+            return Err(session_not_found(session_id));
+        }
+
+        let mut guests = self.write();
+        if guests
+            .iter()
+            .any(|guest| guest.invited_to(workspace, session_id) && guest.name == name)
+        {
+            return Err(ApiError::new(
+                Status::Conflict,
+                "member_exists",
+                format!("{name} is already a member of this session"),
+            ));
+        }
+        let (auth, token) = AuthManager::in_memory();
+        guests.push(Arc::new(Guest {
+            name: name.to_string(),
+            grant: Grant {
+                session_id: session_id.to_string(),
+                role,
+            },
+            auth,
+            workspace: workspace.clone(),
+        }));
+        Ok(token)
+    }
+
+    /// Revokes an invitation and closes the guest's connections.
+    pub fn revoke(&self, workspace: &Workspace, session_id: &str, name: &str) -> ApiResult<()> {
+        let mut guests = self.write();
+        let Some(pos) = guests
+            .iter()
+            .position(|guest| guest.invited_to(workspace, session_id) && guest.name == name)
+        else {
+            return Err(ApiError::new(
+                Status::NotFound,
+                "member_not_found",
+                format!("{name} is not a member of this session"),
+            ));
+        };
+        let guest = guests.remove(pos);
+        drop(guests);
+        workspace.ws_manager.disconnect(&guest.viewer());
+        Ok(())
+    }
+}
+
+fn session_not_found(session_id: &str) -> ApiError {
+    ApiError::new(
+        Status::NotFound,
+        "session_not_found",
+        format!("Session not found: {session_id}"),
+    )
+}
+
+/// Request guard for anyone taking part in a workspace's sessions: an
+/// account in its own workspace, or a guest in the workspace of the session
+/// it was invited to.
+pub struct Participant {
+    pub viewer: Viewer,
+    pub admin: bool,
+    pub workspace: Workspace,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Participant {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let account = match request.guard::<Caller>().await {
+            Outcome::Success(Caller(account)) => account,
+            Outcome::Error(error) => return Outcome::Error(error),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        if let Account::Guest(guest) = &account {
+            return Outcome::Success(Participant {
+                viewer: guest.viewer(),
+                admin: false,
+                workspace: guest.workspace.clone(),
+            });
+        }
+        request
+            .guard::<Workspace>()
+            .await
+            .map(|workspace| Participant {
+                viewer: Viewer {
+                    name: account.name().to_string(),
+                    grant: None,
+                },
+                admin: account.is_admin(),
+                workspace,
+            })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Member {
+    pub name: String,
+    pub role: Role,
+    /// Whether they are watching the session right now.
+    pub watching: bool,
+}
+
+#[derive(Deserialize)]
+pub struct InviteRequest {
+    name: String,
+    role: Role,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Invitation {
+    pub name: String,
+    pub role: Role,
+    pub token: String,
+}
+
+/// The owner and guests of a live session.
+#[get("/sessions/<session_id>/members")]
+pub fn members(
+    workspace: Workspace,
+    session_id: &str,
+    guests: &State<Guests>,
+) -> ApiResult<Json<Vec<Member>>> {
+    if !workspace.is_live(session_id) {
+        return Err(session_not_found(session_id));
+    }
+    let watchers = workspace.ws_manager.watchers(session_id);
+    let watching = |name: &str, role: Role| {
+        watchers.contains(&Watcher {
+            name: name.to_string(),
+            role,
+        })
+    };
+    let owner = Member {
+        name: workspace.user.clone(),
+        role: Role::Owner,
+        watching: watching(&workspace.user, Role::Owner),
+    };
+    let invited = guests
+        .of_session(&workspace, session_id)
+        .into_iter()
+        .map(|guest| Member {
+            watching: watching(&guest.name, guest.grant.role),
+            name: guest.name.clone(),
+            role: guest.grant.role,
+        });
+    Ok(Json(std::iter::once(owner).chain(invited).collect()))
+}
+
+/// Invites someone to a live session as a controller or observer.
+#[post("/sessions/<session_id>/members", data = "<request>")]
+pub fn invite(
+    workspace: Workspace,
+    session_id: &str,
+    request: Json<InviteRequest>,
+    guests: &State<Guests>,
+) -> ApiResult<Json<Invitation>> {
+    let token = guests.invite(&workspace, session_id, &request.name, request.role)?;
+    println!(
+        "👥 Invited {} to session {session_id} as {}",
+        request.name, request.role
+    );
+    Ok(Json(Invitation {
+        name: request.name.clone(),
+        role: request.role,
+        token,
+    }))
+}
+
+#[delete("/sessions/<session_id>/members/<name>")]
+pub fn revoke(
+    workspace: Workspace,
+    session_id: &str,
+    name: &str,
+    guests: &State<Guests>,
+) -> ApiResult<()> {
+    guests.revoke(&workspace, session_id, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthStatus;
+    use crate::config::{Cli, ServerConfig};
+    use crate::rate_limit::RateLimiter;
+    use crate::users::Users;
+    use crate::ws::{WebSocketManager, WebSocketsEventEmitter};
+    use backend::GeminiBackend;
+    use clap::Parser;
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+    use serde_json::json;
+
+    fn guest(name: &str, role: Role) -> Viewer {
+        Viewer {
+            name: name.to_string(),
+            grant: Some(Grant {
+                session_id: "s1".to_string(),
+                role,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_roles_allow_operations() {
+        let owner = Viewer {
+            name: "admin".to_string(),
+            grant: None,
+        };
+        let controller = guest("carol", Role::Controller);
+        let observer = guest("bob", Role::Observer);
+        let s1 = json!({"session_id": "s1", "conversation_id": "s1"});
+        let s2 = json!({"session_id": "s2"});
+
+        for operation in ["send_message", "kill_process", "list_projects"] {
+            assert!(owner.authorize(operation, &s1).is_ok());
+        }
+        assert!(controller.authorize("send_message", &s1).is_ok());
+        assert!(
+            controller
+                .authorize("send_tool_call_confirmation_response", &s1)
+                .is_ok()
+        );
+        assert!(controller.authorize("kill_process", &s1).is_err());
+        assert!(controller.authorize("send_message", &s2).is_err());
+        assert!(controller.authorize("list_projects", &s1).is_err());
+        assert!(observer.authorize("send_message", &s1).is_err());
+
+        assert!(observer.authorize_watch(&["s1".to_string()]).is_ok());
+        assert!(observer.authorize_watch(&["*".to_string()]).is_err());
+        assert!(owner.authorize_watch(&["*".to_string()]).is_ok());
+    }
+
+    struct Setup {
+        client: Client,
+        admin: Header<'static>,
+        workspace: Workspace,
+        _dir: tempfile::TempDir,
+    }
+
+    fn setup() -> Setup {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ServerConfig::resolve(Cli::parse_from([
+            "gemini-desktop-web",
+            "--data-dir",
+            dir.path().to_str().unwrap(),
+        ]))
+        .unwrap();
+        let auth = AuthManager::load_or_init(dir.path()).unwrap();
+        let admin = Header::new(
+            "Authorization",
+            format!("Bearer {}", auth.rotate_token().unwrap()),
+        );
+        let ws_manager = WebSocketManager::new();
+        let backend = Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+            ws_manager.clone(),
+        )));
+        let workspace = Workspace {
+            user: crate::users::ADMIN.to_string(),
+            data_dir: dir.path().to_path_buf(),
+            backend: backend.clone(),
+            ws_manager: ws_manager.clone(),
+        };
+        let users = Arc::new(Users::load(&config).unwrap());
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(Arc::new(auth))
+            .manage(users)
+            .manage(Guests::default())
+            .manage(RateLimiter::default())
+            .manage(backend)
+            .manage(ws_manager)
+            .register("/api", rocket::catchers![crate::error::default_catcher])
+            .mount("/api", crate::operations::routes())
+            .mount(
+                "/api",
+                rocket::routes![
+                    members,
+                    invite,
+                    revoke,
+                    crate::auth::auth_status,
+                    crate::auth::login,
+                    crate::auth::rotate_token,
+                ],
+            );
+        Setup {
+            client: Client::tracked(rocket).unwrap(),
+            admin,
+            workspace,
+            _dir: dir,
+        }
+    }
+
+    /// Invites a guest without the live session `invite` insists on.
+    fn add_guest(setup: &Setup, name: &str, role: Role) -> Header<'static> {
+        let guests = setup.client.rocket().state::<Guests>().unwrap();
+        let (auth, token) = AuthManager::in_memory();
+        guests.write().push(Arc::new(Guest {
+            name: name.to_string(),
+            grant: guest(name, role).grant.unwrap(),
+            auth,
+            workspace: setup.workspace.clone(),
+        }));
+        Header::new("Authorization", format!("Bearer {token}"))
+    }
+
+    fn error_code(response: rocket::local::blocking::LocalResponse<'_>) -> String {
+        response.into_json::<ErrorBody>().unwrap().code
+    }
+
+    #[test]
+    fn test_guests_are_held_to_their_role() {
+        let setup = setup();
+        let client = &setup.client;
+        let carol = add_guest(&setup, "carol", Role::Controller);
+        let bob = add_guest(&setup, "bob", Role::Observer);
+        let send = |guest: &Header<'static>, session_id: &str| {
+            client
+                .post("/api/send-message")
+                .header(guest.clone())
+                .body(
+                    json!({"session_id": session_id, "message": "hi", "conversation_history": ""})
+                        .to_string(),
+                )
+                .dispatch()
+        };
+
+        // Passing the role check leaves the backend to report the missing session.
+        let response = send(&carol, "s1");
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(error_code(response), "session_not_found");
+        assert_eq!(send(&carol, "s2").status(), Status::Forbidden);
+        assert_eq!(send(&bob, "s1").status(), Status::Forbidden);
+        let response = client
+            .post("/api/kill-process")
+            .header(carol.clone())
+            .body(json!({"conversation_id": "s1"}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        // Nothing else is open to guests.
+        for uri in ["/api/process-statuses", "/api/sessions/s1/members"] {
+            let response = client.get(uri).header(carol.clone()).dispatch();
+            assert_eq!(response.status(), Status::Forbidden, "{uri}");
+        }
+        let response = client
+            .post("/api/auth/rotate-token")
+            .header(carol.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        // Only live sessions take members.
+        let response = client
+            .post("/api/sessions/s1/members")
+            .header(setup.admin.clone())
+            .body(json!({"name": "dave", "role": "observer"}).to_string())
+            .dispatch();
+        assert_eq!(error_code(response), "session_not_found");
+
+        let response = client
+            .delete("/api/sessions/s1/members/bob")
+            .header(setup.admin.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(send(&bob, "s1").status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_guests_log_in_with_their_token() {
+        let setup = setup();
+        let carol = add_guest(&setup, "carol", Role::Controller);
+        let token = carol.value().strip_prefix("Bearer ").unwrap().to_string();
+        let response = setup
+            .client
+            .post("/api/auth/login")
+            .body(json!({"token": token}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let status: serde_json::Value = setup
+            .client
+            .get("/api/auth/status")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(status["authenticated"], true);
+        assert_eq!(status["user"], "carol");
+        assert_eq!(status["session_id"], "s1");
+        assert_eq!(status["role"], "controller");
+        let _: AuthStatus = serde_json::from_value(status).unwrap();
+    }
+}
@@ -0,0 +1,193 @@
+//! Server-Sent Events fallback for networks that break WebSocket upgrades.
+//!
+//! `GET /api/events` streams the same envelopes as `/api/ws`, one per SSE
+//! message, with the sequence number as the message `id`. The subscription
+//! is fixed by the query string: `session` (repeatable, `*` for every
+//! session) and `mute` (repeatable event kinds). Clients change it by
+//! reconnecting; `Last-Event-ID`, or `last_event_id` for a fresh
+//! `EventSource`, replays what they missed from the journal.
+
+use crate::error::{ApiError, ApiResult};
+use crate::roles::Participant;
+use crate::ws::{CONNECTION_QUEUE_CAPACITY, ConnectionGuard, unknown_kind};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::{Shutdown, get};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+/// The `Last-Event-ID` header a reconnecting `EventSource` sends.
+pub struct LastEventId(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = request
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|id| id.trim().parse().ok());
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+/// Turns a queued envelope into an SSE message carrying its sequence.
+fn to_event(message: String) -> Event {
+    #[derive(Deserialize)]
+    struct Envelope {
+        sequence: Option<u64>,
+    }
+
+    let sequence = serde_json::from_str::<Envelope>(&message)
+        .ok()
+        .and_then(|envelope| envelope.sequence);
+    let event = Event::data(message);
+    match sequence {
+        Some(sequence) => event.id(sequence.to_string()),
+        None => event,
+    }
+}
+
+#[get("/events?<session>&<mute>&<last_event_id>")]
+pub fn events(
+    participant: Participant,
+    session: Vec<String>,
+    mute: Vec<String>,
+    last_event_id: Option<u64>,
+    header_id: LastEventId,
+    mut shutdown: Shutdown,
+) -> ApiResult<EventStream![]> {
+    if let Some(kind) = unknown_kind(&mute) {
+        return Err(ApiError::new(
+            Status::UnprocessableEntity,
+            "invalid_request_body",
+            format!("Unknown event kind: {kind}"),
+        ));
+    }
+
+    participant
+        .viewer
+        .authorize_watch(&session)
+        .map_err(ApiError)?;
+
+    let ws_manager = participant.workspace.ws_manager;
+    let (tx, mut rx) = mpsc::channel::<String>(CONNECTION_QUEUE_CAPACITY);
+    let connection_id = ws_manager.add_viewer(tx, participant.viewer);
+    let guard = ConnectionGuard::new(ws_manager.clone(), connection_id);
+    // Mute first so the replay skips muted kinds.
+    ws_manager.unsubscribe(connection_id, &[], &mute);
+    ws_manager.subscribe(connection_id, &session, &[], header_id.0.or(last_event_id));
+    println!("📡 SSE stream opened (ID: {connection_id})");
+
+    Ok(EventStream! {
+        let _guard = guard;
+        loop {
+            let message = tokio::select! {
+                // Drain what's queued before honouring a shutdown.
+                biased;
+                message = rx.recv() => match message {
+                    Some(message) => message,
+                    // Dropped for falling behind; the client reconnects.
+                    None => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            yield to_event(message);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthManager;
+    use crate::ws::{WebSocketManager, WebSocketsEventEmitter};
+    use backend::GeminiBackend;
+    use rocket::http::Header;
+    use rocket::local::asynchronous::Client;
+    use std::sync::Arc;
+
+    async fn client(ws_manager: WebSocketManager) -> (Client, Header<'static>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = AuthManager::load_or_init(dir.path()).unwrap();
+        let token = Header::new(
+            "Authorization",
+            format!("Bearer {}", auth.rotate_token().unwrap()),
+        );
+        let backend = GeminiBackend::new(WebSocketsEventEmitter::new(ws_manager.clone()));
+        let rocket = rocket::build()
+            .manage(Arc::new(auth))
+            .manage(Arc::new(backend))
+            .manage(ws_manager)
+            .mount("/api", rocket::routes![events]);
+        (Client::tracked(rocket).await.unwrap(), token, dir)
+    }
+
+    /// Lines of the messages sent before the server shut the stream down.
+    async fn read_until_shutdown(
+        client: &Client,
+        uri: &str,
+        token: Header<'static>,
+        last: Option<&str>,
+    ) -> String {
+        let mut request = client.get(uri.to_string()).header(token);
+        if let Some(last) = last {
+            request = request.header(Header::new("Last-Event-ID", last.to_string()));
+        }
+        let response = request.dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        client.rocket().shutdown().notify();
+        response.into_string().await.unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn test_stream_filters_and_resumes() {
+        let ws_manager = WebSocketManager::new();
+        let (client, token, _dir) = client(ws_manager.clone()).await;
+
+        ws_manager.publish("gemini-output-s1", "before").unwrap(); // 0
+        ws_manager.publish("gemini-output-s2", "other").unwrap(); // 1
+        ws_manager.publish("cli-io-s1", "muted").unwrap(); // 2
+        ws_manager.publish("gemini-output-s1", "missed").unwrap(); // 3
+
+        let body = read_until_shutdown(
+            &client,
+            "/api/events?session=s1&mute=cli-io",
+            token,
+            Some("0"),
+        )
+        .await;
+        assert!(body.contains("id:3\n"), "{body}");
+        assert!(body.contains(r#""payload":"missed""#));
+        assert!(!body.contains("before"));
+        assert!(!body.contains("other"));
+        assert!(!body.contains("muted"));
+        // The stream's connection is gone once it ends.
+        assert_eq!(ws_manager.stats().connections, 0);
+    }
+
+    #[rocket::async_test]
+    async fn test_unknown_kinds_and_missing_auth_are_rejected() {
+        let (client, token, _dir) = client(WebSocketManager::new()).await;
+        let response = client.get("/api/events").dispatch().await;
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .get("/api/events?mute=nope")
+            .header(token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn test_event_ids_are_sequences() {
+        let message = r#"{"event":"gemini-output-s1","payload":"hi","sequence":3}"#;
+        assert_eq!(to_event(message.to_string()), Event::data(message).id("3"));
+        // Control events such as gaps carry no sequence.
+        let gap = r#"{"event":"event-gap-s1","payload":{}}"#;
+        assert_eq!(to_event(gap.to_string()), Event::data(gap));
+    }
+}
@@ -0,0 +1,790 @@
+//! Multi-user mode (`--multi-user`).
+//!
+//! The account in `<data-dir>/auth.json` is the admin. Every other user has
+//! a directory `<data-dir>/users/<name>/` holding their `auth.json`, which is
+//! also their data root: projects, logs, `servers.json`, themes and
+//! settings. Each user gets a backend and event stream of their own, so
+//! session ids, CLI processes and events never cross between users. The
+//! admin can list and stop everyone's sessions under `/api/admin`.
+
+use crate::auth::{Account, AuthManager, Authenticated};
+use crate::config::{ServerConfig, Writable};
+use crate::error::{ApiError, ApiResult};
+use crate::journal::EventJournal;
+use crate::ws::{WebSocketManager, WebSocketsEventEmitter};
+use backend::{BackendError, EventEmitter, GeminiBackend, ProcessStatus};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{State, delete, get, post};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+/// Name of the account in `<data-dir>/auth.json`.
+pub const ADMIN: &str = "admin";
+
+/// Operations that change state shared by every user, such as the storage
+/// encryption key, or that reach past a user's own data into the host:
+/// terminals, shell commands, MCP server processes, theme files and browsing
+/// its file system. Only the admin may run them.
+pub const ADMIN_OPERATIONS: &[&str] = &[
+    "save_redaction_config",
+    "enable_encryption",
+    "unlock_storage",
+    "lock_storage",
+    "migrate_storage",
+    "list_terminals",
+    "create_terminal",
+    "write_terminal",
+    "resize_terminal",
+    "kill_terminal",
+    "send_terminal_to_chat",
+    "execute_confirmed_command",
+    "add_server",
+    "edit_server",
+    "start_server",
+    "save_theme",
+    "delete_theme",
+    "validate_directory",
+    "is_home_directory",
+    "get_home_directory",
+    "get_parent_directory",
+    "list_directory_contents",
+    "list_volumes",
+];
+
+/// Whether a call answers a confirmation for a shell command with anything
+/// but a refusal. Like [`ADMIN_OPERATIONS`], only the admin may do that.
+pub fn approves_command<E: EventEmitter + 'static>(
+    backend: &GeminiBackend<E>,
+    method: &str,
+    params: &Value,
+) -> bool {
+    if method != "send_tool_call_confirmation_response"
+        || matches!(params["outcome"].as_str(), Some("reject" | "cancel"))
+    {
+        return false;
+    }
+    let session_id = params["session_id"].as_str().unwrap_or_default();
+    params["request_id"]
+        .as_u64()
+        .and_then(|id| u32::try_from(id).ok())
+        .and_then(|id| backend.confirmation_type(session_id, id))
+        .is_some_and(|kind| kind == "execute")
+}
+
+type Backend = GeminiBackend<WebSocketsEventEmitter>;
+
+/// What a request acts on: one user's backend, event stream and data root.
+#[derive(Clone)]
+pub struct Workspace {
+    pub user: String,
+    pub data_dir: PathBuf,
+    pub backend: Arc<Backend>,
+    pub ws_manager: WebSocketManager,
+}
+
+impl Workspace {
+    pub fn is_admin(&self) -> bool {
+        self.user == ADMIN
+    }
+
+    pub fn live_sessions(&self) -> Vec<ProcessStatus> {
+        self.backend
+            .get_process_statuses()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|status| status.is_alive)
+            .collect()
+    }
+
+    pub fn is_live(&self, session_id: &str) -> bool {
+        self.live_sessions()
+            .iter()
+            .any(|status| status.conversation_id == session_id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Workspace {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let account = match request.guard::<Authenticated>().await {
+            Outcome::Success(Authenticated(account)) => account,
+            Outcome::Error(error) => return Outcome::Error(error),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        let user = match account {
+            Account::User(user) => return Outcome::Success(user.workspace.clone()),
+            Account::Guest(_) => return Outcome::Error((Status::Forbidden, ())),
+            Account::Admin => ADMIN.to_string(),
+        };
+
+        let rocket = request.rocket();
+        let (Some(backend), Some(ws_manager)) = (
+            rocket.state::<Arc<Backend>>(),
+            rocket.state::<WebSocketManager>(),
+        ) else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        let data_dir = match rocket.state::<ServerConfig>() {
+            Some(config) => config.data_dir.clone(),
+            None => backend::paths::data_dir().unwrap_or_default(),
+        };
+        Outcome::Success(Workspace {
+            user,
+            data_dir,
+            backend: backend.clone(),
+            ws_manager: ws_manager.clone(),
+        })
+    }
+}
+
+/// Request guard for the admin account; fails with 403 Forbidden for others.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<Authenticated>().await {
+            Outcome::Success(Authenticated(Account::Admin)) => Outcome::Success(Admin),
+            Outcome::Success(_) => Outcome::Error((Status::Forbidden, ())),
+            Outcome::Error(error) => Outcome::Error(error),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
+
+/// A user other than the admin.
+pub struct User {
+    pub auth: AuthManager,
+    pub workspace: Workspace,
+}
+
+/// The users other than the admin, loaded from `<data-dir>/users`.
+pub struct Users {
+    enabled: bool,
+    dir: PathBuf,
+    journal_size: usize,
+    journal_spill: bool,
+    users: RwLock<BTreeMap<String, Arc<User>>>,
+}
+
+impl Users {
+    /// Loads every user with an `auth.json` when multi-user mode is on.
+    pub fn load(config: &ServerConfig) -> std::io::Result<Self> {
+        let users = Self {
+            enabled: config.multi_user,
+            dir: config.data_dir.join("users"),
+            journal_size: config.journal_size,
+            journal_spill: config.journal_spill,
+            users: RwLock::default(),
+        };
+        if !users.enabled {
+            return Ok(users);
+        }
+
+        let entries = match fs::read_dir(&users.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(users),
+            Err(e) => return Err(e),
+        };
+        for entry in entries.flatten() {
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if !is_valid_name(&name) {
+                continue;
+            }
+            if let Some(auth) = AuthManager::load(&entry.path())? {
+                let user = users.user(name.clone(), auth);
+                users.write().insert(name, user);
+            }
+        }
+        println!("👥 Multi-user mode: {} users", users.read().len());
+        Ok(users)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<String, Arc<User>>> {
+        self.users.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BTreeMap<String, Arc<User>>> {
+        self.users.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn user(&self, name: String, auth: AuthManager) -> Arc<User> {
+        let data_dir = self.dir.join(&name);
+        let journal_spill_dir = self.journal_spill.then(|| data_dir.join("journal"));
+        let ws_manager =
+            WebSocketManager::with_journal(EventJournal::new(self.journal_size, journal_spill_dir));
+        let backend = GeminiBackend::new(WebSocketsEventEmitter::new(ws_manager.clone()))
+            .with_data_dir(data_dir.clone());
+        Arc::new(User {
+            auth,
+            workspace: Workspace {
+                user: name,
+                data_dir,
+                backend: Arc::new(backend),
+                ws_manager,
+            },
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<User>> {
+        self.read().get(name).cloned()
+    }
+
+    pub fn all(&self) -> Vec<Arc<User>> {
+        self.read().values().cloned().collect()
+    }
+
+    pub fn by_token(&self, token: &str) -> Option<Arc<User>> {
+        self.read()
+            .values()
+            .find(|user| user.auth.verify_token(token))
+            .cloned()
+    }
+
+    pub fn by_session(&self, session: &str) -> Option<Arc<User>> {
+        self.read()
+            .values()
+            .find(|user| user.auth.validate_session(session))
+            .cloned()
+    }
+
+    /// Adds a user, returning their access token.
+    pub fn create(&self, name: &str) -> ApiResult<String> {
+        if !self.enabled {
+            return Err(ApiError::new(
+                Status::Conflict,
+                "multi_user_disabled",
+                "Start the server with --multi-user to add users",
+            ));
+        }
+        if !is_valid_name(name) {
+            return Err(ApiError::new(
+                Status::UnprocessableEntity,
+                "invalid_user_name",
+                "User names are 1-32 lowercase letters, digits, '-' or '_'",
+            ));
+        }
+        let mut users = self.write();
+        if users.contains_key(name) {
+            return Err(ApiError::new(
+                Status::Conflict,
+                "user_exists",
+                format!("User {name} already exists"),
+            ));
+        }
+        let (auth, token) = AuthManager::create(&self.dir.join(name)).map_err(|e| {
+            ApiError::new(Status::InternalServerError, "internal_error", e.to_string())
+        })?;
+        users.insert(name.to_string(), self.user(name.to_string(), auth));
+        println!("👤 Added user {name}");
+        Ok(token)
+    }
+
+    /// Stops a user's sessions, terminals and servers and removes their
+    /// credentials. Their data stays on disk.
+    pub async fn remove(&self, name: &str) -> ApiResult<()> {
+        let user = self
+            .write()
+            .remove(name)
+            .ok_or_else(|| user_not_found(name))?;
+        if let Err(e) = user.workspace.backend.shutdown(Duration::ZERO).await {
+            eprintln!("⚠️ Failed to stop everything of user {name}: {e}");
+        }
+        user.workspace.ws_manager.close_all_connections();
+        fs::remove_file(user.workspace.data_dir.join("auth.json")).map_err(|e| {
+            ApiError::new(Status::InternalServerError, "internal_error", e.to_string())
+        })?;
+        println!("👤 Removed user {name}");
+        Ok(())
+    }
+
+    /// Waits for every user's running turns, up to `grace`, then stops their
+    /// sessions and servers.
+    pub async fn shutdown(&self, grace: Duration) {
+        let users = self.all();
+        let stops = users.iter().map(|user| async move {
+            if let Err(e) = user.workspace.backend.shutdown(grace).await {
+                eprintln!(
+                    "⚠️ Shutdown of user {} incomplete: {e}",
+                    user.workspace.user
+                );
+            }
+            user.workspace.ws_manager.close_all_connections();
+        });
+        futures::future::join_all(stops).await;
+    }
+}
+
+/// Whether `name` can name a user or session member: 1-32 lowercase
+/// letters, digits, `-` or `_`, and not the admin.
+pub fn is_valid_name(name: &str) -> bool {
+    name != ADMIN
+        && (1..=32).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+}
+
+fn user_not_found(name: &str) -> ApiError {
+    ApiError::new(
+        Status::NotFound,
+        "user_not_found",
+        format!("No user named {name}"),
+    )
+}
+
+/// The workspace of `name`, where `admin` is the admin's own.
+fn workspace(users: &Users, admin: &Workspace, name: &str) -> ApiResult<Workspace> {
+    if name == ADMIN {
+        return Ok(admin.clone());
+    }
+    users
+        .get(name)
+        .map(|user| user.workspace.clone())
+        .ok_or_else(|| user_not_found(name))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserInfo {
+    name: String,
+    admin: bool,
+    /// Running CLI sessions.
+    sessions: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreateUserRequest {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreatedUser {
+    name: String,
+    /// Shown only now; the user logs in with it and can then set a password.
+    token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserSession {
+    user: String,
+    #[serde(flatten)]
+    status: ProcessStatus,
+}
+
+#[get("/admin/users")]
+pub fn list_users(
+    _admin: Admin,
+    admin: Workspace,
+    users: &State<Arc<Users>>,
+) -> Json<Vec<UserInfo>> {
+    let mut list = vec![UserInfo {
+        name: ADMIN.to_string(),
+        admin: true,
+        sessions: admin.live_sessions().len(),
+    }];
+    list.extend(users.all().iter().map(|user| UserInfo {
+        name: user.workspace.user.clone(),
+        admin: false,
+        sessions: user.workspace.live_sessions().len(),
+    }));
+    Json(list)
+}
+
+#[post("/admin/users", data = "<request>")]
+pub fn create_user(
+    _admin: Admin,
+    _writable: Writable,
+    request: Json<CreateUserRequest>,
+    users: &State<Arc<Users>>,
+) -> ApiResult<Json<CreatedUser>> {
+    let token = users.create(&request.name)?;
+    Ok(Json(CreatedUser {
+        name: request.into_inner().name,
+        token,
+    }))
+}
+
+#[delete("/admin/users/<name>")]
+pub async fn delete_user(
+    _admin: Admin,
+    _writable: Writable,
+    name: &str,
+    users: &State<Arc<Users>>,
+) -> ApiResult<()> {
+    users.remove(name).await
+}
+
+/// Every user's live sessions.
+#[get("/admin/sessions")]
+pub fn list_sessions(
+    _admin: Admin,
+    admin: Workspace,
+    users: &State<Arc<Users>>,
+) -> Json<Vec<UserSession>> {
+    let workspaces =
+        std::iter::once(admin).chain(users.all().into_iter().map(|user| user.workspace.clone()));
+    let sessions = workspaces
+        .flat_map(|workspace| {
+            workspace
+                .live_sessions()
+                .into_iter()
+                .map(move |status| UserSession {
+                    user: workspace.user.clone(),
+                    status,
+                })
+        })
+        .collect();
+    Json(sessions)
+}
+
+#[delete("/admin/sessions/<user>/<session_id>")]
+pub fn kill_session(
+    _admin: Admin,
+    _writable: Writable,
+    admin: Workspace,
+    user: &str,
+    session_id: &str,
+    users: &State<Arc<Users>>,
+) -> ApiResult<()> {
+    let workspace = workspace(users, &admin, user)?;
+    if !workspace.is_live(session_id) {
+        return Err(BackendError::SessionNotFound(session_id.to_string()).into());
+    }
+    workspace.backend.kill_process(session_id)?;
+    println!("🛑 Admin stopped session {session_id} of {user}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Cli;
+    use backend::ErrorBody;
+    use clap::Parser;
+    use rocket::http::{Header, Method};
+    use rocket::local::asynchronous::Client;
+    use serde_json::json;
+
+    struct Setup {
+        client: Client,
+        admin: Header<'static>,
+        users: Arc<Users>,
+        _dir: tempfile::TempDir,
+    }
+
+    async fn setup(multi_user: bool) -> Setup {
+        let dir = tempfile::tempdir().unwrap();
+        let mut args = vec![
+            "gemini-desktop-web",
+            "--data-dir",
+            dir.path().to_str().unwrap(),
+        ];
+        if multi_user {
+            args.push("--multi-user");
+        }
+        let config = ServerConfig::resolve(Cli::parse_from(args)).unwrap();
+        let auth = AuthManager::load_or_init(dir.path()).unwrap();
+        let admin = Header::new(
+            "Authorization",
+            format!("Bearer {}", auth.rotate_token().unwrap()),
+        );
+        let ws_manager = WebSocketManager::new();
+        let backend = Arc::new(GeminiBackend::new(WebSocketsEventEmitter::new(
+            ws_manager.clone(),
+        )));
+        let users = Arc::new(Users::load(&config).unwrap());
+        let rocket = rocket::build()
+            .manage(config)
+            .manage(Arc::new(auth))
+            .manage(users.clone())
+            .manage(crate::roles::Guests::default())
+            .manage(crate::rate_limit::RateLimiter::default())
+            .manage(Arc::new(crate::prompt::BusySessions::default()))
+            .manage(backend)
+            .manage(ws_manager)
+            .register("/api", rocket::catchers![crate::error::default_catcher])
+            .mount("/api", crate::operations::routes())
+            .mount(
+                "/api",
+                rocket::routes![
+                    list_users,
+                    create_user,
+                    delete_user,
+                    list_sessions,
+                    kill_session,
+                    crate::auth::auth_status,
+                    crate::auth::login,
+                    crate::prompt::prompt,
+                ],
+            );
+        Setup {
+            client: Client::tracked(rocket).await.unwrap(),
+            admin,
+            users,
+            _dir: dir,
+        }
+    }
+
+    fn bearer(token: &str) -> Header<'static> {
+        Header::new("Authorization", format!("Bearer {token}"))
+    }
+
+    #[test]
+    fn test_user_names() {
+        assert!(is_valid_name("alice"));
+        assert!(is_valid_name("dev-ops_2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name(ADMIN));
+        assert!(!is_valid_name("Alice"));
+        assert!(!is_valid_name("../etc"));
+        assert!(!is_valid_name(&"a".repeat(33)));
+    }
+
+    #[rocket::async_test]
+    async fn test_users_get_their_own_workspace() {
+        let setup = setup(true).await;
+        let response = setup
+            .client
+            .post("/api/admin/users")
+            .header(setup.admin.clone())
+            .body(json!({"name": "alice"}).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let created: CreatedUser = response.into_json().await.unwrap();
+        let alice = bearer(&created.token);
+
+        let response = setup
+            .client
+            .get("/api/auth/status")
+            .header(alice.clone())
+            .dispatch()
+            .await;
+        let status: AuthStatusView = response.into_json().await.unwrap();
+        assert_eq!(status.user.as_deref(), Some("alice"));
+        assert!(!status.admin && status.multi_user);
+
+        // Users can't reach the admin routes.
+        let response = setup
+            .client
+            .get("/api/admin/sessions")
+            .header(alice.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        // Nor the host: terminals, commands and the file system.
+        for (method, uri, body) in [
+            (Method::Post, "/api/terminals", json!({})),
+            (
+                Method::Post,
+                "/api/execute-command",
+                json!({"session_id": "s1", "command": "id"}),
+            ),
+            (Method::Post, "/api/list-directory", json!({"path": "/"})),
+            (
+                Method::Post,
+                "/api/validate-directory",
+                json!({"path": "/"}),
+            ),
+            (Method::Get, "/api/terminals", json!({})),
+            (Method::Delete, "/api/themes/default", json!({})),
+            (
+                Method::Post,
+                "/api/prompt",
+                json!({"prompt": "hi", "working_directory": "/", "tool_policy": "allow-all"}),
+            ),
+        ] {
+            let response = setup
+                .client
+                .req(method, uri)
+                .header(alice.clone())
+                .body(body.to_string())
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Forbidden, "{uri}");
+        }
+        let response = setup
+            .client
+            .get("/api/terminals")
+            .header(setup.admin.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let user = setup.users.get("alice").unwrap();
+        assert!(user.workspace.data_dir.ends_with("users/alice"));
+        assert!(user.workspace.data_dir.join("auth.json").exists());
+
+        let response = setup
+            .client
+            .post("/api/admin/users")
+            .header(setup.admin.clone())
+            .body(json!({"name": "alice"}).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Conflict);
+
+        let response = setup
+            .client
+            .get("/api/admin/users")
+            .header(setup.admin.clone())
+            .dispatch()
+            .await;
+        let listed: Vec<UserInfo> = response.into_json().await.unwrap();
+        let names: Vec<_> = listed.iter().map(|user| user.name.as_str()).collect();
+        assert_eq!(names, ["admin", "alice"]);
+
+        let response = setup
+            .client
+            .delete("/api/admin/sessions/alice/nope")
+            .header(setup.admin.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+        let body: ErrorBody = response.into_json().await.unwrap();
+        assert_eq!(body.code, "session_not_found");
+
+        // Removing a user revokes their token but keeps their data.
+        let response = setup
+            .client
+            .delete("/api/admin/users/alice")
+            .header(setup.admin.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = setup
+            .client
+            .get("/api/admin/users")
+            .header(alice)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert!(user.workspace.data_dir.exists());
+
+        // Users are loaded again on restart.
+        setup.users.create("bob").unwrap();
+        let config = ServerConfig::resolve(Cli::parse_from([
+            "gemini-desktop-web",
+            "--multi-user",
+            "--data-dir",
+            setup._dir.path().to_str().unwrap(),
+        ]))
+        .unwrap();
+        let reloaded = Users::load(&config).unwrap();
+        let names: Vec<_> = reloaded
+            .all()
+            .iter()
+            .map(|user| user.workspace.user.clone())
+            .collect();
+        assert_eq!(names, ["bob"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_login_picks_the_account() {
+        let setup = setup(true).await;
+        let token = setup.users.create("alice").unwrap();
+        let alice = setup.users.get("alice").unwrap();
+        alice.auth.set_password("alice's password").unwrap();
+
+        let login = |body: serde_json::Value| {
+            setup
+                .client
+                .post("/api/auth/login")
+                .body(body.to_string())
+                .dispatch()
+        };
+        assert_eq!(
+            login(json!({"password": "alice's password"}))
+                .await
+                .status(),
+            Status::Unauthorized
+        );
+        assert_eq!(
+            login(json!({"user": "alice", "password": "alice's password"}))
+                .await
+                .status(),
+            Status::Ok
+        );
+        let response = setup.client.get("/api/auth/status").dispatch().await;
+        let status: AuthStatusView = response.into_json().await.unwrap();
+        assert_eq!(status.user.as_deref(), Some("alice"));
+
+        assert_eq!(login(json!({"token": token})).await.status(), Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn test_repeated_failed_logins_are_locked_out() {
+        let setup = setup(true).await;
+        setup.users.create("alice").unwrap();
+        let alice = setup.users.get("alice").unwrap();
+        alice.auth.set_password("alice's password").unwrap();
+
+        let login = |password: &str| {
+            setup
+                .client
+                .post("/api/auth/login")
+                .body(json!({"user": "alice", "password": password}).to_string())
+                .dispatch()
+        };
+        for _ in 0..4 {
+            assert_eq!(login("guess").await.status(), Status::Unauthorized);
+        }
+        // Locked out, even with the right password.
+        assert_eq!(
+            login("alice's password").await.status(),
+            Status::TooManyRequests
+        );
+    }
+
+    #[rocket::async_test]
+    async fn test_single_user_mode_has_no_other_users() {
+        let setup = setup(false).await;
+        let response = setup
+            .client
+            .post("/api/admin/users")
+            .header(setup.admin.clone())
+            .body(json!({"name": "alice"}).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Conflict);
+        let body: ErrorBody = response.into_json().await.unwrap();
+        assert_eq!(body.code, "multi_user_disabled");
+
+        let response = setup
+            .client
+            .get("/api/admin/sessions")
+            .header(setup.admin.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<serde_json::Value>().await.unwrap(),
+            json!([])
+        );
+    }
+
+    #[derive(Deserialize)]
+    struct AuthStatusView {
+        user: Option<String>,
+        admin: bool,
+        multi_user: bool,
+    }
+}
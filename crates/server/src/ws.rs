@@ -0,0 +1,849 @@
+//! WebSocket connection registry and the event emitter that feeds it.
+//!
+//! Every connection carries a [`Subscription`]. Per-session events
+//! (`gemini-output-<id>`, `command-output-<id>`, ...) are only delivered to
+//! clients subscribed to that session and not muting that event kind, and
+//! terminal events (`terminal-output-<id>`, `terminal-exit-<id>`) only to
+//! clients subscribed to that terminal; any other event goes to everyone.
+//! Per-session events are also kept in an [`EventJournal`] so reconnecting
+//! clients can catch up.
+//!
+//! Connections opened for a [`Viewer`] show up in `presence-<session_id>`
+//! events, sent to everyone following the session whenever someone starts
+//! or stops watching it. A guest's connection only ever gets the events of
+//! the session it was invited to.
+
+use crate::journal::{DEFAULT_JOURNAL_SIZE, EventJournal, Replay};
+use crate::roles::{Role, Viewer};
+use backend::{EventEmitter, SESSION_EVENT_KINDS, split_session_event, split_terminal_event};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::{
+    Arc, Mutex, MutexGuard, PoisonError,
+    atomic::{AtomicU64, Ordering},
+};
+use tokio::sync::mpsc::{self as tokio_mpsc, error::TrySendError};
+
+/// Session id that subscribes a client to every session.
+pub const ALL_SESSIONS: &str = "*";
+
+/// Which per-session and terminal events a client wants. New connections
+/// start with no sessions or terminals and every kind enabled.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Subscription {
+    pub all_sessions: bool,
+    pub sessions: BTreeSet<String>,
+    pub terminals: BTreeSet<String>,
+    pub muted_kinds: BTreeSet<String>,
+}
+
+impl Subscription {
+    /// Whether an event with this name should be delivered.
+    pub fn wants(&self, event: &str) -> bool {
+        if let Some(terminal_id) = split_terminal_event(event) {
+            return self.terminals.contains(terminal_id);
+        }
+        match split_session_event(event) {
+            None => true,
+            Some((kind, session_id)) => {
+                !self.muted_kinds.contains(kind)
+                    && (self.all_sessions || self.sessions.contains(session_id))
+            }
+        }
+    }
+
+    /// Adds sessions and re-enables kinds.
+    pub fn subscribe(&mut self, session_ids: &[String], kinds: &[String]) {
+        for session_id in session_ids {
+            if session_id == ALL_SESSIONS {
+                self.all_sessions = true;
+            } else {
+                self.sessions.insert(session_id.clone());
+            }
+        }
+        for kind in kinds {
+            self.muted_kinds.remove(kind);
+        }
+    }
+
+    /// Drops sessions and mutes kinds.
+    pub fn unsubscribe(&mut self, session_ids: &[String], kinds: &[String]) {
+        for session_id in session_ids {
+            if session_id == ALL_SESSIONS {
+                self.all_sessions = false;
+            } else {
+                self.sessions.remove(session_id);
+            }
+        }
+        self.muted_kinds.extend(kinds.iter().cloned());
+    }
+
+    /// Adds or drops terminals.
+    pub fn follow_terminals(&mut self, terminal_ids: &[String], follow: bool) {
+        for terminal_id in terminal_ids {
+            if follow {
+                self.terminals.insert(terminal_id.clone());
+            } else {
+                self.terminals.remove(terminal_id);
+            }
+        }
+    }
+}
+
+/// Returns the first kind that isn't a per-session event kind.
+pub fn unknown_kind(kinds: &[String]) -> Option<&str> {
+    kinds
+        .iter()
+        .map(String::as_str)
+        .find(|kind| !SESSION_EVENT_KINDS.contains(kind))
+}
+
+/// Messages queued per client. A client that falls this far behind is
+/// disconnected; it can reconnect and resume from the journal.
+pub const CONNECTION_QUEUE_CAPACITY: usize = 1024;
+
+struct Connection {
+    id: u64,
+    sender: tokio_mpsc::Sender<String>,
+    subscription: Subscription,
+    viewer: Option<Viewer>,
+    /// Live events waiting for a replay to be queued ahead of them.
+    held_back: Option<Vec<String>>,
+}
+
+impl Connection {
+    /// Whether an event should be delivered to this connection.
+    fn wants(&self, event: &str) -> bool {
+        if !self.subscription.wants(event) {
+            return false;
+        }
+        match self
+            .viewer
+            .as_ref()
+            .and_then(|viewer| viewer.grant.as_ref())
+        {
+            None => true,
+            Some(grant) => split_session_event(event)
+                .is_some_and(|(_, session_id)| session_id == grant.session_id),
+        }
+    }
+
+    fn may_see(&self, session_id: &str) -> bool {
+        self.viewer
+            .as_ref()
+            .is_none_or(|viewer| viewer.role(session_id).is_some())
+    }
+
+    /// The role this connection's viewer watches a session with. Only
+    /// sessions subscribed by id count, not `*`.
+    fn watching(&self, session_id: &str) -> Option<Role> {
+        if !self.subscription.sessions.contains(session_id) {
+            return None;
+        }
+        self.viewer.as_ref()?.role(session_id)
+    }
+}
+
+/// Someone watching a session, as listed in presence events.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Watcher {
+    pub name: String,
+    pub role: Role,
+}
+
+/// Payload of `presence-<session_id>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    pub watchers: Vec<Watcher>,
+}
+
+struct Inner {
+    connections: Vec<Connection>,
+    journal: EventJournal,
+    next_sequence: u64,
+    messages_queued: u64,
+    slow_disconnects: u64,
+}
+
+impl Inner {
+    fn watchers(&self, session_id: &str) -> Vec<Watcher> {
+        let watchers: BTreeSet<Watcher> = self
+            .connections
+            .iter()
+            .filter_map(|conn| {
+                let role = conn.watching(session_id)?;
+                let name = conn.viewer.as_ref()?.name.clone();
+                Some(Watcher { name, role })
+            })
+            .collect();
+        watchers.into_iter().collect()
+    }
+
+    /// Tells everyone following these sessions who is watching them now.
+    fn announce_presence<'a>(&self, session_ids: impl IntoIterator<Item = &'a String>) {
+        for session_id in session_ids {
+            let message = serde_json::json!({
+                "event": format!("presence-{session_id}"),
+                "payload": Presence {
+                    watchers: self.watchers(session_id),
+                },
+            })
+            .to_string();
+            for conn in &self.connections {
+                let following = conn.subscription.all_sessions
+                    || conn.subscription.sessions.contains(session_id);
+                if following && conn.may_see(session_id) {
+                    let _ = conn.sender.try_send(message.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Delivery counters since the server started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebSocketStats {
+    pub connections: usize,
+    pub events_published: u64,
+    /// Event messages queued for clients, counting each recipient.
+    pub messages_queued: u64,
+    /// Clients dropped for falling behind.
+    pub slow_disconnects: u64,
+}
+
+/// Manages active WebSocket connections for event delivery. Publishing is
+/// synchronous and never blocks, so it can be called straight from
+/// `EventEmitter::emit` on any thread.
+#[derive(Clone)]
+pub struct WebSocketManager {
+    inner: Arc<Mutex<Inner>>,
+    connection_counter: Arc<AtomicU64>,
+}
+
+impl Default for WebSocketManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebSocketManager {
+    pub fn new() -> Self {
+        Self::with_journal(EventJournal::new(DEFAULT_JOURNAL_SIZE, None))
+    }
+
+    pub fn with_journal(journal: EventJournal) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                connections: Vec::new(),
+                journal,
+                next_sequence: 0,
+                messages_queued: 0,
+                slow_disconnects: 0,
+            })),
+            connection_counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register a new WebSocket connection
+    pub fn add_connection(&self, sender: tokio_mpsc::Sender<String>) -> u64 {
+        self.add(sender, None)
+    }
+
+    /// Registers a connection opened by `viewer`, who appears in presence
+    /// events of the sessions it subscribes to.
+    pub fn add_viewer(&self, sender: tokio_mpsc::Sender<String>, viewer: Viewer) -> u64 {
+        self.add(sender, Some(viewer))
+    }
+
+    fn add(&self, sender: tokio_mpsc::Sender<String>, viewer: Option<Viewer>) -> u64 {
+        let connection_id = self.connection_counter.fetch_add(1, Ordering::SeqCst);
+        let mut inner = self.lock();
+        inner.connections.push(Connection {
+            id: connection_id,
+            sender,
+            subscription: Subscription::default(),
+            viewer,
+            held_back: None,
+        });
+        println!(
+            "📡 WebSocket connection added (ID: {}). Total connections: {}",
+            connection_id,
+            inner.connections.len()
+        );
+        connection_id
+    }
+
+    /// Remove a specific WebSocket connection
+    pub fn remove_connection(&self, connection_id: u64) {
+        let mut inner = self.lock();
+        if let Some(pos) = inner
+            .connections
+            .iter()
+            .position(|conn| conn.id == connection_id)
+        {
+            let conn = inner.connections.remove(pos);
+            println!(
+                "📡 WebSocket connection removed. Total connections: {}",
+                inner.connections.len()
+            );
+            if conn.viewer.is_some() {
+                inner.announce_presence(&conn.subscription.sessions);
+            }
+        }
+    }
+
+    /// Closes every connection of `viewer`, such as a guest whose invitation
+    /// was revoked.
+    pub fn disconnect(&self, viewer: &Viewer) {
+        let mut inner = self.lock();
+        let (gone, kept) = std::mem::take(&mut inner.connections)
+            .into_iter()
+            .partition::<Vec<_>, _>(|conn| conn.viewer.as_ref() == Some(viewer));
+        inner.connections = kept;
+        let sessions: BTreeSet<String> = gone
+            .into_iter()
+            .flat_map(|conn| conn.subscription.sessions)
+            .collect();
+        inner.announce_presence(&sessions);
+    }
+
+    /// Who is watching a session right now.
+    pub fn watchers(&self, session_id: &str) -> Vec<Watcher> {
+        self.lock().watchers(session_id)
+    }
+
+    /// Adds sessions and kinds to a connection's subscription. With
+    /// `last_sequence`, the newly subscribed sessions' events after it are
+    /// queued first, or an `event-gap-<session_id>` event when they are gone.
+    /// Events spilled to disk are read without the lock; live events
+    /// published in the meantime are held back until the replay is queued.
+    /// When the replay doesn't fit in the connection's queue, the client gets
+    /// what did and is then disconnected, like one falling behind; it resumes
+    /// from the last sequence it received when it reconnects.
+    pub fn subscribe(
+        &self,
+        connection_id: u64,
+        session_ids: &[String],
+        kinds: &[String],
+        last_sequence: Option<u64>,
+    ) -> Option<Subscription> {
+        let (subscription, replays) = {
+            let mut inner = self.lock();
+            let Inner {
+                connections,
+                journal,
+                ..
+            } = &mut *inner;
+            let conn = connections
+                .iter_mut()
+                .find(|conn| conn.id == connection_id)?;
+            let before = conn.subscription.clone();
+            conn.subscription.subscribe(session_ids, kinds);
+
+            let mut replays = Vec::new();
+            if let Some(last_sequence) = last_sequence {
+                let new_sessions: Vec<String> = if conn.subscription.all_sessions {
+                    journal.session_ids()
+                } else {
+                    conn.subscription.sessions.iter().cloned().collect()
+                };
+                for session_id in new_sessions {
+                    if before.all_sessions
+                        || before.sessions.contains(&session_id)
+                        || !conn.may_see(&session_id)
+                    {
+                        continue;
+                    }
+                    replays.push(journal.replay(&session_id, last_sequence));
+                }
+                if !replays.is_empty() {
+                    conn.held_back.get_or_insert_with(Vec::new);
+                }
+            }
+            let subscription = conn.subscription.clone();
+            if conn.viewer.is_some() {
+                inner.announce_presence(subscription.sessions.difference(&before.sessions));
+            }
+            (subscription, replays)
+        };
+        if replays.is_empty() {
+            return Some(subscription);
+        }
+
+        let mut messages = Vec::new();
+        let mut replay = Vec::new();
+        for outcome in replays {
+            match outcome.and_then(Replay::read) {
+                Ok(events) => replay.extend(events),
+                Err(gap) => {
+                    let message = serde_json::json!({
+                        "event": format!("event-gap-{}", gap.session_id),
+                        "payload": gap,
+                    });
+                    messages.push(message.to_string());
+                }
+            }
+        }
+        replay.sort_by_key(|event| event.sequence);
+
+        let mut inner = self.lock();
+        let pos = inner
+            .connections
+            .iter()
+            .position(|conn| conn.id == connection_id)?;
+        let conn = &mut inner.connections[pos];
+        messages.extend(
+            replay
+                .into_iter()
+                .filter(|event| conn.wants(&event.event))
+                .map(|event| event.message),
+        );
+        messages.extend(conn.held_back.take().unwrap_or_default());
+        let overflowed = messages
+            .into_iter()
+            .any(|message| conn.sender.try_send(message).is_err());
+        if overflowed {
+            println!(
+                "📡 WebSocket connection (ID: {connection_id}) has too much to replay; disconnecting"
+            );
+            let conn = inner.connections.remove(pos);
+            inner.slow_disconnects += 1;
+            if conn.viewer.is_some() {
+                inner.announce_presence(&conn.subscription.sessions);
+            }
+        }
+        Some(subscription)
+    }
+
+    /// Removes sessions from a connection's subscription and mutes kinds.
+    pub fn unsubscribe(
+        &self,
+        connection_id: u64,
+        session_ids: &[String],
+        kinds: &[String],
+    ) -> Option<Subscription> {
+        let mut inner = self.lock();
+        let conn = inner
+            .connections
+            .iter_mut()
+            .find(|conn| conn.id == connection_id)?;
+        let before = conn.subscription.clone();
+        conn.subscription.unsubscribe(session_ids, kinds);
+        let subscription = conn.subscription.clone();
+        if conn.viewer.is_some() {
+            inner.announce_presence(before.sessions.difference(&subscription.sessions));
+        }
+        Some(subscription)
+    }
+
+    /// Adds terminals to, or with `follow` unset drops them from, a
+    /// connection's subscription.
+    pub fn follow_terminals(
+        &self,
+        connection_id: u64,
+        terminal_ids: &[String],
+        follow: bool,
+    ) -> Option<Subscription> {
+        let mut inner = self.lock();
+        let conn = inner
+            .connections
+            .iter_mut()
+            .find(|conn| conn.id == connection_id)?;
+        conn.subscription.follow_terminals(terminal_ids, follow);
+        Some(conn.subscription.clone())
+    }
+
+    /// Assigns the next sequence number, journals the event and queues it for
+    /// every client subscribed to it. Clients whose queue is full or closed
+    /// are dropped rather than slowing everyone else down.
+    pub fn publish<S: Serialize>(&self, event: &str, payload: S) -> backend::BackendResult<u64> {
+        let mut inner = self.lock();
+        let sequence = inner.next_sequence;
+
+        // Create WebSocket event message with sequence number for ordering
+        let message = serde_json::to_string(&WebSocketEvent {
+            event,
+            payload,
+            sequence,
+        })
+        .map_err(|e| backend::BackendError::JsonError(e.to_string()))?;
+        inner.next_sequence += 1;
+        inner.journal.record(event, sequence, &message);
+
+        let before = inner.connections.len();
+        let (mut queued, mut slow) = (0, 0);
+        inner.connections.retain_mut(|conn| {
+            if !conn.wants(event) {
+                return true;
+            }
+            if let Some(held_back) = &mut conn.held_back {
+                if held_back.len() < CONNECTION_QUEUE_CAPACITY {
+                    held_back.push(message.clone());
+                    queued += 1;
+                    return true;
+                }
+                println!(
+                    "📡 WebSocket connection (ID: {}) fell behind; disconnecting",
+                    conn.id
+                );
+                slow += 1;
+                return false;
+            }
+            match conn.sender.try_send(message.clone()) {
+                Ok(()) => {
+                    queued += 1;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    println!(
+                        "📡 WebSocket connection (ID: {}) fell behind; disconnecting",
+                        conn.id
+                    );
+                    slow += 1;
+                    false
+                }
+                Err(TrySendError::Closed(_)) => false,
+            }
+        });
+        inner.messages_queued += queued;
+        inner.slow_disconnects += slow;
+
+        let removed = before - inner.connections.len();
+        if removed > 0 {
+            println!(
+                "📡 Removed {} dead WebSocket connections. Active: {}",
+                removed,
+                inner.connections.len()
+            );
+        }
+
+        Ok(sequence)
+    }
+
+    pub fn stats(&self) -> WebSocketStats {
+        let inner = self.lock();
+        WebSocketStats {
+            connections: inner.connections.len(),
+            events_published: inner.next_sequence,
+            messages_queued: inner.messages_queued,
+            slow_disconnects: inner.slow_disconnects,
+        }
+    }
+
+    /// Close all WebSocket connections gracefully
+    pub fn close_all_connections(&self) {
+        let mut inner = self.lock();
+        println!(
+            "📡 Closing {} WebSocket connections for graceful shutdown",
+            inner.connections.len()
+        );
+        inner.connections.clear();
+    }
+}
+
+/// Unregisters a connection when dropped, for streams that end whenever
+/// their client goes away.
+pub struct ConnectionGuard {
+    ws_manager: WebSocketManager,
+    connection_id: u64,
+}
+
+impl ConnectionGuard {
+    pub fn new(ws_manager: WebSocketManager, connection_id: u64) -> Self {
+        Self {
+            ws_manager,
+            connection_id,
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.ws_manager.remove_connection(self.connection_id);
+    }
+}
+
+/// WebSocket event message format with sequence number for ordering
+#[derive(Serialize)]
+struct WebSocketEvent<'a, T> {
+    event: &'a str,
+    payload: T,
+    sequence: u64,
+}
+
+// =====================================
+// WebSockets EventEmitter Implementation
+// =====================================
+
+/// WebSocket-based event emitter that implements EventEmitter
+#[derive(Clone)]
+pub struct WebSocketsEventEmitter {
+    ws_manager: WebSocketManager,
+}
+
+impl WebSocketsEventEmitter {
+    pub fn new(ws_manager: WebSocketManager) -> Self {
+        Self { ws_manager }
+    }
+}
+
+impl EventEmitter for WebSocketsEventEmitter {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) -> backend::BackendResult<()> {
+        self.ws_manager.publish(event, payload).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_subscription_filters_sessions_and_kinds() {
+        let mut subscription = Subscription::default();
+        assert!(subscription.wants("server-notice"));
+        assert!(!subscription.wants("gemini-output-s1"));
+        assert!(!subscription.wants("command-output-s1"));
+
+        subscription.subscribe(&strings(&["s1"]), &[]);
+        assert!(subscription.wants("gemini-output-s1"));
+        assert!(subscription.wants("cli-io-s1"));
+        assert!(!subscription.wants("gemini-output-s2"));
+
+        subscription.unsubscribe(&[], &strings(&["cli-io"]));
+        assert!(!subscription.wants("cli-io-s1"));
+        assert!(subscription.wants("gemini-thought-s1"));
+
+        subscription.subscribe(&strings(&[ALL_SESSIONS]), &strings(&["cli-io"]));
+        assert!(subscription.wants("cli-io-s2"));
+
+        subscription.unsubscribe(&strings(&[ALL_SESSIONS, "s1"]), &[]);
+        assert!(!subscription.wants("gemini-output-s1"));
+        assert_eq!(unknown_kind(&strings(&["cli-io", "bogus"])), Some("bogus"));
+    }
+
+    fn message(rx: &mut tokio_mpsc::Receiver<String>) -> serde_json::Value {
+        serde_json::from_str(&rx.try_recv().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_publish_routes_by_subscription() {
+        let manager = WebSocketManager::new();
+        let (tx_a, mut rx_a) = tokio_mpsc::channel(8);
+        let (tx_b, mut rx_b) = tokio_mpsc::channel(8);
+        let id_a = manager.add_connection(tx_a);
+        let id_b = manager.add_connection(tx_b);
+        manager
+            .subscribe(id_a, &strings(&["a"]), &[], None)
+            .unwrap();
+        let subscription = manager
+            .follow_terminals(id_b, &strings(&["t1"]), true)
+            .unwrap();
+        assert_eq!(subscription.terminals, BTreeSet::from(["t1".to_string()]));
+
+        assert_eq!(manager.publish("gemini-output-a", "for a").unwrap(), 0);
+        assert_eq!(
+            manager.publish("command-output-a", "a's command").unwrap(),
+            1
+        );
+        assert_eq!(manager.publish("server-notice", "for all").unwrap(), 2);
+        manager
+            .publish("terminal-output-t1", "b's terminal")
+            .unwrap();
+        manager.publish("terminal-output-t2", "nobody's").unwrap();
+
+        assert_eq!(message(&mut rx_a)["payload"], "for a");
+        let command = message(&mut rx_a);
+        assert_eq!(command["payload"], "a's command");
+        assert_eq!(command["sequence"], 1);
+        assert_eq!(message(&mut rx_a)["payload"], "for all");
+        assert!(rx_a.try_recv().is_err());
+        assert_eq!(message(&mut rx_b)["payload"], "for all");
+        assert_eq!(message(&mut rx_b)["payload"], "b's terminal");
+        assert!(rx_b.try_recv().is_err());
+
+        manager.follow_terminals(id_b, &strings(&["t1"]), false);
+        manager.publish("terminal-exit-t1", "gone").unwrap();
+        assert!(rx_b.try_recv().is_err());
+
+        manager.remove_connection(id_a);
+        assert_eq!(manager.stats().connections, 1);
+    }
+
+    #[test]
+    fn test_presence_and_guest_scope() {
+        use crate::roles::Grant;
+
+        let manager = WebSocketManager::new();
+        let (tx_owner, mut rx_owner) = tokio_mpsc::channel(8);
+        let (tx_guest, mut rx_guest) = tokio_mpsc::channel(8);
+        let owner = manager.add_viewer(
+            tx_owner,
+            Viewer {
+                name: "admin".to_string(),
+                grant: None,
+            },
+        );
+        let bob = Viewer {
+            name: "bob".to_string(),
+            grant: Some(Grant {
+                session_id: "s1".to_string(),
+                role: Role::Observer,
+            }),
+        };
+        let guest = manager.add_viewer(tx_guest, bob.clone());
+
+        manager
+            .subscribe(owner, &strings(&["s1"]), &[], None)
+            .unwrap();
+        let presence = message(&mut rx_owner);
+        assert_eq!(presence["event"], "presence-s1");
+        assert_eq!(presence["payload"]["watchers"][0]["role"], "owner");
+
+        // A guest only sees its own session, even when asking for more.
+        manager
+            .subscribe(guest, &strings(&["s1", "s2"]), &[], None)
+            .unwrap();
+        let watchers = vec![
+            Watcher {
+                name: "admin".to_string(),
+                role: Role::Owner,
+            },
+            Watcher {
+                name: "bob".to_string(),
+                role: Role::Observer,
+            },
+        ];
+        assert_eq!(manager.watchers("s1"), watchers);
+        assert!(manager.watchers("s2").is_empty());
+        assert_eq!(
+            message(&mut rx_owner)["payload"]["watchers"][1]["name"],
+            "bob"
+        );
+        assert_eq!(message(&mut rx_guest)["event"], "presence-s1");
+
+        manager.publish("gemini-output-s2", "secret").unwrap();
+        manager.publish("terminal-output-t1", "secret").unwrap();
+        manager.publish("gemini-output-s1", "shared").unwrap();
+        assert_eq!(message(&mut rx_guest)["payload"], "shared");
+        assert!(rx_guest.try_recv().is_err());
+
+        // Revoking the guest closes its connection and tells the others.
+        while rx_owner.try_recv().is_ok() {}
+        manager.disconnect(&bob);
+        assert_eq!(manager.stats().connections, 1);
+        assert_eq!(
+            message(&mut rx_owner)["payload"]["watchers"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_slow_clients_are_disconnected() {
+        let manager = WebSocketManager::new();
+        let (tx, _rx) = tokio_mpsc::channel(2);
+        manager.add_connection(tx);
+        for _ in 0..2 {
+            manager.publish("server-notice", "x").unwrap();
+        }
+        assert_eq!(manager.stats().connections, 1);
+        manager.publish("server-notice", "x").unwrap();
+        assert_eq!(manager.stats().connections, 0);
+        assert_eq!(
+            manager.stats(),
+            WebSocketStats {
+                connections: 0,
+                events_published: 3,
+                messages_queued: 2,
+                slow_disconnects: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_subscribe_replays_missed_events() {
+        let manager = WebSocketManager::with_journal(EventJournal::new(2, None));
+        for sequence in 0..7 {
+            let event = format!("gemini-output-s{}", sequence % 2);
+            manager.publish(&event, sequence).unwrap();
+        }
+
+        let (tx, mut rx) = tokio_mpsc::channel(8);
+        let id = manager.add_connection(tx);
+        manager
+            .subscribe(id, &strings(&["s1"]), &[], Some(3))
+            .unwrap();
+        assert_eq!(message(&mut rx)["sequence"], 5);
+        assert!(rx.try_recv().is_err());
+
+        // s0 only kept sequences 4 and 6, so resuming from 1 is a gap.
+        manager
+            .subscribe(id, &strings(&["s0"]), &[], Some(1))
+            .unwrap();
+        let gap = message(&mut rx);
+        assert_eq!(gap["event"], "event-gap-s0");
+        assert_eq!(gap["payload"]["oldestSequence"], 4);
+    }
+
+    #[test]
+    fn test_replay_overflowing_the_queue_disconnects() {
+        let total = CONNECTION_QUEUE_CAPACITY as u64 + 100;
+        let manager = WebSocketManager::with_journal(EventJournal::new(total as usize, None));
+        for sequence in 0..=total {
+            manager.publish("gemini-output-s1", sequence).unwrap();
+        }
+
+        let (tx, mut rx) = tokio_mpsc::channel(CONNECTION_QUEUE_CAPACITY);
+        let id = manager.add_connection(tx);
+        manager
+            .subscribe(id, &strings(&["s1"]), &[], Some(0))
+            .unwrap();
+        assert_eq!(manager.stats().connections, 0);
+        assert_eq!(manager.stats().slow_disconnects, 1);
+
+        // What fit is still delivered, in order, then the queue closes.
+        for sequence in 1..=CONNECTION_QUEUE_CAPACITY as u64 {
+            assert_eq!(message(&mut rx)["sequence"], sequence);
+        }
+        assert!(matches!(
+            rx.try_recv(),
+            Err(tokio_mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn test_replay_from_disk_goes_ahead_of_live_events() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let journal = EventJournal::new(1, Some(temp_dir.path().join("journal")));
+        let manager = WebSocketManager::with_journal(journal);
+        for sequence in 0..4 {
+            manager.publish("gemini-output-s1", sequence).unwrap();
+        }
+
+        let (tx, mut rx) = tokio_mpsc::channel(8);
+        let id = manager.add_connection(tx);
+        // As if published while the spilled events were being read.
+        manager.lock().connections[0].held_back = Some(Vec::new());
+        manager.publish("server-notice", "live").unwrap();
+        assert!(rx.try_recv().is_err());
+
+        manager
+            .subscribe(id, &strings(&["s1"]), &[], Some(0))
+            .unwrap();
+        for sequence in 1..4 {
+            assert_eq!(message(&mut rx)["sequence"], sequence);
+        }
+        assert_eq!(message(&mut rx)["payload"], "live");
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,638 @@
+//! JSON-RPC 2.0 over the `/api/ws` WebSocket.
+//!
+//! Clients send `{"jsonrpc":"2.0","id":…,"method":…,"params":{…}}` frames on
+//! the same socket that streams events. Methods are the operations of
+//! [`backend::operations`], named like the Tauri commands and taking the
+//! same parameters as the matching REST endpoint. Each
+//! reply echoes the request `id` (a number or a string), so requests can be
+//! pipelined and answered out of order. Event frames never carry `jsonrpc`,
+//! which is how clients tell the two apart. Requests without an `id` are
+//! notifications and get no reply.
+
+use crate::rate_limit::RateLimiter;
+use crate::roles::Viewer;
+use crate::users::{ADMIN_OPERATIONS, approves_command};
+use crate::ws::{WebSocketManager, unknown_kind};
+use backend::{Access, BackendError, ErrorBody, EventEmitter, GeminiBackend};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+/// Failures reported by the backend; `data` holds the usual [`ErrorBody`].
+const SERVER_ERROR: i32 = -32000;
+
+/// Everything a connection needs to execute requests independently of Rocket.
+pub struct RpcContext<E: EventEmitter + 'static> {
+    pub backend: Arc<GeminiBackend<E>>,
+    pub limiter: RateLimiter,
+    pub client: IpAddr,
+    pub read_only: bool,
+    /// Whether the connection belongs to the admin, who alone may run
+    /// [`ADMIN_OPERATIONS`] and approve shell commands.
+    pub admin: bool,
+    /// Who opened the connection; guests are held to their session role.
+    pub viewer: Viewer,
+    /// Owner of this connection's subscription.
+    pub ws_manager: WebSocketManager,
+    pub connection_id: u64,
+}
+
+impl<E: EventEmitter + 'static> Clone for RpcContext<E> {
+    fn clone(&self) -> Self {
+        Self {
+            backend: self.backend.clone(),
+            limiter: self.limiter.clone(),
+            client: self.client,
+            read_only: self.read_only,
+            admin: self.admin,
+            viewer: self.viewer.clone(),
+            ws_manager: self.ws_manager.clone(),
+            connection_id: self.connection_id,
+        }
+    }
+}
+
+/// Params of `subscribe` / `unsubscribe`. `session_ids` may contain `"*"`
+/// for every session; `kinds` are event kinds such as `cli-io`. A client
+/// resuming after a reconnect passes the last `sequence` it received to
+/// `subscribe` to have missed events replayed. `terminal_ids` follow
+/// terminals; a connection creating a terminal follows it automatically.
+#[derive(Deserialize)]
+struct SubscriptionParams {
+    #[serde(default)]
+    session_ids: Vec<String>,
+    #[serde(default)]
+    terminal_ids: Vec<String>,
+    #[serde(default)]
+    kinds: Vec<String>,
+    #[serde(default)]
+    last_sequence: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<ErrorBody>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<ErrorBody> for RpcError {
+    fn from(body: ErrorBody) -> Self {
+        Self {
+            code: SERVER_ERROR,
+            message: body.message.clone(),
+            data: Some(body),
+        }
+    }
+}
+
+impl From<BackendError> for RpcError {
+    fn from(error: BackendError) -> Self {
+        match error {
+            BackendError::InvalidRequest(message) => Self::new(INVALID_PARAMS, message),
+            error => ErrorBody::from(error).into(),
+        }
+    }
+}
+
+fn reply(id: Value, outcome: Result<Value, RpcError>) -> String {
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    let response = RpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result,
+        error,
+    };
+    serde_json::to_string(&response).unwrap_or_else(|e| {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":null,"error":{{"code":{SERVER_ERROR},"message":"{e}"}}}}"#
+        )
+    })
+}
+
+/// Handles one text frame and returns the serialized reply, if any.
+pub async fn handle_message<E: EventEmitter + 'static>(
+    text: &str,
+    ctx: &RpcContext<E>,
+) -> Option<String> {
+    let request: RpcRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(reply(
+                Value::Null,
+                Err(RpcError::new(PARSE_ERROR, e.to_string())),
+            ));
+        }
+    };
+    if request.jsonrpc.as_deref() != Some("2.0") {
+        let id = request.id.unwrap_or(Value::Null);
+        return Some(reply(
+            id,
+            Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+        ));
+    }
+
+    let outcome = dispatch(&request.method, request.params, ctx).await;
+    request.id.map(|id| reply(id, outcome))
+}
+
+/// Whether a frame is a `subscribe` / `unsubscribe` request, which the
+/// connection handles inline so they apply in the order they were sent.
+pub fn is_subscription_change(text: &str) -> bool {
+    #[derive(Deserialize)]
+    struct Method {
+        method: String,
+    }
+    serde_json::from_str::<Method>(text)
+        .is_ok_and(|request| matches!(request.method.as_str(), "subscribe" | "unsubscribe"))
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| BackendError::JsonError(e.to_string()).into())
+}
+
+async fn dispatch<E: EventEmitter + 'static>(
+    method: &str,
+    params: Value,
+    ctx: &RpcContext<E>,
+) -> Result<Value, RpcError> {
+    let operation = backend::find_operation::<E>(method);
+    if ctx.read_only && operation.is_some_and(|op| op.access == Access::Write) {
+        return Err(ErrorBody::new("forbidden", "Server is in read-only mode", 403).into());
+    }
+    if !ctx.admin && ADMIN_OPERATIONS.contains(&method) {
+        return Err(ErrorBody::new("forbidden", "Only the admin can do this", 403).into());
+    }
+    if let Err(wait) = ctx.limiter.check(ctx.client, method) {
+        let secs = wait.as_secs().max(1);
+        return Err(ErrorBody::new(
+            "rate_limited",
+            format!("Too many requests; retry in {secs}s"),
+            429,
+        )
+        .into());
+    }
+    if let Some(operation) = operation {
+        let params = backend::snake_case_keys(params);
+        ctx.viewer.authorize(method, &params)?;
+        if !ctx.admin && approves_command(&ctx.backend, method, &params) {
+            return Err(ErrorBody::new(
+                "forbidden",
+                "Only the admin can approve shell commands",
+                403,
+            )
+            .into());
+        }
+        let result = operation.call(ctx.backend.clone(), params).await?;
+        if method == "create_terminal"
+            && let Some(terminal_id) = result.get("id").and_then(Value::as_str)
+        {
+            ctx.ws_manager
+                .follow_terminals(ctx.connection_id, &[terminal_id.to_string()], true);
+        }
+        return Ok(result);
+    }
+
+    match method {
+        "subscribe" | "unsubscribe" => {
+            let request: SubscriptionParams = parse(params)?;
+            if let Some(kind) = unknown_kind(&request.kinds) {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("Unknown event kind: {kind}"),
+                ));
+            }
+            if !ctx.admin && !request.terminal_ids.is_empty() {
+                return Err(ErrorBody::new(
+                    "forbidden",
+                    "Only the admin can follow terminals",
+                    403,
+                )
+                .into());
+            }
+            let follow = method == "subscribe";
+            if follow {
+                ctx.viewer.authorize_watch(&request.session_ids)?;
+            }
+            ctx.ws_manager
+                .follow_terminals(ctx.connection_id, &request.terminal_ids, follow);
+            let subscription = if follow {
+                ctx.ws_manager.subscribe(
+                    ctx.connection_id,
+                    &request.session_ids,
+                    &request.kinds,
+                    request.last_sequence,
+                )
+            } else {
+                ctx.ws_manager
+                    .unsubscribe(ctx.connection_id, &request.session_ids, &request.kinds)
+            };
+            let subscription =
+                subscription.ok_or_else(|| RpcError::new(INVALID_REQUEST, "Connection closed"))?;
+            to_value(subscription)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method: {method}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::Limit;
+    use crate::roles::{Grant, Role};
+    use crate::ws::Subscription;
+    use backend::BackendResult;
+    use std::net::Ipv4Addr;
+
+    #[derive(Clone)]
+    struct NullEmitter;
+
+    impl EventEmitter for NullEmitter {
+        fn emit<S: Serialize + Clone>(&self, _event: &str, _payload: S) -> BackendResult<()> {
+            Ok(())
+        }
+    }
+
+    async fn context(read_only: bool, limiter: RateLimiter) -> RpcContext<NullEmitter> {
+        user_context(read_only, limiter, true).await
+    }
+
+    async fn user_context(
+        read_only: bool,
+        limiter: RateLimiter,
+        admin: bool,
+    ) -> RpcContext<NullEmitter> {
+        let ws_manager = WebSocketManager::new();
+        let (tx, _rx) = tokio::sync::mpsc::channel(8);
+        let connection_id = ws_manager.add_connection(tx);
+        RpcContext {
+            backend: Arc::new(GeminiBackend::new(NullEmitter)),
+            limiter,
+            client: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            read_only,
+            admin,
+            viewer: Viewer {
+                name: "tester".to_string(),
+                grant: None,
+            },
+            ws_manager,
+            connection_id,
+        }
+    }
+
+    async fn call(ctx: &RpcContext<NullEmitter>, text: &str) -> RpcResponse {
+        let reply = handle_message(text, ctx).await.expect("expected a reply");
+        serde_json::from_str(&reply).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replies_echo_request_id() {
+        let ctx = context(false, RateLimiter::default()).await;
+        let response = call(
+            &ctx,
+            r#"{"jsonrpc":"2.0","id":"abc","method":"get_process_statuses"}"#,
+        )
+        .await;
+        assert_eq!(response.id, Value::String("abc".to_string()));
+        assert_eq!(response.result, Some(Value::Array(vec![])));
+        assert!(response.error.is_none());
+
+        let response = call(&ctx, r#"{"jsonrpc":"2.0","id":7,"method":"nope"}"#).await;
+        assert_eq!(response.id, Value::from(7));
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_every_operation_is_a_method() {
+        let names: Vec<_> = backend::operations::<NullEmitter>()
+            .into_iter()
+            .map(|op| op.name)
+            .collect();
+        assert!(!names.contains(&"subscribe") && !names.contains(&"unsubscribe"));
+
+        let ctx = context(false, RateLimiter::default()).await;
+        for method in ["get_theme_presets", "get_mcp_categories"] {
+            assert!(names.contains(&method));
+            let text = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{method}"}}"#);
+            let response = call(&ctx, &text).await;
+            assert!(response.error.is_none(), "{method} failed");
+            assert!(response.result.unwrap().is_array());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() {
+        let ctx = context(false, RateLimiter::default()).await;
+        assert_eq!(
+            call(&ctx, "not json").await.error.unwrap().code,
+            PARSE_ERROR
+        );
+        assert_eq!(
+            call(&ctx, r#"{"id":1,"method":"get_recent_chats"}"#)
+                .await
+                .error
+                .unwrap()
+                .code,
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            call(
+                &ctx,
+                r#"{"jsonrpc":"2.0","id":1,"method":"kill_process","params":{}}"#
+            )
+            .await
+            .error
+            .unwrap()
+            .code,
+            INVALID_PARAMS
+        );
+
+        // Notifications are executed but never answered.
+        let notification = r#"{"jsonrpc":"2.0","method":"get_process_statuses"}"#;
+        assert!(handle_message(notification, &ctx).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_backend_errors_carry_error_body() {
+        let ctx = context(false, RateLimiter::default()).await;
+        let response = call(
+            &ctx,
+            r#"{"jsonrpc":"2.0","id":1,"method":"send_message","params":{"session_id":"missing","message":"hi","conversation_history":""}}"#,
+        )
+        .await;
+        let error = response.error.unwrap();
+        assert_eq!(error.code, SERVER_ERROR);
+        assert_eq!(error.data.unwrap().code, "session_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_updates_connection_filter() {
+        let ctx = context(true, RateLimiter::default()).await;
+        let response = call(
+            &ctx,
+            r#"{"jsonrpc":"2.0","id":1,"method":"subscribe","params":{"session_ids":["s1"]}}"#,
+        )
+        .await;
+        let subscription: Subscription = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert!(subscription.wants("gemini-output-s1"));
+
+        let response = call(
+            &ctx,
+            r#"{"jsonrpc":"2.0","id":2,"method":"unsubscribe","params":{"kinds":["cli-io"]}}"#,
+        )
+        .await;
+        let subscription: Subscription = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert!(!subscription.wants("cli-io-s1"));
+        assert!(subscription.wants("gemini-thought-s1"));
+
+        let response = call(
+            &ctx,
+            r#"{"jsonrpc":"2.0","id":4,"method":"subscribe","params":{"terminal_ids":["t1"]}}"#,
+        )
+        .await;
+        let subscription: Subscription = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert!(subscription.wants("terminal-output-t1"));
+        assert!(!subscription.wants("terminal-output-t2"));
+
+        let response = call(
+            &ctx,
+            r#"{"jsonrpc":"2.0","id":3,"method":"subscribe","params":{"kinds":["nope"]}}"#,
+        )
+        .await;
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_and_rate_limits() {
+        let ctx = context(true, RateLimiter::default()).await;
+        let response = call(
+            &ctx,
+            r#"{"jsonrpc":"2.0","id":1,"method":"kill_process","params":{"conversation_id":"x"}}"#,
+        )
+        .await;
+        assert_eq!(response.error.unwrap().data.unwrap().status, 403);
+
+        let ctx = context(
+            false,
+            RateLimiter::new([("get_process_statuses", Limit::per_minute(1))]),
+        )
+        .await;
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"get_process_statuses"}"#;
+        assert!(call(&ctx, request).await.error.is_none());
+        let error = call(&ctx, request).await.error.unwrap();
+        assert_eq!(error.data.unwrap().code, "rate_limited");
+
+        // Other users can't touch server-wide settings, nor open terminals
+        // or run commands on the host.
+        let ctx = user_context(false, RateLimiter::default(), false).await;
+        for request in [
+            r#"{"jsonrpc":"2.0","id":1,"method":"lock_storage"}"#,
+            r#"{"jsonrpc":"2.0","id":1,"method":"create_terminal","params":{}}"#,
+            r#"{"jsonrpc":"2.0","id":1,"method":"execute_confirmed_command","params":{"session_id":"s1","command":"id"}}"#,
+        ] {
+            let error = call(&ctx, request).await.error.unwrap();
+            assert_eq!(error.data.unwrap().status, 403, "{request}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observers_only_watch() {
+        let mut ctx = user_context(false, RateLimiter::default(), false).await;
+        ctx.viewer = Viewer {
+            name: "bob".to_string(),
+            grant: Some(Grant {
+                session_id: "s1".to_string(),
+                role: Role::Observer,
+            }),
+        };
+        let status = |response: RpcResponse| response.error.map(|e| e.data.unwrap().status);
+
+        let send = r#"{"jsonrpc":"2.0","id":1,"method":"send_message","params":{"session_id":"s1","message":"hi","conversation_history":""}}"#;
+        assert_eq!(status(call(&ctx, send).await), Some(403));
+        let subscribe =
+            r#"{"jsonrpc":"2.0","id":2,"method":"subscribe","params":{"session_ids":["*"]}}"#;
+        assert_eq!(status(call(&ctx, subscribe).await), Some(403));
+        let subscribe =
+            r#"{"jsonrpc":"2.0","id":3,"method":"subscribe","params":{"session_ids":["s1"]}}"#;
+        assert_eq!(status(call(&ctx, subscribe).await), None);
+    }
+
+    #[tokio::test]
+    async fn test_guests_are_checked_against_the_params_the_operation_sees() {
+        let mut ctx = user_context(false, RateLimiter::default(), false).await;
+        ctx.viewer = Viewer {
+            name: "bob".to_string(),
+            grant: Some(Grant {
+                session_id: "s1".to_string(),
+                role: Role::Controller,
+            }),
+        };
+        let status = |response: RpcResponse| response.error.map(|e| e.data.unwrap().status);
+
+        let send = |params: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"send_message","params":{{{params},"message":"hi","conversation_history":""}}}}"#
+            )
+        };
+        assert_ne!(
+            status(call(&ctx, &send(r#""sessionId":"s1""#)).await),
+            Some(403)
+        );
+        assert_eq!(
+            status(call(&ctx, &send(r#""sessionId":"s2""#)).await),
+            Some(403)
+        );
+        // With both spellings, whichever one the operation ends up with is
+        // the one that gets checked.
+        for params in [
+            r#""session_id":"s1","sessionId":"s2""#,
+            r#""sessionId":"s2","session_id":"s1""#,
+        ] {
+            let request = send(params);
+            let sent: Value = serde_json::from_str(&request).unwrap();
+            let seen = backend::snake_case_keys(sent["params"].clone());
+            let allowed = seen["session_id"] == "s1";
+            let response = call(&ctx, &request).await;
+            assert_eq!(status(response) != Some(403), allowed, "{params}");
+        }
+    }
+
+    /// Answering a shell command's confirmation runs it, so other users may
+    /// only turn it down.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_only_the_admin_approves_shell_commands() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A CLI that asks to run a command right after starting; with any
+        // other model it isn't there, as for the other tests.
+        let dir = tempfile::tempdir().unwrap();
+        let cli = dir.path().join("gemini");
+        std::fs::write(
+            &cli,
+            concat!(
+                "#!/bin/sh\n",
+                "[ \"$2\" = shell-test ] || exit 127\n",
+                "read -r init\n",
+                "echo '{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{}}'\n",
+                "echo '{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":\"requestToolCallConfirmation\",",
+                "\"params\":{\"label\":\"Run id\",\"icon\":\"terminal\",",
+                "\"confirmation\":{\"type\":\"execute\",\"command\":\"id\"},\"locations\":[]}}'\n",
+                "exec cat > /dev/null\n",
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = std::env::var("PATH").unwrap_or_default();
+        // SAFETY: only this test puts a `gemini` there, and it answers no
+        // one else.
+        unsafe { std::env::set_var("PATH", format!("{}:{path}", dir.path().display())) };
+
+        let mut ctx = user_context(false, RateLimiter::default(), false).await;
+        ctx.backend = Arc::new(GeminiBackend::new(NullEmitter).with_data_dir(dir.path().into()));
+        backend::paths::scope(
+            Some(dir.path().into()),
+            ctx.backend.initialize_session(
+                "s1".to_string(),
+                dir.path().display().to_string(),
+                "shell-test".to_string(),
+            ),
+        )
+        .await
+        .unwrap();
+        while ctx.backend.confirmation_type("s1", 7).is_none() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let answer = |outcome: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"send_tool_call_confirmation_response","params":{{"session_id":"s1","request_id":7,"tool_call_id":"1001","outcome":"{outcome}"}}}}"#
+            )
+        };
+        let status = |response: RpcResponse| response.error.map(|e| e.data.unwrap().status);
+        assert_eq!(status(call(&ctx, &answer("allow")).await), Some(403));
+        assert_eq!(status(call(&ctx, &answer("reject")).await), None);
+        assert_eq!(ctx.backend.confirmation_type("s1", 7), None);
+        ctx.backend.kill_process("s1").unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_requests_share_the_backend() {
+        let ctx = context(false, RateLimiter::default()).await;
+        let requests = (0..500).map(|id| {
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                let text = match id % 3 {
+                    0 => format!(r#"{{"jsonrpc":"2.0","id":{id},"method":"get_process_statuses"}}"#),
+                    1 => format!(
+                        r#"{{"jsonrpc":"2.0","id":{id},"method":"send_message","params":{{"session_id":"s{id}","message":"hi","conversation_history":""}}}}"#
+                    ),
+                    _ => format!(
+                        r#"{{"jsonrpc":"2.0","id":{id},"method":"kill_process","params":{{"conversation_id":"s{id}"}}}}"#
+                    ),
+                };
+                (id, call(&ctx, &text).await)
+            })
+        });
+
+        let responses = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            futures::future::join_all(requests),
+        )
+        .await
+        .expect("parallel requests should not block each other");
+        for response in responses {
+            let (id, response) = response.unwrap();
+            assert_eq!(response.id, Value::from(id));
+            assert_eq!(response.error.is_some(), id % 3 == 1);
+        }
+    }
+}
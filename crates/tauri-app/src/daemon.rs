@@ -0,0 +1,164 @@
+//! Connecting to the backend daemon (`gemini-desktop-web --daemon`), which
+//! owns the sessions so the web UI sees the same ones. When none is running
+//! the app starts one, and leaves it running for the web UI when it quits.
+
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use backend::{paths, DaemonClient, ErrorBody, GeminiBackend, Operation};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::event_emitter::TauriEventEmitter;
+
+const DAEMON_BINARY: &str = "gemini-desktop-web";
+const START_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Where commands go once the link is up.
+#[derive(Clone)]
+enum Target {
+    Daemon(Arc<DaemonClient>),
+    /// The daemon couldn't be reached at startup, so this process runs them.
+    Local(Arc<GeminiBackend<TauriEventEmitter>>),
+}
+
+/// The app's link to the daemon. It connects in the background, so startup
+/// doesn't wait for a daemon to start, and connects again after the daemon
+/// hangs up; commands sent meanwhile wait for it.
+pub struct DaemonLink {
+    emitter: TauriEventEmitter,
+    target: Mutex<Option<Target>>,
+}
+
+impl DaemonLink {
+    pub const fn new(emitter: TauriEventEmitter) -> Self {
+        Self {
+            emitter,
+            target: Mutex::const_new(None),
+        }
+    }
+
+    /// Connects, and connects again each time the daemon hangs up, so its
+    /// events keep coming. Gives up when that fails; the next command tries
+    /// again.
+    pub async fn keep_connected(self: Arc<Self>) {
+        loop {
+            match self.target().await {
+                Ok(Target::Daemon(client)) => client.closed().await,
+                Ok(Target::Local(_)) => return,
+                Err(e) => {
+                    eprintln!("{}", e.message);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Runs an operation on the daemon, or locally if it was unreachable at
+    /// startup.
+    pub async fn call(
+        &self,
+        operation: Operation<TauriEventEmitter>,
+        params: Value,
+    ) -> Result<Value, ErrorBody> {
+        match self.target().await? {
+            Target::Daemon(client) => client.call(operation.name, params).await,
+            Target::Local(backend) => operation
+                .call(backend, params)
+                .await
+                .map_err(ErrorBody::from),
+        }
+    }
+
+    async fn target(&self) -> Result<Target, ErrorBody> {
+        let mut target = self.target.lock().await;
+        match &*target {
+            Some(Target::Daemon(client)) if client.is_closed() => {}
+            Some(current) => return Ok(current.clone()),
+            None => {}
+        }
+        let reconnecting = target.is_some();
+        let next = match connect_or_start(self.emitter.clone()).await {
+            Ok(client) => Target::Daemon(Arc::new(client)),
+            // Sessions live in the daemon by now; running locally would
+            // hide them.
+            Err(e) if reconnecting => {
+                return Err(ErrorBody::new(
+                    "daemon_unavailable",
+                    format!("Lost the connection to the backend daemon: {e}"),
+                    503,
+                ));
+            }
+            Err(e) => {
+                eprintln!("Failed to reach the backend daemon, running locally: {e}");
+                Target::Local(Arc::new(GeminiBackend::new(self.emitter.clone())))
+            }
+        };
+        *target = Some(next.clone());
+        Ok(next)
+    }
+}
+
+/// Connects to the running daemon, or starts one and waits for it.
+async fn connect_or_start(emitter: TauriEventEmitter) -> io::Result<DaemonClient> {
+    if let Ok(client) = DaemonClient::connect(emitter.clone()).await {
+        return Ok(client);
+    }
+    let mut child = start()?;
+    let deadline = Instant::now() + START_TIMEOUT;
+    loop {
+        tokio::time::sleep(RETRY_INTERVAL).await;
+        match DaemonClient::connect(emitter.clone()).await {
+            Ok(client) => return Ok(client),
+            Err(e) if Instant::now() >= deadline => return Err(e),
+            Err(_) => {}
+        }
+        if let Some(status) = child.try_wait()? {
+            return Err(io::Error::other(format!(
+                "{DAEMON_BINARY} --daemon exited with {status}; see daemon.log in the data directory"
+            )));
+        }
+    }
+}
+
+/// Starts the daemon detached, logging to `<data-dir>/daemon.log`. Its web
+/// UI only listens on localhost; serving the network is left to an
+/// explicitly started server.
+fn start() -> io::Result<Child> {
+    let data_dir = paths::data_dir().map_err(|e| io::Error::other(e.to_string()))?;
+    std::fs::create_dir_all(&data_dir)?;
+    let log = File::create(data_dir.join("daemon.log"))?;
+
+    let mut command = Command::new(binary());
+    command
+        .arg("--daemon")
+        .arg("--bind")
+        .arg("127.0.0.1")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    #[cfg(unix)]
+    {
+        // Keep it out of the app's process group, so it outlives a Ctrl+C.
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command.spawn()
+}
+
+/// The server binary installed next to this one, else the one on `PATH`.
+fn binary() -> PathBuf {
+    let name = format!("{DAEMON_BINARY}{}", std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(&name)))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
@@ -1,5 +1,5 @@
 use tauri::{AppHandle, Runtime};
-use crate::settings::AppSettings;
+use backend::AppSettings;
 
 pub fn register_hotkeys<R: Runtime>(_app: &AppHandle<R>, settings: &AppSettings) {
     println!("Hotkeys configured:");
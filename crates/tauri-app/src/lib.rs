@@ -3,14 +3,14 @@
 mod event_emitter;
 mod state;
 mod commands;
-mod settings;
+mod daemon;
 mod tray;
 mod hotkeys;
 
 use std::sync::Arc;
-use backend::GeminiBackend;
+use backend::{settings, GeminiBackend};
 use event_emitter::TauriEventEmitter;
-use state::AppState;
+use state::{AppState, Backend};
 use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -24,11 +24,15 @@ pub fn run() {
             let settings = settings::load_settings();
             
             let emitter = TauriEventEmitter::new(app.handle().clone());
-            let backend = GeminiBackend::new(emitter);
-            
-            let app_state = AppState {
-                backend: Arc::new(backend),
+            let backend = if settings.use_daemon {
+                let daemon = Arc::new(daemon::DaemonLink::new(emitter));
+                tauri::async_runtime::spawn(daemon.clone().keep_connected());
+                Backend::Daemon(daemon)
+            } else {
+                Backend::Local(Arc::new(GeminiBackend::new(emitter)))
             };
+            
+            let app_state = AppState { backend };
             app.manage(app_state);
             
             // Create system tray
@@ -58,40 +62,7 @@ pub fn run() {
                 }
             }
         })
-        .invoke_handler(tauri::generate_handler![
-            commands::check_cli_installed,
-            commands::start_session,
-            commands::send_message,
-            commands::get_process_statuses,
-            commands::kill_process,
-            commands::test_gemini_command,
-            commands::send_tool_call_confirmation_response,
-            commands::execute_confirmed_command,
-            commands::generate_conversation_title,
-            commands::validate_directory,
-            commands::is_home_directory,
-            commands::get_home_directory,
-            commands::get_parent_directory,
-            commands::list_directory_contents,
-            commands::list_volumes,
-            commands::debug_environment,
-            commands::get_recent_chats,
-            commands::search_chats,
-            commands::list_projects,
-            commands::list_enriched_projects,
-            commands::get_project,
-            commands::get_project_discussions,
-            commands::list_servers,
-            commands::add_server,
-            commands::edit_server,
-            commands::delete_server,
-            commands::start_server,
-            commands::stop_server,
-            commands::get_settings,
-            commands::save_settings,
-            commands::take_screenshot,
-            commands::import_file
-        ]);
+        .invoke_handler(commands::invoke_handler());
 
     builder
         .run(tauri::generate_context!())
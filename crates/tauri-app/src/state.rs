@@ -1,7 +1,17 @@
 use std::sync::Arc;
 use backend::GeminiBackend;
+use crate::daemon::DaemonLink;
 use crate::event_emitter::TauriEventEmitter;
 
+/// Where IPC commands run.
+#[derive(Clone)]
+pub enum Backend {
+    /// In this process.
+    Local(Arc<GeminiBackend<TauriEventEmitter>>),
+    /// In the backend daemon, shared with the web UI.
+    Daemon(Arc<DaemonLink>),
+}
+
 pub struct AppState {
-    pub backend: Arc<GeminiBackend<TauriEventEmitter>>,
-}
\ No newline at end of file
+    pub backend: Backend,
+}
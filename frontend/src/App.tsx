@@ -9,6 +9,7 @@ import { AppFooter } from "./components/layout/AppFooter";
 import { QuickPanelToggle } from "./components/layout/QuickPanelToggle";
 import { PanelManager } from "./components/layout/PanelManager";
 import { CliWarnings } from "./components/common/CliWarnings";
+import { StorageUnlockDialog } from "./components/common/StorageUnlockDialog";
 import { ConversationContext } from "./contexts/ConversationContext";
 import { HomeDashboard } from "./pages/HomeDashboard";
 import ProjectsPage from "./pages/Projects";
@@ -153,6 +154,7 @@ function RootLayout() {
           selectedModel={selectedModel}
           isCliInstalled={isCliInstalled}
         />
+        <StorageUnlockDialog />
 
         <ConversationContext.Provider
           value={{
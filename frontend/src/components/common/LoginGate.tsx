@@ -0,0 +1,204 @@
+import { ReactNode, useCallback, useEffect, useState } from "react";
+import { KeyRound } from "lucide-react";
+import { Button } from "../ui/button";
+import {
+  Card,
+  CardContent,
+  CardDescription,
+  CardFooter,
+  CardHeader,
+  CardTitle,
+} from "../ui/card";
+import { Input } from "../ui/input";
+import { webApi } from "../../lib/webApi";
+
+type GateState = "checking" | "login" | "authenticated";
+
+/**
+ * Blocks the web UI until the browser holds a valid session cookie. Logs in
+ * with the account password or the access token printed by the server on
+ * first run, or handed out by the admin in multi-user mode. Desktop builds
+ * render their children directly.
+ */
+export function LoginGate({ children }: { children: ReactNode }) {
+  const [state, setState] = useState<GateState>(
+    __WEB__ ? "checking" : "authenticated"
+  );
+  const [passwordSet, setPasswordSet] = useState(false);
+  const [multiUser, setMultiUser] = useState(false);
+  const [userName, setUserName] = useState("");
+  const [useToken, setUseToken] = useState(false);
+  const [secret, setSecret] = useState("");
+  const [newPassword, setNewPassword] = useState("");
+  const [error, setError] = useState("");
+  const [submitting, setSubmitting] = useState(false);
+  const [promptPassword, setPromptPassword] = useState(false);
+
+  const checkStatus = useCallback(async () => {
+    try {
+      const status = await webApi.auth_status();
+      setPasswordSet(status.password_set);
+      setMultiUser(status.multi_user);
+      setUseToken(!status.password_set && !status.multi_user);
+      setState(status.authenticated ? "authenticated" : "login");
+    } catch (err) {
+      console.error("Failed to get auth status:", err);
+      setState("login");
+    }
+  }, []);
+
+  useEffect(() => {
+    if (!__WEB__) return;
+    checkStatus();
+    const onAuthRequired = () => setState("login");
+    window.addEventListener("auth-required", onAuthRequired);
+    return () => window.removeEventListener("auth-required", onAuthRequired);
+  }, [checkStatus]);
+
+  const handleLogin = async () => {
+    setSubmitting(true);
+    setError("");
+    try {
+      await webApi.login(
+        useToken
+          ? { token: secret }
+          : { user: userName || undefined, password: secret }
+      );
+      setSecret("");
+      // First login with the token: offer to set a password for next time.
+      // The token alone doesn't say whose it is, so ask the server.
+      const status = await webApi.auth_status();
+      setPasswordSet(status.password_set);
+      // Guests only have their invitation token.
+      if (!status.password_set && !status.role) {
+        setPromptPassword(true);
+      } else {
+        setState("authenticated");
+      }
+    } catch {
+      setError(
+        useToken
+          ? "Invalid access token."
+          : "Incorrect password. Please try again."
+      );
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  const handleSetPassword = async () => {
+    setSubmitting(true);
+    setError("");
+    try {
+      await webApi.set_password({ password: newPassword });
+      setNewPassword("");
+      setPasswordSet(true);
+      setPromptPassword(false);
+      setState("authenticated");
+    } catch {
+      setError("Password must be at least 8 characters.");
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  if (state === "authenticated") {
+    return <>{children}</>;
+  }
+
+  if (state === "checking") {
+    return null;
+  }
+
+  return (
+    <div className="flex min-h-screen items-center justify-center bg-background p-4">
+      <Card className="w-full max-w-sm">
+        <CardHeader>
+          <CardTitle className="flex items-center gap-2">
+            <KeyRound className="h-4 w-4" />
+            {promptPassword ? "Set a password" : "Sign in to Gemini Desktop"}
+          </CardTitle>
+          <CardDescription>
+            {promptPassword
+              ? "Choose a password so you don't need the access token next time."
+              : useToken
+                ? "Enter the access token printed in the server console on first run."
+                : multiUser
+                  ? "Enter your user name and password."
+                  : "Enter your password."}
+          </CardDescription>
+        </CardHeader>
+        <CardContent className="space-y-2">
+          {promptPassword ? (
+            <Input
+              type="password"
+              autoFocus
+              value={newPassword}
+              placeholder="New password"
+              onChange={(e) => setNewPassword(e.target.value)}
+              onKeyDown={(e) => {
+                if (e.key === "Enter" && newPassword) handleSetPassword();
+              }}
+            />
+          ) : (
+            <>
+              {multiUser && !useToken && (
+                <Input
+                  autoFocus
+                  value={userName}
+                  placeholder="User name (blank for admin)"
+                  onChange={(e) => setUserName(e.target.value)}
+                />
+              )}
+              <Input
+                type="password"
+                autoFocus={!multiUser || useToken}
+                value={secret}
+                placeholder={useToken ? "Access token" : "Password"}
+                onChange={(e) => setSecret(e.target.value)}
+                onKeyDown={(e) => {
+                  if (e.key === "Enter" && secret) handleLogin();
+                }}
+              />
+            </>
+          )}
+          {error && <p className="text-sm text-destructive">{error}</p>}
+        </CardContent>
+        <CardFooter className="flex justify-between">
+          {promptPassword ? (
+            <>
+              <Button variant="ghost" onClick={() => setState("authenticated")}>
+                Skip
+              </Button>
+              <Button
+                onClick={handleSetPassword}
+                disabled={!newPassword || submitting}
+              >
+                {submitting ? "Saving..." : "Save password"}
+              </Button>
+            </>
+          ) : (
+            <>
+              {passwordSet || multiUser ? (
+                <Button
+                  variant="ghost"
+                  onClick={() => {
+                    setUseToken(!useToken);
+                    setError("");
+                  }}
+                >
+                  {useToken ? "Use password" : "Use access token"}
+                </Button>
+              ) : (
+                <span />
+              )}
+              <Button onClick={handleLogin} disabled={!secret || submitting}>
+                {submitting ? "Signing in..." : "Sign in"}
+              </Button>
+            </>
+          )}
+        </CardFooter>
+      </Card>
+    </div>
+  );
+}
@@ -0,0 +1,82 @@
+import { useEffect, useState } from "react";
+import { Lock } from "lucide-react";
+import { Button } from "../ui/button";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogFooter,
+  DialogHeader,
+  DialogTitle,
+} from "../ui/dialog";
+import { Input } from "../ui/input";
+import { api } from "../../lib/api";
+import type { EncryptionStatus } from "../../lib/webApi";
+
+/**
+ * Prompts for the storage passphrase at startup when encryption at rest is
+ * enabled and the backend has not been unlocked yet.
+ */
+export function StorageUnlockDialog() {
+  const [open, setOpen] = useState(false);
+  const [passphrase, setPassphrase] = useState("");
+  const [error, setError] = useState("");
+  const [unlocking, setUnlocking] = useState(false);
+
+  useEffect(() => {
+    api
+      .invoke<EncryptionStatus>("get_encryption_status")
+      .then((status) => setOpen(status.enabled && !status.unlocked))
+      .catch((err) => console.error("Failed to get encryption status:", err));
+  }, []);
+
+  const handleUnlock = async () => {
+    setUnlocking(true);
+    setError("");
+    try {
+      await api.invoke("unlock_storage", { passphrase });
+      setPassphrase("");
+      setOpen(false);
+    } catch {
+      setError("Incorrect passphrase. Please try again.");
+    } finally {
+      setUnlocking(false);
+    }
+  };
+
+  return (
+    <Dialog open={open} onOpenChange={setOpen}>
+      <DialogContent>
+        <DialogHeader>
+          <DialogTitle className="flex items-center gap-2">
+            <Lock className="h-4 w-4" />
+            Unlock encrypted storage
+          </DialogTitle>
+          <DialogDescription>
+            Your chat logs are encrypted. Enter your passphrase to read past
+            conversations and start new sessions.
+          </DialogDescription>
+        </DialogHeader>
+        <Input
+          type="password"
+          autoFocus
+          value={passphrase}
+          placeholder="Passphrase"
+          onChange={(e) => setPassphrase(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter" && passphrase) handleUnlock();
+          }}
+        />
+        {error && <p className="text-sm text-destructive">{error}</p>}
+        <DialogFooter>
+          <Button variant="outline" onClick={() => setOpen(false)}>
+            Later
+          </Button>
+          <Button onClick={handleUnlock} disabled={!passphrase || unlocking}>
+            {unlocking ? "Unlocking..." : "Unlock"}
+          </Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+}
@@ -21,6 +21,7 @@ interface AppSettingsType {
     close_to_tray: boolean;
     theme: string;
   };
+  use_daemon: boolean;
 }
 
 export function AppSettings() {
@@ -220,6 +221,20 @@ export function AppSettings() {
                   onCheckedChange={(checked) => updateUI({ close_to_tray: checked })}
                 />
               </div>
+
+              <div className="flex items-center justify-between">
+                <div className="space-y-0.5">
+                  <Label htmlFor="use-daemon">Share Sessions with the Web UI</Label>
+                  <div className="text-sm text-muted-foreground">
+                    Run sessions in the backend daemon, starting it if needed. Takes effect on restart
+                  </div>
+                </div>
+                <Switch
+                  id="use-daemon"
+                  checked={settings.use_daemon}
+                  onCheckedChange={(checked) => updateSettings({ use_daemon: checked })}
+                />
+              </div>
               
               <div className="space-y-2">
                 <Label htmlFor="theme">Theme</Label>
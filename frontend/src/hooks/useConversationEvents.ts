@@ -370,6 +370,28 @@ export const useConversationEvents = (
           }
         );
 
+        // Events were missed while the web socket was disconnected and the
+        // server no longer has them, so the transcript may be incomplete.
+        await api.listen<{ lastSequence: number }>(
+          `event-gap-${conversationId}`,
+          () => {
+            updateConversation(conversationId, (conv) => {
+              conv.isStreaming = false;
+              conv.messages.push({
+                id: Date.now().toString(),
+                parts: [
+                  {
+                    type: "text",
+                    text: "⚠️ Some output was missed while disconnected. Reload the conversation to see the full transcript.",
+                  },
+                ],
+                sender: "assistant",
+                timestamp: new Date(),
+              });
+            });
+          }
+        );
+
         // Listen for turn finished events to stop streaming indicator
         await api.listen<boolean>(
           `gemini-turn-finished-${conversationId}`,
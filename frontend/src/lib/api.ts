@@ -1,6 +1,14 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { webApi, webListen } from "./webApi";
+import {
+  getWebSocketManager,
+  webApi,
+  webListen,
+  type ExecutionOptions,
+  type RedactionConfig,
+  type TerminalOptions,
+} from "./webApi";
+import { toApiError } from "./errors";
 
 declare global {
   interface Window {
@@ -8,141 +16,210 @@ declare global {
   }
 }
 
-// Abstraction layer for API calls
-export const api = {
-  async invoke<T>(command: string, args?: Record<string, unknown>): Promise<T> {
-    if (__WEB__) {
-      switch (command) {
-        case "check_cli_installed":
-          return webApi.check_cli_installed() as Promise<T>;
-        case "send_message":
-          if (!args) throw new Error("Missing arguments for send_message");
-          return webApi.send_message(
-            args as {
-              sessionId: string;
-              message: string;
-              conversationHistory: string;
-              model?: string;
-            }
-          ) as Promise<T>;
-        case "get_process_statuses":
-          return webApi.get_process_statuses() as Promise<T>;
-        case "kill_process":
-          if (!args) throw new Error("Missing arguments for kill_process");
-          return webApi.kill_process(
-            args as { conversationId: string }
-          ) as Promise<T>;
-        case "send_tool_call_confirmation_response":
-          if (!args)
-            throw new Error(
-              "Missing arguments for send_tool_call_confirmation_response"
-            );
-          return webApi.send_tool_call_confirmation_response(
-            args as {
-              sessionId: string;
-              requestId: number;
-              toolCallId: string;
-              outcome: string;
-            }
-          ) as Promise<T>;
-        case "execute_confirmed_command":
-          if (!args)
-            throw new Error("Missing arguments for execute_confirmed_command");
-          return webApi.execute_confirmed_command(
-            args as unknown as string
-          ) as Promise<T>;
-        case "generate_conversation_title":
-          if (!args)
-            throw new Error(
-              "Missing arguments for generate_conversation_title"
-            );
-          return webApi.generate_conversation_title(
-            args as { message: string; model?: string }
-          ) as Promise<T>;
-        case "validate_directory":
-          if (!args)
-            throw new Error("Missing arguments for validate_directory");
-          return webApi.validate_directory(
-            args as unknown as string
-          ) as Promise<T>;
-        case "is_home_directory":
-          if (!args) throw new Error("Missing arguments for is_home_directory");
-          return webApi.is_home_directory(
-            args as unknown as string
-          ) as Promise<T>;
-        case "list_projects":
-          return webApi.list_projects(args) as Promise<T>;
-        case "get_project_discussions":
-          if (!args)
-            throw new Error("Missing arguments for get_project_discussions");
-          return webApi.get_project_discussions(
-            args as unknown as string
-          ) as Promise<T>;
-        case "list_enriched_projects":
-          return webApi.list_projects_enriched() as Promise<T>;
-        case "start_session": {
-          if (!args) throw new Error("Missing arguments for start_session");
-          const sessionArgs = args as {
+async function dispatch<T>(
+  command: string,
+  args?: Record<string, unknown>
+): Promise<T> {
+  if (__WEB__) {
+    switch (command) {
+      case "check_cli_installed":
+        return webApi.check_cli_installed() as Promise<T>;
+      case "send_message":
+        if (!args) throw new Error("Missing arguments for send_message");
+        return webApi.send_message(
+          args as {
             sessionId: string;
-            workingDirectory: string;
+            message: string;
+            conversationHistory: string;
             model?: string;
-          };
-          return webApi.start_session(
-            sessionArgs.sessionId,
-            sessionArgs.workingDirectory,
-            sessionArgs.model
-          ) as Promise<T>;
-        }
-        case "start_server":
-          if (!args) throw new Error("Missing arguments for start_server");
-          return webApi.start_server(args as { id: string }) as Promise<T>;
-        case "stop_server":
-          if (!args) throw new Error("Missing arguments for stop_server");
-          return webApi.stop_server(args as { id: string }) as Promise<T>;
-        case "get_available_models":
-          return webApi.get_available_models() as Promise<T>;
-        case "search_mcp_servers":
-          return webApi.search_mcp_servers(args?.query as string) as Promise<T>;
-        case "get_popular_mcp_servers":
-          return webApi.get_popular_mcp_servers(args?.limit as number) as Promise<T>;
-        case "get_mcp_categories":
-          return webApi.get_mcp_categories() as Promise<T>;
-        case "auto_discover_models":
-          return webApi.auto_discover_models() as Promise<T>;
-        case "get_model_sources":
-          return webApi.get_model_sources() as Promise<T>;
-        case "save_theme":
-          if (!args) throw new Error("Missing arguments for save_theme");
-          return webApi.save_theme(args as Record<string, unknown>) as Promise<T>;
-        case "load_theme":
-          if (!args) throw new Error("Missing arguments for load_theme");
-          return webApi.load_theme(args as { name: string }) as Promise<T>;
-        case "list_themes":
-          return webApi.list_themes() as Promise<T>;
-        case "delete_theme":
-          if (!args) throw new Error("Missing arguments for delete_theme");
-          return webApi.delete_theme(args as { name: string }) as Promise<T>;
-        case "get_theme_presets":
-          return webApi.get_theme_presets() as Promise<T>;
-        case "export_theme_css":
-          if (!args) throw new Error("Missing arguments for export_theme_css");
-          return webApi.export_theme_css(args as { theme: Record<string, unknown>; outputPath: string }) as Promise<T>;
-        case "list_servers":
-          return webApi.list_servers() as Promise<T>;
-        case "add_server":
-          if (!args) throw new Error("Missing arguments for add_server");
-          return webApi.add_server(args as Record<string, unknown>) as Promise<T>;
-        case "edit_server":
-          if (!args) throw new Error("Missing arguments for edit_server");
-          return webApi.edit_server(args as Record<string, unknown>) as Promise<T>;
-        case "delete_server":
-          if (!args) throw new Error("Missing arguments for delete_server");
-          return webApi.delete_server(args as { id: string }) as Promise<T>;
-        default:
-          throw new Error(`Unknown command: ${command}`);
+          }
+        ) as Promise<T>;
+      case "get_process_statuses":
+        return webApi.get_process_statuses() as Promise<T>;
+      case "kill_process":
+        if (!args) throw new Error("Missing arguments for kill_process");
+        return webApi.kill_process(
+          args as { conversationId: string }
+        ) as Promise<T>;
+      case "send_tool_call_confirmation_response":
+        if (!args)
+          throw new Error(
+            "Missing arguments for send_tool_call_confirmation_response"
+          );
+        return webApi.send_tool_call_confirmation_response(
+          args as {
+            sessionId: string;
+            requestId: number;
+            toolCallId: string;
+            outcome: string;
+          }
+        ) as Promise<T>;
+      case "execute_confirmed_command":
+        if (!args)
+          throw new Error("Missing arguments for execute_confirmed_command");
+        return webApi.execute_confirmed_command(
+          args as {
+            sessionId: string;
+            command: string;
+            options?: ExecutionOptions;
+          }
+        ) as Promise<T>;
+      case "create_terminal":
+        return webApi.create_terminal(
+          args as { options?: TerminalOptions } | undefined
+        ) as Promise<T>;
+      case "write_terminal":
+        if (!args) throw new Error("Missing arguments for write_terminal");
+        return webApi.write_terminal(
+          args as { terminalId: string; data: string }
+        ) as Promise<T>;
+      case "resize_terminal":
+        if (!args) throw new Error("Missing arguments for resize_terminal");
+        return webApi.resize_terminal(
+          args as { terminalId: string; cols: number; rows: number }
+        ) as Promise<T>;
+      case "kill_terminal":
+        if (!args) throw new Error("Missing arguments for kill_terminal");
+        return webApi.kill_terminal(
+          args as { terminalId: string }
+        ) as Promise<T>;
+      case "list_terminals":
+        return webApi.list_terminals() as Promise<T>;
+      case "send_terminal_to_chat":
+        if (!args)
+          throw new Error("Missing arguments for send_terminal_to_chat");
+        return webApi.send_terminal_to_chat(
+          args as {
+            terminalId: string;
+            sessionId: string;
+            lines: number;
+            message?: string;
+          }
+        ) as Promise<T>;
+      case "get_redaction_config":
+        return webApi.get_redaction_config() as Promise<T>;
+      case "save_redaction_config":
+        if (!args)
+          throw new Error("Missing arguments for save_redaction_config");
+        return webApi.save_redaction_config(
+          args as { config: RedactionConfig }
+        ) as Promise<T>;
+      case "scrub_rpc_logs":
+        return webApi.scrub_rpc_logs() as Promise<T>;
+      case "get_encryption_status":
+        return webApi.get_encryption_status() as Promise<T>;
+      case "enable_encryption":
+        if (!args) throw new Error("Missing arguments for enable_encryption");
+        return webApi.enable_encryption(
+          args as { passphrase: string }
+        ) as Promise<T>;
+      case "unlock_storage":
+        if (!args) throw new Error("Missing arguments for unlock_storage");
+        return webApi.unlock_storage(args as { passphrase: string }) as Promise<T>;
+      case "lock_storage":
+        return webApi.lock_storage() as Promise<T>;
+      case "migrate_storage":
+        return webApi.migrate_storage() as Promise<T>;
+      case "generate_conversation_title":
+        if (!args)
+          throw new Error(
+            "Missing arguments for generate_conversation_title"
+          );
+        return webApi.generate_conversation_title(
+          args as { message: string; model?: string }
+        ) as Promise<T>;
+      case "validate_directory":
+        if (!args)
+          throw new Error("Missing arguments for validate_directory");
+        return webApi.validate_directory(
+          args as unknown as string
+        ) as Promise<T>;
+      case "is_home_directory":
+        if (!args) throw new Error("Missing arguments for is_home_directory");
+        return webApi.is_home_directory(
+          args as unknown as string
+        ) as Promise<T>;
+      case "list_projects":
+        return webApi.list_projects(args) as Promise<T>;
+      case "get_project_discussions":
+        if (!args)
+          throw new Error("Missing arguments for get_project_discussions");
+        return webApi.get_project_discussions(
+          args as unknown as string
+        ) as Promise<T>;
+      case "list_enriched_projects":
+        return webApi.list_projects_enriched() as Promise<T>;
+      case "start_session": {
+        if (!args) throw new Error("Missing arguments for start_session");
+        const sessionArgs = args as {
+          sessionId: string;
+          workingDirectory: string;
+          model?: string;
+        };
+        return webApi.start_session(
+          sessionArgs.sessionId,
+          sessionArgs.workingDirectory,
+          sessionArgs.model
+        ) as Promise<T>;
       }
-    } else {
-      return invoke<T>(command, args);
+      case "start_server":
+        if (!args) throw new Error("Missing arguments for start_server");
+        return webApi.start_server(args as { id: string }) as Promise<T>;
+      case "stop_server":
+        if (!args) throw new Error("Missing arguments for stop_server");
+        return webApi.stop_server(args as { id: string }) as Promise<T>;
+      case "get_available_models":
+        return webApi.get_available_models() as Promise<T>;
+      case "search_mcp_servers":
+        return webApi.search_mcp_servers(args?.query as string) as Promise<T>;
+      case "get_popular_mcp_servers":
+        return webApi.get_popular_mcp_servers(args?.limit as number) as Promise<T>;
+      case "get_mcp_categories":
+        return webApi.get_mcp_categories() as Promise<T>;
+      case "auto_discover_models":
+        return webApi.auto_discover_models() as Promise<T>;
+      case "get_model_sources":
+        return webApi.get_model_sources() as Promise<T>;
+      case "save_theme":
+        if (!args) throw new Error("Missing arguments for save_theme");
+        return webApi.save_theme(args as Record<string, unknown>) as Promise<T>;
+      case "load_theme":
+        if (!args) throw new Error("Missing arguments for load_theme");
+        return webApi.load_theme(args as { name: string }) as Promise<T>;
+      case "list_themes":
+        return webApi.list_themes() as Promise<T>;
+      case "delete_theme":
+        if (!args) throw new Error("Missing arguments for delete_theme");
+        return webApi.delete_theme(args as { name: string }) as Promise<T>;
+      case "get_theme_presets":
+        return webApi.get_theme_presets() as Promise<T>;
+      case "list_servers":
+        return webApi.list_servers() as Promise<T>;
+      case "add_server":
+        if (!args) throw new Error("Missing arguments for add_server");
+        return webApi.add_server(args as Record<string, unknown>) as Promise<T>;
+      case "edit_server":
+        if (!args) throw new Error("Missing arguments for edit_server");
+        return webApi.edit_server(args as Record<string, unknown>) as Promise<T>;
+      case "delete_server":
+        if (!args) throw new Error("Missing arguments for delete_server");
+        return webApi.delete_server(args as { id: string }) as Promise<T>;
+      default:
+        // Every backend operation is also a JSON-RPC method on the socket.
+        return getWebSocketManager().request<T>(command, args ?? {});
+    }
+  } else {
+    return invoke<T>(command, args);
+  }
+}
+
+// Abstraction layer for API calls
+export const api = {
+  async invoke<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+    try {
+      return await dispatch<T>(command, args);
+    } catch (err) {
+      throw toApiError(err);
     }
   },
 
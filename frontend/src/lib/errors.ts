@@ -0,0 +1,60 @@
+import axios from "axios";
+
+// Error body returned by both the web server and the Tauri commands.
+export interface ErrorBody {
+  code: string;
+  message: string;
+  status: number;
+}
+
+export class ApiError extends Error {
+  readonly code: string;
+  readonly status: number;
+
+  constructor(body: ErrorBody) {
+    super(body.message);
+    this.name = "ApiError";
+    this.code = body.code;
+    this.status = body.status;
+  }
+
+  // Keep `${error}` readable in existing toasts and logs.
+  override toString(): string {
+    return this.message;
+  }
+}
+
+function isErrorBody(value: unknown): value is ErrorBody {
+  return (
+    typeof value === "object" &&
+    value !== null &&
+    typeof (value as ErrorBody).code === "string" &&
+    typeof (value as ErrorBody).message === "string"
+  );
+}
+
+// Normalizes whatever a failed call rejected with into an ApiError.
+export function toApiError(err: unknown): ApiError {
+  if (err instanceof ApiError) return err;
+
+  if (axios.isAxiosError(err)) {
+    const data = err.response?.data;
+    if (isErrorBody(data)) return new ApiError(data);
+    return new ApiError({
+      code: err.response ? "request_failed" : "network_error",
+      message: err.message,
+      status: err.response?.status ?? 0,
+    });
+  }
+
+  if (isErrorBody(err)) {
+    return new ApiError({ ...err, status: err.status ?? 500 });
+  }
+
+  const message = err instanceof Error ? err.message : String(err);
+  return new ApiError({ code: "internal_error", message, status: 500 });
+}
+
+export function isApiError(err: unknown, code?: string): err is ApiError {
+  return err instanceof ApiError && (code === undefined || err.code === code);
+}
@@ -1,5 +1,6 @@
 import axios from "axios";
 import { Server } from "../types";
+import { ApiError, ErrorBody } from "./errors";
 
 // Create axios client with base URL /api
 const apiClient = axios.create({
@@ -7,6 +8,17 @@ const apiClient = axios.create({
   timeout: 30000, // 30 second timeout
 });
 
+// Let the login gate know when the session has expired or been revoked.
+apiClient.interceptors.response.use(
+  (response) => response,
+  (error) => {
+    if (axios.isAxiosError(error) && error.response?.status === 401) {
+      window.dispatchEvent(new Event("auth-required"));
+    }
+    return Promise.reject(error);
+  }
+);
+
 // Types matching the server's request/response types
 interface StartSessionRequest {
   session_id: string;
@@ -33,7 +45,68 @@ interface ToolConfirmationRequest {
 }
 
 interface ExecuteCommandRequest {
+  session_id: string;
+  command: string;
+  options?: ExecutionOptions;
+}
+
+export interface ExecutionOptions {
+  execution_id?: string;
+  /** Inside the session's project, relative to it; defaults to its root. */
+  working_directory?: string;
+  timeout_secs?: number;
+  max_output_bytes?: number;
+  scrub_env?: boolean;
+  limits?: ResourceLimits;
+}
+
+export interface ResourceLimits {
+  cpu_seconds?: number | null;
+  memory_bytes?: number | null;
+  file_size_bytes?: number | null;
+}
+
+export interface AuthStatus {
+  authenticated: boolean;
+  password_set: boolean;
+  multi_user: boolean;
+  user: string | null;
+  admin: boolean;
+  /** Set for guests: the session they were invited to and their role. */
+  session_id?: string | null;
+  role?: SessionRole | null;
+}
+
+export type SessionRole = "owner" | "controller" | "observer";
+
+export interface SessionMember {
+  name: string;
+  role: SessionRole;
+  watching: boolean;
+}
+
+export interface SessionInvitation {
+  name: string;
+  role: SessionRole;
+  token: string;
+}
+
+/** Payload of `presence-<session_id>` events. */
+export interface SessionPresence {
+  watchers: { name: string; role: SessionRole }[];
+}
+
+export interface CommandExecution {
+  execution_id: string;
   command: string;
+  exit_code: number | null;
+  signal: number | null;
+  success: boolean;
+  stdout: string;
+  stderr: string;
+  timed_out: boolean;
+  truncated: boolean;
+  duration_ms: number;
 }
 
 interface GenerateTitleRequest {
@@ -143,9 +216,165 @@ export const webApi = {
     await apiClient.post("/tool-confirmation", request);
   },
 
-  async execute_confirmed_command(command: string): Promise<string> {
-    const request: ExecuteCommandRequest = { command };
-    const response = await apiClient.post<string>("/execute-command", request);
+  async execute_confirmed_command(params: {
+    sessionId: string;
+    command: string;
+    options?: ExecutionOptions;
+  }): Promise<CommandExecution> {
+    const request: ExecuteCommandRequest = {
+      session_id: params.sessionId,
+      command: params.command,
+      options: params.options,
+    };
+    const response = await apiClient.post<CommandExecution>(
+      "/execute-command",
+      request
+    );
+    return response.data;
+  },
+
+  async create_terminal(params?: {
+    options?: TerminalOptions;
+  }): Promise<TerminalInfo> {
+    const response = await apiClient.post<TerminalInfo>("/terminals", {
+      options: params?.options ?? {},
+    });
+    return response.data;
+  },
+
+  async write_terminal(params: {
+    terminalId: string;
+    data: string;
+  }): Promise<void> {
+    await apiClient.post(`/terminals/${params.terminalId}/write`, {
+      data: params.data,
+    });
+  },
+
+  async resize_terminal(params: {
+    terminalId: string;
+    cols: number;
+    rows: number;
+  }): Promise<void> {
+    await apiClient.post(`/terminals/${params.terminalId}/resize`, {
+      cols: params.cols,
+      rows: params.rows,
+    });
+  },
+
+  async kill_terminal(params: { terminalId: string }): Promise<void> {
+    await apiClient.delete(`/terminals/${params.terminalId}`);
+  },
+
+  async list_terminals(): Promise<TerminalInfo[]> {
+    const response = await apiClient.get<TerminalInfo[]>("/terminals");
+    return response.data;
+  },
+
+  async send_terminal_to_chat(params: {
+    terminalId: string;
+    sessionId: string;
+    lines: number;
+    message?: string;
+  }): Promise<void> {
+    await apiClient.post(`/terminals/${params.terminalId}/send-to-chat`, {
+      session_id: params.sessionId,
+      lines: params.lines,
+      message: params.message,
+    });
+  },
+
+  async auth_status(): Promise<AuthStatus> {
+    const response = await apiClient.get<AuthStatus>("/auth/status");
+    return response.data;
+  },
+
+  async login(params: {
+    user?: string;
+    password?: string;
+    token?: string;
+  }): Promise<void> {
+    await apiClient.post("/auth/login", params);
+  },
+
+  async logout(): Promise<void> {
+    await apiClient.post("/auth/logout");
+  },
+
+  async set_password(params: { password: string }): Promise<void> {
+    await apiClient.post("/auth/password", params);
+  },
+
+  async rotate_token(): Promise<string> {
+    const response = await apiClient.post<{ token: string }>(
+      "/auth/rotate-token"
+    );
+    return response.data.token;
+  },
+
+  async get_session_members(params: {
+    sessionId: string;
+  }): Promise<SessionMember[]> {
+    const response = await apiClient.get<SessionMember[]>(
+      `/sessions/${params.sessionId}/members`
+    );
+    return response.data;
+  },
+
+  async invite_session_member(params: {
+    sessionId: string;
+    name: string;
+    role: Exclude<SessionRole, "owner">;
+  }): Promise<SessionInvitation> {
+    const response = await apiClient.post<SessionInvitation>(
+      `/sessions/${params.sessionId}/members`,
+      { name: params.name, role: params.role }
+    );
+    return response.data;
+  },
+
+  async remove_session_member(params: {
+    sessionId: string;
+    name: string;
+  }): Promise<void> {
+    await apiClient.delete(`/sessions/${params.sessionId}/members/${params.name}`);
+  },
+
+  async get_redaction_config(): Promise<RedactionConfig> {
+    const response = await apiClient.get<RedactionConfig>("/redaction-config");
+    return response.data;
+  },
+
+  async save_redaction_config(params: {
+    config: RedactionConfig;
+  }): Promise<void> {
+    await apiClient.post("/redaction-config", params);
+  },
+
+  async scrub_rpc_logs(): Promise<ScrubReport> {
+    const response = await apiClient.post<ScrubReport>("/scrub-logs");
+    return response.data;
+  },
+
+  async get_encryption_status(): Promise<EncryptionStatus> {
+    const response = await apiClient.get<EncryptionStatus>("/storage/status");
+    return response.data;
+  },
+
+  async enable_encryption(params: { passphrase: string }): Promise<void> {
+    await apiClient.post("/storage/enable", params);
+  },
+
+  async unlock_storage(params: { passphrase: string }): Promise<void> {
+    await apiClient.post("/storage/unlock", params);
+  },
+
+  async lock_storage(): Promise<void> {
+    await apiClient.post("/storage/lock");
+  },
+
+  async migrate_storage(): Promise<MigrationReport> {
+    const response = await apiClient.post<MigrationReport>("/storage/migrate");
     return response.data;
   },
 
@@ -349,12 +578,58 @@ export const webApi = {
     const response = await apiClient.get<ThemePreset[]>("/theme-presets");
     return response.data;
   },
-
-  async export_theme_css(params: { theme: Record<string, unknown>; outputPath: string }): Promise<void> {
-    await apiClient.post("/themes/export", params);
-  },
 };
 
+export interface TerminalOptions {
+  shell?: string;
+  args?: string[];
+  working_directory?: string;
+  env?: Record<string, string>;
+  cols?: number;
+  rows?: number;
+}
+
+export interface TerminalInfo {
+  id: string;
+  shell: string;
+  working_directory: string | null;
+  pid: number | null;
+  cols: number;
+  rows: number;
+  created_at: number;
+  is_alive: boolean;
+  exit_code: number | null;
+}
+
+export interface RedactionPattern {
+  name: string;
+  pattern: string;
+}
+
+export interface RedactionConfig {
+  enabled: boolean;
+  detect_high_entropy: boolean;
+  min_entropy_length: number;
+  entropy_threshold: number;
+  custom_patterns: RedactionPattern[];
+}
+
+export interface ScrubReport {
+  files_scanned: number;
+  files_changed: number;
+  redactions: number;
+}
+
+export interface EncryptionStatus {
+  enabled: boolean;
+  unlocked: boolean;
+}
+
+export interface MigrationReport {
+  files_scanned: number;
+  files_encrypted: number;
+}
+
 export interface RecentChat {
   id: string;
   title: string;
@@ -515,7 +790,45 @@ export interface ThemePreset {
 interface WebSocketEvent<T = unknown> {
   event: string;
   payload: T;
-  sequence: number;
+  // Absent on control events such as `event-gap-<sessionId>`.
+  sequence?: number;
+}
+
+// JSON-RPC reply to a request sent with WebSocketManager.request.
+interface RpcResponse {
+  jsonrpc: "2.0";
+  id: number | string | null;
+  result?: unknown;
+  error?: { code: number; message: string; data?: ErrorBody };
+}
+
+// Per-session event kinds, emitted as `<kind>-<sessionId>`. Longer kinds that
+// share a prefix come first. Mirrors SESSION_EVENT_KINDS in the backend.
+const SESSION_EVENT_KINDS = [
+  "gemini-tool-call-confirmation",
+  "gemini-tool-call-update",
+  "gemini-tool-call",
+  "gemini-turn-finished",
+  "gemini-output",
+  "gemini-thought",
+  "gemini-error",
+  "cli-io",
+];
+
+function splitSessionEvent(
+  event: string
+): { kind: string; sessionId: string } | null {
+  for (const kind of SESSION_EVENT_KINDS) {
+    if (event.startsWith(`${kind}-`) && event.length > kind.length + 1) {
+      return { kind, sessionId: event.slice(kind.length + 1) };
+    }
+  }
+  return null;
+}
+
+interface PendingRequest {
+  resolve: (result: unknown) => void;
+  reject: (error: ApiError) => void;
 }
 
 export class WebSocketManager {
@@ -527,6 +840,15 @@ export class WebSocketManager {
   private isConnecting = false;
   private connectionReadyPromise: Promise<void> | null = null;
   private connectionReadyResolve: (() => void) | null = null;
+  private nextRequestId = 1;
+  private pendingRequests: Map<number, PendingRequest> = new Map();
+  // What the server currently delivers to this connection.
+  private subscribedSessions: Set<string> = new Set();
+  private mutedKinds: Set<string> = new Set();
+  // Highest event sequence received, sent on reconnect to replay missed events.
+  private lastSequence: number | null = null;
+  // Server-Sent Events stream used once WebSocket upgrades keep failing.
+  private eventSource: EventSource | null = null;
 
   constructor() {
     this.connect();
@@ -564,6 +886,11 @@ export class WebSocketManager {
         this.reconnectTimeout = null;
       }
 
+      // A new connection starts with no sessions and every kind enabled.
+      this.subscribedSessions.clear();
+      this.mutedKinds.clear();
+      this.syncSubscriptions(this.lastSequence);
+
       // Resolve the connection ready promise
       if (this.connectionReadyResolve) {
         this.connectionReadyResolve();
@@ -571,30 +898,13 @@ export class WebSocketManager {
       }
     };
 
-    this.ws.onmessage = (event) => {
-      try {
-        const wsEvent: WebSocketEvent = JSON.parse(event.data);
-        console.log("📨 WebSocket event:", wsEvent.event, wsEvent.payload);
-
-        const eventListeners = this.listeners.get(wsEvent.event);
-        if (eventListeners) {
-          eventListeners.forEach((listener) => {
-            try {
-              listener(wsEvent.payload);
-            } catch (error) {
-              console.error("❌ Error in WebSocket event listener:", error);
-            }
-          });
-        }
-      } catch (error) {
-        console.error("❌ Failed to parse WebSocket message:", error);
-      }
-    };
+    this.ws.onmessage = (event) => this.handleMessage(event.data);
 
     this.ws.onclose = (event) => {
       console.log("❌ WebSocket disconnected:", event.code, event.reason);
       this.isConnecting = false;
       this.ws = null;
+      this.rejectPendingRequests("WebSocket connection closed");
 
       // Attempt to reconnect if not a normal closure
       if (
@@ -615,6 +925,9 @@ export class WebSocketManager {
         }, delay);
       } else if (this.reconnectAttempts >= this.maxReconnectAttempts) {
         console.error("❌ Max reconnection attempts reached");
+        if (event.code !== 1000) {
+          this.openEventStream();
+        }
       }
     };
 
@@ -624,6 +937,68 @@ export class WebSocketManager {
     };
   }
 
+  private handleMessage(message: string) {
+    try {
+      const data = JSON.parse(message);
+      if (data.jsonrpc === "2.0") {
+        this.handleRpcResponse(data as RpcResponse);
+        return;
+      }
+
+      const wsEvent = data as WebSocketEvent;
+      if (typeof wsEvent.sequence === "number") {
+        this.lastSequence = Math.max(this.lastSequence ?? 0, wsEvent.sequence);
+      }
+      console.log("📨 WebSocket event:", wsEvent.event, wsEvent.payload);
+
+      const eventListeners = this.listeners.get(wsEvent.event);
+      if (eventListeners) {
+        eventListeners.forEach((listener) => {
+          try {
+            listener(wsEvent.payload);
+          } catch (error) {
+            console.error("❌ Error in WebSocket event listener:", error);
+          }
+        });
+      }
+    } catch (error) {
+      console.error("❌ Failed to parse WebSocket message:", error);
+    }
+  }
+
+  // Falls back to `/api/events` when WebSocket upgrades keep failing, e.g.
+  // behind a proxy that strips them. The subscription is part of the URL,
+  // so the stream is reopened whenever it changes; `last_event_id` (and the
+  // browser's own `Last-Event-ID` on reconnect) replays what was missed.
+  private openEventStream() {
+    this.eventSource?.close();
+
+    const { sessions, kinds } = this.wantedSubscriptions();
+    const muted = SESSION_EVENT_KINDS.filter((kind) => !kinds.has(kind));
+    const query = new URLSearchParams();
+    sessions.forEach((id) => query.append("session", id));
+    muted.forEach((kind) => query.append("mute", kind));
+    if (this.lastSequence !== null) {
+      query.set("last_event_id", String(this.lastSequence));
+    }
+
+    console.log("📡 Falling back to the event stream");
+    const source = new EventSource(`/api/events?${query}`);
+    source.onmessage = (event) => this.handleMessage(event.data);
+    source.onerror = (error) => {
+      console.error("❌ Event stream error:", error);
+    };
+    this.eventSource = source;
+    this.subscribedSessions = sessions;
+    this.mutedKinds = new Set(muted);
+
+    // Let waiting callers through; `request` reports the socket as down.
+    if (this.connectionReadyResolve) {
+      this.connectionReadyResolve();
+      this.connectionReadyResolve = null;
+    }
+  }
+
   public async waitForConnection(): Promise<void> {
     if (this.ws && this.ws.readyState === WebSocket.OPEN) {
       return Promise.resolve();
@@ -661,17 +1036,139 @@ export class WebSocketManager {
     console.log(
       `👂 Added listener for event: ${event} (total: ${eventListeners.size})`
     );
+    this.syncSubscriptions();
 
     // Return unsubscribe function
     return () => {
       eventListeners.delete(wrappedCallback);
       if (eventListeners.size === 0) {
         this.listeners.delete(event);
+        this.syncSubscriptions();
       }
       console.log(`🔇 Removed listener for event: ${event}`);
     };
   }
 
+  // Subscribes to exactly the sessions and event kinds that have listeners,
+  // so the server only streams what this tab displays. After a reconnect,
+  // `lastSequence` asks the server to replay what was missed.
+  private syncSubscriptions(lastSequence: number | null = null) {
+    const { sessions, kinds } = this.wantedSubscriptions();
+    if (this.eventSource) {
+      const changed =
+        sessions.size !== this.subscribedSessions.size ||
+        [...sessions].some((id) => !this.subscribedSessions.has(id)) ||
+        SESSION_EVENT_KINDS.some(
+          (kind) => kinds.has(kind) === this.mutedKinds.has(kind)
+        );
+      if (changed) this.openEventStream();
+      return;
+    }
+    if (!this.ws || this.ws.readyState !== WebSocket.OPEN) return;
+
+    const addSessions = [...sessions].filter(
+      (id) => !this.subscribedSessions.has(id)
+    );
+    const removeSessions = [...this.subscribedSessions].filter(
+      (id) => !sessions.has(id)
+    );
+    const enableKinds = [...kinds].filter((kind) => this.mutedKinds.has(kind));
+    const muteKinds = SESSION_EVENT_KINDS.filter(
+      (kind) => !kinds.has(kind) && !this.mutedKinds.has(kind)
+    );
+
+    // Mute first so a replay doesn't include kinds nobody listens to.
+    if (removeSessions.length > 0 || muteKinds.length > 0) {
+      this.notify("unsubscribe", {
+        session_ids: removeSessions,
+        kinds: muteKinds,
+      });
+    }
+    if (addSessions.length > 0 || enableKinds.length > 0) {
+      this.notify("subscribe", {
+        session_ids: addSessions,
+        kinds: enableKinds,
+        last_sequence: lastSequence ?? undefined,
+      });
+    }
+
+    this.subscribedSessions = sessions;
+    this.mutedKinds = new Set(
+      SESSION_EVENT_KINDS.filter((kind) => !kinds.has(kind))
+    );
+  }
+
+  // Sessions and event kinds that currently have listeners.
+  private wantedSubscriptions() {
+    const sessions = new Set<string>();
+    const kinds = new Set<string>();
+    for (const event of this.listeners.keys()) {
+      const parsed = splitSessionEvent(event);
+      if (parsed) {
+        sessions.add(parsed.sessionId);
+        kinds.add(parsed.kind);
+      }
+    }
+    return { sessions, kinds };
+  }
+
+  // Sends a JSON-RPC notification, which the server doesn't answer.
+  private notify(method: string, params: unknown) {
+    this.ws?.send(JSON.stringify({ jsonrpc: "2.0", method, params }));
+  }
+
+  // Calls a backend method over the socket; replies are matched by id.
+  public async request<T>(method: string, params?: unknown): Promise<T> {
+    await this.waitForConnection();
+    const ws = this.ws;
+    if (!ws || ws.readyState !== WebSocket.OPEN) {
+      throw new ApiError({
+        code: "network_error",
+        message: "WebSocket is not connected",
+        status: 0,
+      });
+    }
+
+    const id = this.nextRequestId++;
+    return new Promise<T>((resolve, reject) => {
+      this.pendingRequests.set(id, {
+        resolve: (result) => resolve(result as T),
+        reject,
+      });
+      ws.send(JSON.stringify({ jsonrpc: "2.0", id, method, params }));
+    });
+  }
+
+  private handleRpcResponse(response: RpcResponse) {
+    if (typeof response.id !== "number") {
+      console.error("❌ WebSocket RPC error:", response.error);
+      return;
+    }
+    const pending = this.pendingRequests.get(response.id);
+    if (!pending) return;
+    this.pendingRequests.delete(response.id);
+
+    if (response.error) {
+      pending.reject(
+        new ApiError(
+          response.error.data ?? {
+            code: "rpc_error",
+            message: response.error.message,
+            status: 400,
+          }
+        )
+      );
+    } else {
+      pending.resolve(response.result ?? null);
+    }
+  }
+
+  private rejectPendingRequests(message: string) {
+    const error = new ApiError({ code: "network_error", message, status: 0 });
+    this.pendingRequests.forEach((pending) => pending.reject(error));
+    this.pendingRequests.clear();
+  }
+
   public disconnect() {
     if (this.reconnectTimeout) {
       clearTimeout(this.reconnectTimeout);
@@ -684,7 +1181,10 @@ export class WebSocketManager {
       this.ws.close(1000, "Manual disconnect");
       this.ws = null;
     }
+    this.eventSource?.close();
+    this.eventSource = null;
 
+    this.rejectPendingRequests("WebSocket disconnected");
     this.listeners.clear();
     console.log("🔌 WebSocket disconnected manually");
   }
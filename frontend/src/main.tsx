@@ -3,6 +3,7 @@ import { createRoot } from "react-dom/client";
 import { BrowserRouter } from "react-router-dom";
 import { TooltipProvider } from "./components/ui/tooltip";
 import { ThemeProvider } from "./components/providers/theme-provider";
+import { LoginGate } from "./components/common/LoginGate";
 import App from "./App.tsx";
 import "./index.css";
 
@@ -11,7 +12,9 @@ createRoot(document.getElementById("root")!).render(
     <ThemeProvider defaultTheme="dark" storageKey="gemini-ui-theme">
       <BrowserRouter>
         <TooltipProvider>
-          <App />
+          <LoginGate>
+            <App />
+          </LoginGate>
         </TooltipProvider>
       </BrowserRouter>
     </ThemeProvider>
@@ -9,7 +9,10 @@ const host = process.env.TAURI_DEV_HOST;
 // https://vitejs.dev/config/
 export default defineConfig({
   plugins: [react(), tailwindcss()],
-  base: './',
+  // The web server falls back to index.html for deep links like /projects/abc,
+  // so its build needs absolute asset paths; Tauri loads the files relatively.
+  // eslint-disable-next-line no-undef
+  base: process.env.GEMINI_DESKTOP_WEB === "true" ? "/" : "./",
   build: {
     rollupOptions: {
       external: process.env.NODE_ENV === 'production' ? ['reload.js'] : []